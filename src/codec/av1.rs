@@ -2,8 +2,10 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+pub mod av1c;
 mod helpers;
 pub mod parser;
 pub mod reader;
 pub mod synthesizer;
+pub mod temporal_unit;
 pub mod writer;
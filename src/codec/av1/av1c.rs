@@ -0,0 +1,263 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Support for the AV1CodecConfigurationRecord, i.e. the codec private data of
+//! AV1 streams stored in MP4 (`av1C` box) or Matroska files, as defined by the
+//! "AV1 Codec ISO Media File Format Binding" specification.
+
+use std::io::Write;
+use std::rc::Rc;
+
+use anyhow::anyhow;
+
+use crate::codec::av1::parser::ChromaSamplePosition;
+use crate::codec::av1::parser::ObuType;
+use crate::codec::av1::parser::ParsedObu;
+use crate::codec::av1::parser::Parser;
+use crate::codec::av1::parser::Profile;
+use crate::codec::av1::parser::SequenceHeaderObu;
+use crate::codec::av1::synthesizer::Synthesizer;
+
+/// The marker bit and version of the only version of the record.
+const MARKER_AND_VERSION: u8 = 0x81;
+
+/// An AV1CodecConfigurationRecord.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Av1CodecConfigurationRecord {
+    pub seq_profile: Profile,
+    pub seq_level_idx_0: u8,
+    pub seq_tier_0: bool,
+    pub high_bitdepth: bool,
+    pub twelve_bit: bool,
+    pub monochrome: bool,
+    pub chroma_subsampling_x: bool,
+    pub chroma_subsampling_y: bool,
+    pub chroma_sample_position: ChromaSamplePosition,
+    /// The number of frames minus one to buffer before presenting the first
+    /// one, if signaled.
+    pub initial_presentation_delay_minus_one: Option<u8>,
+    /// Zero or more OBUs in low-overhead format, usually the sequence header
+    /// and possibly metadata OBUs.
+    pub config_obus: Vec<u8>,
+}
+
+impl Av1CodecConfigurationRecord {
+    /// Builds a record describing the sequence `seq`, which is stored in the
+    /// configOBUs.
+    pub fn from_sequence_header(seq: &SequenceHeaderObu) -> anyhow::Result<Self> {
+        let op = &seq.operating_points[0];
+        let cc = &seq.color_config;
+
+        let initial_presentation_delay_minus_one = if seq.initial_display_delay_present_flag
+            && op.initial_display_delay_present_for_this_op
+        {
+            Some(std::cmp::min(op.initial_display_delay_minus_1, 15) as u8)
+        } else {
+            None
+        };
+
+        // The configOBUs must have a size field.
+        let mut obu = seq.clone();
+        obu.obu_header.has_size_field = true;
+        let mut config_obus = vec![];
+        Synthesizer::<'_, SequenceHeaderObu, _>::synthesize(&obu, &mut config_obus)?;
+
+        Ok(Self {
+            seq_profile: seq.seq_profile,
+            seq_level_idx_0: op.seq_level_idx as u8,
+            seq_tier_0: op.seq_tier != 0,
+            high_bitdepth: cc.high_bitdepth,
+            twelve_bit: cc.twelve_bit,
+            monochrome: cc.mono_chrome,
+            chroma_subsampling_x: cc.subsampling_x,
+            chroma_subsampling_y: cc.subsampling_y,
+            chroma_sample_position: cc.chroma_sample_position,
+            initial_presentation_delay_minus_one,
+            config_obus,
+        })
+    }
+
+    /// Parses a record, e.g. from the contents of an MP4 `av1C` box.
+    pub fn parse(data: &[u8]) -> anyhow::Result<Self> {
+        if data.len() < 4 {
+            return Err(anyhow!("Broken data: av1C record is too short"));
+        }
+
+        if data[0] != MARKER_AND_VERSION {
+            return Err(anyhow!(
+                "Unsupported av1C marker and version {:#x}",
+                data[0]
+            ));
+        }
+
+        let seq_profile =
+            Profile::n(data[1] >> 5).ok_or(anyhow!("Invalid seq_profile {}", data[1] >> 5))?;
+
+        let initial_presentation_delay_minus_one = if data[3] & 0x10 != 0 {
+            Some(data[3] & 0xf)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            seq_profile,
+            seq_level_idx_0: data[1] & 0x1f,
+            seq_tier_0: data[2] & 0x80 != 0,
+            high_bitdepth: data[2] & 0x40 != 0,
+            twelve_bit: data[2] & 0x20 != 0,
+            monochrome: data[2] & 0x10 != 0,
+            chroma_subsampling_x: data[2] & 0x08 != 0,
+            chroma_subsampling_y: data[2] & 0x04 != 0,
+            chroma_sample_position: ChromaSamplePosition::n(data[2] & 0x3).unwrap(),
+            initial_presentation_delay_minus_one,
+            config_obus: data[4..].to_vec(),
+        })
+    }
+
+    /// Writes the record into `writer`.
+    pub fn write_into(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let initial_presentation_delay = match self.initial_presentation_delay_minus_one {
+            Some(delay) => 0x10 | (delay & 0xf),
+            None => 0,
+        };
+
+        writer.write_all(&[
+            MARKER_AND_VERSION,
+            (self.seq_profile as u8) << 5 | (self.seq_level_idx_0 & 0x1f),
+            u8::from(self.seq_tier_0) << 7
+                | u8::from(self.high_bitdepth) << 6
+                | u8::from(self.twelve_bit) << 5
+                | u8::from(self.monochrome) << 4
+                | u8::from(self.chroma_subsampling_x) << 3
+                | u8::from(self.chroma_subsampling_y) << 2
+                | self.chroma_sample_position as u8,
+            initial_presentation_delay,
+        ])?;
+        writer.write_all(&self.config_obus)
+    }
+
+    /// The bit depth of the stream.
+    pub fn bit_depth(&self) -> u32 {
+        match (self.high_bitdepth, self.twelve_bit) {
+            (false, _) => 8,
+            (true, false) => 10,
+            (true, true) => 12,
+        }
+    }
+
+    /// Returns the codec string identifying the stream, e.g. for the `codecs`
+    /// MIME type parameter, in its short form "av01.P.LLT.DD".
+    pub fn codec_string(&self) -> String {
+        format!(
+            "av01.{}.{:02}{}.{:02}",
+            self.seq_profile as u8,
+            self.seq_level_idx_0,
+            if self.seq_tier_0 { 'H' } else { 'M' },
+            self.bit_depth()
+        )
+    }
+
+    /// Parses the configOBUs with `parser`, returning the sequence header they
+    /// contain, if any.
+    pub fn parse_config_obus(
+        &self,
+        parser: &mut Parser,
+    ) -> anyhow::Result<Option<Rc<SequenceHeaderObu>>> {
+        let mut sequence_header = None;
+        let mut consumed = 0;
+
+        while consumed < self.config_obus.len() {
+            let obu = match parser.parse_obu(&self.config_obus[consumed..])? {
+                ParsedObu::Process(obu) => obu,
+                ParsedObu::Drop(length) => {
+                    consumed += usize::try_from(length)?;
+                    continue;
+                }
+            };
+
+            consumed += obu.data.len();
+
+            if obu.header.obu_type == ObuType::SequenceHeader {
+                sequence_header = Some(parser.parse_sequence_header_obu(&obu)?);
+            }
+        }
+
+        Ok(sequence_header)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::IvfIterator;
+
+    const STREAM_TEST_25_FPS: &[u8] = include_bytes!("test_data/test-25fps.ivf.av1");
+
+    #[test]
+    fn av1c_test25fps() {
+        let packet = IvfIterator::new(STREAM_TEST_25_FPS).next().unwrap();
+
+        let mut parser = Parser::default();
+        let mut consumed = 0;
+        let mut seq = None;
+        while seq.is_none() {
+            let obu = match parser.parse_obu(&packet[consumed..]).unwrap() {
+                ParsedObu::Process(obu) => obu,
+                ParsedObu::Drop(length) => {
+                    consumed += length as usize;
+                    continue;
+                }
+            };
+            consumed += obu.data.len();
+
+            if obu.header.obu_type == ObuType::SequenceHeader {
+                seq = Some(parser.parse_sequence_header_obu(&obu).unwrap());
+            }
+        }
+        let seq = seq.unwrap();
+
+        let record = Av1CodecConfigurationRecord::from_sequence_header(&seq).unwrap();
+        assert_eq!(record.seq_profile, Profile::Profile0);
+        assert!(record.chroma_subsampling_x && record.chroma_subsampling_y);
+        assert_eq!(record.bit_depth(), 8);
+        assert_eq!(
+            record.codec_string(),
+            format!("av01.0.{:02}M.08", seq.operating_points[0].seq_level_idx)
+        );
+
+        let mut data = vec![];
+        record.write_into(&mut data).unwrap();
+        assert_eq!(data[0], 0x81);
+        assert_eq!(data[2], 0x0c);
+
+        let parsed = Av1CodecConfigurationRecord::parse(&data).unwrap();
+        assert_eq!(parsed, record);
+
+        let mut parser = Parser::default();
+        let parsed_seq = parsed.parse_config_obus(&mut parser).unwrap().unwrap();
+        assert_eq!(*parsed_seq, *seq);
+
+        data[0] = 0x01;
+        assert!(Av1CodecConfigurationRecord::parse(&data).is_err());
+        assert!(Av1CodecConfigurationRecord::parse(&data[..3]).is_err());
+    }
+
+    #[test]
+    fn codec_string() {
+        let record = Av1CodecConfigurationRecord {
+            seq_profile: Profile::Profile2,
+            seq_level_idx_0: 13,
+            seq_tier_0: true,
+            high_bitdepth: true,
+            twelve_bit: true,
+            ..Default::default()
+        };
+
+        assert_eq!(record.codec_string(), "av01.2.13H.12");
+
+        let mut data = vec![];
+        record.write_into(&mut data).unwrap();
+        assert_eq!(data, [0x81, 0x4d, 0xe0, 0x00]);
+    }
+}
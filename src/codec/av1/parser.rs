@@ -51,6 +51,7 @@ pub const GM_ABS_TRANS_ONLY_BITS: u32 = 9;
 pub const GM_TRANS_ONLY_PREC_BITS: u32 = 3;
 pub const GM_ABS_TRANS_BITS: u32 = 12;
 pub const GM_TRANS_PREC_BITS: u32 = 6;
+pub const SCALABILITY_SS: u32 = 14;
 
 // Same as Segmentation_Feature_Bits in the specification. See 5.9.14
 pub const FEATURE_BITS: [u8; SEG_LVL_MAX] = [8, 6, 6, 6, 6, 3, 0, 0];
@@ -175,6 +176,37 @@ pub struct TileGroupObu<'a> {
     pub tiles: Vec<Tile>,
 }
 
+/// An entry of a TileListOBU. See 5.12.2
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TileListEntry {
+    /// The index into an array of anchor frames that the tile uses for
+    /// prediction.
+    pub anchor_frame_idx: u32,
+    /// The row coordinate of the tile in the frame that it belongs, in tiles.
+    pub anchor_tile_row: u32,
+    /// The column coordinate of the tile in the frame that it belongs, in
+    /// tiles.
+    pub anchor_tile_col: u32,
+    /// Where coded_tile_data starts in the OBU data.
+    pub tile_data_offset: u32,
+    /// Same as tile_data_size_minus_1 + 1 in the specification.
+    pub tile_data_size: u32,
+}
+
+/// A TileListOBU, used by the large scale tile decoding process. See 5.12
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TileListObu<'a> {
+    /// The OBU backing this tile list.
+    pub obu: Obu<'a>,
+    /// The width of the output frame, in tiles, minus 1.
+    pub output_frame_width_in_tiles_minus_1: u32,
+    /// The height of the output frame, in tiles, minus 1.
+    pub output_frame_height_in_tiles_minus_1: u32,
+    /// The tile list entries. Use `tile_data_offset` to index into the OBU
+    /// data.
+    pub entries: Vec<TileListEntry>,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct OperatingPoint {
     /// Specifies the level that the coded video sequence conforms to when
@@ -230,9 +262,9 @@ pub struct TimingInfo {
     pub time_scale: u32,
     /// If set, indicates that pictures should be displayed according to their
     /// output order with the number of ticks between two consecutive pictures
-    /// (without dropping frames) specified by num_ticks_per_picture_minus_1 +
-    /// 1. If not set, indicates that the interval between two consecutive
-    /// pictures is not specified.
+    /// (without dropping frames) specified by num_ticks_per_picture_minus_1
+    /// plus 1. If not set, indicates that the interval between two
+    /// consecutive pictures is not specified.
     pub equal_picture_interval: bool,
     /// Plus 1 specifies the number of clock ticks corresponding to output time
     /// between two consecutive pictures in the output order.
@@ -519,6 +551,223 @@ pub struct TemporalDelimiterObu {
     pub obu_header: ObuHeader,
 }
 
+/// A PaddingOBU. See 5.7
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PaddingObu {
+    pub obu_header: ObuHeader,
+    /// Same as obu_padding_length in the specification, i.e. the number of
+    /// padding bytes before the trailing bits.
+    pub obu_padding_length: u32,
+}
+
+/// The metadata types. See 6.7.1
+#[derive(N, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MetadataType {
+    HdrCll = 1,
+    HdrMdcv = 2,
+    Scalability = 3,
+    ItutT35 = 4,
+    Timecode = 5,
+}
+
+/// High dynamic range content light level metadata. See 5.8.3
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataHdrCll {
+    pub obu_header: ObuHeader,
+    /// Specifies the maximum content light level as specified in CEA-861.3,
+    /// Appendix A.
+    pub max_cll: u32,
+    /// Specifies the maximum frame-average light level as specified in
+    /// CEA-861.3, Appendix A.
+    pub max_fall: u32,
+}
+
+/// High dynamic range mastering display color volume metadata. See 5.8.4
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataHdrMdcv {
+    pub obu_header: ObuHeader,
+    /// Specifies a 0.16 fixed-point X chromaticity coordinate as defined by CIE
+    /// 1931, where i = 0,1,2 specifies Red, Green, Blue respectively.
+    pub primary_chromaticity_x: [u32; 3],
+    /// Specifies a 0.16 fixed-point Y chromaticity coordinate as defined by CIE
+    /// 1931, where i = 0,1,2 specifies Red, Green, Blue respectively.
+    pub primary_chromaticity_y: [u32; 3],
+    /// Specifies a 0.16 fixed-point white X chromaticity coordinate as defined
+    /// by CIE 1931.
+    pub white_point_chromaticity_x: u32,
+    /// Specifies a 0.16 fixed-point white Y chromaticity coordinate as defined
+    /// by CIE 1931.
+    pub white_point_chromaticity_y: u32,
+    /// A 24.8 fixed-point maximum luminance, represented in candelas per square
+    /// meter.
+    pub luminance_max: u32,
+    /// A 18.14 fixed-point minimum luminance, represented in candelas per
+    /// square meter.
+    pub luminance_min: u32,
+}
+
+/// The description of a temporal group in the scalability structure. See
+/// 5.8.6
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemporalGroupDescription {
+    /// Specifies the temporal layer ID of this picture in the temporal group.
+    pub temporal_id: u32,
+    /// If set, indicates that switching up to a higher temporal layer can be
+    /// done at this picture.
+    pub temporal_switching_up_point_flag: bool,
+    /// If set, indicates that switching up to a higher spatial layer can be
+    /// done at this picture.
+    pub spatial_switching_up_point_flag: bool,
+    /// Indicates the number of reference pictures used by this picture.
+    pub ref_cnt: u32,
+    /// Indicates, in units of temporal group entries, the position of the
+    /// reference pictures used by this picture.
+    pub ref_pic_diff: [u32; 7],
+}
+
+/// The scalability structure. See 5.8.6
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ScalabilityStructure {
+    /// Indicates the number of spatial layers present in the video sequence
+    /// minus one.
+    pub spatial_layers_cnt_minus_1: u32,
+    /// If set, indicates that the spatial_layer_max_width and
+    /// spatial_layer_max_height parameters are present for each of the
+    /// spatial layers.
+    pub spatial_layer_dimensions_present_flag: bool,
+    /// If set, indicates that the spatial_layer_ref_id is present for each of
+    /// the spatial layers.
+    pub spatial_layer_description_present_flag: bool,
+    /// If set, indicates that the temporal dependency information is present.
+    pub temporal_group_description_present_flag: bool,
+    /// Specifies the maximum frame width for the frames with spatial_id equal
+    /// to i.
+    pub spatial_layer_max_width: [u32; MAX_NUM_SPATIAL_LAYERS],
+    /// Specifies the maximum frame height for the frames with spatial_id equal
+    /// to i.
+    pub spatial_layer_max_height: [u32; MAX_NUM_SPATIAL_LAYERS],
+    /// Specifies the spatial_id value of the frame within the current temporal
+    /// unit that may be used as a reference for a frame with spatial_id equal
+    /// to i.
+    pub spatial_layer_ref_id: [u32; MAX_NUM_SPATIAL_LAYERS],
+    /// The description of each picture in the temporal group, whose size is
+    /// temporal_group_size in the specification.
+    pub temporal_group: Vec<TemporalGroupDescription>,
+}
+
+/// Scalability metadata. See 5.8.5
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataScalability {
+    pub obu_header: ObuHeader,
+    /// Indicates the picture prediction structure of the bitstream.
+    pub scalability_mode_idc: u32,
+    /// The scalability structure, present if scalability_mode_idc is
+    /// SCALABILITY_SS.
+    pub scalability_structure: Option<ScalabilityStructure>,
+}
+
+/// ITU-T T.35 metadata. See 5.8.2
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataItutT35 {
+    pub obu_header: ObuHeader,
+    /// Shall be a byte having a value specified as a country code by Annex A
+    /// of Recommendation ITU-T T.35.
+    pub itu_t_t35_country_code: u32,
+    /// Shall be a byte having a value specified as a country code by Annex B
+    /// of Recommendation ITU-T T.35. Only present if itu_t_t35_country_code
+    /// is 0xff.
+    pub itu_t_t35_country_code_extension_byte: u32,
+    /// The bytes following the country code, as registered with ITU-T T.35.
+    pub itu_t_t35_payload_bytes: Vec<u8>,
+}
+
+/// Timecode metadata. See 5.8.7
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataTimecode {
+    pub obu_header: ObuHeader,
+    /// Specifies the method of dropping values of the n_frames syntax element.
+    pub counting_type: u32,
+    /// If set, specifies that the seconds_value, minutes_value, hours_value
+    /// syntax elements will be present.
+    pub full_timestamp_flag: bool,
+    /// If set, indicates that the difference between the current value of
+    /// clockTimestamp and the value of clockTimestamp computed from the
+    /// previous set of timestamp syntax elements in output order can be
+    /// interpreted as the time difference between the times of origin or
+    /// capture of the associated frames or fields.
+    pub discontinuity_flag: bool,
+    /// Specifies the skipping of one or more values of n_frames using the
+    /// counting method specified by counting_type.
+    pub cnt_dropped_flag: bool,
+    /// Used to compute clockTimestamp.
+    pub n_frames: u32,
+    /// If set, specifies that seconds_value and minutes_flag are present
+    /// when full_timestamp_flag is not set.
+    pub seconds_flag: bool,
+    /// If set, specifies that minutes_value and hours_flag are present.
+    pub minutes_flag: bool,
+    /// If set, specifies that hours_value is present.
+    pub hours_flag: bool,
+    /// Used to compute clockTimestamp.
+    pub seconds_value: u32,
+    /// Used to compute clockTimestamp.
+    pub minutes_value: u32,
+    /// Used to compute clockTimestamp.
+    pub hours_value: u32,
+    /// Specifies the length in bits of the time_offset_value syntax element.
+    pub time_offset_length: u32,
+    /// Used to compute clockTimestamp.
+    pub time_offset_value: u32,
+}
+
+/// Metadata of a type that is not handled, i.e. either unregistered user
+/// private data or reserved for future use.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MetadataUnknown {
+    pub obu_header: ObuHeader,
+    /// The metadata type, as signaled in the bitstream.
+    pub metadata_type: u32,
+    /// The raw payload, without the trailing bits.
+    pub payload: Vec<u8>,
+}
+
+/// A MetadataOBU. See 5.8.1
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum MetadataObu {
+    HdrCll(MetadataHdrCll),
+    HdrMdcv(MetadataHdrMdcv),
+    Scalability(MetadataScalability),
+    ItutT35(MetadataItutT35),
+    Timecode(MetadataTimecode),
+    Unknown(MetadataUnknown),
+}
+
+impl MetadataObu {
+    /// The header of the OBU holding this metadata.
+    pub fn obu_header(&self) -> &ObuHeader {
+        match self {
+            MetadataObu::HdrCll(m) => &m.obu_header,
+            MetadataObu::HdrMdcv(m) => &m.obu_header,
+            MetadataObu::Scalability(m) => &m.obu_header,
+            MetadataObu::ItutT35(m) => &m.obu_header,
+            MetadataObu::Timecode(m) => &m.obu_header,
+            MetadataObu::Unknown(m) => &m.obu_header,
+        }
+    }
+
+    /// The metadata_type syntax element for this metadata.
+    pub fn metadata_type(&self) -> u32 {
+        match self {
+            MetadataObu::HdrCll(_) => MetadataType::HdrCll as u32,
+            MetadataObu::HdrMdcv(_) => MetadataType::HdrMdcv as u32,
+            MetadataObu::Scalability(_) => MetadataType::Scalability as u32,
+            MetadataObu::ItutT35(_) => MetadataType::ItutT35 as u32,
+            MetadataObu::Timecode(_) => MetadataType::Timecode as u32,
+            MetadataObu::Unknown(m) => m.metadata_type,
+        }
+    }
+}
+
 #[derive(N, Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
 pub enum InterpolationFilter {
     #[default]
@@ -1468,8 +1717,11 @@ impl Parser {
         Ok(())
     }
 
-    fn parse_obu_header(r: &mut Reader) -> anyhow::Result<ObuHeader> {
-        let _obu_forbidden_bit = r.read_bit()?;
+    pub(crate) fn parse_obu_header(r: &mut Reader) -> anyhow::Result<ObuHeader> {
+        let obu_forbidden_bit = r.read_bit()?;
+        if obu_forbidden_bit {
+            return Err(anyhow!("Broken data: obu_forbidden_bit is set"));
+        }
 
         let mut header = ObuHeader {
             obu_type: ObuType::n(r.read_bits(4)?).ok_or(anyhow!("Invalid OBU type"))?,
@@ -1480,7 +1732,10 @@ impl Parser {
         };
 
         let obu_reserved_1bit = r.read_bit()?;
-        assert!(!obu_reserved_1bit); // Must be set to zero as per spec.
+        if obu_reserved_1bit {
+            // Must be set to zero as per spec.
+            return Err(anyhow!("Broken data: obu_reserved_1bit is set"));
+        }
 
         if header.extension_flag {
             header.temporal_id = r.read_bits(3)?;
@@ -1532,19 +1787,20 @@ impl Parser {
         // Both "low-overhead" and Annex B are now at the same point, i.e.: a
         // open_bitstream_unit() follows.
         let header = Self::parse_obu_header(&mut reader)?;
-        if matches!(self.stream_format, StreamFormat::LowOverhead) {
-            assert!(header.has_size_field);
+        if matches!(self.stream_format, StreamFormat::LowOverhead) && !header.has_size_field {
+            return Err(anyhow!(
+                "Broken data: obu_has_size_field is not set in a low-overhead stream"
+            ));
         }
 
         let obu_size = if header.has_size_field {
             reader.read_leb128()? as usize
         } else {
-            /* trap any bugs when computing the final length */
             obu_length
-                .checked_sub(1)
-                .unwrap()
-                .checked_sub(usize::from(header.extension_flag))
-                .unwrap()
+                .checked_sub(1 + usize::from(header.extension_flag))
+                .ok_or(anyhow!(
+                    "Broken data: obu_length is smaller than the OBU header"
+                ))?
         };
 
         let consumed = reader.consumed(start_pos);
@@ -1557,7 +1813,7 @@ impl Parser {
             annexb_state.frame_unit_consumed += u32::try_from(obu_size).unwrap();
         }
 
-        assert!(reader.position() % 8 == 0);
+        assert!(reader.position().is_multiple_of(8));
         let start_offset: usize = (reader.position() / 8).try_into().unwrap();
 
         log::debug!(
@@ -1580,9 +1836,13 @@ impl Parser {
             }
         }
 
+        let data = data
+            .get(..start_offset + obu_size)
+            .ok_or(anyhow!("Broken data: obu_size is larger than the data"))?;
+
         Ok(ParsedObu::Process(Obu {
             header,
-            data: Cow::from(&data[..start_offset + obu_size]),
+            data: Cow::from(data),
             start_offset,
             size: obu_size,
         }))
@@ -2100,7 +2360,7 @@ impl Parser {
                 self.mi_col_starts[i] = start_sb << sb_shift;
 
                 let max_width = std::cmp::min(sb_cols - start_sb, max_tile_width_sb);
-                ti.width_in_sbs_minus_1[i] = r.read_ns(max_width.try_into().unwrap())?;
+                ti.width_in_sbs_minus_1[i] = r.read_ns(max_width)?;
 
                 let size_sb = ti.width_in_sbs_minus_1[i] + 1;
                 widest_tile_sb = std::cmp::max(size_sb, widest_tile_sb);
@@ -2125,7 +2385,7 @@ impl Parser {
             while start_sb < sb_rows {
                 self.mi_row_starts[i] = start_sb << sb_shift;
                 let max_height = std::cmp::min(sb_rows - start_sb, max_tile_height_sb);
-                ti.height_in_sbs_minus_1[i] = r.read_ns(max_height.try_into().unwrap())?;
+                ti.height_in_sbs_minus_1[i] = r.read_ns(max_height)?;
 
                 let size_sb = ti.height_in_sbs_minus_1[i] + 1;
                 start_sb += size_sb;
@@ -3579,7 +3839,7 @@ impl Parser {
 
         let mut r = Reader::new(tg.obu.as_ref());
 
-        if r.remaining_bits() % 8 != 0 {
+        if !r.remaining_bits().is_multiple_of(8) {
             return Err(anyhow!("Bitstream is not byte aligned"));
         }
 
@@ -3605,7 +3865,9 @@ impl Parser {
 
         let end_bit_pos = r.position();
         let header_bytes = (end_bit_pos - start_bit_pos) / 8;
-        sz -= header_bytes;
+        sz = sz.checked_sub(header_bytes).ok_or(anyhow!(
+            "Broken data: tile group header larger than the OBU"
+        ))?;
 
         let mut tile_num = tg.tg_start;
         while tile_num <= tg.tg_end {
@@ -3618,7 +3880,11 @@ impl Parser {
                 tile_size = u32::try_from(sz).unwrap();
             } else {
                 tile_size = r.read_le(self.tile_size_bytes.try_into().unwrap())? + 1;
-                sz -= u64::from(tile_size + self.tile_size_bytes);
+                sz = sz
+                    .checked_sub(u64::from(tile_size) + u64::from(self.tile_size_bytes))
+                    .ok_or(anyhow!(
+                        "Broken data: tile_size_minus_1 larger than the OBU"
+                    ))?;
             }
 
             let tile = Tile {
@@ -3656,6 +3922,48 @@ impl Parser {
         Ok(tg)
     }
 
+    pub fn parse_tile_list_obu<'a>(&mut self, obu: Obu<'a>) -> anyhow::Result<TileListObu<'a>> {
+        if !matches!(obu.header.obu_type, ObuType::TileList) {
+            return Err(anyhow!(
+                "Expected a TileListOBU, got {:?}",
+                obu.header.obu_type
+            ));
+        }
+
+        let mut tl = TileListObu {
+            obu,
+            ..Default::default()
+        };
+
+        let mut r = Reader::new(tl.obu.as_ref());
+
+        tl.output_frame_width_in_tiles_minus_1 = r.read_bits(8)?;
+        tl.output_frame_height_in_tiles_minus_1 = r.read_bits(8)?;
+        let tile_count_minus_1 = r.read_bits(16)?;
+        if tile_count_minus_1 > 511 {
+            return Err(anyhow!("Invalid tile_count_minus_1 {}", tile_count_minus_1));
+        }
+
+        for _ in 0..=tile_count_minus_1 {
+            let mut entry = TileListEntry {
+                anchor_frame_idx: r.read_bits(8)?,
+                anchor_tile_row: r.read_bits(8)?,
+                anchor_tile_col: r.read_bits(8)?,
+                ..Default::default()
+            };
+
+            entry.tile_data_size = r.read_bits(16)? + 1;
+            entry.tile_data_offset = u32::try_from(r.position() / 8)?;
+
+            // Skip coded_tile_data, which is left to the accelerator.
+            r.skip(u64::from(entry.tile_data_size) * 8)?;
+
+            tl.entries.push(entry);
+        }
+
+        Ok(tl)
+    }
+
     pub fn parse_frame_obu<'a>(&mut self, obu: Obu<'a>) -> anyhow::Result<FrameObu<'a>> {
         if !matches!(obu.header.obu_type, ObuType::Frame) {
             return Err(anyhow!(
@@ -3679,8 +3987,15 @@ impl Parser {
         })
     }
 
+    /// Parses a FrameHeaderOBU, or the frame header of a FrameOBU. As per
+    /// frame_header_obu() in the specification, this returns a copy of the
+    /// previous frame header if one has already been seen for the current
+    /// frame, e.g. for a RedundantFrameHeaderOBU.
     pub fn parse_frame_header_obu(&mut self, obu: &Obu) -> anyhow::Result<FrameHeaderObu> {
-        if !matches!(obu.header.obu_type, ObuType::FrameHeader | ObuType::Frame) {
+        if !matches!(
+            obu.header.obu_type,
+            ObuType::FrameHeader | ObuType::RedundantFrameHeader | ObuType::Frame
+        ) {
             return Err(anyhow!(
                 "Expected a FrameHeaderOBU, got {:?}",
                 obu.header.obu_type
@@ -3691,7 +4006,6 @@ impl Parser {
             Ok(self
                 .last_frame_header
                 .clone()
-                .take()
                 .ok_or(anyhow!("Broken stream: no previous frame header to copy"))?)
         } else {
             self.seen_frame_header = true;
@@ -3709,6 +4023,195 @@ impl Parser {
         }
     }
 
+    /// Returns the bytes in `data` that precede the trailing bits. See 5.3.4
+    fn strip_trailing_bits(data: &[u8]) -> anyhow::Result<&[u8]> {
+        let last = data
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .ok_or(anyhow!("bad padding: trailing_one_bit is not set"))?;
+
+        if data[last] != 0x80 {
+            return Err(anyhow!("bad padding: payload is not byte aligned"));
+        }
+
+        Ok(&data[..last])
+    }
+
+    // 5.8.6
+    fn parse_scalability_structure(r: &mut Reader) -> anyhow::Result<ScalabilityStructure> {
+        let mut ss = ScalabilityStructure {
+            spatial_layers_cnt_minus_1: r.read_bits(2)?,
+            spatial_layer_dimensions_present_flag: r.read_bit()?,
+            spatial_layer_description_present_flag: r.read_bit()?,
+            temporal_group_description_present_flag: r.read_bit()?,
+            ..Default::default()
+        };
+
+        let _scalability_structure_reserved_3bits = r.read_bits(3)?;
+
+        let num_spatial_layers = ss.spatial_layers_cnt_minus_1 as usize + 1;
+
+        if ss.spatial_layer_dimensions_present_flag {
+            for i in 0..num_spatial_layers {
+                ss.spatial_layer_max_width[i] = r.read_bits(16)?;
+                ss.spatial_layer_max_height[i] = r.read_bits(16)?;
+            }
+        }
+
+        if ss.spatial_layer_description_present_flag {
+            for i in 0..num_spatial_layers {
+                ss.spatial_layer_ref_id[i] = r.read_bits(8)?;
+            }
+        }
+
+        if ss.temporal_group_description_present_flag {
+            let temporal_group_size = r.read_bits(8)?;
+            for _ in 0..temporal_group_size {
+                let mut tg = TemporalGroupDescription {
+                    temporal_id: r.read_bits(3)?,
+                    temporal_switching_up_point_flag: r.read_bit()?,
+                    spatial_switching_up_point_flag: r.read_bit()?,
+                    ref_cnt: r.read_bits(3)?,
+                    ..Default::default()
+                };
+
+                for j in 0..tg.ref_cnt as usize {
+                    tg.ref_pic_diff[j] = r.read_bits(8)?;
+                }
+
+                ss.temporal_group.push(tg);
+            }
+        }
+
+        Ok(ss)
+    }
+
+    // 5.8.7
+    fn parse_metadata_timecode(m: &mut MetadataTimecode, r: &mut Reader) -> anyhow::Result<()> {
+        m.counting_type = r.read_bits(5)?;
+        m.full_timestamp_flag = r.read_bit()?;
+        m.discontinuity_flag = r.read_bit()?;
+        m.cnt_dropped_flag = r.read_bit()?;
+        m.n_frames = r.read_bits(9)?;
+
+        if m.full_timestamp_flag {
+            m.seconds_value = r.read_bits(6)?;
+            m.minutes_value = r.read_bits(6)?;
+            m.hours_value = r.read_bits(5)?;
+        } else {
+            m.seconds_flag = r.read_bit()?;
+            if m.seconds_flag {
+                m.seconds_value = r.read_bits(6)?;
+                m.minutes_flag = r.read_bit()?;
+                if m.minutes_flag {
+                    m.minutes_value = r.read_bits(6)?;
+                    m.hours_flag = r.read_bit()?;
+                    if m.hours_flag {
+                        m.hours_value = r.read_bits(5)?;
+                    }
+                }
+            }
+        }
+
+        m.time_offset_length = r.read_bits(5)?;
+        if m.time_offset_length > 0 {
+            m.time_offset_value = r.read_bits(m.time_offset_length as u8)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses a MetadataOBU. Metadata types that are not handled are returned
+    /// as [`MetadataObu::Unknown`], holding the raw payload.
+    pub fn parse_metadata_obu(&mut self, obu: &Obu) -> anyhow::Result<MetadataObu> {
+        if !matches!(obu.header.obu_type, ObuType::Metadata) {
+            return Err(anyhow!(
+                "Expected a MetadataOBU, got {:?}",
+                obu.header.obu_type
+            ));
+        }
+
+        let data = obu.as_ref();
+        let mut r = Reader::new(data);
+        let obu_header = obu.header.clone();
+
+        let metadata_type = r.read_leb128()?;
+        let payload_start = usize::try_from(r.position() / 8)?;
+
+        let metadata = match MetadataType::n(metadata_type) {
+            Some(MetadataType::HdrCll) => MetadataObu::HdrCll(MetadataHdrCll {
+                obu_header,
+                max_cll: r.read_bits(16)?,
+                max_fall: r.read_bits(16)?,
+            }),
+            Some(MetadataType::HdrMdcv) => {
+                let mut m = MetadataHdrMdcv {
+                    obu_header,
+                    ..Default::default()
+                };
+
+                for i in 0..3 {
+                    m.primary_chromaticity_x[i] = r.read_bits(16)?;
+                    m.primary_chromaticity_y[i] = r.read_bits(16)?;
+                }
+
+                m.white_point_chromaticity_x = r.read_bits(16)?;
+                m.white_point_chromaticity_y = r.read_bits(16)?;
+                m.luminance_max = r.read_bits(32)?;
+                m.luminance_min = r.read_bits(32)?;
+
+                MetadataObu::HdrMdcv(m)
+            }
+            Some(MetadataType::Scalability) => {
+                let scalability_mode_idc = r.read_bits(8)?;
+                let scalability_structure = if scalability_mode_idc == SCALABILITY_SS {
+                    Some(Self::parse_scalability_structure(&mut r)?)
+                } else {
+                    None
+                };
+
+                MetadataObu::Scalability(MetadataScalability {
+                    obu_header,
+                    scalability_mode_idc,
+                    scalability_structure,
+                })
+            }
+            Some(MetadataType::ItutT35) => {
+                let mut m = MetadataItutT35 {
+                    obu_header,
+                    itu_t_t35_country_code: r.read_bits(8)?,
+                    ..Default::default()
+                };
+
+                if m.itu_t_t35_country_code == 0xff {
+                    m.itu_t_t35_country_code_extension_byte = r.read_bits(8)?;
+                }
+
+                let payload_bytes = usize::try_from(r.position() / 8)?;
+                m.itu_t_t35_payload_bytes =
+                    Self::strip_trailing_bits(&data[payload_bytes..])?.to_vec();
+
+                MetadataObu::ItutT35(m)
+            }
+            Some(MetadataType::Timecode) => {
+                let mut m = MetadataTimecode {
+                    obu_header,
+                    ..Default::default()
+                };
+
+                Self::parse_metadata_timecode(&mut m, &mut r)?;
+                MetadataObu::Timecode(m)
+            }
+            None => MetadataObu::Unknown(MetadataUnknown {
+                obu_header,
+                metadata_type,
+                payload: Self::strip_trailing_bits(&data[payload_start..])?.to_vec(),
+            }),
+        };
+
+        Ok(metadata)
+    }
+
     /// Implements 7.20. This function should be called right after decoding a
     /// frame.
     pub fn ref_frame_update(&mut self, fh: &FrameHeaderObu) -> anyhow::Result<()> {
@@ -3840,7 +4343,10 @@ impl Clone for Parser {
 
 #[cfg(test)]
 mod tests {
-    use crate::codec::av1::parser::{ParsedObu, Parser, StreamFormat};
+    use crate::codec::av1::parser::{
+        MetadataHdrCll, MetadataItutT35, MetadataObu, MetadataUnknown, ObuHeader, ParsedObu,
+        Parser, StreamFormat,
+    };
     use crate::utils::IvfIterator;
 
     use super::ObuType;
@@ -4010,4 +4516,61 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn parse_metadata_obus() {
+        // A temporal delimiter, followed by HDR_CLL, ITU-T T.35 and
+        // unregistered metadata.
+        const METADATA_OBUS: [u8; 28] = [
+            0x12, 0x00, 0x2a, 0x06, 0x01, 0x03, 0xe8, 0x01, 0x90, 0x80, 0x2a, 0x09, 0x04, 0xb5,
+            0x00, 0x3c, 0x00, 0x01, 0x04, 0x01, 0x80, 0x2a, 0x05, 0x06, 0xab, 0x00, 0x80, 0x00,
+        ];
+
+        let obu_header = ObuHeader {
+            obu_type: ObuType::Metadata,
+            extension_flag: false,
+            has_size_field: true,
+            temporal_id: 0,
+            spatial_id: 0,
+        };
+
+        let mut parser = Parser::default();
+        let mut metadata = vec![];
+        let mut consumed = 0;
+
+        while consumed < METADATA_OBUS.len() {
+            let ParsedObu::Process(obu) = parser.parse_obu(&METADATA_OBUS[consumed..]).unwrap()
+            else {
+                panic!("unexpected dropped OBU");
+            };
+
+            if obu.header.obu_type == ObuType::Metadata {
+                metadata.push(parser.parse_metadata_obu(&obu).unwrap());
+            }
+
+            consumed += obu.data.len();
+        }
+
+        assert_eq!(
+            metadata,
+            vec![
+                MetadataObu::HdrCll(MetadataHdrCll {
+                    obu_header: obu_header.clone(),
+                    max_cll: 1000,
+                    max_fall: 400,
+                }),
+                MetadataObu::ItutT35(MetadataItutT35 {
+                    obu_header: obu_header.clone(),
+                    itu_t_t35_country_code: 0xb5,
+                    itu_t_t35_country_code_extension_byte: 0,
+                    itu_t_t35_payload_bytes: vec![0x00, 0x3c, 0x00, 0x01, 0x04, 0x01],
+                }),
+                MetadataObu::Unknown(MetadataUnknown {
+                    obu_header,
+                    metadata_type: 6,
+                    payload: vec![0xab, 0x00],
+                }),
+            ]
+        );
+    }
 }
@@ -83,9 +83,10 @@ impl<'a> Reader<'a> {
             }
         }
 
-        assert!(leb128bytes < 8);
-        assert!(value <= u32::MAX.into());
-        Ok(value as u32)
+        if leb128bytes >= 8 {
+            return Err(anyhow!("Broken data: leb128 value longer than 7 bytes"));
+        }
+        u32::try_from(value).map_err(|_| anyhow!("Broken data: leb128 value larger than 32 bits"))
     }
 
     /// Implements su(n): Signed integer converted from an n bits unsigned
@@ -104,17 +105,17 @@ impl<'a> Reader<'a> {
 
     /// Implements ns(n): Unsigned encoded integer with maximum number of values
     /// n (i.e. output in range 0..n-1). See 4.10.7
-    pub fn read_ns(&mut self, num_bits: u8) -> anyhow::Result<u32> {
-        let w = helpers::floor_log2(u32::from(num_bits)) + 1;
-        let m = (1 << w) - num_bits;
+    pub fn read_ns(&mut self, n: u32) -> anyhow::Result<u32> {
+        let w = helpers::floor_log2(n) + 1;
+        let m = ((1u64 << w) - u64::from(n)) as u32;
         let v = self.read_bits(u8::try_from(w)? - 1)?;
 
-        if v < m.into() {
+        if v < m {
             return Ok(v);
         }
 
         let extra_bit = self.read_bit()?;
-        Ok((v << 1) - u32::from(m) + u32::from(extra_bit))
+        Ok((v << 1) - m + u32::from(extra_bit))
     }
 
     /// Implements 5.9.13: Delta quantizer syntax.
@@ -133,7 +134,7 @@ impl<'a> Reader<'a> {
     }
 
     pub(crate) fn consumed(&self, start_pos: u32) -> u32 {
-        assert!(self.position() % 8 == 0);
+        assert!(self.position().is_multiple_of(8));
         (self.position() / 8) as u32 - start_pos
     }
 
@@ -233,8 +234,7 @@ impl<'a> Reader<'a> {
             let b2 = if i != 0 { k + i - 1 } else { k };
             let a = 1 << b2;
             if num_syms <= mk + 3 * a {
-                let num_bits = u8::try_from(num_syms - mk).unwrap();
-                let subexp_final_bits = self.read_ns(num_bits)?;
+                let subexp_final_bits = self.read_ns(u32::try_from(num_syms - mk)?)?;
                 return Ok(subexp_final_bits);
             } else {
                 let subexp_more_bits = self.read_bit()?;
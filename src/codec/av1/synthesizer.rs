@@ -12,16 +12,27 @@ use crate::codec::av1::parser::BitDepth;
 use crate::codec::av1::parser::ChromaSamplePosition;
 use crate::codec::av1::parser::ColorPrimaries;
 use crate::codec::av1::parser::FrameHeaderObu;
+use crate::codec::av1::parser::FrameObu;
 use crate::codec::av1::parser::FrameRestorationType;
 use crate::codec::av1::parser::FrameType;
 use crate::codec::av1::parser::InterpolationFilter;
 use crate::codec::av1::parser::MatrixCoefficients;
+use crate::codec::av1::parser::MetadataHdrMdcv;
+use crate::codec::av1::parser::MetadataObu;
+use crate::codec::av1::parser::MetadataTimecode;
+use crate::codec::av1::parser::MetadataType;
 use crate::codec::av1::parser::ObuHeader;
 use crate::codec::av1::parser::ObuType;
+use crate::codec::av1::parser::PaddingObu;
 use crate::codec::av1::parser::Profile;
 use crate::codec::av1::parser::ReferenceFrameType;
+use crate::codec::av1::parser::ScalabilityStructure;
 use crate::codec::av1::parser::SequenceHeaderObu;
 use crate::codec::av1::parser::TemporalDelimiterObu;
+use crate::codec::av1::parser::TileGroupObu;
+use crate::codec::av1::parser::TileInfo;
+use crate::codec::av1::parser::TileListEntry;
+use crate::codec::av1::parser::TileListObu;
 use crate::codec::av1::parser::TransferCharacteristics;
 use crate::codec::av1::parser::TxMode;
 use crate::codec::av1::parser::WarpModelType;
@@ -34,6 +45,7 @@ use crate::codec::av1::parser::MAX_SEGMENTS;
 use crate::codec::av1::parser::NUM_REF_FRAMES;
 use crate::codec::av1::parser::PRIMARY_REF_NONE;
 use crate::codec::av1::parser::REFS_PER_FRAME;
+use crate::codec::av1::parser::SCALABILITY_SS;
 use crate::codec::av1::parser::SEG_LVL_MAX;
 use crate::codec::av1::parser::SELECT_INTEGER_MV;
 use crate::codec::av1::parser::SELECT_SCREEN_CONTENT_TOOLS;
@@ -54,6 +66,16 @@ impl private::ObuStruct for TemporalDelimiterObu {}
 
 impl private::ObuStruct for FrameHeaderObu {}
 
+impl private::ObuStruct for MetadataObu {}
+
+impl private::ObuStruct for PaddingObu {}
+
+impl private::ObuStruct for TileGroupObu<'_> {}
+
+impl private::ObuStruct for FrameObu<'_> {}
+
+impl private::ObuStruct for TileListObu<'_> {}
+
 #[derive(Error, Debug)]
 pub enum SynthesizerError {
     #[error("tried to synthesize unsupported settings")]
@@ -149,6 +171,15 @@ where
         self.leb128(size)
     }
 
+    /// Writes AV1 5.3.5. Byte alignment syntax
+    fn byte_alignment(&mut self) -> SynthesizerResult<()> {
+        while !self.writer.aligned() {
+            self.f(1, /* zero_bit */ 0u32)?;
+        }
+
+        Ok(())
+    }
+
     /// Writes AV1 5.3.4. Trailing bits syntax
     fn trailing_bits(&mut self) -> SynthesizerResult<()> {
         self.f(1, /* trailing_one_bit */ 1u32)?;
@@ -602,6 +633,186 @@ where
     }
 }
 
+impl<'o, W> Synthesizer<'o, MetadataObu, W>
+where
+    W: Write,
+{
+    pub fn synthesize(obu: &'o MetadataObu, mut writer: W) -> SynthesizerResult<()> {
+        let mut s = Synthesizer::new(&mut writer, obu);
+
+        if obu.obu_header().obu_type != ObuType::Metadata {
+            s.invalid_element_value("obu_type")?;
+        }
+
+        s.obu_header(obu.obu_header())?;
+
+        if !obu.obu_header().has_size_field {
+            s.metadata_obu()?;
+            s.trailing_bits()?;
+            return Ok(());
+        }
+
+        let mut buf = Vec::<u8>::new();
+        let mut buffered = Synthesizer::new(&mut buf, obu);
+        buffered.metadata_obu()?;
+        buffered.trailing_bits()?;
+        drop(buffered);
+
+        s.obu_size(buf.len() as u32)?;
+        drop(s);
+
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
+
+    /// Writes raw bytes, e.g. a payload whose syntax is not defined by AV1.
+    fn payload_bytes(&mut self, bytes: &[u8]) -> SynthesizerResult<()> {
+        for &byte in bytes {
+            self.f(8, byte)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.8.1. General metadata OBU syntax
+    fn metadata_obu(&mut self) -> SynthesizerResult<()> {
+        self.leb128(self.obu.metadata_type())?;
+
+        match self.obu {
+            MetadataObu::HdrCll(m) => {
+                self.f(16, m.max_cll)?;
+                self.f(16, m.max_fall)?;
+            }
+            MetadataObu::HdrMdcv(m) => self.metadata_hdr_mdcv(m)?,
+            MetadataObu::Scalability(m) => {
+                self.f(8, m.scalability_mode_idc)?;
+
+                match &m.scalability_structure {
+                    Some(ss) if m.scalability_mode_idc == SCALABILITY_SS => {
+                        self.scalability_structure(ss)?
+                    }
+                    None if m.scalability_mode_idc != SCALABILITY_SS => (),
+                    _ => self.invalid_element_value("scalability_structure")?,
+                }
+            }
+            MetadataObu::ItutT35(m) => {
+                self.f(8, m.itu_t_t35_country_code)?;
+                if m.itu_t_t35_country_code == 0xff {
+                    self.f(8, m.itu_t_t35_country_code_extension_byte)?;
+                }
+
+                self.payload_bytes(&m.itu_t_t35_payload_bytes)?;
+            }
+            MetadataObu::Timecode(m) => self.metadata_timecode(m)?,
+            MetadataObu::Unknown(m) => {
+                if MetadataType::n(m.metadata_type).is_some() {
+                    self.invalid_element_value("metadata_type")?;
+                }
+
+                self.payload_bytes(&m.payload)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.8.4. Metadata high dynamic range mastering display color
+    /// volume syntax
+    fn metadata_hdr_mdcv(&mut self, m: &MetadataHdrMdcv) -> SynthesizerResult<()> {
+        for i in 0..3 {
+            self.f(16, m.primary_chromaticity_x[i])?;
+            self.f(16, m.primary_chromaticity_y[i])?;
+        }
+
+        self.f(16, m.white_point_chromaticity_x)?;
+        self.f(16, m.white_point_chromaticity_y)?;
+        self.f(32, m.luminance_max)?;
+        self.f(32, m.luminance_min)?;
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.8.6. Scalability structure syntax
+    fn scalability_structure(&mut self, ss: &ScalabilityStructure) -> SynthesizerResult<()> {
+        self.f(2, ss.spatial_layers_cnt_minus_1)?;
+        self.f(1, ss.spatial_layer_dimensions_present_flag)?;
+        self.f(1, ss.spatial_layer_description_present_flag)?;
+        self.f(1, ss.temporal_group_description_present_flag)?;
+        self.f(3, /* scalability_structure_reserved_3bits */ 0u32)?;
+
+        let num_spatial_layers = ss.spatial_layers_cnt_minus_1 as usize + 1;
+
+        if ss.spatial_layer_dimensions_present_flag {
+            for i in 0..num_spatial_layers {
+                self.f(16, ss.spatial_layer_max_width[i])?;
+                self.f(16, ss.spatial_layer_max_height[i])?;
+            }
+        }
+
+        if ss.spatial_layer_description_present_flag {
+            for i in 0..num_spatial_layers {
+                self.f(8, ss.spatial_layer_ref_id[i])?;
+            }
+        }
+
+        if ss.temporal_group_description_present_flag {
+            if ss.temporal_group.len() > 0xff {
+                self.invalid_element_value("temporal_group_size")?;
+            }
+
+            self.f(8, ss.temporal_group.len() as u32)?;
+            for tg in &ss.temporal_group {
+                self.f(3, tg.temporal_id)?;
+                self.f(1, tg.temporal_switching_up_point_flag)?;
+                self.f(1, tg.spatial_switching_up_point_flag)?;
+                self.f(3, tg.ref_cnt)?;
+
+                for j in 0..tg.ref_cnt as usize {
+                    self.f(8, tg.ref_pic_diff[j])?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.8.7. Metadata timecode syntax
+    fn metadata_timecode(&mut self, m: &MetadataTimecode) -> SynthesizerResult<()> {
+        self.f(5, m.counting_type)?;
+        self.f(1, m.full_timestamp_flag)?;
+        self.f(1, m.discontinuity_flag)?;
+        self.f(1, m.cnt_dropped_flag)?;
+        self.f(9, m.n_frames)?;
+
+        if m.full_timestamp_flag {
+            self.f(6, m.seconds_value)?;
+            self.f(6, m.minutes_value)?;
+            self.f(5, m.hours_value)?;
+        } else {
+            self.f(1, m.seconds_flag)?;
+            if m.seconds_flag {
+                self.f(6, m.seconds_value)?;
+                self.f(1, m.minutes_flag)?;
+                if m.minutes_flag {
+                    self.f(6, m.minutes_value)?;
+                    self.f(1, m.hours_flag)?;
+                    if m.hours_flag {
+                        self.f(5, m.hours_value)?;
+                    }
+                }
+            }
+        }
+
+        self.f(5, m.time_offset_length)?;
+        if m.time_offset_length > 0 {
+            self.f(m.time_offset_length as usize, m.time_offset_value)?;
+        }
+
+        Ok(())
+    }
+}
+
 impl<'o, W> Synthesizer<'o, FrameHeaderObu, W>
 where
     W: Write,
@@ -1056,7 +1267,7 @@ where
         self.f(6, self.obu.loop_filter_params.loop_filter_level[1])?;
         if sequence.num_planes > 1 {
             if self.obu.loop_filter_params.loop_filter_level[0] != 0
-                && self.obu.loop_filter_params.loop_filter_level[1] != 0
+                || self.obu.loop_filter_params.loop_filter_level[1] != 0
             {
                 self.f(6, self.obu.loop_filter_params.loop_filter_level[2])?;
                 self.f(6, self.obu.loop_filter_params.loop_filter_level[3])?;
@@ -1073,6 +1284,7 @@ where
                     // frames, always update the value to make sure the decoder will recreate
                     // the same state.
                     const UPDATE_REF_DELTA: bool = true;
+                    self.f(1, UPDATE_REF_DELTA)?;
                     if UPDATE_REF_DELTA {
                         self.su(1 + 6, self.obu.loop_filter_params.loop_filter_ref_deltas[i])?;
                     }
@@ -1081,6 +1293,7 @@ where
                 for i in 0..2 {
                     // NOTE: Same as above
                     const UPDATE_MODE_DELTA: bool = true;
+                    self.f(1, UPDATE_MODE_DELTA)?;
                     if UPDATE_MODE_DELTA {
                         self.su(
                             1 + 6,
@@ -1524,6 +1737,288 @@ where
     }
 }
 
+impl<'o, W> Synthesizer<'o, PaddingObu, W>
+where
+    W: Write,
+{
+    /// Writes a padding OBU. Its total size is [`PaddingObu::obu_padding_length`]
+    /// plus the OBU header, the `obu_size` field if present and the trailing
+    /// byte.
+    pub fn synthesize(obu: &'o PaddingObu, mut writer: W) -> SynthesizerResult<()> {
+        let mut s = Synthesizer::new(&mut writer, obu);
+
+        if obu.obu_header.obu_type != ObuType::Padding {
+            s.invalid_element_value("obu_type")?;
+        }
+
+        s.obu_header(&obu.obu_header)?;
+
+        if obu.obu_header.has_size_field {
+            // The padding bytes followed by the trailing bits.
+            s.obu_size(obu.obu_padding_length + 1)?;
+        }
+
+        s.padding_obu()?;
+        s.trailing_bits()?;
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.7. Padding OBU syntax
+    fn padding_obu(&mut self) -> SynthesizerResult<()> {
+        for _ in 0..self.obu.obu_padding_length {
+            self.f(8, /* obu_padding_byte */ 0u32)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'o, 'a, W> Synthesizer<'o, TileGroupObu<'a>, W>
+where
+    W: Write,
+{
+    /// Writes a tile group OBU holding the tiles in [`TileGroupObu::tiles`],
+    /// whose data is read from [`TileGroupObu::obu`] at `tile_offset`. The
+    /// tile layout is given by `tile_info`, usually the one of the frame
+    /// header preceding this tile group.
+    pub fn synthesize(
+        obu: &'o TileGroupObu<'a>,
+        tile_info: &TileInfo,
+        mut writer: W,
+    ) -> SynthesizerResult<()> {
+        let mut s = Synthesizer::new(&mut writer, obu);
+
+        if obu.obu.header.obu_type != ObuType::TileGroup {
+            s.invalid_element_value("obu_type")?;
+        }
+
+        s.obu_header(&obu.obu.header)?;
+
+        if !obu.obu.header.has_size_field {
+            s.tile_group_obu(tile_info)?;
+            return Ok(());
+        }
+
+        let mut buf = Vec::<u8>::new();
+        let mut buffered = Synthesizer::new(&mut buf, obu);
+        buffered.tile_group_obu(tile_info)?;
+        drop(buffered);
+
+        s.obu_size(buf.len() as u32)?;
+        drop(s);
+
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.11.1. General tile group OBU syntax
+    fn tile_group_obu(&mut self, tile_info: &TileInfo) -> SynthesizerResult<()> {
+        // NumTiles
+        let num_tiles = tile_info.tile_cols * tile_info.tile_rows;
+
+        if num_tiles > 1 {
+            self.f(1, self.obu.tile_start_and_end_present_flag)?;
+        } else if self.obu.tile_start_and_end_present_flag {
+            self.invalid_element_value("tile_start_and_end_present_flag")?;
+        }
+
+        if num_tiles > 1 && self.obu.tile_start_and_end_present_flag {
+            let tile_bits = usize::try_from(tile_info.tile_cols_log2 + tile_info.tile_rows_log2)?;
+            self.f(tile_bits, self.obu.tg_start)?;
+            self.f(tile_bits, self.obu.tg_end)?;
+        } else if self.obu.tg_start != 0 || self.obu.tg_end + 1 != num_tiles {
+            self.invalid_element_value("tg_end")?;
+        }
+
+        self.byte_alignment()?;
+
+        if self.obu.tg_end < self.obu.tg_start
+            || self.obu.tiles.len() != (self.obu.tg_end - self.obu.tg_start + 1) as usize
+        {
+            return Err(SynthesizerError::InvalidSyntaxElementValue("tiles"));
+        }
+
+        let data = self.obu.obu.as_ref();
+        let tile_size_bytes = usize::try_from(tile_info.tile_size_bytes)?;
+
+        for (i, tile) in self.obu.tiles.iter().enumerate() {
+            let last_tile = i + 1 == self.obu.tiles.len();
+
+            if !last_tile {
+                if tile.tile_size == 0
+                    || u64::from(tile.tile_size - 1) >> (8 * tile_size_bytes) != 0
+                {
+                    self.invalid_element_value("tile_size_minus_1")?;
+                }
+
+                self.writer
+                    .write_le(tile_size_bytes, tile.tile_size.saturating_sub(1))?;
+            }
+
+            let start = tile.tile_offset as usize;
+            let tile_data = data
+                .get(start..start + tile.tile_size as usize)
+                .ok_or(SynthesizerError::InvalidSyntaxElementValue("tile_offset"))?;
+
+            for &byte in tile_data {
+                self.f(8, byte)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'o, 'a, W> Synthesizer<'o, FrameObu<'a>, W>
+where
+    W: Write,
+{
+    /// Writes a frame OBU, i.e. the frame header followed by the tile group in
+    /// a single OBU. The header of the OBU is taken from
+    /// [`FrameObu::header`].
+    pub fn synthesize(
+        obu: &'o FrameObu<'a>,
+        sequence: &'o SequenceHeaderObu,
+        mut writer: W,
+    ) -> SynthesizerResult<()> {
+        let mut s = Synthesizer::new(&mut writer, obu);
+
+        if obu.header.obu_header.obu_type != ObuType::Frame {
+            s.invalid_element_value("obu_type")?;
+        }
+
+        s.obu_header(&obu.header.obu_header)?;
+
+        if !obu.header.obu_header.has_size_field {
+            drop(s);
+            return Self::frame_obu(obu, sequence, writer);
+        }
+
+        let mut buf = Vec::<u8>::new();
+        Self::frame_obu(obu, sequence, &mut buf)?;
+
+        s.obu_size(buf.len() as u32)?;
+        drop(s);
+
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.10. Frame OBU syntax
+    fn frame_obu<O: Write>(
+        obu: &'o FrameObu<'a>,
+        sequence: &'o SequenceHeaderObu,
+        mut writer: O,
+    ) -> SynthesizerResult<()> {
+        let mut header = Synthesizer::new(&mut writer, &obu.header);
+        header.frame_header_obu(sequence)?;
+        header.byte_alignment()?;
+        drop(header);
+
+        let mut tile_group = Synthesizer::new(&mut writer, &obu.tile_group);
+        tile_group.tile_group_obu(&obu.header.tile_info)
+    }
+}
+
+impl<'o, 'a, W> Synthesizer<'o, TileListObu<'a>, W>
+where
+    W: Write,
+{
+    /// Writes a tile list OBU holding the entries in [`TileListObu::entries`],
+    /// whose coded tile data is read from [`TileListObu::obu`] at
+    /// `tile_data_offset`.
+    pub fn synthesize(obu: &'o TileListObu<'a>, mut writer: W) -> SynthesizerResult<()> {
+        let mut s = Synthesizer::new(&mut writer, obu);
+
+        if obu.obu.header.obu_type != ObuType::TileList {
+            s.invalid_element_value("obu_type")?;
+        }
+
+        s.obu_header(&obu.obu.header)?;
+
+        if !obu.obu.header.has_size_field {
+            s.tile_list_obu()?;
+            return Ok(());
+        }
+
+        let mut buf = Vec::<u8>::new();
+        let mut buffered = Synthesizer::new(&mut buf, obu);
+        buffered.tile_list_obu()?;
+        drop(buffered);
+
+        s.obu_size(buf.len() as u32)?;
+        drop(s);
+
+        writer.write_all(&buf)?;
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.12.1. General tile list OBU syntax
+    fn tile_list_obu(&mut self) -> SynthesizerResult<()> {
+        let obu = self.obu;
+
+        self.f(8, obu.output_frame_width_in_tiles_minus_1)?;
+        self.f(8, obu.output_frame_height_in_tiles_minus_1)?;
+
+        // It is a requirement of bitstream conformance that
+        // tile_count_minus_1 is less than or equal to 511.
+        if obu.entries.is_empty() || obu.entries.len() > 512 {
+            return Err(SynthesizerError::InvalidSyntaxElementValue(
+                "tile_count_minus_1",
+            ));
+        }
+
+        self.f(16, obu.entries.len() as u32 - 1)?;
+
+        for entry in &obu.entries {
+            self.tile_list_entry(entry)?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes AV1 5.12.2. Tile list entry syntax
+    fn tile_list_entry(&mut self, entry: &TileListEntry) -> SynthesizerResult<()> {
+        // It is a requirement of bitstream conformance that anchor_frame_idx
+        // is less than or equal to 127.
+        if entry.anchor_frame_idx > 127 {
+            self.invalid_element_value("anchor_frame_idx")?;
+        }
+
+        self.f(8, entry.anchor_frame_idx)?;
+        self.f(8, entry.anchor_tile_row)?;
+        self.f(8, entry.anchor_tile_col)?;
+
+        if entry.tile_data_size == 0 || entry.tile_data_size > 1 << 16 {
+            return Err(SynthesizerError::InvalidSyntaxElementValue(
+                "tile_data_size_minus_1",
+            ));
+        }
+
+        self.f(16, entry.tile_data_size - 1)?;
+
+        let start = entry.tile_data_offset as usize;
+        let tile_data = self
+            .obu
+            .obu
+            .as_ref()
+            .get(start..start + entry.tile_data_size as usize)
+            .ok_or(SynthesizerError::InvalidSyntaxElementValue(
+                "tile_data_offset",
+            ))?;
+
+        for &byte in tile_data {
+            self.f(8, /* coded_tile_data */ byte)?;
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -1531,7 +2026,18 @@ mod tests {
     use crate::codec::av1::parser::CdefParams;
     use crate::codec::av1::parser::ChromaSamplePosition;
     use crate::codec::av1::parser::ColorConfig;
+    use crate::codec::av1::parser::MetadataHdrCll;
+    use crate::codec::av1::parser::MetadataItutT35;
+    use crate::codec::av1::parser::MetadataScalability;
+    use crate::codec::av1::parser::MetadataUnknown;
+    use crate::codec::av1::parser::Obu;
+    use crate::codec::av1::parser::ParsedObu;
+    use crate::codec::av1::parser::Parser;
+    use crate::codec::av1::parser::QuantizationParams;
+    use crate::codec::av1::parser::TemporalGroupDescription;
+    use crate::codec::av1::parser::Tile;
     use crate::codec::av1::parser::TileInfo;
+    use crate::codec::av1::parser::TileListEntry;
     use crate::codec::av1::parser::MAX_TILE_COLS;
     use crate::codec::av1::parser::MAX_TILE_ROWS;
 
@@ -1692,12 +2198,126 @@ mod tests {
     }
 
     #[test]
-    fn frame_header_obu() {
-        let _ = env_logger::try_init();
+    fn metadata_obu_hdr_cll() {
+        const HDR_CLL_RAW: [u8; 8] = [0x2a, 0x06, 0x01, 0x03, 0xe8, 0x01, 0x90, 0x80];
+
+        let metadata = MetadataObu::HdrCll(MetadataHdrCll {
+            obu_header: ObuHeader {
+                obu_type: ObuType::Metadata,
+                extension_flag: false,
+                has_size_field: true,
+                temporal_id: 0,
+                spatial_id: 0,
+            },
+            max_cll: 1000,
+            max_fall: 400,
+        });
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, MetadataObu, _>::synthesize(&metadata, &mut buf).unwrap();
+        assert_eq!(buf, HDR_CLL_RAW);
+    }
+
+    #[test]
+    fn metadata_obu_roundtrip() {
+        let obu_header = ObuHeader {
+            obu_type: ObuType::Metadata,
+            extension_flag: true,
+            has_size_field: true,
+            temporal_id: 1,
+            spatial_id: 0,
+        };
+
+        let metadata = [
+            MetadataObu::HdrMdcv(MetadataHdrMdcv {
+                obu_header: obu_header.clone(),
+                primary_chromaticity_x: [34000, 13250, 7500],
+                primary_chromaticity_y: [16000, 34500, 3000],
+                white_point_chromaticity_x: 15635,
+                white_point_chromaticity_y: 16450,
+                luminance_max: 1000 << 8,
+                luminance_min: 50,
+            }),
+            MetadataObu::Scalability(MetadataScalability {
+                obu_header: obu_header.clone(),
+                scalability_mode_idc: SCALABILITY_SS,
+                scalability_structure: Some(ScalabilityStructure {
+                    spatial_layers_cnt_minus_1: 1,
+                    spatial_layer_dimensions_present_flag: true,
+                    spatial_layer_description_present_flag: true,
+                    temporal_group_description_present_flag: true,
+                    spatial_layer_max_width: [320, 640, 0, 0],
+                    spatial_layer_max_height: [240, 480, 0, 0],
+                    spatial_layer_ref_id: [0, 0, 0, 0],
+                    temporal_group: vec![
+                        TemporalGroupDescription {
+                            temporal_id: 0,
+                            temporal_switching_up_point_flag: true,
+                            spatial_switching_up_point_flag: true,
+                            ref_cnt: 1,
+                            ref_pic_diff: [2, 0, 0, 0, 0, 0, 0],
+                        },
+                        TemporalGroupDescription {
+                            temporal_id: 1,
+                            temporal_switching_up_point_flag: true,
+                            spatial_switching_up_point_flag: false,
+                            ref_cnt: 2,
+                            ref_pic_diff: [1, 3, 0, 0, 0, 0, 0],
+                        },
+                    ],
+                }),
+            }),
+            MetadataObu::ItutT35(MetadataItutT35 {
+                obu_header: obu_header.clone(),
+                itu_t_t35_country_code: 0xff,
+                itu_t_t35_country_code_extension_byte: 0x12,
+                itu_t_t35_payload_bytes: vec![0x00, 0x00, 0x01, 0x80],
+            }),
+            MetadataObu::Timecode(MetadataTimecode {
+                obu_header: obu_header.clone(),
+                counting_type: 4,
+                discontinuity_flag: true,
+                n_frames: 300,
+                seconds_flag: true,
+                seconds_value: 59,
+                minutes_flag: true,
+                minutes_value: 30,
+                time_offset_length: 12,
+                time_offset_value: 0xabc,
+                ..Default::default()
+            }),
+            MetadataObu::Unknown(MetadataUnknown {
+                obu_header,
+                metadata_type: 200,
+                payload: vec![0xde, 0xad, 0xbe, 0xef],
+            }),
+        ];
+
+        let mut buf = Vec::<u8>::new();
+        for m in &metadata {
+            Synthesizer::<'_, MetadataObu, _>::synthesize(m, &mut buf).unwrap();
+        }
+
+        let mut parser = Parser::default();
+        let mut consumed = 0;
+        for m in &metadata {
+            let ParsedObu::Process(obu) = parser.parse_obu(&buf[consumed..]).unwrap() else {
+                panic!("unexpected dropped OBU");
+            };
+
+            assert_eq!(&parser.parse_metadata_obu(&obu).unwrap(), m);
+            consumed += obu.data.len();
+        }
+
+        assert_eq!(consumed, buf.len());
+    }
 
-        const WIDTH: u32 = 512;
-        const HEIGHT: u32 = 512;
+    const WIDTH: u32 = 512;
+    const HEIGHT: u32 = 512;
 
+    /// Returns a sequence header and the header of a single tile key frame
+    /// for a WIDTH x HEIGHT stream.
+    fn key_frame_headers() -> (SequenceHeaderObu, FrameHeaderObu) {
         let seq = SequenceHeaderObu {
             obu_header: ObuHeader {
                 obu_type: ObuType::SequenceHeader,
@@ -1768,6 +2388,11 @@ mod tests {
                 ..Default::default()
             },
 
+            quantization_params: QuantizationParams {
+                base_q_idx: 100,
+                ..Default::default()
+            },
+
             cdef_params: CdefParams {
                 cdef_damping: 3,
                 ..Default::default()
@@ -1783,6 +2408,15 @@ mod tests {
             ..Default::default()
         };
 
+        (seq, frame)
+    }
+
+    #[test]
+    fn frame_header_obu() {
+        let _ = env_logger::try_init();
+
+        let (seq, frame) = key_frame_headers();
+
         let mut buf = Vec::<u8>::new();
         Synthesizer::<'_, SequenceHeaderObu, _>::synthesize(&seq, &mut buf).unwrap();
 
@@ -1797,4 +2431,274 @@ mod tests {
             out.flush().unwrap();
         }
     }
+
+    #[test]
+    fn frame_header_obu_loop_filter_params() {
+        let (seq, mut frame) = key_frame_headers();
+
+        // The chroma levels are present as soon as one of the luma levels is
+        // not zero.
+        let lf = &mut frame.loop_filter_params;
+        lf.loop_filter_level = [0, 10, 4, 6];
+        lf.loop_filter_sharpness = 2;
+        lf.loop_filter_delta_enabled = true;
+        lf.loop_filter_delta_update = true;
+        lf.loop_filter_ref_deltas = [1, 0, 0, 0, -1, 0, -1, -2];
+        lf.loop_filter_mode_deltas = [3, -3];
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, SequenceHeaderObu, _>::synthesize(&seq, &mut buf).unwrap();
+        Synthesizer::<'_, FrameHeaderObu, _>::synthesize(&frame, &seq, &mut buf).unwrap();
+
+        let mut parser = Parser::default();
+        let ParsedObu::Process(obu) = parser.parse_obu(&buf).unwrap() else {
+            panic!("unexpected dropped OBU");
+        };
+        parser.parse_sequence_header_obu(&obu).unwrap();
+        let consumed = obu.data.len();
+
+        let ParsedObu::Process(obu) = parser.parse_obu(&buf[consumed..]).unwrap() else {
+            panic!("unexpected dropped OBU");
+        };
+        let parsed = parser.parse_frame_header_obu(&obu).unwrap();
+        assert_eq!(parsed.loop_filter_params, frame.loop_filter_params);
+        assert_eq!(
+            parsed.quantization_params.base_q_idx,
+            frame.quantization_params.base_q_idx
+        );
+    }
+
+    #[test]
+    fn padding_obu() {
+        const PADDING_RAW: [u8; 6] = [0x7a, 0x04, 0x00, 0x00, 0x00, 0x80];
+
+        let padding = PaddingObu {
+            obu_header: ObuHeader {
+                obu_type: ObuType::Padding,
+                extension_flag: false,
+                has_size_field: true,
+                temporal_id: 0,
+                spatial_id: 0,
+            },
+            obu_padding_length: 3,
+        };
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, PaddingObu, _>::synthesize(&padding, &mut buf).unwrap();
+        assert_eq!(buf, PADDING_RAW);
+    }
+
+    #[test]
+    fn tile_group_obu() {
+        // The last three tiles of a 2x2 tile layout.
+        const TILE_DATA: [u8; 6] = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06];
+        const TILE_GROUP_RAW: [u8; 13] = [
+            0x22, 0x0b, 0xb8, 0x00, 0x00, 0x01, 0x01, 0x00, 0x02, 0x03, 0x04, 0x05, 0x06,
+        ];
+
+        let tile_info = TileInfo {
+            tile_cols: 2,
+            tile_rows: 2,
+            tile_cols_log2: 1,
+            tile_rows_log2: 1,
+            tile_size_bytes: 2,
+            ..Default::default()
+        };
+
+        let tile = |tile_offset, tile_size| Tile {
+            tile_offset,
+            tile_size,
+            ..Default::default()
+        };
+
+        let tile_group = TileGroupObu {
+            obu: Obu {
+                header: ObuHeader {
+                    obu_type: ObuType::TileGroup,
+                    extension_flag: false,
+                    has_size_field: true,
+                    temporal_id: 0,
+                    spatial_id: 0,
+                },
+                data: TILE_DATA[..].into(),
+                start_offset: 0,
+                size: TILE_DATA.len(),
+            },
+            tile_start_and_end_present_flag: true,
+            tg_start: 1,
+            tg_end: 3,
+            tiles: vec![tile(0, 1), tile(1, 2), tile(3, 3)],
+        };
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, TileGroupObu, _>::synthesize(&tile_group, &tile_info, &mut buf).unwrap();
+        assert_eq!(buf, TILE_GROUP_RAW);
+
+        // The tiles must span from tg_start to tg_end.
+        let tile_group = TileGroupObu {
+            tg_end: 2,
+            ..tile_group
+        };
+        assert!(Synthesizer::<'_, TileGroupObu, _>::synthesize(
+            &tile_group,
+            &tile_info,
+            &mut Vec::<u8>::new()
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn tile_list_obu() {
+        const TILE_DATA: [u8; 3] = [0xaa, 0xbb, 0xcc];
+        const TILE_LIST_RAW: [u8; 19] = [
+            0x42, 0x11, 0x01, 0x00, 0x00, 0x01, 0x03, 0x01, 0x02, 0x00, 0x01, 0xaa, 0xbb, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0xcc,
+        ];
+
+        let tile_list = TileListObu {
+            obu: Obu {
+                header: ObuHeader {
+                    obu_type: ObuType::TileList,
+                    extension_flag: false,
+                    has_size_field: true,
+                    temporal_id: 0,
+                    spatial_id: 0,
+                },
+                data: TILE_DATA[..].into(),
+                start_offset: 0,
+                size: TILE_DATA.len(),
+            },
+            output_frame_width_in_tiles_minus_1: 1,
+            output_frame_height_in_tiles_minus_1: 0,
+            entries: vec![
+                TileListEntry {
+                    anchor_frame_idx: 3,
+                    anchor_tile_row: 1,
+                    anchor_tile_col: 2,
+                    tile_data_offset: 0,
+                    tile_data_size: 2,
+                },
+                TileListEntry {
+                    tile_data_offset: 2,
+                    tile_data_size: 1,
+                    ..Default::default()
+                },
+            ],
+        };
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, TileListObu, _>::synthesize(&tile_list, &mut buf).unwrap();
+        assert_eq!(buf, TILE_LIST_RAW);
+
+        // Parse the synthesized OBU back.
+        let mut parser = Parser::default();
+        let ParsedObu::Process(obu) = parser.parse_obu(&buf).unwrap() else {
+            panic!("unexpected dropped OBU");
+        };
+        assert_eq!(obu.data.len(), buf.len());
+
+        // The coded tile data must fit in the OBU.
+        let truncated = Obu {
+            size: obu.size - 1,
+            ..obu.clone()
+        };
+        assert!(parser.parse_tile_list_obu(truncated).is_err());
+
+        let parsed = parser.parse_tile_list_obu(obu).unwrap();
+        assert_eq!(
+            parsed.output_frame_width_in_tiles_minus_1,
+            tile_list.output_frame_width_in_tiles_minus_1
+        );
+        assert_eq!(
+            parsed.output_frame_height_in_tiles_minus_1,
+            tile_list.output_frame_height_in_tiles_minus_1
+        );
+        assert_eq!(parsed.entries.len(), tile_list.entries.len());
+
+        for (parsed_entry, entry) in parsed.entries.iter().zip(&tile_list.entries) {
+            assert_eq!(parsed_entry.anchor_frame_idx, entry.anchor_frame_idx);
+            assert_eq!(parsed_entry.anchor_tile_row, entry.anchor_tile_row);
+            assert_eq!(parsed_entry.anchor_tile_col, entry.anchor_tile_col);
+            assert_eq!(parsed_entry.tile_data_size, entry.tile_data_size);
+
+            let parsed_start = parsed_entry.tile_data_offset as usize;
+            let start = entry.tile_data_offset as usize;
+            let size = entry.tile_data_size as usize;
+            assert_eq!(
+                &parsed.obu.as_ref()[parsed_start..parsed_start + size],
+                &TILE_DATA[start..start + size]
+            );
+        }
+
+        // A tile list holds at least one tile.
+        let tile_list = TileListObu {
+            entries: vec![],
+            ..tile_list
+        };
+        assert!(
+            Synthesizer::<'_, TileListObu, _>::synthesize(&tile_list, &mut Vec::<u8>::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn frame_obu() {
+        const TILE_DATA: [u8; 4] = [0xde, 0xad, 0xbe, 0xef];
+
+        let (seq, mut header) = key_frame_headers();
+        header.obu_header.obu_type = ObuType::Frame;
+
+        let frame = FrameObu {
+            header,
+            tile_group: TileGroupObu {
+                obu: Obu {
+                    data: TILE_DATA[..].into(),
+                    size: TILE_DATA.len(),
+                    ..Default::default()
+                },
+                tg_start: 0,
+                tg_end: 0,
+                tiles: vec![Tile {
+                    tile_offset: 0,
+                    tile_size: TILE_DATA.len() as u32,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        };
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, SequenceHeaderObu, _>::synthesize(&seq, &mut buf).unwrap();
+        Synthesizer::<'_, FrameObu, _>::synthesize(&frame, &seq, &mut buf).unwrap();
+
+        // Parse the synthesized OBUs back.
+        let mut parser = Parser::default();
+        let mut consumed = 0;
+
+        let ParsedObu::Process(obu) = parser.parse_obu(&buf).unwrap() else {
+            panic!("unexpected dropped OBU");
+        };
+        parser.parse_sequence_header_obu(&obu).unwrap();
+        consumed += obu.data.len();
+
+        let ParsedObu::Process(obu) = parser.parse_obu(&buf[consumed..]).unwrap() else {
+            panic!("unexpected dropped OBU");
+        };
+        assert_eq!(obu.header.obu_type, ObuType::Frame);
+        consumed += obu.data.len();
+        assert_eq!(consumed, buf.len());
+
+        let parsed = parser.parse_frame_obu(obu).unwrap();
+        assert_eq!(parsed.header.frame_width, WIDTH);
+        assert_eq!(parsed.header.frame_height, HEIGHT);
+
+        let tile_group = parsed.tile_group;
+        assert_eq!(tile_group.tiles.len(), 1);
+        let tile = &tile_group.tiles[0];
+        let tile_offset = tile.tile_offset as usize;
+        assert_eq!(
+            &tile_group.obu.as_ref()[tile_offset..tile_offset + tile.tile_size as usize],
+            TILE_DATA
+        );
+    }
 }
@@ -0,0 +1,460 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Grouping of AV1 OBUs into temporal units and frames, for both the
+//! low-overhead and Annex B formats.
+
+use std::ops::Range;
+
+use anyhow::anyhow;
+
+use crate::codec::av1::parser::FrameHeaderObu;
+use crate::codec::av1::parser::Obu;
+use crate::codec::av1::parser::ObuType;
+use crate::codec::av1::parser::ParsedObu;
+use crate::codec::av1::parser::Parser;
+use crate::codec::av1::parser::Tile;
+use crate::codec::av1::parser::TileGroupObu;
+
+/// The location of the data of a tile.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TileData {
+    /// The tile, as parsed from its tile group.
+    pub tile: Tile,
+    /// The range of the tile data in the buffer walked by the
+    /// [`TemporalUnitIterator`].
+    pub range: Range<usize>,
+}
+
+/// A frame, i.e. a frame header along with the tile groups holding all of its
+/// tiles. Frames with `show_existing_frame` set have no tile groups.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Frame<'a> {
+    /// The frame header.
+    pub header: FrameHeaderObu,
+    /// The tile groups of the frame, in decoding order.
+    pub tile_groups: Vec<TileGroupObu<'a>>,
+    /// The data of all the tiles of the frame, in decoding order.
+    pub tiles: Vec<TileData>,
+}
+
+impl<'a> Frame<'a> {
+    fn num_tiles(&self) -> u32 {
+        self.header.tile_info.tile_cols * self.header.tile_info.tile_rows
+    }
+}
+
+/// A temporal unit, i.e. all the OBUs from a temporal delimiter up to the next
+/// one.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemporalUnit<'a> {
+    /// The OBUs of the temporal unit that are not part of a frame, e.g. the
+    /// temporal delimiter, sequence header or metadata, in decoding order.
+    pub obus: Vec<Obu<'a>>,
+    /// The frames of the temporal unit, in decoding order.
+    pub frames: Vec<Frame<'a>>,
+    /// The range of the temporal unit in the buffer walked by the
+    /// [`TemporalUnitIterator`].
+    pub range: Range<usize>,
+}
+
+impl<'a> TemporalUnit<'a> {
+    fn is_empty(&self) -> bool {
+        self.obus.is_empty() && self.frames.is_empty()
+    }
+}
+
+/// Iterates over the temporal units of a buffer of OBUs.
+///
+/// The OBUs are parsed with the provided [`Parser`], which keeps track of the
+/// sequence header and of the reference frames. The reference frame update
+/// process is run as soon as a frame is complete, so that the headers of the
+/// following frames can be parsed.
+///
+/// The iterator stops after returning an error.
+pub struct TemporalUnitIterator<'a, 'p> {
+    parser: &'p mut Parser,
+    data: &'a [u8],
+    pos: usize,
+    /// The temporal unit being assembled.
+    current: TemporalUnit<'a>,
+    /// The frame whose header has been seen, i.e. the frame for which
+    /// SeenFrameHeader is set in the specification, until all of its tiles
+    /// are received.
+    frame: Option<Frame<'a>>,
+    failed: bool,
+}
+
+impl<'a, 'p> TemporalUnitIterator<'a, 'p> {
+    /// Creates an iterator over the temporal units in `data`, which is parsed
+    /// with `parser`.
+    pub fn new(parser: &'p mut Parser, data: &'a [u8]) -> Self {
+        Self {
+            parser,
+            data,
+            pos: 0,
+            current: Default::default(),
+            frame: None,
+            failed: false,
+        }
+    }
+
+    fn next_temporal_unit(&mut self) -> anyhow::Result<Option<TemporalUnit<'a>>> {
+        while self.pos < self.data.len() {
+            let obu = match self.parser.parse_obu(&self.data[self.pos..])? {
+                ParsedObu::Process(obu) => obu,
+                ParsedObu::Drop(length) => {
+                    self.pos += usize::try_from(length)?;
+                    continue;
+                }
+            };
+
+            let obu_pos = self.pos;
+            self.pos += obu.data.len();
+
+            if obu.header.obu_type == ObuType::TemporalDelimiter && !self.current.is_empty() {
+                let completed = self.take()?;
+                self.push_obu(obu, obu_pos)?;
+                return Ok(completed);
+            }
+
+            self.push_obu(obu, obu_pos)?;
+        }
+
+        self.take()
+    }
+
+    fn push_obu(&mut self, obu: Obu<'a>, obu_pos: usize) -> anyhow::Result<()> {
+        if self.current.is_empty() && self.frame.is_none() {
+            self.current.range = obu_pos..obu_pos;
+        }
+        self.current.range.end = obu_pos + obu.data.len();
+
+        match obu.header.obu_type {
+            ObuType::TemporalDelimiter => {
+                self.parser.parse_temporal_delimiter_obu(&obu)?;
+            }
+            ObuType::SequenceHeader => {
+                self.parser.parse_sequence_header_obu(&obu)?;
+            }
+            ObuType::FrameHeader | ObuType::RedundantFrameHeader => {
+                // Once the frame header has been seen, the following ones must
+                // be redundant copies of it and can be ignored.
+                if self.frame.is_none() {
+                    let header = self.parser.parse_frame_header_obu(&obu)?;
+                    self.start_frame(header)?;
+                } else if obu.header.obu_type == ObuType::FrameHeader {
+                    return Err(anyhow!(
+                        "Broken data: frame header OBU before the end of the previous frame"
+                    ));
+                }
+
+                return Ok(());
+            }
+            ObuType::TileGroup => {
+                // The tile info comes from the frame header.
+                if self.frame.is_none() {
+                    return Err(anyhow!("Broken data: tile group without a frame header"));
+                }
+
+                let tile_group = self.parser.parse_tile_group_obu(obu)?;
+                return self.push_tile_group(tile_group, obu_pos);
+            }
+            ObuType::Frame => {
+                if self.frame.is_some() {
+                    return Err(anyhow!(
+                        "Broken data: frame OBU before the end of the previous frame"
+                    ));
+                }
+
+                let frame = self.parser.parse_frame_obu(obu)?;
+                if frame.header.show_existing_frame {
+                    return Err(anyhow!("Broken data: frame OBU showing an existing frame"));
+                }
+
+                self.start_frame(frame.header)?;
+                return self.push_tile_group(frame.tile_group, obu_pos);
+            }
+            _ => (),
+        }
+
+        self.current.obus.push(obu);
+        Ok(())
+    }
+
+    fn start_frame(&mut self, header: FrameHeaderObu) -> anyhow::Result<()> {
+        let frame = Frame {
+            header,
+            ..Default::default()
+        };
+
+        if frame.header.show_existing_frame {
+            self.finish_frame(frame)
+        } else {
+            self.frame = Some(frame);
+            Ok(())
+        }
+    }
+
+    fn push_tile_group(
+        &mut self,
+        tile_group: TileGroupObu<'a>,
+        obu_pos: usize,
+    ) -> anyhow::Result<()> {
+        let frame = self.frame.as_mut().unwrap();
+
+        let data_pos = obu_pos + tile_group.obu.start_offset;
+        frame.tiles.extend(tile_group.tiles.iter().map(|tile| {
+            let start = data_pos + tile.tile_offset as usize;
+            TileData {
+                tile: tile.clone(),
+                range: start..start + tile.tile_size as usize,
+            }
+        }));
+
+        let last = tile_group.tg_end + 1 == frame.num_tiles();
+        frame.tile_groups.push(tile_group);
+
+        if last {
+            let frame = self.frame.take().unwrap();
+            self.finish_frame(frame)?;
+        }
+
+        Ok(())
+    }
+
+    fn finish_frame(&mut self, frame: Frame<'a>) -> anyhow::Result<()> {
+        self.parser.ref_frame_update(&frame.header)?;
+        self.current.frames.push(frame);
+        Ok(())
+    }
+
+    fn take(&mut self) -> anyhow::Result<Option<TemporalUnit<'a>>> {
+        if self.frame.is_some() {
+            return Err(anyhow!(
+                "Broken data: incomplete frame at the end of a temporal unit"
+            ));
+        }
+
+        if self.current.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(std::mem::take(&mut self.current)))
+        }
+    }
+}
+
+impl<'a, 'p> Iterator for TemporalUnitIterator<'a, 'p> {
+    type Item = anyhow::Result<TemporalUnit<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+
+        let temporal_unit = self.next_temporal_unit();
+        if temporal_unit.is_err() {
+            self.failed = true;
+        }
+
+        temporal_unit.transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::av1::parser::TxMode;
+    use crate::codec::av1::synthesizer::Synthesizer;
+    use crate::codec::av1::writer::ObuWriter;
+    use crate::utils::IvfIterator;
+
+    const STREAM_TEST_25_FPS: &[u8] = include_bytes!("test_data/test-25fps.ivf.av1");
+    const STREAM_ANNEXB: &[u8] = include_bytes!("test_data/av1-annexb.ivf.av1");
+
+    /// Concatenates the IVF frames of `stream`, and returns them along with
+    /// their number.
+    fn concat(stream: &[u8]) -> (Vec<u8>, usize) {
+        let packets: Vec<_> = IvfIterator::new(stream).collect();
+        (packets.concat(), packets.len())
+    }
+
+    fn check_temporal_units(data: &[u8], temporal_units: &[TemporalUnit]) {
+        let mut end = 0;
+        for tu in temporal_units {
+            assert!(tu.range.start >= end);
+            end = tu.range.end;
+
+            assert_eq!(tu.obus[0].header.obu_type, ObuType::TemporalDelimiter);
+            assert!(!tu.frames.is_empty());
+
+            for frame in &tu.frames {
+                if frame.header.show_existing_frame {
+                    assert!(frame.tile_groups.is_empty());
+                    continue;
+                }
+
+                assert_eq!(frame.tiles.len() as u32, frame.num_tiles());
+                for tile in &frame.tiles {
+                    assert!(tile.range.start >= tu.range.start && tile.range.end <= tu.range.end);
+                    assert!(!data[tile.range.clone()].is_empty());
+                }
+            }
+        }
+        assert!(end <= data.len());
+    }
+
+    #[test]
+    fn temporal_units_test25fps() {
+        let (data, num_packets) = concat(STREAM_TEST_25_FPS);
+
+        let mut parser = Parser::default();
+        let temporal_units = TemporalUnitIterator::new(&mut parser, &data)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(temporal_units.len(), num_packets);
+        assert_eq!(temporal_units[0].range.start, 0);
+        assert_eq!(temporal_units.last().unwrap().range.end, data.len());
+        assert_eq!(
+            temporal_units[0].obus[1].header.obu_type,
+            ObuType::SequenceHeader
+        );
+        check_temporal_units(&data, &temporal_units);
+
+        // The same temporal units are found packet by packet.
+        let mut parser = Parser::default();
+        for (packet, expected) in IvfIterator::new(STREAM_TEST_25_FPS).zip(&temporal_units) {
+            let tus = TemporalUnitIterator::new(&mut parser, packet)
+                .collect::<anyhow::Result<Vec<_>>>()
+                .unwrap();
+            assert_eq!(tus.len(), 1);
+            assert_eq!(tus[0].frames.len(), expected.frames.len());
+            assert_eq!(tus[0].frames[0].header, expected.frames[0].header);
+        }
+    }
+
+    #[test]
+    fn temporal_units_annexb() {
+        let (data, num_packets) = concat(STREAM_ANNEXB);
+
+        let mut parser = Parser::default();
+        let temporal_units = TemporalUnitIterator::new(&mut parser, &data)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .unwrap();
+
+        assert_eq!(temporal_units.len(), num_packets);
+        check_temporal_units(&data, &temporal_units);
+    }
+
+    /// Builds a frame header OBU out of the frame header of `frame`, using the
+    /// sequence header of `parser`.
+    fn frame_header_obu(parser: &Parser, frame: &Frame) -> Vec<u8> {
+        let mut header = frame.header.clone();
+        header.obu_header.obu_type = ObuType::FrameHeader;
+        header.obu_header.has_size_field = true;
+        // The parser only records TxMode, from which tx_mode_select follows.
+        header.tx_mode_select = u32::from(header.tx_mode == TxMode::Select);
+
+        let sequence = parser.sequence_header.as_ref().unwrap();
+        let mut obu = vec![];
+        Synthesizer::<'_, FrameHeaderObu, _>::synthesize(&header, sequence, &mut obu).unwrap();
+        obu
+    }
+
+    #[test]
+    fn redundant_frame_headers() {
+        let packet = IvfIterator::new(STREAM_TEST_25_FPS).next().unwrap();
+        let mut parser = Parser::default();
+        let tu = TemporalUnitIterator::new(&mut parser, packet)
+            .next()
+            .unwrap()
+            .unwrap();
+        let frame = &tu.frames[0];
+        assert_eq!(frame.tile_groups[0].obu.header.obu_type, ObuType::Frame);
+
+        // Split the frame OBU into a frame header OBU, a redundant frame header
+        // OBU and a tile group OBU.
+        let obus = [&tu.obus[0].data[..], &tu.obus[1].data[..]].concat();
+        let frame_header = frame_header_obu(&parser, frame);
+
+        let mut redundant_frame_header = frame_header.clone();
+        redundant_frame_header[0] = (ObuType::RedundantFrameHeader as u8) << 3 | 0x2;
+
+        let tile_group_data = frame.tile_groups[0].obu.as_ref();
+        let mut tile_group = vec![];
+        let mut writer = ObuWriter::new(&mut tile_group);
+        writer
+            .write_f(8, (ObuType::TileGroup as u32) << 3 | 0x2)
+            .unwrap();
+        writer
+            .write_leb128(tile_group_data.len() as u32, 0)
+            .unwrap();
+        drop(writer);
+        tile_group.extend_from_slice(tile_group_data);
+
+        let temporal_units = |split_obus: &[&[u8]]| {
+            let data = [&obus[..], &split_obus.concat()].concat();
+            let mut parser = Parser::default();
+            let tus = TemporalUnitIterator::new(&mut parser, &data)
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            assert_eq!(tus.len(), 1);
+            assert_eq!(tus[0].obus.len(), 2);
+            assert_eq!(tus[0].frames.len(), 1);
+
+            let parsed = &tus[0].frames[0];
+            assert_eq!(parsed.header.frame_width, frame.header.frame_width);
+            assert_eq!(
+                parsed.tile_groups[0].obu.header.obu_type,
+                ObuType::TileGroup
+            );
+            assert_eq!(parsed.tiles.len(), frame.tiles.len());
+            for (tile, expected) in parsed.tiles.iter().zip(&frame.tiles) {
+                assert_eq!(data[tile.range.clone()], packet[expected.range.clone()]);
+            }
+
+            anyhow::Ok(())
+        };
+
+        temporal_units(&[&frame_header, &redundant_frame_header, &tile_group]).unwrap();
+
+        // Without the frame header, the redundant one is used.
+        temporal_units(&[&redundant_frame_header, &tile_group]).unwrap();
+
+        // A tile group needs a frame header.
+        assert!(temporal_units(&[&tile_group]).is_err());
+
+        // A second frame header must be a redundant one.
+        assert!(temporal_units(&[&frame_header, &frame_header, &tile_group]).is_err());
+    }
+
+    #[test]
+    fn broken_data() {
+        // A frame OBU with a flipped bit in its tile info, whose tile sizes
+        // then exceed the OBU.
+        let mut packet = IvfIterator::new(STREAM_TEST_25_FPS)
+            .next()
+            .unwrap()
+            .to_vec();
+        packet[20] ^= 0x20;
+
+        for data in [
+            &packet[..],
+            // A frame OBU without a size field in a low-overhead stream.
+            &[0x12, 0x00, 0x30],
+            // A frame OBU larger than the data.
+            &[0x12, 0x00, 0x32, 0x10],
+            // A reserved bit set in the OBU header.
+            &[0x13, 0x00],
+            // A leb128() size of 8 bytes, and one larger than 32 bits.
+            &[0x12, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+            &[0x12, 0x80, 0x80, 0x80, 0x80, 0x10],
+        ] {
+            let mut parser = Parser::default();
+            let mut temporal_units = TemporalUnitIterator::new(&mut parser, data);
+            assert!(temporal_units.next().unwrap().is_err());
+            assert!(temporal_units.next().is_none());
+        }
+    }
+}
@@ -55,7 +55,7 @@ impl<W: Write> ObuWriter<W> {
         let mut value = value.to_le();
 
         for _ in 0..n {
-            self.write_f(4, value & 0xff)?;
+            self.write_f(8, value & 0xff)?;
             value >>= 8;
         }
 
@@ -152,6 +152,20 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_le() {
+        for &value in TEST_VECTOR {
+            let mut buf = Vec::<u8>::new();
+
+            ObuWriter::new(&mut buf).write_le(4, value).unwrap();
+            assert_eq!(buf, value.to_le_bytes());
+
+            let read = Reader::new(&buf).read_le(4).unwrap();
+
+            assert_eq!(read, value, "failed testing {}", value);
+        }
+    }
+
     #[test]
     fn test_leb128() {
         for &value in TEST_VECTOR {
@@ -2,9 +2,12 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+pub mod access_unit;
+pub mod avcc;
 pub mod dpb;
 pub mod nalu;
 pub mod nalu_reader;
+pub mod nalu_splitter;
 pub mod nalu_writer;
 pub mod parser;
 pub mod picture;
@@ -0,0 +1,312 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Grouping of H.264 NAL units into access units, as specified in clauses
+//! 7.4.1.2.3 and 7.4.1.2.4 of the specification.
+
+use crate::codec::h264::parser::Nalu;
+use crate::codec::h264::parser::NaluType;
+use crate::codec::h264::parser::Parser;
+use crate::codec::h264::parser::ParserResult;
+use crate::codec::h264::parser::Slice;
+
+/// A complete access unit, i.e. the NAL units of exactly one primary coded
+/// picture, along with its redundant pictures, parameter sets and SEI.
+#[derive(Default)]
+pub struct AccessUnit<'a> {
+    /// The NAL units of the access unit that are not slices with a slice
+    /// header, e.g. the access unit delimiter, parameter sets, SEI or end of
+    /// sequence, in decoding order.
+    pub nalus: Vec<Nalu<'a>>,
+    /// The slices of the access unit, in decoding order.
+    pub slices: Vec<Slice<'a>>,
+}
+
+impl<'a> AccessUnit<'a> {
+    /// Returns the first slice of the primary coded picture.
+    pub fn first_slice(&self) -> Option<&Slice<'a>> {
+        self.slices
+            .iter()
+            .find(|slice| slice.header.redundant_pic_cnt == 0)
+    }
+
+    fn is_empty(&self) -> bool {
+        self.nalus.is_empty() && self.slices.is_empty()
+    }
+}
+
+/// Assembles access units from a sequence of NAL units.
+///
+/// The parameter sets are parsed by the assembler's [`Parser`] as they are
+/// pushed, so that the headers of the following slices can be parsed.
+#[derive(Default)]
+pub struct AccessUnitAssembler<'a> {
+    parser: Parser,
+    current: AccessUnit<'a>,
+    /// Whether the current access unit ends with an end of sequence NAL unit.
+    end_of_sequence: bool,
+}
+
+impl<'a> AccessUnitAssembler<'a> {
+    /// Returns the parser holding the parameter sets seen so far.
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    /// Pushes the next NAL unit in decoding order, and returns the access unit
+    /// it completes, if any.
+    ///
+    /// On error, `nalu` is dropped and the access unit being assembled is left
+    /// untouched.
+    pub fn push(&mut self, nalu: Nalu<'a>) -> ParserResult<Option<AccessUnit<'a>>> {
+        match nalu.header.type_ {
+            NaluType::Slice | NaluType::SliceDpa | NaluType::SliceIdr => {
+                let slice = self.parser.parse_slice_header(nalu)?;
+
+                let completed = if self.starts_new_picture(&slice) {
+                    self.take()
+                } else {
+                    None
+                };
+
+                self.current.slices.push(slice);
+                Ok(completed)
+            }
+
+            // Only the end of stream can follow the end of sequence in an
+            // access unit, and nothing can follow the end of stream.
+            NaluType::SeqEnd => {
+                self.current.nalus.push(nalu);
+                self.end_of_sequence = true;
+                Ok(None)
+            }
+            NaluType::StreamEnd => {
+                self.current.nalus.push(nalu);
+                Ok(self.take())
+            }
+
+            type_ => {
+                match type_ {
+                    NaluType::Sps => {
+                        self.parser.parse_sps(&nalu)?;
+                    }
+                    NaluType::Pps => {
+                        self.parser.parse_pps(&nalu)?;
+                    }
+                    _ => (),
+                }
+
+                // See 7.4.1.2.3: the first of these NAL units after the last
+                // VCL NAL unit of a primary coded picture starts a new access
+                // unit.
+                let starts_new_access_unit = matches!(
+                    type_,
+                    NaluType::AuDelimiter
+                        | NaluType::Sps
+                        | NaluType::Pps
+                        | NaluType::Sei
+                        | NaluType::SpsExt
+                        | NaluType::PrefixUnit
+                        | NaluType::SubsetSps
+                        | NaluType::DepthSps
+                );
+
+                let completed = if (starts_new_access_unit && !self.current.slices.is_empty())
+                    || self.end_of_sequence
+                {
+                    self.take()
+                } else {
+                    None
+                };
+
+                self.current.nalus.push(nalu);
+                Ok(completed)
+            }
+        }
+    }
+
+    /// Returns the access unit being assembled, if any, e.g. at the end of the
+    /// stream.
+    pub fn flush(&mut self) -> Option<AccessUnit<'a>> {
+        self.take()
+    }
+
+    fn take(&mut self) -> Option<AccessUnit<'a>> {
+        self.end_of_sequence = false;
+
+        if self.current.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.current))
+        }
+    }
+
+    /// Whether `slice` is the first VCL NAL unit of a new primary coded
+    /// picture, as specified in 7.4.1.2.4.
+    fn starts_new_picture(&self, slice: &Slice) -> bool {
+        if self.end_of_sequence {
+            return true;
+        }
+
+        let Some(prev) = self.current.first_slice() else {
+            return false;
+        };
+
+        // Slices of redundant pictures belong to the primary coded picture
+        // preceding them.
+        if slice.header.redundant_pic_cnt > 0 {
+            return false;
+        }
+
+        let (prev_hdr, hdr) = (&prev.header, &slice.header);
+        let (prev_nalu_hdr, nalu_hdr) = (&prev.nalu.header, &slice.nalu.header);
+
+        if hdr.frame_num != prev_hdr.frame_num
+            || hdr.pic_parameter_set_id != prev_hdr.pic_parameter_set_id
+            || hdr.field_pic_flag != prev_hdr.field_pic_flag
+            || (hdr.field_pic_flag && hdr.bottom_field_flag != prev_hdr.bottom_field_flag)
+            || (nalu_hdr.ref_idc != prev_nalu_hdr.ref_idc
+                && (nalu_hdr.ref_idc == 0 || prev_nalu_hdr.ref_idc == 0))
+            || nalu_hdr.idr_pic_flag != prev_nalu_hdr.idr_pic_flag
+            || (nalu_hdr.idr_pic_flag && hdr.idr_pic_id != prev_hdr.idr_pic_id)
+        {
+            return true;
+        }
+
+        let Some(pps) = self.parser.get_pps(hdr.pic_parameter_set_id) else {
+            return false;
+        };
+
+        match pps.sps.pic_order_cnt_type {
+            0 => {
+                hdr.pic_order_cnt_lsb != prev_hdr.pic_order_cnt_lsb
+                    || hdr.delta_pic_order_cnt_bottom != prev_hdr.delta_pic_order_cnt_bottom
+            }
+            1 => hdr.delta_pic_order_cnt != prev_hdr.delta_pic_order_cnt,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const STREAM_TEST_25_FPS: &[u8] = include_bytes!("test_data/test-25fps.h264");
+    const STREAM_TEST_25_FPS_INTERLACED: &[u8] =
+        include_bytes!("test_data/test-25fps-interlaced.h264");
+
+    fn assemble<'a>(nalus: impl Iterator<Item = Nalu<'a>>) -> Vec<AccessUnit<'a>> {
+        let mut assembler = AccessUnitAssembler::default();
+        let mut access_units = vec![];
+
+        for nalu in nalus {
+            access_units.extend(assembler.push(nalu).unwrap());
+        }
+        access_units.extend(assembler.flush());
+
+        access_units
+    }
+
+    fn nalus(stream: &[u8]) -> impl Iterator<Item = Nalu<'_>> {
+        let mut cursor = Cursor::new(stream);
+        std::iter::from_fn(move || Nalu::next(&mut cursor).ok())
+    }
+
+    #[test]
+    fn access_units_test25fps() {
+        let access_units = assemble(nalus(STREAM_TEST_25_FPS));
+        assert_eq!(access_units.len(), 250);
+
+        let types = |au: &AccessUnit| -> Vec<NaluType> {
+            au.nalus.iter().map(|nalu| nalu.header.type_).collect()
+        };
+        assert_eq!(
+            types(&access_units[0]),
+            [NaluType::Sei, NaluType::Sei, NaluType::Sps, NaluType::Pps]
+        );
+        assert!(
+            access_units[0]
+                .first_slice()
+                .unwrap()
+                .nalu
+                .header
+                .idr_pic_flag
+        );
+
+        for au in &access_units {
+            // Each picture is made of two slices.
+            assert_eq!(au.slices.len(), 2);
+            assert_eq!(au.slices[0].header.first_mb_in_slice, 0);
+            assert_eq!(au.slices[1].header.first_mb_in_slice, 150);
+            assert_eq!(au.slices[0].header.frame_num, au.slices[1].header.frame_num);
+        }
+
+        // The following access units start with SEI, some of them followed by
+        // the parameter sets again.
+        for au in &access_units[1..] {
+            assert_eq!(types(au)[0], NaluType::Sei);
+        }
+    }
+
+    #[test]
+    fn access_units_without_delimiting_nalus() {
+        // Without SEI, the boundaries are found by comparing the slice headers.
+        let access_units =
+            assemble(nalus(STREAM_TEST_25_FPS).filter(|nalu| nalu.header.type_ != NaluType::Sei));
+        assert_eq!(access_units.len(), 250);
+        assert!(access_units.iter().all(|au| au.slices.len() == 2));
+
+        // Pictures sharing the same frame_num are told apart by their POC.
+        let access_units = assemble(
+            nalus(STREAM_TEST_25_FPS_INTERLACED).filter(|nalu| nalu.header.type_ != NaluType::Sei),
+        );
+        assert_eq!(access_units.len(), 250);
+        assert!(access_units.iter().all(|au| au.slices.len() == 1));
+    }
+
+    #[test]
+    fn access_units_end_of_sequence() {
+        let mut assembler = AccessUnitAssembler::default();
+        let mut stream = nalus(STREAM_TEST_25_FPS);
+
+        // SEI, SEI, SPS, PPS and the two slices of the first picture.
+        for nalu in stream.by_ref().take(6) {
+            assert!(assembler.push(nalu).unwrap().is_none());
+        }
+
+        let seq_end = Nalu::next(&mut Cursor::new(&[0x00, 0x00, 0x01, 0x0a][..])).unwrap();
+        assert!(assembler.push(seq_end).unwrap().is_none());
+        let stream_end = Nalu::next(&mut Cursor::new(&[0x00, 0x00, 0x01, 0x0b][..])).unwrap();
+        let au = assembler.push(stream_end).unwrap().unwrap();
+        assert_eq!(au.slices.len(), 2);
+        assert_eq!(
+            au.nalus[au.nalus.len() - 2..]
+                .iter()
+                .map(|nalu| nalu.header.type_)
+                .collect::<Vec<_>>(),
+            [NaluType::SeqEnd, NaluType::StreamEnd]
+        );
+        assert!(assembler.flush().is_none());
+
+        // Whatever follows the end of sequence starts a new access unit.
+        let mut stream = nalus(STREAM_TEST_25_FPS);
+        for nalu in stream.by_ref().take(6) {
+            assembler.push(nalu).unwrap();
+        }
+        let seq_end = Nalu::next(&mut Cursor::new(&[0x00, 0x00, 0x01, 0x0a][..])).unwrap();
+        assert!(assembler.push(seq_end).unwrap().is_none());
+        let au = assembler.push(stream.next().unwrap()).unwrap().unwrap();
+        assert_eq!(au.nalus.last().unwrap().header.type_, NaluType::SeqEnd);
+        assert_eq!(assembler.flush().unwrap().nalus.len(), 1);
+
+        // The slice of an unknown PPS is rejected.
+        let mut assembler = AccessUnitAssembler::default();
+        let slice = stream.find(|nalu| nalu.header.type_ == NaluType::Slice);
+        assert!(assembler.push(slice.unwrap()).is_err());
+        assert!(assembler.flush().is_none());
+    }
+}
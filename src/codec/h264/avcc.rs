@@ -0,0 +1,357 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Support for the AVCDecoderConfigurationRecord of ISO/IEC 14496-15, i.e. the
+//! codec private data of H.264 streams stored in MP4 or Matroska files, in
+//! which NAL units are prefixed by their length instead of a start code.
+
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::io::Write;
+
+use bytes::Buf;
+
+use crate::codec::h264::nalu::Header;
+use crate::codec::h264::nalu::LengthPrefixedNaluIterator;
+use crate::codec::h264::parser::Nalu;
+use crate::codec::h264::parser::NaluHeader;
+use crate::codec::h264::parser::NaluType;
+use crate::codec::h264::parser::Parser;
+use crate::codec::h264::parser::ParserError;
+use crate::codec::h264::parser::ParserResult;
+
+/// The only configuration version defined by the specification.
+const CONFIGURATION_VERSION: u8 = 1;
+
+/// The fields that follow the parameter sets for the profiles with chroma
+/// format and bit depth information, i.e. High, High 10, High 4:2:2 and High
+/// 4:4:4 Predictive.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AvcConfigExtension {
+    pub chroma_format: u8,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    /// The SPS extension NAL units, without length prefix.
+    pub sps_ext: Vec<Vec<u8>>,
+}
+
+/// An AVCDecoderConfigurationRecord as per ISO/IEC 14496-15, 5.3.3.1.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AvcDecoderConfigurationRecord {
+    pub avc_profile_indication: u8,
+    pub profile_compatibility: u8,
+    pub avc_level_indication: u8,
+    /// The size in bytes of the NAL unit length fields, minus one.
+    pub length_size_minus_one: u8,
+    /// The SPS NAL units, without length prefix.
+    pub sps: Vec<Vec<u8>>,
+    /// The PPS NAL units, without length prefix.
+    pub pps: Vec<Vec<u8>>,
+    /// Present only for some High profiles, and often omitted even then.
+    pub ext: Option<AvcConfigExtension>,
+}
+
+fn read_parameter_sets(data: &mut &[u8], count: usize) -> ParserResult<Vec<Vec<u8>>> {
+    let mut nalus = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        if data.remaining() < 2 {
+            return Err(ParserError::NoMoreData);
+        }
+
+        let len = usize::from(data.get_u16());
+        if data.remaining() < len {
+            return Err(ParserError::NoMoreData);
+        }
+
+        nalus.push(data[..len].to_vec());
+        data.advance(len);
+    }
+
+    Ok(nalus)
+}
+
+fn write_parameter_sets(writer: &mut impl Write, nalus: &[Vec<u8>]) -> std::io::Result<()> {
+    for nalu in nalus {
+        let len = u16::try_from(nalu.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "parameter set too large")
+        })?;
+
+        writer.write_all(&len.to_be_bytes())?;
+        writer.write_all(nalu)?;
+    }
+
+    Ok(())
+}
+
+impl AvcDecoderConfigurationRecord {
+    /// Builds a record for the given parameter sets, taking the profile and
+    /// level from the first SPS.
+    pub fn new(sps: Vec<Vec<u8>>, pps: Vec<Vec<u8>>, length_size: usize) -> ParserResult<Self> {
+        if !matches!(length_size, 1 | 2 | 4) {
+            return Err(ParserError::BrokenStream("invalid NALU length size"));
+        }
+
+        let (avc_profile_indication, profile_compatibility, avc_level_indication) =
+            match sps.first().map(Vec::as_slice) {
+                Some([_, profile, compatibility, level, ..]) => (*profile, *compatibility, *level),
+                _ => return Err(ParserError::BrokenStream("missing or truncated SPS")),
+            };
+
+        Ok(Self {
+            avc_profile_indication,
+            profile_compatibility,
+            avc_level_indication,
+            length_size_minus_one: (length_size - 1) as u8,
+            sps,
+            pps,
+            ext: None,
+        })
+    }
+
+    /// Parses a record, e.g. from the contents of an MP4 `avcC` box.
+    pub fn parse(mut data: &[u8]) -> ParserResult<Self> {
+        if data.remaining() < 6 {
+            return Err(ParserError::NoMoreData);
+        }
+
+        if data.get_u8() != CONFIGURATION_VERSION {
+            return Err(ParserError::UnsupportedFeature(
+                "unsupported AVCDecoderConfigurationRecord version",
+            ));
+        }
+
+        let avc_profile_indication = data.get_u8();
+        let profile_compatibility = data.get_u8();
+        let avc_level_indication = data.get_u8();
+        let length_size_minus_one = data.get_u8() & 0x3;
+
+        if length_size_minus_one == 2 {
+            return Err(ParserError::BrokenStream("invalid NALU length size"));
+        }
+
+        let num_sps = usize::from(data.get_u8() & 0x1f);
+        let sps = read_parameter_sets(&mut data, num_sps)?;
+
+        if !data.has_remaining() {
+            return Err(ParserError::NoMoreData);
+        }
+
+        let num_pps = usize::from(data.get_u8());
+        let pps = read_parameter_sets(&mut data, num_pps)?;
+
+        let ext =
+            if matches!(avc_profile_indication, 100 | 110 | 122 | 144) && data.remaining() >= 4 {
+                let chroma_format = data.get_u8() & 0x3;
+                let bit_depth_luma_minus8 = data.get_u8() & 0x7;
+                let bit_depth_chroma_minus8 = data.get_u8() & 0x7;
+                let num_sps_ext = usize::from(data.get_u8());
+
+                Some(AvcConfigExtension {
+                    chroma_format,
+                    bit_depth_luma_minus8,
+                    bit_depth_chroma_minus8,
+                    sps_ext: read_parameter_sets(&mut data, num_sps_ext)?,
+                })
+            } else {
+                None
+            };
+
+        Ok(Self {
+            avc_profile_indication,
+            profile_compatibility,
+            avc_level_indication,
+            length_size_minus_one,
+            sps,
+            pps,
+            ext,
+        })
+    }
+
+    /// Writes the record into `writer`.
+    pub fn write_into(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        if self.sps.len() > 0x1f || self.pps.len() > 0xff {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "too many parameter sets",
+            ));
+        }
+
+        writer.write_all(&[
+            CONFIGURATION_VERSION,
+            self.avc_profile_indication,
+            self.profile_compatibility,
+            self.avc_level_indication,
+            0xfc | (self.length_size_minus_one & 0x3),
+            0xe0 | self.sps.len() as u8,
+        ])?;
+        write_parameter_sets(writer, &self.sps)?;
+
+        writer.write_all(&[self.pps.len() as u8])?;
+        write_parameter_sets(writer, &self.pps)?;
+
+        if let Some(ext) = &self.ext {
+            if ext.sps_ext.len() > 0xff {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidInput,
+                    "too many parameter sets",
+                ));
+            }
+
+            writer.write_all(&[
+                0xfc | (ext.chroma_format & 0x3),
+                0xf8 | (ext.bit_depth_luma_minus8 & 0x7),
+                0xf8 | (ext.bit_depth_chroma_minus8 & 0x7),
+                ext.sps_ext.len() as u8,
+            ])?;
+            write_parameter_sets(writer, &ext.sps_ext)?;
+        }
+
+        Ok(())
+    }
+
+    /// The size in bytes of the length prefix of the NAL units.
+    pub fn length_size(&self) -> usize {
+        usize::from(self.length_size_minus_one) + 1
+    }
+
+    /// Returns an iterator over the NAL units of a sample of the stream.
+    pub fn nalus<'a>(&self, sample: &'a [u8]) -> LengthPrefixedNaluIterator<'a, NaluHeader> {
+        LengthPrefixedNaluIterator::new(sample, self.length_size())
+    }
+
+    /// Parses the SPSes and PPSes of the record with `parser`, so that the
+    /// slices of the stream can be parsed.
+    pub fn parse_parameter_sets(&self, parser: &mut Parser) -> ParserResult<()> {
+        fn nalu(data: &[u8]) -> ParserResult<Nalu<'_>> {
+            let header = NaluHeader::parse(&Cursor::new(data))
+                .map_err(|_| ParserError::BrokenStream("invalid parameter set NALU header"))?;
+
+            Ok(Nalu {
+                header,
+                data: Cow::Borrowed(data),
+                size: data.len(),
+                offset: 0,
+            })
+        }
+
+        for sps in &self.sps {
+            parser.parse_sps(&nalu(sps)?)?;
+        }
+
+        for pps in &self.pps {
+            let nalu = nalu(pps)?;
+            // Some muxers put other NAL units among the parameter sets.
+            if nalu.header.type_ == NaluType::Pps {
+                parser.parse_pps(&nalu)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM_TEST_25_FPS: &[u8] = include_bytes!("test_data/test-25fps.h264");
+
+    /// Converts an Annex B stream into a length-prefixed one.
+    fn to_length_prefixed(stream: &[u8], length_size: usize) -> Vec<u8> {
+        let mut cursor = Cursor::new(stream);
+        let mut out = vec![];
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            let len = nalu.size.to_be_bytes();
+            out.extend_from_slice(&len[len.len() - length_size..]);
+            out.extend_from_slice(nalu.as_ref());
+        }
+
+        out
+    }
+
+    #[test]
+    fn parse_and_write_record() {
+        let mut cursor = Cursor::new(STREAM_TEST_25_FPS);
+        let mut sps = vec![];
+        let mut pps = vec![];
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::Sps => sps.push(nalu.as_ref().to_vec()),
+                NaluType::Pps => pps.push(nalu.as_ref().to_vec()),
+                _ => (),
+            }
+        }
+        sps.truncate(1);
+        pps.truncate(1);
+
+        let record = AvcDecoderConfigurationRecord::new(sps, pps, 4).unwrap();
+        assert_eq!(record.length_size(), 4);
+
+        let mut data = vec![];
+        record.write_into(&mut data).unwrap();
+        assert_eq!(&data[..6], &[1, 77, 64, 13, 0xff, 0xe1]);
+
+        let parsed = AvcDecoderConfigurationRecord::parse(&data).unwrap();
+        assert_eq!(parsed, record);
+
+        let mut parser = Parser::default();
+        parsed.parse_parameter_sets(&mut parser).unwrap();
+        let sps = parser.get_sps(0).unwrap();
+        assert_eq!(sps.profile_idc, 77);
+        assert!(parser.get_pps(0).is_some());
+
+        // High profiles carry the chroma format and bit depths.
+        let mut high = parsed.clone();
+        high.avc_profile_indication = 100;
+        high.ext = Some(AvcConfigExtension {
+            chroma_format: 1,
+            bit_depth_luma_minus8: 2,
+            bit_depth_chroma_minus8: 2,
+            sps_ext: vec![vec![13, 0]],
+        });
+        data.clear();
+        high.write_into(&mut data).unwrap();
+        assert_eq!(AvcDecoderConfigurationRecord::parse(&data).unwrap(), high);
+
+        assert!(AvcDecoderConfigurationRecord::parse(&data[..data.len() - 1]).is_err());
+        data[0] = 2;
+        assert!(AvcDecoderConfigurationRecord::parse(&data).is_err());
+    }
+
+    #[test]
+    fn length_prefixed_nalus() {
+        let mut cursor = Cursor::new(STREAM_TEST_25_FPS);
+        let mut annex_b = vec![];
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            annex_b.push((nalu.header.type_, nalu.as_ref().to_vec()));
+        }
+
+        for length_size in [2, 4] {
+            let stream = to_length_prefixed(STREAM_TEST_25_FPS, length_size);
+            let nalus = LengthPrefixedNaluIterator::<NaluHeader>::new(&stream, length_size)
+                .map(|nalu| {
+                    let nalu = nalu.unwrap();
+                    (nalu.header.type_, nalu.as_ref().to_vec())
+                })
+                .collect::<Vec<_>>();
+
+            assert_eq!(nalus, annex_b);
+        }
+
+        // One byte lengths, with a truncated last NAL unit.
+        let stream = [2, 0x67, 0x42, 1, 0x68, 3, 0x65];
+        let mut nalus = LengthPrefixedNaluIterator::<NaluHeader>::new(&stream, 1);
+        assert_eq!(nalus.next().unwrap().unwrap().header.type_, NaluType::Sps);
+        assert_eq!(nalus.next().unwrap().unwrap().as_ref(), &[0x68]);
+        assert!(nalus.next().unwrap().is_err());
+        assert!(nalus.next().is_none());
+
+        assert!(LengthPrefixedNaluIterator::<NaluHeader>::new(&stream, 3)
+            .next()
+            .unwrap()
+            .is_err());
+    }
+}
@@ -980,7 +980,7 @@ impl<T: Clone> Dpb<T> {
 
     /// 8.2.4.2.1 Initialization process for the reference picture list for P
     /// and SP slices in frames
-    fn build_ref_pic_list_p(&self) -> DpbPicRefList<T> {
+    fn build_ref_pic_list_p(&self) -> DpbPicRefList<'_, T> {
         let mut ref_pic_list_p0: Vec<_> = self
             .short_term_refs_iter()
             .filter(|h| !h.pic.borrow().is_second_field())
@@ -1004,7 +1004,7 @@ impl<T: Clone> Dpb<T> {
 
     /// 8.2.4.2.2 Initialization process for the reference picture list for P
     /// and SP slices in fields
-    fn build_ref_field_pic_list_p(&self, cur_pic: &PictureData) -> DpbPicRefList<T> {
+    fn build_ref_field_pic_list_p(&self, cur_pic: &PictureData) -> DpbPicRefList<'_, T> {
         let mut ref_pic_list_p0 = vec![];
 
         let mut ref_frame_list_0_short_term: Vec<_> = self.short_term_refs_iter().collect();
@@ -1035,7 +1035,10 @@ impl<T: Clone> Dpb<T> {
 
     // 8.2.4.2.3 Initialization process for reference picture lists for B slices
     // in frames
-    fn build_ref_pic_list_b(&self, cur_pic: &PictureData) -> (DpbPicRefList<T>, DpbPicRefList<T>) {
+    fn build_ref_pic_list_b(
+        &self,
+        cur_pic: &PictureData,
+    ) -> (DpbPicRefList<'_, T>, DpbPicRefList<'_, T>) {
         let mut short_term_refs: Vec<_> = self
             .short_term_refs_iter()
             .filter(|h| !h.pic.borrow().is_second_field())
@@ -1117,7 +1120,7 @@ impl<T: Clone> Dpb<T> {
     fn build_ref_field_pic_list_b(
         &self,
         cur_pic: &PictureData,
-    ) -> (DpbPicRefList<T>, DpbPicRefList<T>) {
+    ) -> (DpbPicRefList<'_, T>, DpbPicRefList<'_, T>) {
         let mut ref_pic_list_b0 = vec![];
         let mut ref_pic_list_b1 = vec![];
         let mut ref_frame_list_0_short_term = vec![];
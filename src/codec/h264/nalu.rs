@@ -5,6 +5,7 @@
 use std::borrow::Cow;
 use std::fmt::Debug;
 use std::io::Cursor;
+use std::marker::PhantomData;
 
 use bytes::Buf;
 use thiserror::Error;
@@ -38,6 +39,10 @@ pub enum NaluError {
     HeaderParseError(#[from] Box<dyn std::error::Error>),
     #[error("failed to convert read input to target type")]
     ConversionFailed,
+    #[error("invalid NALU length size: {0}")]
+    InvalidLengthSize(usize),
+    #[error("NALU of {0} bytes is larger than the remaining {1} bytes")]
+    TruncatedNalu(usize, usize),
 }
 
 impl<'a, U> Nalu<'a, U>
@@ -89,6 +94,12 @@ where
             next_nalu_offset
         };
 
+        // The header may have been parsed from the start code or trailing
+        // zero bytes that follow a truncated NALU.
+        if nal_size < hdr.len() {
+            return Err(NaluError::TruncatedNalu(hdr.len(), nal_size));
+        }
+
         Ok(Nalu {
             header: hdr,
             data: Cow::from(&bitstream[start_code_offset..nalu_offset + nal_size]),
@@ -96,6 +107,48 @@ where
             offset: nalu_offset - start_code_offset,
         })
     }
+
+    /// Find the next NAL unit prefixed by its length, as in the ISO/IEC
+    /// 14496-15 formats. `length_size` is the size of the big-endian length
+    /// field, and must be 1, 2 or 4.
+    pub fn next_length_prefixed(
+        cursor: &mut Cursor<&'a [u8]>,
+        length_size: usize,
+    ) -> Result<Nalu<'a, U>, NaluError> {
+        if !matches!(length_size, 1 | 2 | 4) {
+            return Err(NaluError::InvalidLengthSize(length_size));
+        }
+
+        let bitstream = cursor.clone().into_inner();
+        let pos = usize::try_from(cursor.position()).map_err(|_| NaluError::ConversionFailed)?;
+
+        if !cursor.has_remaining() {
+            return Err(NaluError::NoNalFound);
+        } else if cursor.remaining() < length_size {
+            return Err(NaluError::TruncatedNalu(length_size, cursor.remaining()));
+        }
+
+        let nal_size = usize::try_from(cursor.get_uint(length_size))
+            .map_err(|_| NaluError::ConversionFailed)?;
+        if cursor.remaining() < nal_size {
+            return Err(NaluError::TruncatedNalu(nal_size, cursor.remaining()));
+        }
+
+        let nalu_offset = pos + length_size;
+        let data = &bitstream[pos..nalu_offset + nal_size];
+        let hdr = U::parse(&Cursor::new(&data[length_size..]))?;
+
+        cursor.set_position(
+            u64::try_from(nalu_offset + nal_size).map_err(|_| NaluError::ConversionFailed)?,
+        );
+
+        Ok(Nalu {
+            header: hdr,
+            data: Cow::from(data),
+            size: nal_size,
+            offset: length_size,
+        })
+    }
 }
 
 impl<'a, U> Nalu<'a, U>
@@ -124,3 +177,46 @@ impl<'a, U> AsRef<[u8]> for Nalu<'a, U> {
         &self.data[self.offset..self.offset + self.size]
     }
 }
+
+/// Iterator over the NAL units of a buffer in which each NAL unit is prefixed
+/// by its length, as found in MP4 or Matroska samples.
+///
+/// The iteration stops after the first error.
+pub struct LengthPrefixedNaluIterator<'a, U> {
+    cursor: Cursor<&'a [u8]>,
+    length_size: usize,
+    header: PhantomData<U>,
+}
+
+impl<'a, U> LengthPrefixedNaluIterator<'a, U> {
+    /// Creates an iterator over `data`, with lengths of `length_size` bytes.
+    pub fn new(data: &'a [u8], length_size: usize) -> Self {
+        Self {
+            cursor: Cursor::new(data),
+            length_size,
+            header: PhantomData,
+        }
+    }
+}
+
+impl<'a, U> Iterator for LengthPrefixedNaluIterator<'a, U>
+where
+    U: Debug + Header,
+{
+    type Item = Result<Nalu<'a, U>, NaluError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.cursor.has_remaining() {
+            return None;
+        }
+
+        let nalu = Nalu::next_length_prefixed(&mut self.cursor, self.length_size);
+        if nalu.is_err() {
+            // Do not try to resynchronize on garbage.
+            let end = self.cursor.get_ref().len() as u64;
+            self.cursor.set_position(end);
+        }
+
+        Some(nalu)
+    }
+}
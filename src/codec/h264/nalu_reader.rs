@@ -9,6 +9,7 @@ use thiserror::Error;
 
 /// A bit reader for h264 bitstreams. It properly handles emulation-prevention
 /// bytes and stop bits.
+#[derive(Clone)]
 pub(crate) struct NaluReader<'a> {
     /// A reference into the next unread byte in the stream.
     data: Cursor<&'a [u8]>,
@@ -0,0 +1,297 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! An incremental splitter of Annex B streams into NAL units, for input that
+//! arrives in arbitrary chunks. This works for both H.264 and H.265.
+
+use std::borrow::Cow;
+use std::fmt::Debug;
+use std::io::Cursor;
+use std::marker::PhantomData;
+
+use crate::codec::h264::nalu::Header;
+use crate::codec::h264::nalu::Nalu;
+use crate::codec::h264::nalu::NaluError;
+
+const START_CODE: [u8; 3] = [0x00, 0x00, 0x01];
+
+fn find_start_code(data: &[u8]) -> Option<usize> {
+    data.windows(START_CODE.len())
+        .position(|window| window == START_CODE)
+}
+
+/// Splits an Annex B stream pushed in chunks into owned NAL units.
+///
+/// A NAL unit is only returned once the start code of the next one is found,
+/// or once [`NaluSplitter::flush`] signals the end of the stream. The returned
+/// units are the same as the ones returned by [`Nalu::next`] on the whole
+/// stream, i.e. they include their start code and exclude the trailing zero
+/// bytes.
+pub struct NaluSplitter<U> {
+    /// The data not returned yet.
+    buf: Vec<u8>,
+    /// The position in `buf` of the start code of the current NAL unit.
+    start: Option<usize>,
+    /// The position in `buf` from which to look for the next start code.
+    scan: usize,
+    /// Whether the end of the stream has been reached.
+    eos: bool,
+    header: PhantomData<U>,
+}
+
+impl<U> Default for NaluSplitter<U> {
+    fn default() -> Self {
+        Self {
+            buf: Vec::new(),
+            start: None,
+            scan: 0,
+            eos: false,
+            header: PhantomData,
+        }
+    }
+}
+
+impl<U> NaluSplitter<U>
+where
+    U: Debug + Header,
+{
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Appends `data` to the stream.
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+        self.eos = false;
+    }
+
+    /// Signals the end of the stream, so that the last NAL unit can be
+    /// returned. More data can be pushed afterwards, e.g. for a new stream.
+    pub fn flush(&mut self) {
+        self.eos = true;
+    }
+
+    /// Returns the next complete NAL unit, or `None` if more data is needed.
+    ///
+    /// A NAL unit whose header cannot be parsed is returned as an error and
+    /// skipped.
+    pub fn next_nalu(&mut self) -> Option<Result<Nalu<'static, U>, NaluError>> {
+        let start = match self.start {
+            Some(start) => start,
+            None => match find_start_code(&self.buf[self.scan..]) {
+                Some(pos) => {
+                    let start = self.scan + pos;
+                    self.start = Some(start);
+                    start
+                }
+                None => {
+                    // Drop the leading garbage, but keep what could be the
+                    // beginning of a four byte start code.
+                    let keep = if self.eos { 0 } else { START_CODE.len() };
+                    self.buf.drain(..self.buf.len().saturating_sub(keep));
+                    self.scan = 0;
+                    return None;
+                }
+            },
+        };
+
+        let nalu_offset = start + START_CODE.len();
+        let search_from = std::cmp::max(self.scan, nalu_offset);
+
+        match find_start_code(&self.buf[search_from..]) {
+            Some(pos) => {
+                let next_start = search_from + pos;
+                let nalu = self.build_nalu(start, next_start);
+
+                // Keep the byte before the next start code, which may be its
+                // zero_byte.
+                let drained = next_start - 1;
+                self.buf.drain(..drained);
+                self.start = Some(next_start - drained);
+                self.scan = 0;
+
+                Some(nalu)
+            }
+            None if self.eos => {
+                let nalu = self.build_nalu(start, self.buf.len());
+
+                self.buf.clear();
+                self.start = None;
+                self.scan = 0;
+
+                Some(nalu)
+            }
+            None => {
+                // The next start code may straddle the end of the data.
+                self.scan = std::cmp::max(
+                    self.buf.len().saturating_sub(START_CODE.len() - 1),
+                    nalu_offset,
+                );
+
+                None
+            }
+        }
+    }
+
+    /// Builds the NAL unit whose start code is at `start` and whose data ends
+    /// at `end`, trailing zero bytes included.
+    fn build_nalu(&self, start: usize, mut end: usize) -> Result<Nalu<'static, U>, NaluError> {
+        let nalu_offset = start + START_CODE.len();
+
+        // Discard trailing_zero_8bits.
+        while end > nalu_offset && self.buf[end - 1] == 0 {
+            end -= 1;
+        }
+
+        let hdr = U::parse(&Cursor::new(&self.buf[nalu_offset..end]))?;
+
+        // Include the zero_byte of four byte start codes.
+        let data_start = if start > 0 && self.buf[start - 1] == 0 {
+            start - 1
+        } else {
+            start
+        };
+
+        let size = if hdr.is_end() {
+            hdr.len()
+        } else {
+            end - nalu_offset
+        };
+
+        Ok(Nalu {
+            header: hdr,
+            data: Cow::Owned(self.buf[data_start..nalu_offset + size].to_vec()),
+            size,
+            offset: nalu_offset - data_start,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::h264::parser::NaluHeader as H264NaluHeader;
+    use crate::codec::h265::parser::NaluHeader as H265NaluHeader;
+
+    const STREAM_H264: &[u8] = include_bytes!("test_data/test-25fps.h264");
+    const STREAM_H265: &[u8] = include_bytes!("../h265/test_data/test-25fps.h265");
+
+    fn split_whole<U: Debug + Header>(stream: &[u8]) -> Vec<(Vec<u8>, usize, usize)> {
+        let mut cursor = Cursor::new(stream);
+        let mut nalus = vec![];
+        while let Ok(nalu) = Nalu::<U>::next(&mut cursor) {
+            nalus.push((nalu.data.to_vec(), nalu.offset, nalu.size));
+        }
+
+        nalus
+    }
+
+    fn split_chunked<U: Debug + Header>(
+        stream: &[u8],
+        chunk_size: usize,
+    ) -> Vec<(Vec<u8>, usize, usize)> {
+        let mut splitter = NaluSplitter::<U>::new();
+        let mut nalus = vec![];
+        let mut collect = |splitter: &mut NaluSplitter<U>| {
+            while let Some(nalu) = splitter.next_nalu() {
+                let nalu = nalu.unwrap();
+                nalus.push((nalu.data.to_vec(), nalu.offset, nalu.size));
+            }
+        };
+
+        for chunk in stream.chunks(chunk_size) {
+            splitter.push(chunk);
+            collect(&mut splitter);
+        }
+        splitter.flush();
+        collect(&mut splitter);
+
+        nalus
+    }
+
+    #[test]
+    fn split_streams() {
+        let h264 = split_whole::<H264NaluHeader>(STREAM_H264);
+        let h265 = split_whole::<H265NaluHeader>(STREAM_H265);
+
+        for chunk_size in [1, 2, 3, 5, 1000, STREAM_H264.len()] {
+            assert_eq!(
+                split_chunked::<H264NaluHeader>(STREAM_H264, chunk_size),
+                h264
+            );
+        }
+
+        for chunk_size in [1, 4, 4096] {
+            assert_eq!(
+                split_chunked::<H265NaluHeader>(STREAM_H265, chunk_size),
+                h265
+            );
+        }
+    }
+
+    #[test]
+    fn split_start_codes_and_trailing_zeros() {
+        // Leading garbage, a four byte start code, trailing zeros followed by
+        // another start code, and trailing zeros at the end of the stream.
+        const STREAM: &[u8] = &[
+            0xff, 0x00, 0x00, 0x00, 0x01, 0x09, 0x10, 0x00, 0x00, 0x00, 0x00, 0x01, 0x0c, 0xff,
+            0x00, 0x00,
+        ];
+
+        assert_eq!(
+            split_whole::<H264NaluHeader>(STREAM),
+            split_chunked::<H264NaluHeader>(STREAM, STREAM.len())
+        );
+
+        for chunk_size in 1..STREAM.len() {
+            let nalus = split_chunked::<H264NaluHeader>(STREAM, chunk_size);
+            assert_eq!(
+                nalus,
+                [
+                    (vec![0x00, 0x00, 0x00, 0x01, 0x09, 0x10], 4, 2),
+                    (vec![0x00, 0x00, 0x00, 0x01, 0x0c, 0xff], 4, 2),
+                ]
+            );
+        }
+
+        // Nothing is returned before the end of the stream or the next start
+        // code.
+        let mut splitter = NaluSplitter::<H264NaluHeader>::new();
+        splitter.push(&STREAM[..9]);
+        assert!(splitter.next_nalu().is_none());
+        splitter.push(&STREAM[9..12]);
+        assert_eq!(
+            splitter.next_nalu().unwrap().unwrap().as_ref(),
+            &[0x09, 0x10]
+        );
+        assert!(splitter.next_nalu().is_none());
+        splitter.push(&STREAM[12..]);
+        assert!(splitter.next_nalu().is_none());
+        splitter.flush();
+        assert_eq!(
+            splitter.next_nalu().unwrap().unwrap().as_ref(),
+            &[0x0c, 0xff]
+        );
+        assert!(splitter.next_nalu().is_none());
+
+        // An invalid header is reported and skipped.
+        splitter.push(&[0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x0a]);
+        splitter.flush();
+        assert!(splitter.next_nalu().unwrap().is_err());
+        assert_eq!(splitter.next_nalu().unwrap().unwrap().as_ref(), &[0x0a]);
+
+        // An H.265 NAL unit shorter than its two byte header is reported
+        // rather than parsed.
+        let mut splitter = NaluSplitter::<H265NaluHeader>::new();
+        splitter.push(&[0x00, 0x00, 0x01, 0x40]);
+        splitter.flush();
+        assert!(splitter.next_nalu().unwrap().is_err());
+        assert!(splitter.next_nalu().is_none());
+
+        // The same goes for Nalu::next, which must not take the header from
+        // the following start code.
+        let mut cursor = Cursor::new(&[0x00, 0x00, 0x01, 0x40, 0x00, 0x00, 0x01, 0x40, 0x01][..]);
+        assert!(Nalu::<H265NaluHeader>::next(&mut cursor).is_err());
+    }
+}
@@ -3,7 +3,6 @@
 // found in the LICENSE file.
 use std::io::Write;
 
-use log::error;
 use thiserror::Error;
 
 use crate::utils::BitWriter;
@@ -30,8 +29,9 @@ impl<W: Write> EmulationPrevention<W> {
     fn write_byte(&mut self, curr_byte: u8) -> std::io::Result<()> {
         if self.prev_bytes[1] == Some(0x00) && self.prev_bytes[0] == Some(0x00) && curr_byte <= 0x03
         {
-            self.out.write_all(&[0x00, 0x00, 0x03, curr_byte])?;
-            self.prev_bytes = [None; 2];
+            self.out.write_all(&[0x00, 0x00, 0x03])?;
+            // Keep the current byte pending, it may start another zero sequence.
+            self.prev_bytes = [Some(curr_byte), None];
         } else {
             if let Some(byte) = self.prev_bytes[1] {
                 self.out.write_all(&[byte])?;
@@ -44,15 +44,10 @@ impl<W: Write> EmulationPrevention<W> {
         Ok(())
     }
 
-    /// Writes a H.264 NALU header.
-    fn write_header(&mut self, idc: u8, type_: u8) -> NaluWriterResult<()> {
-        self.out.write_all(&[
-            0x00,
-            0x00,
-            0x00,
-            0x01,
-            (idc & 0b11) << 5 | (type_ & 0b11111),
-        ])?;
+    /// Writes a start code followed by the NALU header bytes.
+    fn write_header(&mut self, header: &[u8]) -> NaluWriterResult<()> {
+        self.out.write_all(&[0x00, 0x00, 0x00, 0x01])?;
+        self.out.write_all(header)?;
 
         Ok(())
     }
@@ -171,8 +166,14 @@ impl<W: Write> NaluWriter<W> {
 
     /// Writes a H.264 NALU header.
     pub fn write_header(&mut self, idc: u8, _type: u8) -> NaluWriterResult<()> {
+        self.write_raw_header(&[(idc & 0b11) << 5 | (_type & 0b11111)])
+    }
+
+    /// Writes a start code followed by already packed NALU header bytes. Useful for codecs
+    /// whose NALU header layout differs from H.264, e.g. the two byte H.265 header.
+    pub fn write_raw_header(&mut self, header: &[u8]) -> NaluWriterResult<()> {
         self.0.flush()?;
-        self.0.inner_mut().write_header(idc, _type)?;
+        self.0.inner_mut().write_header(header)?;
         Ok(())
     }
 
@@ -290,5 +291,14 @@ mod tests {
         test(&[0x00, 0x00, 0x00, 0x01], &[0x00, 0x00, 0x03, 0x00, 0x01]);
         test(&[0x00, 0x00, 0x00, 0x02], &[0x00, 0x00, 0x03, 0x00, 0x02]);
         test(&[0x00, 0x00, 0x00, 0x03], &[0x00, 0x00, 0x03, 0x00, 0x03]);
+
+        test(
+            &[0x00, 0x00, 0x00, 0x00, 0x00],
+            &[0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00],
+        );
+        test(
+            &[0x90, 0x00, 0x00, 0x00, 0x00, 0x00, 0x3c],
+            &[0x90, 0x00, 0x00, 0x03, 0x00, 0x00, 0x03, 0x00, 0x3c],
+        );
     }
 }
@@ -444,9 +444,10 @@ pub struct Slice<'a> {
     pub nalu: Nalu<'a>,
 }
 
-#[derive(N, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(N, Clone, Copy, Debug, Default, PartialEq, Eq)]
 /// See table 7-6 in the specification.
 pub enum SliceType {
+    #[default]
     P = 0,
     B = 1,
     I = 2,
@@ -481,12 +482,6 @@ impl SliceType {
     }
 }
 
-impl Default for SliceType {
-    fn default() -> Self {
-        Self::P
-    }
-}
-
 #[derive(N, Clone, Copy)]
 #[repr(u8)]
 pub enum Profile {
@@ -949,8 +944,8 @@ impl SpsBuilder {
     pub fn resolution(mut self, width: u32, height: u32) -> Self {
         const MB_SIZE: u32 = 16;
 
-        let mb_width = (width + MB_SIZE - 1) / MB_SIZE;
-        let mb_height = (height + MB_SIZE - 1) / MB_SIZE;
+        let mb_width = width.div_ceil(MB_SIZE);
+        let mb_height = height.div_ceil(MB_SIZE);
 
         self = self.resolution_in_mbs(mb_width, mb_height);
 
@@ -1563,6 +1558,238 @@ impl PpsBuilder {
     }
 }
 
+/// The payload types of the SEI messages that can be parsed, see Annex D.
+const SEI_BUFFERING_PERIOD: u32 = 0;
+const SEI_PIC_TIMING: u32 = 1;
+const SEI_USER_DATA_REGISTERED_ITU_T_T35: u32 = 4;
+const SEI_USER_DATA_UNREGISTERED: u32 = 5;
+const SEI_RECOVERY_POINT: u32 = 6;
+const SEI_FRAME_PACKING_ARRANGEMENT: u32 = 45;
+const SEI_MASTERING_DISPLAY_COLOUR_VOLUME: u32 = 137;
+const SEI_CONTENT_LIGHT_LEVEL_INFO: u32 = 144;
+
+/// Buffering period SEI message, see D.1.2.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferingPeriod {
+    /// Specifies the sequence parameter set that contains the sequence HRD
+    /// attributes.
+    pub seq_parameter_set_id: u8,
+    /// `[ SchedSelIdx ]` specifies the delay between the time of arrival in
+    /// the CPB of the first bit of the coded data associated with the access
+    /// unit and the time of removal from the CPB, for the NAL HRD.
+    pub nal_initial_cpb_removal_delay: [u32; 32],
+    /// `[ SchedSelIdx ]` is used in combination with the
+    /// `cpb_removal_delay` to specify the initial delivery time of coded
+    /// access units to the CPB, for the NAL HRD.
+    pub nal_initial_cpb_removal_delay_offset: [u32; 32],
+    /// Same as `nal_initial_cpb_removal_delay`, for the VCL HRD.
+    pub vcl_initial_cpb_removal_delay: [u32; 32],
+    /// Same as `nal_initial_cpb_removal_delay_offset`, for the VCL HRD.
+    pub vcl_initial_cpb_removal_delay_offset: [u32; 32],
+}
+
+/// A clock timestamp of the picture timing SEI message, see D.1.3.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClockTimestamp {
+    /// Indicates the scan type (interlaced or progressive) of the source
+    /// material.
+    pub ct_type: u8,
+    /// Used in computing clockTimestamp.
+    pub nuit_field_based_flag: bool,
+    /// Specifies the method of dropping values of `n_frames`.
+    pub counting_type: u8,
+    /// Specifies that the `seconds_value`, `minutes_value` and `hours_value`
+    /// syntax elements are present.
+    pub full_timestamp_flag: bool,
+    /// Indicates whether the difference between the current and the previous
+    /// clockTimestamp can be interpreted as the time difference between the
+    /// times of origin or capture of the associated frames or fields.
+    pub discontinuity_flag: bool,
+    /// Specifies the skipping of one or more values of `n_frames` using the
+    /// counting method specified by `counting_type`.
+    pub cnt_dropped_flag: bool,
+    /// Specifies the value of nFrames used to compute clockTimestamp.
+    pub n_frames: u8,
+    /// Specifies that `seconds_value` is present.
+    pub seconds_flag: bool,
+    /// Specifies the value of sS used to compute clockTimestamp.
+    pub seconds_value: u8,
+    /// Specifies that `minutes_value` is present.
+    pub minutes_flag: bool,
+    /// Specifies the value of mM used to compute clockTimestamp.
+    pub minutes_value: u8,
+    /// Specifies that `hours_value` is present.
+    pub hours_flag: bool,
+    /// Specifies the value of hH used to compute clockTimestamp.
+    pub hours_value: u8,
+    /// Specifies the value of tOffset used to compute clockTimestamp.
+    pub time_offset: i32,
+}
+
+/// Picture timing SEI message, see D.1.3.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PicTiming {
+    /// Specifies how many clock ticks to wait after removal from the CPB of
+    /// the access unit associated with the most recent buffering period SEI
+    /// message before removing from the buffer the access unit data
+    /// associated with the picture timing SEI message.
+    pub cpb_removal_delay: u32,
+    /// Used to compute the DPB output time of the picture.
+    pub dpb_output_delay: u32,
+    /// Indicates whether a picture should be displayed as a frame or one or
+    /// more fields, according to Table D-1.
+    pub pic_struct: u8,
+    /// `[ i ]` specifies that the i-th clock timestamp is present.
+    pub clock_timestamp_flag: [bool; 3],
+    /// The clock timestamps, as many as NumClockTS.
+    pub clock_timestamp: [ClockTimestamp; 3],
+}
+
+/// User data registered by Rec. ITU-T T.35 SEI message, see D.1.6.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserDataRegisteredItuTT35 {
+    /// A country code as specified by Annex A of Rec. ITU-T T.35.
+    pub itu_t_t35_country_code: u8,
+    /// A country code as specified by Annex B of Rec. ITU-T T.35, if
+    /// `itu_t_t35_country_code` is 0xff.
+    pub itu_t_t35_country_code_extension_byte: u8,
+    /// The user data, starting with the terminal provider code.
+    pub itu_t_t35_payload: Vec<u8>,
+}
+
+/// User data unregistered SEI message, see D.1.7.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct UserDataUnregistered {
+    /// A UUID according to the procedures of ISO/IEC 11578.
+    pub uuid_iso_iec_11578: [u8; 16],
+    /// The user data.
+    pub user_data_payload: Vec<u8>,
+}
+
+/// Recovery point SEI message, see D.1.8.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryPoint {
+    /// Specifies the recovery point of output pictures in output order.
+    pub recovery_frame_cnt: u32,
+    /// Indicates whether decoded pictures at and subsequent to the recovery
+    /// point in output order are an exact match to the pictures that would be
+    /// produced by starting the decoding process at the previous IDR picture.
+    pub exact_match_flag: bool,
+    /// Indicates the presence or absence of a broken link in the NAL unit
+    /// stream at the location of the recovery point SEI message.
+    pub broken_link_flag: bool,
+    /// Indicates whether decoded slice group change cycles are present.
+    pub changing_slice_group_idc: u8,
+}
+
+/// Frame packing arrangement SEI message, see D.1.26.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FramePackingArrangement {
+    /// Identifies the frame packing arrangement.
+    pub frame_packing_arrangement_id: u32,
+    /// Indicates that the SEI message cancels the persistence of any previous
+    /// frame packing arrangement SEI message in output order.
+    pub frame_packing_arrangement_cancel_flag: bool,
+    /// Indicates the type of packing arrangement of the frames, as per Table
+    /// D-8.
+    pub frame_packing_arrangement_type: u8,
+    /// Indicates that each color component plane of each constituent frame
+    /// is quincunx sampled.
+    pub quincunx_sampling_flag: bool,
+    /// Indicates the intended interpretation of the constituent frames, as
+    /// per Table D-9.
+    pub content_interpretation_type: u8,
+    /// Indicates that one of the two constituent frames is spatially flipped.
+    pub spatial_flipping_flag: bool,
+    /// Indicates which one of the two constituent frames is flipped.
+    pub frame0_flipped_flag: bool,
+    /// Indicates that the constituent frames are fields.
+    pub field_views_flag: bool,
+    /// Indicates whether the current decoded frame is constituent frame 0.
+    pub current_frame_is_frame0_flag: bool,
+    /// Indicates that constituent frame 0 can be decoded without referencing
+    /// constituent frame 1.
+    pub frame0_self_contained_flag: bool,
+    /// Indicates that constituent frame 1 can be decoded without referencing
+    /// constituent frame 0.
+    pub frame1_self_contained_flag: bool,
+    /// Specifies the horizontal location of the upper left sample of
+    /// constituent frame 0.
+    pub frame0_grid_position_x: u8,
+    /// Specifies the vertical location of the upper left sample of
+    /// constituent frame 0.
+    pub frame0_grid_position_y: u8,
+    /// Specifies the horizontal location of the upper left sample of
+    /// constituent frame 1.
+    pub frame1_grid_position_x: u8,
+    /// Specifies the vertical location of the upper left sample of
+    /// constituent frame 1.
+    pub frame1_grid_position_y: u8,
+    /// Reserved for future use.
+    pub frame_packing_arrangement_reserved_byte: u8,
+    /// Specifies the persistence of the frame packing arrangement SEI
+    /// message.
+    pub frame_packing_arrangement_repetition_period: u32,
+    /// Indicates that additional data follows within the SEI message.
+    pub frame_packing_arrangement_extension_flag: bool,
+}
+
+/// Mastering display colour volume SEI message, see D.1.29.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MasteringDisplayColourVolume {
+    /// `[ c ]` specifies the normalized x chromaticity coordinate of the
+    /// colour primary component c of the mastering display, in increments of
+    /// 0.00002.
+    pub display_primaries_x: [u16; 3],
+    /// `[ c ]` specifies the normalized y chromaticity coordinate of the
+    /// colour primary component c of the mastering display, in increments of
+    /// 0.00002.
+    pub display_primaries_y: [u16; 3],
+    /// Specifies the normalized x chromaticity coordinate of the white point
+    /// of the mastering display, in increments of 0.00002.
+    pub white_point_x: u16,
+    /// Specifies the normalized y chromaticity coordinate of the white point
+    /// of the mastering display, in increments of 0.00002.
+    pub white_point_y: u16,
+    /// Specifies the nominal maximum display luminance of the mastering
+    /// display, in units of 0.0001 candelas per square metre.
+    pub max_display_mastering_luminance: u32,
+    /// Specifies the nominal minimum display luminance of the mastering
+    /// display, in units of 0.0001 candelas per square metre.
+    pub min_display_mastering_luminance: u32,
+}
+
+/// Content light level information SEI message, see D.1.31.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ContentLightLevelInfo {
+    /// Indicates an upper bound on the maximum light level among all
+    /// individual samples, in units of candelas per square metre.
+    pub max_content_light_level: u16,
+    /// Indicates an upper bound on the maximum average light level among the
+    /// samples of any individual picture, in units of candelas per square
+    /// metre.
+    pub max_pic_average_light_level: u16,
+}
+
+/// A SEI message, as parsed by [`Parser::parse_sei`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SeiMessage {
+    BufferingPeriod(Box<BufferingPeriod>),
+    PicTiming(PicTiming),
+    UserDataRegisteredItuTT35(UserDataRegisteredItuTT35),
+    UserDataUnregistered(UserDataUnregistered),
+    RecoveryPoint(RecoveryPoint),
+    FramePackingArrangement(FramePackingArrangement),
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    ContentLightLevelInfo(ContentLightLevelInfo),
+    /// A message whose payload type is not parsed. `payload` holds the
+    /// payload bytes, with the emulation prevention bytes removed.
+    Unknown {
+        payload_type: u32,
+        payload: Vec<u8>,
+    },
+}
+
 #[derive(Error, Debug)]
 pub enum ParserError {
     #[error("NaluReaderError: {0}")]
@@ -1596,6 +1823,8 @@ pub type ParserResult<T> = Result<T, ParserError>;
 pub struct Parser {
     active_spses: BTreeMap<u8, Rc<Sps>>,
     active_ppses: BTreeMap<u8, Rc<Pps>>,
+    /// The SPS used to parse the picture timing SEI messages.
+    last_sps_id: Option<u8>,
 }
 
 impl Parser {
@@ -1611,7 +1840,7 @@ impl Parser {
 
     fn fill_default_scaling_list_8x8(scaling_list8x8: &mut [u8; 64], i: usize) {
         assert!(i < 6);
-        if i % 2 == 0 {
+        if i.is_multiple_of(2) {
             *scaling_list8x8 = DEFAULT_8X8_INTRA;
         } else {
             *scaling_list8x8 = DEFAULT_8X8_INTER;
@@ -2086,6 +2315,7 @@ impl Parser {
 
         let key = sps.seq_parameter_set_id;
         self.active_spses.insert(key, Rc::new(sps));
+        self.last_sps_id = Some(key);
 
         if self.active_spses.keys().len() > MAX_SPS_COUNT as usize {
             return Err(ParserError::BrokenStream(
@@ -2585,6 +2815,396 @@ impl Parser {
         Ok(Slice { header, nalu })
     }
 
+    /// Reads a `u(v)` syntax element of up to 32 bits.
+    fn read_bits_u32(r: &mut NaluReader, num_bits: usize) -> ParserResult<u32> {
+        if num_bits == 32 {
+            let value = r.read_bits::<u32>(31)? << 1;
+            Ok(value | r.read_bit()? as u32)
+        } else {
+            Ok(r.read_bits(num_bits)?)
+        }
+    }
+
+    /// Reads `num_bytes` bytes of SEI payload.
+    fn read_payload_bytes(r: &mut NaluReader, num_bytes: usize) -> ParserResult<Vec<u8>> {
+        (0..num_bytes).map(|_| Ok(r.read_bits(8)?)).collect()
+    }
+
+    /// Reads a `payloadType` or `payloadSize` value, coded as a run of 0xff
+    /// bytes followed by a last byte. See 7.3.2.3.1.
+    fn read_sei_value(r: &mut NaluReader) -> ParserResult<u32> {
+        let mut value = 0u32;
+        loop {
+            let byte = r.read_bits::<u32>(8)?;
+            value = value.checked_add(byte).ok_or(ParserError::BrokenStream(
+                "SEI payload type or size overflow",
+            ))?;
+
+            if byte != 0xff {
+                return Ok(value);
+            }
+        }
+    }
+
+    fn parse_initial_cpb_removal_delays(
+        r: &mut NaluReader,
+        hrd: &HrdParams,
+        delay: &mut [u32; 32],
+        delay_offset: &mut [u32; 32],
+    ) -> ParserResult<()> {
+        let length = usize::from(hrd.initial_cpb_removal_delay_length_minus1) + 1;
+
+        for sched_sel_idx in 0..=usize::from(hrd.cpb_cnt_minus1) {
+            delay[sched_sel_idx] = Parser::read_bits_u32(r, length)?;
+            delay_offset[sched_sel_idx] = Parser::read_bits_u32(r, length)?;
+        }
+
+        Ok(())
+    }
+
+    /// Parses a buffering period SEI message. Returns `None` if the SPS it
+    /// references has not been parsed yet.
+    fn parse_buffering_period(
+        &mut self,
+        r: &mut NaluReader,
+    ) -> ParserResult<Option<BufferingPeriod>> {
+        let mut bp = BufferingPeriod {
+            seq_parameter_set_id: r.read_ue_max(MAX_SPS_COUNT as u32 - 1)?,
+            ..Default::default()
+        };
+
+        let Some(sps) = self.get_sps(bp.seq_parameter_set_id) else {
+            return Ok(None);
+        };
+        let vui = &sps.vui_parameters;
+
+        if vui.nal_hrd_parameters_present_flag {
+            Parser::parse_initial_cpb_removal_delays(
+                r,
+                &vui.nal_hrd_parameters,
+                &mut bp.nal_initial_cpb_removal_delay,
+                &mut bp.nal_initial_cpb_removal_delay_offset,
+            )?;
+        }
+
+        if vui.vcl_hrd_parameters_present_flag {
+            Parser::parse_initial_cpb_removal_delays(
+                r,
+                &vui.vcl_hrd_parameters,
+                &mut bp.vcl_initial_cpb_removal_delay,
+                &mut bp.vcl_initial_cpb_removal_delay_offset,
+            )?;
+        }
+
+        // The buffering period activates the SPS it references.
+        self.last_sps_id = Some(bp.seq_parameter_set_id);
+
+        Ok(Some(bp))
+    }
+
+    fn parse_clock_timestamp(
+        r: &mut NaluReader,
+        time_offset_length: u8,
+        ts: &mut ClockTimestamp,
+    ) -> ParserResult<()> {
+        ts.ct_type = r.read_bits(2)?;
+        ts.nuit_field_based_flag = r.read_bit()?;
+        ts.counting_type = r.read_bits(5)?;
+        ts.full_timestamp_flag = r.read_bit()?;
+        ts.discontinuity_flag = r.read_bit()?;
+        ts.cnt_dropped_flag = r.read_bit()?;
+        ts.n_frames = r.read_bits(8)?;
+
+        if ts.full_timestamp_flag {
+            ts.seconds_value = r.read_bits(6)?;
+            ts.minutes_value = r.read_bits(6)?;
+            ts.hours_value = r.read_bits(5)?;
+        } else {
+            ts.seconds_flag = r.read_bit()?;
+            if ts.seconds_flag {
+                ts.seconds_value = r.read_bits(6)?;
+                ts.minutes_flag = r.read_bit()?;
+                if ts.minutes_flag {
+                    ts.minutes_value = r.read_bits(6)?;
+                    ts.hours_flag = r.read_bit()?;
+                    if ts.hours_flag {
+                        ts.hours_value = r.read_bits(5)?;
+                    }
+                }
+            }
+        }
+
+        if time_offset_length > 0 {
+            // i(v): a two's complement value of time_offset_length bits.
+            let length = usize::from(time_offset_length);
+            let value = i64::from(r.read_bits::<u32>(length)?);
+            let sign = (value >> (length - 1)) & 1;
+            ts.time_offset = (value - (sign << length)) as i32;
+        }
+
+        Ok(())
+    }
+
+    /// Parses a picture timing SEI message. Returns `None` if no SPS has been
+    /// parsed yet.
+    fn parse_pic_timing(&self, r: &mut NaluReader) -> ParserResult<Option<PicTiming>> {
+        let Some(sps) = self.last_sps_id.and_then(|sps_id| self.get_sps(sps_id)) else {
+            return Ok(None);
+        };
+        let vui = &sps.vui_parameters;
+
+        // Both HRDs shall use the same lengths if present.
+        let hrd = if vui.nal_hrd_parameters_present_flag {
+            Some(&vui.nal_hrd_parameters)
+        } else if vui.vcl_hrd_parameters_present_flag {
+            Some(&vui.vcl_hrd_parameters)
+        } else {
+            None
+        };
+
+        let mut pt = PicTiming::default();
+
+        // CpbDpbDelaysPresentFlag
+        if let Some(hrd) = hrd {
+            pt.cpb_removal_delay =
+                Parser::read_bits_u32(r, usize::from(hrd.cpb_removal_delay_length_minus1) + 1)?;
+            pt.dpb_output_delay =
+                Parser::read_bits_u32(r, usize::from(hrd.dpb_output_delay_length_minus1) + 1)?;
+        }
+
+        if vui.pic_struct_present_flag {
+            pt.pic_struct = r.read_bits(4)?;
+
+            // See Table D-1.
+            let num_clock_ts = match pt.pic_struct {
+                0..=2 => 1,
+                3 | 4 | 7 => 2,
+                5 | 6 | 8 => 3,
+                _ => return Err(ParserError::NonCompliantStream("reserved pic_struct value")),
+            };
+
+            // time_offset_length is inferred to be 24 when not present.
+            let time_offset_length = hrd.map_or(24, |hrd| hrd.time_offset_length);
+
+            for i in 0..num_clock_ts {
+                pt.clock_timestamp_flag[i] = r.read_bit()?;
+                if pt.clock_timestamp_flag[i] {
+                    Parser::parse_clock_timestamp(
+                        r,
+                        time_offset_length,
+                        &mut pt.clock_timestamp[i],
+                    )?;
+                }
+            }
+        }
+
+        Ok(Some(pt))
+    }
+
+    fn parse_user_data_registered_itu_t_t35(
+        r: &mut NaluReader,
+        payload_size: usize,
+    ) -> ParserResult<UserDataRegisteredItuTT35> {
+        let mut ud = UserDataRegisteredItuTT35::default();
+        let mut header_size = 1;
+
+        ud.itu_t_t35_country_code = r.read_bits(8)?;
+        if ud.itu_t_t35_country_code == 0xff {
+            ud.itu_t_t35_country_code_extension_byte = r.read_bits(8)?;
+            header_size += 1;
+        }
+
+        let payload_size =
+            payload_size
+                .checked_sub(header_size)
+                .ok_or(ParserError::BrokenStream(
+                    "user data registered SEI payload too small",
+                ))?;
+        ud.itu_t_t35_payload = Parser::read_payload_bytes(r, payload_size)?;
+
+        Ok(ud)
+    }
+
+    fn parse_user_data_unregistered(
+        r: &mut NaluReader,
+        payload_size: usize,
+    ) -> ParserResult<UserDataUnregistered> {
+        let mut ud = UserDataUnregistered::default();
+
+        let payload_size = payload_size
+            .checked_sub(ud.uuid_iso_iec_11578.len())
+            .ok_or(ParserError::BrokenStream(
+                "user data unregistered SEI payload too small",
+            ))?;
+
+        for byte in ud.uuid_iso_iec_11578.iter_mut() {
+            *byte = r.read_bits(8)?;
+        }
+
+        ud.user_data_payload = Parser::read_payload_bytes(r, payload_size)?;
+
+        Ok(ud)
+    }
+
+    fn parse_recovery_point(r: &mut NaluReader) -> ParserResult<RecoveryPoint> {
+        Ok(RecoveryPoint {
+            recovery_frame_cnt: r.read_ue()?,
+            exact_match_flag: r.read_bit()?,
+            broken_link_flag: r.read_bit()?,
+            changing_slice_group_idc: r.read_bits(2)?,
+        })
+    }
+
+    fn parse_frame_packing_arrangement(
+        r: &mut NaluReader,
+    ) -> ParserResult<FramePackingArrangement> {
+        let mut fpa = FramePackingArrangement {
+            frame_packing_arrangement_id: r.read_ue()?,
+            frame_packing_arrangement_cancel_flag: r.read_bit()?,
+            ..Default::default()
+        };
+
+        if !fpa.frame_packing_arrangement_cancel_flag {
+            fpa.frame_packing_arrangement_type = r.read_bits(7)?;
+            fpa.quincunx_sampling_flag = r.read_bit()?;
+            fpa.content_interpretation_type = r.read_bits(6)?;
+            fpa.spatial_flipping_flag = r.read_bit()?;
+            fpa.frame0_flipped_flag = r.read_bit()?;
+            fpa.field_views_flag = r.read_bit()?;
+            fpa.current_frame_is_frame0_flag = r.read_bit()?;
+            fpa.frame0_self_contained_flag = r.read_bit()?;
+            fpa.frame1_self_contained_flag = r.read_bit()?;
+
+            if !fpa.quincunx_sampling_flag && fpa.frame_packing_arrangement_type != 5 {
+                fpa.frame0_grid_position_x = r.read_bits(4)?;
+                fpa.frame0_grid_position_y = r.read_bits(4)?;
+                fpa.frame1_grid_position_x = r.read_bits(4)?;
+                fpa.frame1_grid_position_y = r.read_bits(4)?;
+            }
+
+            fpa.frame_packing_arrangement_reserved_byte = r.read_bits(8)?;
+            fpa.frame_packing_arrangement_repetition_period = r.read_ue_max(16384)?;
+        }
+
+        fpa.frame_packing_arrangement_extension_flag = r.read_bit()?;
+
+        Ok(fpa)
+    }
+
+    fn parse_mastering_display_colour_volume(
+        r: &mut NaluReader,
+    ) -> ParserResult<MasteringDisplayColourVolume> {
+        let mut mdcv = MasteringDisplayColourVolume::default();
+
+        for c in 0..3 {
+            mdcv.display_primaries_x[c] = r.read_bits(16)?;
+            mdcv.display_primaries_y[c] = r.read_bits(16)?;
+        }
+
+        mdcv.white_point_x = r.read_bits(16)?;
+        mdcv.white_point_y = r.read_bits(16)?;
+        mdcv.max_display_mastering_luminance = Parser::read_bits_u32(r, 32)?;
+        mdcv.min_display_mastering_luminance = Parser::read_bits_u32(r, 32)?;
+
+        Ok(mdcv)
+    }
+
+    fn parse_content_light_level_info(r: &mut NaluReader) -> ParserResult<ContentLightLevelInfo> {
+        Ok(ContentLightLevelInfo {
+            max_content_light_level: r.read_bits(16)?,
+            max_pic_average_light_level: r.read_bits(16)?,
+        })
+    }
+
+    /// Parses the SEI messages of a SEI NALU. Payload types that are not
+    /// handled are returned as [`SeiMessage::Unknown`].
+    ///
+    /// Picture timing messages are parsed using the SPS referenced by the last
+    /// buffering period message, or else the last parsed SPS. Buffering period
+    /// and picture timing messages are also returned raw if their SPS has not
+    /// been parsed yet, which can happen as SEI NALUs may precede the SPS in
+    /// an access unit.
+    pub fn parse_sei(&mut self, nalu: &Nalu) -> ParserResult<Vec<SeiMessage>> {
+        if !matches!(nalu.header.type_, NaluType::Sei) {
+            return Err(ParserError::InvalidNaluType {
+                expected: &[NaluType::Sei],
+                actual: nalu.header.type_,
+            });
+        }
+
+        let data = nalu.as_ref();
+        // Skip the header
+        let rbsp = &data[nalu.header.len()..];
+        let mut r = NaluReader::new(rbsp);
+
+        // The number of bits read so far, excluding emulation prevention bytes.
+        let bits_read = |r: &NaluReader| rbsp.len() * 8 - r.num_bits_left() - r.num_epb() * 8;
+
+        let mut messages = Vec::new();
+
+        loop {
+            let payload_type = Parser::read_sei_value(&mut r)?;
+            let payload_size = Parser::read_sei_value(&mut r)? as usize;
+            let payload_start = bits_read(&r);
+
+            let payload_reader = r.clone();
+
+            let message = match payload_type {
+                SEI_BUFFERING_PERIOD => self
+                    .parse_buffering_period(&mut r)?
+                    .map(|bp| SeiMessage::BufferingPeriod(Box::new(bp))),
+                SEI_PIC_TIMING => self.parse_pic_timing(&mut r)?.map(SeiMessage::PicTiming),
+                SEI_USER_DATA_REGISTERED_ITU_T_T35 => Some(SeiMessage::UserDataRegisteredItuTT35(
+                    Parser::parse_user_data_registered_itu_t_t35(&mut r, payload_size)?,
+                )),
+                SEI_USER_DATA_UNREGISTERED => Some(SeiMessage::UserDataUnregistered(
+                    Parser::parse_user_data_unregistered(&mut r, payload_size)?,
+                )),
+                SEI_RECOVERY_POINT => Some(SeiMessage::RecoveryPoint(
+                    Parser::parse_recovery_point(&mut r)?,
+                )),
+                SEI_FRAME_PACKING_ARRANGEMENT => Some(SeiMessage::FramePackingArrangement(
+                    Parser::parse_frame_packing_arrangement(&mut r)?,
+                )),
+                SEI_MASTERING_DISPLAY_COLOUR_VOLUME => {
+                    Some(SeiMessage::MasteringDisplayColourVolume(
+                        Parser::parse_mastering_display_colour_volume(&mut r)?,
+                    ))
+                }
+                SEI_CONTENT_LIGHT_LEVEL_INFO => Some(SeiMessage::ContentLightLevelInfo(
+                    Parser::parse_content_light_level_info(&mut r)?,
+                )),
+                _ => None,
+            };
+
+            let message = match message {
+                Some(message) => message,
+                None => {
+                    r = payload_reader;
+                    SeiMessage::Unknown {
+                        payload_type,
+                        payload: Parser::read_payload_bytes(&mut r, payload_size)?,
+                    }
+                }
+            };
+
+            // Skip the reserved bits and the payload extension, if any.
+            let payload_bits_read = bits_read(&r) - payload_start;
+            let payload_bits_left = (payload_size * 8).checked_sub(payload_bits_read).ok_or(
+                ParserError::BrokenStream("SEI payload larger than its size"),
+            )?;
+            r.skip_bits(payload_bits_left)?;
+
+            messages.push(message);
+
+            if !r.has_more_rsbp_data() {
+                break;
+            }
+        }
+
+        Ok(messages)
+    }
+
     pub fn get_sps(&self, sps_id: u8) -> Option<&Rc<Sps>> {
         self.active_spses.get(&sps_id)
     }
@@ -2641,14 +3261,22 @@ impl Header for NaluHeader {
 mod tests {
     use std::io::Cursor;
 
+    use crate::codec::h264::parser::ContentLightLevelInfo;
+    use crate::codec::h264::parser::FramePackingArrangement;
     use crate::codec::h264::parser::Level;
+    use crate::codec::h264::parser::MasteringDisplayColourVolume;
     use crate::codec::h264::parser::MaxLongTermFrameIdx;
     use crate::codec::h264::parser::Nalu;
     use crate::codec::h264::parser::NaluType;
     use crate::codec::h264::parser::Parser;
+    use crate::codec::h264::parser::RecoveryPoint;
+    use crate::codec::h264::parser::SeiMessage;
+    use crate::codec::h264::parser::UserDataRegisteredItuTT35;
 
     const STREAM_TEST_25_FPS: &[u8] = include_bytes!("test_data/test-25fps.h264");
     const STREAM_TEST_25_FPS_NUM_NALUS: usize = 759;
+    const STREAM_TEST_25_FPS_INTERLACED: &[u8] =
+        include_bytes!("test_data/test-25fps-interlaced.h264");
 
     const STREAM_TEST_25_FPS_SLICE_0: &[u8] =
         include_bytes!("test_data/test-25fps-h264-slice-data-0.bin");
@@ -2995,6 +3623,136 @@ mod tests {
         }
     }
 
+    #[test]
+    fn parse_sei_test25fps() {
+        let mut cursor = Cursor::new(STREAM_TEST_25_FPS);
+        let mut parser = Parser::default();
+
+        // The first SEI NALU, holding a buffering period message, comes before
+        // the SPS and cannot be fully parsed.
+        let sei = Nalu::next(&mut cursor).unwrap();
+        let expected_payload = vec![0x8c, 0x0b, 0xe1, 0x80, 0x00, 0x00, 0x40];
+        assert_eq!(
+            parser.parse_sei(&sei).unwrap(),
+            vec![SeiMessage::Unknown {
+                payload_type: 0,
+                payload: expected_payload,
+            }]
+        );
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        let messages = parser.parse_sei(&nalu).unwrap();
+        assert_eq!(messages.len(), 1);
+        let SeiMessage::UserDataUnregistered(user_data) = &messages[0] else {
+            panic!("unexpected SEI message {:?}", messages[0]);
+        };
+        assert_eq!(
+            user_data.uuid_iso_iec_11578,
+            [
+                0x03, 0x87, 0xf4, 0x4e, 0xcd, 0x0a, 0x4b, 0xdc, 0xa1, 0x94, 0x3a, 0xc3, 0xd4, 0x9b,
+                0x17, 0x1f
+            ]
+        );
+        assert_eq!(user_data.user_data_payload, vec![0x00]);
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        parser.parse_sps(&nalu).unwrap();
+
+        let messages = parser.parse_sei(&sei).unwrap();
+        assert_eq!(messages.len(), 1);
+        let SeiMessage::BufferingPeriod(bp) = &messages[0] else {
+            panic!("unexpected SEI message {:?}", messages[0]);
+        };
+        assert_eq!(bp.seq_parameter_set_id, 0);
+        assert_eq!(bp.nal_initial_cpb_removal_delay[0], 1578947);
+        assert_eq!(bp.nal_initial_cpb_removal_delay_offset[0], 0);
+    }
+
+    #[test]
+    fn parse_sei_pic_timing() {
+        let mut cursor = Cursor::new(STREAM_TEST_25_FPS_INTERLACED);
+        let mut parser = Parser::default();
+        let mut num_pic_timings = 0;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::Sps => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::Sei => {
+                    for message in parser.parse_sei(&nalu).unwrap() {
+                        if let SeiMessage::PicTiming(pt) = message {
+                            // Field pictures, without HRD nor clock timestamps.
+                            assert!(matches!(pt.pic_struct, 3 | 4));
+                            assert_eq!(pt.cpb_removal_delay, 0);
+                            assert_eq!(pt.clock_timestamp_flag, [false; 3]);
+                            num_pic_timings += 1;
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        assert_eq!(num_pic_timings, 250);
+    }
+
+    #[test]
+    fn parse_sei_messages() {
+        // A SEI NALU with several messages, including emulation prevention
+        // bytes within the payloads.
+        const SEI: [u8; 65] = [
+            0x00, 0x00, 0x00, 0x01, 0x06, 0x06, 0x01, 0xc4, 0x89, 0x18, 0x33, 0xc2, 0x86, 0xc4,
+            0x1d, 0x4c, 0x0b, 0xb8, 0x84, 0xd0, 0x3e, 0x80, 0x3d, 0x13, 0x40, 0x42, 0x00, 0x98,
+            0x96, 0x80, 0x00, 0x00, 0x03, 0x00, 0x03, 0x90, 0x04, 0x03, 0xe8, 0x01, 0x90, 0x2d,
+            0x07, 0x81, 0x81, 0x00, 0x00, 0x03, 0x00, 0x01, 0x20, 0x04, 0x06, 0xb5, 0x00, 0x3c,
+            0x00, 0x01, 0x04, 0xff, 0x2d, 0x02, 0xab, 0xcd, 0x80,
+        ];
+
+        let mut cursor = Cursor::new(&SEI[..]);
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        let messages = Parser::default().parse_sei(&nalu).unwrap();
+
+        assert_eq!(
+            messages,
+            vec![
+                SeiMessage::RecoveryPoint(RecoveryPoint {
+                    recovery_frame_cnt: 0,
+                    exact_match_flag: true,
+                    broken_link_flag: false,
+                    changing_slice_group_idc: 0,
+                }),
+                SeiMessage::MasteringDisplayColourVolume(MasteringDisplayColourVolume {
+                    display_primaries_x: [13250, 7500, 34000],
+                    display_primaries_y: [34500, 3000, 16000],
+                    white_point_x: 15635,
+                    white_point_y: 16450,
+                    max_display_mastering_luminance: 10000000,
+                    min_display_mastering_luminance: 3,
+                }),
+                SeiMessage::ContentLightLevelInfo(ContentLightLevelInfo {
+                    max_content_light_level: 1000,
+                    max_pic_average_light_level: 400,
+                }),
+                SeiMessage::FramePackingArrangement(FramePackingArrangement {
+                    frame_packing_arrangement_type: 3,
+                    content_interpretation_type: 1,
+                    frame_packing_arrangement_repetition_period: 1,
+                    ..Default::default()
+                }),
+                SeiMessage::UserDataRegisteredItuTT35(UserDataRegisteredItuTT35 {
+                    itu_t_t35_country_code: 0xb5,
+                    itu_t_t35_country_code_extension_byte: 0,
+                    itu_t_t35_payload: vec![0x00, 0x3c, 0x00, 0x01, 0x04],
+                }),
+                SeiMessage::Unknown {
+                    payload_type: 300,
+                    payload: vec![0xab, 0xcd],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn max_long_term_frame_idx() {
         assert_eq!(
@@ -10,6 +10,9 @@ use crate::codec::h264::nalu_writer::NaluWriterError;
 use crate::codec::h264::parser::HrdParams;
 use crate::codec::h264::parser::NaluType;
 use crate::codec::h264::parser::Pps;
+use crate::codec::h264::parser::PredWeightTable;
+use crate::codec::h264::parser::RefPicListModification;
+use crate::codec::h264::parser::SliceHeader;
 use crate::codec::h264::parser::Sps;
 use crate::codec::h264::parser::DEFAULT_4X4_INTER;
 use crate::codec::h264::parser::DEFAULT_4X4_INTRA;
@@ -24,6 +27,8 @@ impl private::NaluStruct for Sps {}
 
 impl private::NaluStruct for Pps {}
 
+impl private::NaluStruct for SliceHeader {}
+
 #[derive(Error, Debug)]
 pub enum SynthesizerError {
     #[error("tried to synthesize unsupported settings")]
@@ -443,15 +448,322 @@ impl<'n, W: Write> Synthesizer<'n, Pps, W> {
     }
 }
 
+impl<'n, W: Write> Synthesizer<'n, SliceHeader, W> {
+    /// Writes a slice NALU header followed by the `slice_header()` of `header`.
+    ///
+    /// `nalu_type` must be either [`NaluType::Slice`] or [`NaluType::SliceIdr`]. The syntax
+    /// elements present in the header are selected using `pps` and the SPS it references. Only
+    /// the header is written, so the output is not byte aligned unless the caller appends the
+    /// `slice_data()` to the same stream: any pending bits are zero-padded on flush.
+    pub fn synthesize(
+        ref_idc: u8,
+        nalu_type: NaluType,
+        header: &'n SliceHeader,
+        pps: &Pps,
+        writer: W,
+        ep_enabled: bool,
+    ) -> SynthesizerResult<()> {
+        let idr_pic_flag = match nalu_type {
+            NaluType::Slice => false,
+            NaluType::SliceIdr => true,
+            _ => return Err(SynthesizerError::Unsupported),
+        };
+
+        let mut s = Self {
+            writer: NaluWriter::<W>::new(writer, ep_enabled),
+            nalu: header,
+        };
+
+        s.writer.write_header(ref_idc, nalu_type as u8)?;
+        s.slice_header(ref_idc, idr_pic_flag, pps)
+    }
+
+    fn ref_pic_list_modification(
+        &mut self,
+        modifications: &[RefPicListModification],
+    ) -> SynthesizerResult<()> {
+        // H.264 7.3.3.1
+        for modification in modifications {
+            self.ue(modification.modification_of_pic_nums_idc)?;
+
+            match modification.modification_of_pic_nums_idc {
+                0 | 1 => self.ue(modification.abs_diff_pic_num_minus1)?,
+                2 => self.ue(modification.long_term_pic_num)?,
+                3 => return Ok(()),
+                _ => return Err(SynthesizerError::Unsupported),
+            }
+        }
+
+        // The parser keeps the terminating entry, but tolerate lists without it.
+        self.ue(/* modification_of_pic_nums_idc */ 3u32)
+    }
+
+    fn ref_pic_list_modifications(&mut self) -> SynthesizerResult<()> {
+        let header = self.nalu;
+
+        if !header.slice_type.is_i() && !header.slice_type.is_si() {
+            self.u(1, header.ref_pic_list_modification_flag_l0)?;
+            if header.ref_pic_list_modification_flag_l0 {
+                self.ref_pic_list_modification(&header.ref_pic_list_modification_l0)?;
+            }
+        }
+
+        if header.slice_type.is_b() {
+            self.u(1, header.ref_pic_list_modification_flag_l1)?;
+            if header.ref_pic_list_modification_flag_l1 {
+                self.ref_pic_list_modification(&header.ref_pic_list_modification_l1)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pred_weight_table(&mut self, sps: &Sps) -> SynthesizerResult<()> {
+        // H.264 7.3.3.2
+        let header = self.nalu;
+        let pt: &PredWeightTable = &header.pred_weight_table;
+
+        self.ue(pt.luma_log2_weight_denom)?;
+        if sps.chroma_array_type != 0 {
+            self.ue(pt.chroma_log2_weight_denom)?;
+        }
+
+        // The flags are not stored, so only signal weights that differ from the values the
+        // parser would infer if the flags were unset.
+        let default_luma_weight = 1i16 << pt.luma_log2_weight_denom;
+        let default_chroma_weight = 1i16 << pt.chroma_log2_weight_denom;
+
+        for i in 0..=usize::from(header.num_ref_idx_l0_active_minus1) {
+            let luma_weight_l0_flag =
+                pt.luma_weight_l0[i] != default_luma_weight || pt.luma_offset_l0[i] != 0;
+
+            self.u(1, luma_weight_l0_flag)?;
+            if luma_weight_l0_flag {
+                self.se(pt.luma_weight_l0[i])?;
+                self.se(pt.luma_offset_l0[i])?;
+            }
+
+            if sps.chroma_array_type != 0 {
+                let chroma_weight_l0_flag = (0..2).any(|j| {
+                    pt.chroma_weight_l0[i][j] != default_chroma_weight
+                        || pt.chroma_offset_l0[i][j] != 0
+                });
+
+                self.u(1, chroma_weight_l0_flag)?;
+                if chroma_weight_l0_flag {
+                    for j in 0..2 {
+                        self.se(pt.chroma_weight_l0[i][j])?;
+                        self.se(pt.chroma_offset_l0[i][j])?;
+                    }
+                }
+            }
+        }
+
+        if header.slice_type.is_b() {
+            for i in 0..=usize::from(header.num_ref_idx_l1_active_minus1) {
+                let luma_weight_l1_flag =
+                    pt.luma_weight_l1[i] != default_luma_weight || pt.luma_offset_l1[i] != 0;
+
+                self.u(1, luma_weight_l1_flag)?;
+                if luma_weight_l1_flag {
+                    self.se(pt.luma_weight_l1[i])?;
+                    self.se(pt.luma_offset_l1[i])?;
+                }
+
+                if sps.chroma_array_type != 0 {
+                    let chroma_weight_l1_flag = (0..2).any(|j| {
+                        pt.chroma_weight_l1[i][j] != default_chroma_weight
+                            || pt.chroma_offset_l1[i][j] != 0
+                    });
+
+                    self.u(1, chroma_weight_l1_flag)?;
+                    if chroma_weight_l1_flag {
+                        for j in 0..2 {
+                            self.se(pt.chroma_weight_l1[i][j])?;
+                            self.se(pt.chroma_offset_l1[i][j])?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn dec_ref_pic_marking(&mut self, idr_pic_flag: bool) -> SynthesizerResult<()> {
+        // H.264 7.3.3.3
+        let rpm = &self.nalu.dec_ref_pic_marking;
+
+        if idr_pic_flag {
+            self.u(1, rpm.no_output_of_prior_pics_flag)?;
+            self.u(1, rpm.long_term_reference_flag)?;
+            return Ok(());
+        }
+
+        self.u(1, rpm.adaptive_ref_pic_marking_mode_flag)?;
+        if !rpm.adaptive_ref_pic_marking_mode_flag {
+            return Ok(());
+        }
+
+        for marking in &rpm.inner {
+            let mem_mgmt_ctrl_op = marking.memory_management_control_operation;
+            if mem_mgmt_ctrl_op == 0 {
+                break;
+            }
+
+            self.ue(mem_mgmt_ctrl_op)?;
+
+            if mem_mgmt_ctrl_op == 1 || mem_mgmt_ctrl_op == 3 {
+                self.ue(marking.difference_of_pic_nums_minus1)?;
+            }
+
+            if mem_mgmt_ctrl_op == 2 {
+                self.ue(marking.long_term_pic_num)?;
+            }
+
+            if mem_mgmt_ctrl_op == 3 || mem_mgmt_ctrl_op == 6 {
+                self.ue(marking.long_term_frame_idx)?;
+            }
+
+            if mem_mgmt_ctrl_op == 4 {
+                self.ue(marking.max_long_term_frame_idx.to_value_plus1())?;
+            }
+        }
+
+        self.ue(/* memory_management_control_operation */ 0u32)
+    }
+
+    fn slice_header(
+        &mut self,
+        ref_idc: u8,
+        idr_pic_flag: bool,
+        pps: &Pps,
+    ) -> SynthesizerResult<()> {
+        // H.264 7.3.3
+        let header = self.nalu;
+        let sps = &pps.sps;
+
+        if pps.num_slice_groups_minus1 > 0 {
+            return Err(SynthesizerError::Unsupported);
+        }
+
+        self.ue(header.first_mb_in_slice)?;
+        self.ue(header.slice_type as u32)?;
+        self.ue(header.pic_parameter_set_id)?;
+
+        if sps.separate_colour_plane_flag {
+            self.u(2, header.colour_plane_id)?;
+        }
+
+        self.u(
+            usize::from(sps.log2_max_frame_num_minus4) + 4,
+            header.frame_num,
+        )?;
+
+        if !sps.frame_mbs_only_flag {
+            self.u(1, header.field_pic_flag)?;
+            if header.field_pic_flag {
+                self.u(1, header.bottom_field_flag)?;
+            }
+        }
+
+        if idr_pic_flag {
+            self.ue(header.idr_pic_id)?;
+        }
+
+        if sps.pic_order_cnt_type == 0 {
+            self.u(
+                usize::from(sps.log2_max_pic_order_cnt_lsb_minus4) + 4,
+                header.pic_order_cnt_lsb,
+            )?;
+
+            if pps.bottom_field_pic_order_in_frame_present_flag && !header.field_pic_flag {
+                self.se(header.delta_pic_order_cnt_bottom)?;
+            }
+        }
+
+        if sps.pic_order_cnt_type == 1 && !sps.delta_pic_order_always_zero_flag {
+            self.se(header.delta_pic_order_cnt[0])?;
+            if pps.bottom_field_pic_order_in_frame_present_flag && !header.field_pic_flag {
+                self.se(header.delta_pic_order_cnt[1])?;
+            }
+        }
+
+        if pps.redundant_pic_cnt_present_flag {
+            self.ue(header.redundant_pic_cnt)?;
+        }
+
+        if header.slice_type.is_b() {
+            self.u(1, header.direct_spatial_mv_pred_flag)?;
+        }
+
+        if header.slice_type.is_p() || header.slice_type.is_sp() || header.slice_type.is_b() {
+            self.u(1, header.num_ref_idx_active_override_flag)?;
+            if header.num_ref_idx_active_override_flag {
+                self.ue(header.num_ref_idx_l0_active_minus1)?;
+                if header.slice_type.is_b() {
+                    self.ue(header.num_ref_idx_l1_active_minus1)?;
+                }
+            }
+        }
+
+        self.ref_pic_list_modifications()?;
+
+        if (pps.weighted_pred_flag && (header.slice_type.is_p() || header.slice_type.is_sp()))
+            || (pps.weighted_bipred_idc == 1 && header.slice_type.is_b())
+        {
+            self.pred_weight_table(sps)?;
+        }
+
+        if ref_idc != 0 {
+            self.dec_ref_pic_marking(idr_pic_flag)?;
+        }
+
+        if pps.entropy_coding_mode_flag && !header.slice_type.is_i() && !header.slice_type.is_si() {
+            self.ue(header.cabac_init_idc)?;
+        }
+
+        self.se(header.slice_qp_delta)?;
+
+        if header.slice_type.is_sp() || header.slice_type.is_si() {
+            if header.slice_type.is_sp() {
+                self.u(1, header.sp_for_switch_flag)?;
+            }
+
+            self.se(header.slice_qs_delta)?;
+        }
+
+        if pps.deblocking_filter_control_present_flag {
+            self.ue(header.disable_deblocking_filter_idc)?;
+
+            if header.disable_deblocking_filter_idc != 1 {
+                self.se(header.slice_alpha_c0_offset_div2)?;
+                self.se(header.slice_beta_offset_div2)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
+    use std::rc::Rc;
+
     use super::*;
+    use crate::codec::h264::parser::Level;
+    use crate::codec::h264::parser::MaxLongTermFrameIdx;
     use crate::codec::h264::parser::Nalu;
     use crate::codec::h264::parser::NaluType;
     use crate::codec::h264::parser::Parser;
+    use crate::codec::h264::parser::PpsBuilder;
     use crate::codec::h264::parser::Profile;
+    use crate::codec::h264::parser::RefPicMarkingInner;
+    use crate::codec::h264::parser::SliceHeaderBuilder;
+    use crate::codec::h264::parser::SliceType;
+    use crate::codec::h264::parser::SpsBuilder;
 
     #[test]
     fn synthesize_sps() {
@@ -578,4 +890,211 @@ mod tests {
 
         assert_eq!(buf, raw_sps_pps);
     }
+
+    /// Re-synthesizes every slice header of `stream` and checks that parsing it back yields the
+    /// same header.
+    fn slice_header_round_trip(stream: &[u8]) {
+        let mut cursor = Cursor::new(stream);
+        let mut parser = Parser::default();
+        let mut num_slices = 0;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::Sps => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::Pps => {
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                NaluType::Slice | NaluType::SliceIdr => {
+                    let ref_idc = nalu.header.ref_idc;
+                    let type_ = nalu.header.type_;
+                    let slice = parser.parse_slice_header(nalu).unwrap();
+                    let pps = parser.get_pps(slice.header.pic_parameter_set_id).unwrap();
+
+                    let mut buf = Vec::<u8>::new();
+                    Synthesizer::<'_, SliceHeader, _>::synthesize(
+                        ref_idc,
+                        type_,
+                        &slice.header,
+                        pps,
+                        &mut buf,
+                        true,
+                    )
+                    .unwrap();
+
+                    // Stand in for the slice data, so the zero padding of the header is not
+                    // discarded as trailing_zero_8bits.
+                    buf.push(0x80);
+
+                    let mut cursor = Cursor::new(&buf[..]);
+                    let nalu = Nalu::next(&mut cursor).unwrap();
+                    assert_eq!(nalu.header.ref_idc, ref_idc);
+                    assert_eq!(nalu.header.type_, type_);
+
+                    let slice2 = parser.parse_slice_header(nalu).unwrap();
+                    let mut header2 = slice2.header;
+
+                    // `slice_type` values 5-9 are folded into 0-4 by the parser, so the size
+                    // of the header may change. The emulation prevention bytes of the original
+                    // header may also depend on the slice data that follows it.
+                    header2.header_bit_size = slice.header.header_bit_size;
+                    header2.n_emulation_prevention_bytes =
+                        slice.header.n_emulation_prevention_bytes;
+
+                    assert_eq!(slice.header, header2);
+                    num_slices += 1;
+                }
+                _ => continue,
+            }
+        }
+
+        assert!(num_slices > 0);
+    }
+
+    #[test]
+    fn synthesize_slice_header_test25fps() {
+        slice_header_round_trip(include_bytes!("test_data/test-25fps.h264"));
+    }
+
+    #[test]
+    fn synthesize_slice_header_interlaced() {
+        slice_header_round_trip(include_bytes!("test_data/test-25fps-interlaced.h264"));
+    }
+
+    #[test]
+    fn synthesize_slice_header_high() {
+        slice_header_round_trip(include_bytes!("test_data/64x64-I-P-B-P-high.h264"));
+    }
+
+    #[test]
+    fn synthesize_slice_header_weights_and_marking() {
+        let sps = SpsBuilder::new()
+            .seq_parameter_set_id(0)
+            .profile_idc(Profile::Main)
+            .level_idc(Level::L4)
+            .resolution(320, 240)
+            .chroma_format_idc(1)
+            .frame_mbs_only_flag(true)
+            .max_num_ref_frames(4)
+            .pic_order_cnt_type(0)
+            .max_pic_order_cnt_lsb(64)
+            .max_frame_num(32)
+            .build();
+
+        let mut pps = Rc::try_unwrap(
+            PpsBuilder::new(sps.clone())
+                .pic_parameter_set_id(0)
+                .deblocking_filter_control_present_flag(true)
+                .build(),
+        )
+        .unwrap();
+        pps.entropy_coding_mode_flag = true;
+        pps.weighted_pred_flag = true;
+        pps.weighted_bipred_idc = 1;
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, Sps, _>::synthesize(3, &sps, &mut buf, false).unwrap();
+        Synthesizer::<'_, Pps, _>::synthesize(3, &pps, &mut buf, false).unwrap();
+
+        let mut parser = Parser::default();
+        let mut cursor = Cursor::new(&buf[..]);
+        parser.parse_sps(&Nalu::next(&mut cursor).unwrap()).unwrap();
+        parser.parse_pps(&Nalu::next(&mut cursor).unwrap()).unwrap();
+        let pps = parser.get_pps(0).unwrap().clone();
+
+        let mut header = SliceHeaderBuilder::new(&pps)
+            .slice_type(SliceType::B)
+            .pic_order_cnt_lsb(6)
+            .num_ref_idx_l0_active(2)
+            .num_ref_idx_l1_active(1)
+            .build();
+
+        header.frame_num = 3;
+        header.direct_spatial_mv_pred_flag = true;
+        header.ref_pic_list_modification_flag_l0 = true;
+        header.ref_pic_list_modification_l0 = vec![
+            RefPicListModification {
+                modification_of_pic_nums_idc: 1,
+                abs_diff_pic_num_minus1: 1,
+                ..Default::default()
+            },
+            RefPicListModification {
+                modification_of_pic_nums_idc: 2,
+                long_term_pic_num: 0,
+                ..Default::default()
+            },
+            RefPicListModification {
+                modification_of_pic_nums_idc: 3,
+                ..Default::default()
+            },
+        ];
+
+        let pt = &mut header.pred_weight_table;
+        pt.luma_log2_weight_denom = 5;
+        pt.chroma_log2_weight_denom = 4;
+        pt.luma_weight_l0[0] = 32;
+        pt.luma_weight_l0[1] = 40;
+        pt.chroma_weight_l0[0] = [16, 16];
+        pt.chroma_weight_l0[1] = [16, 16];
+        pt.luma_weight_l1[0] = 32;
+        pt.chroma_weight_l1[0] = [16, 16];
+        pt.luma_offset_l0[1] = -3;
+        pt.chroma_offset_l0[0] = [2, -2];
+        pt.luma_offset_l1[0] = 7;
+
+        header
+            .dec_ref_pic_marking
+            .adaptive_ref_pic_marking_mode_flag = true;
+        header.dec_ref_pic_marking.inner = vec![
+            RefPicMarkingInner {
+                memory_management_control_operation: 4,
+                max_long_term_frame_idx: MaxLongTermFrameIdx::Idx(1),
+                ..Default::default()
+            },
+            RefPicMarkingInner {
+                memory_management_control_operation: 3,
+                difference_of_pic_nums_minus1: 2,
+                long_term_frame_idx: 1,
+                ..Default::default()
+            },
+        ];
+
+        header.cabac_init_idc = 2;
+        header.slice_qp_delta = -4;
+        header.slice_alpha_c0_offset_div2 = -2;
+        header.slice_beta_offset_div2 = 3;
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, SliceHeader, _>::synthesize(
+            2,
+            NaluType::Slice,
+            &header,
+            &pps,
+            &mut buf,
+            true,
+        )
+        .unwrap();
+        buf.push(0x80);
+
+        let mut cursor = Cursor::new(&buf[..]);
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        let header2 = parser.parse_slice_header(nalu).unwrap().header;
+
+        assert_eq!(header2.slice_type, SliceType::B);
+        assert_eq!(header2.frame_num, header.frame_num);
+        assert_eq!(header2.pic_order_cnt_lsb, header.pic_order_cnt_lsb);
+        assert_eq!(header2.num_ref_idx_l0_active_minus1, 1);
+        assert_eq!(header2.num_ref_idx_l1_active_minus1, 0);
+        assert_eq!(
+            header2.ref_pic_list_modification_l0,
+            header.ref_pic_list_modification_l0
+        );
+        assert_eq!(header2.pred_weight_table, header.pred_weight_table);
+        assert_eq!(header2.dec_ref_pic_marking, header.dec_ref_pic_marking);
+        assert_eq!(header2.cabac_init_idc, header.cabac_init_idc);
+        assert_eq!(header2.slice_qp_delta, header.slice_qp_delta);
+        assert_eq!(header2.slice_alpha_c0_offset_div2, -2);
+        assert_eq!(header2.slice_beta_offset_div2, 3);
+    }
 }
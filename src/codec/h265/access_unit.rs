@@ -0,0 +1,278 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Grouping of H.265 NAL units into access units, as specified in clause
+//! 7.4.2.4.4 of the specification.
+
+use crate::codec::h265::parser::Nalu;
+use crate::codec::h265::parser::NaluType;
+use crate::codec::h265::parser::Parser;
+use crate::codec::h265::parser::Slice;
+
+/// A complete access unit, i.e. the NAL units of the coded pictures sharing
+/// the same output time, along with their parameter sets and SEI.
+#[derive(Default)]
+pub struct AccessUnit<'a> {
+    /// The NAL units of the access unit that are not base layer slice
+    /// segments, e.g. the access unit delimiter, parameter sets, SEI or end of
+    /// sequence, in decoding order.
+    ///
+    /// The NAL units of the other layers are also stored here, unparsed, as
+    /// the parser only supports the base layer.
+    pub nalus: Vec<Nalu<'a>>,
+    /// The slice segments of the base layer picture, in decoding order. The
+    /// headers of dependent slice segments are left as parsed, see
+    /// [`Slice::replace_header`].
+    pub slices: Vec<Slice<'a>>,
+}
+
+impl<'a> AccessUnit<'a> {
+    fn is_empty(&self) -> bool {
+        self.nalus.is_empty() && self.slices.is_empty()
+    }
+}
+
+/// Assembles access units from a sequence of NAL units.
+///
+/// The base layer parameter sets are parsed by the assembler's [`Parser`] as
+/// they are pushed, so that the headers of the following slice segments can be
+/// parsed.
+#[derive(Default)]
+pub struct AccessUnitAssembler<'a> {
+    parser: Parser,
+    current: AccessUnit<'a>,
+}
+
+impl<'a> AccessUnitAssembler<'a> {
+    /// Returns the parser holding the parameter sets seen so far.
+    pub fn parser(&self) -> &Parser {
+        &self.parser
+    }
+
+    /// Pushes the next NAL unit in decoding order, and returns the access unit
+    /// it completes, if any.
+    ///
+    /// On error, `nalu` is dropped and the access unit being assembled is left
+    /// untouched.
+    pub fn push(&mut self, nalu: Nalu<'a>) -> anyhow::Result<Option<AccessUnit<'a>>> {
+        // Only the NAL units of the base layer delimit access units.
+        if nalu.header.nuh_layer_id > 0 {
+            self.current.nalus.push(nalu);
+            return Ok(None);
+        }
+
+        let type_ = nalu.header.type_;
+
+        // The reserved VCL NAL unit types are kept as is.
+        let is_slice = (type_ as u32) <= NaluType::CraNut as u32
+            && !matches!(
+                type_,
+                NaluType::RsvVclN10
+                    | NaluType::RsvVclR11
+                    | NaluType::RsvVclN12
+                    | NaluType::RsvVclR13
+                    | NaluType::RsvVclN14
+                    | NaluType::RsvVclR15
+            );
+
+        if is_slice {
+            let slice = self.parser.parse_slice_header(nalu)?;
+
+            let completed = if slice.header.first_slice_segment_in_pic_flag {
+                self.take_if_complete()
+            } else {
+                None
+            };
+
+            self.current.slices.push(slice);
+            return Ok(completed);
+        }
+
+        match type_ {
+            NaluType::VpsNut => {
+                self.parser.parse_vps(&nalu)?;
+            }
+            NaluType::SpsNut => {
+                self.parser.parse_sps(&nalu)?;
+            }
+            NaluType::PpsNut => {
+                self.parser.parse_pps(&nalu)?;
+            }
+            _ => (),
+        }
+
+        // The first of these NAL units after the last VCL NAL unit of an
+        // access unit starts a new one.
+        let completed = match type_ {
+            NaluType::AudNut
+            | NaluType::VpsNut
+            | NaluType::SpsNut
+            | NaluType::PpsNut
+            | NaluType::PrefixSeiNut
+            | NaluType::RsvNvcl41
+            | NaluType::RsvNvcl42
+            | NaluType::RsvNvcl43
+            | NaluType::RsvNvcl44 => self.take_if_complete(),
+            _ => None,
+        };
+
+        self.current.nalus.push(nalu);
+
+        // The end of bitstream is the last NAL unit of the last access unit.
+        if type_ == NaluType::EobNut {
+            Ok(self.take())
+        } else {
+            Ok(completed)
+        }
+    }
+
+    /// Returns the access unit being assembled, if any, e.g. at the end of the
+    /// stream.
+    pub fn flush(&mut self) -> Option<AccessUnit<'a>> {
+        self.take()
+    }
+
+    /// Returns the current access unit if it contains a picture.
+    fn take_if_complete(&mut self) -> Option<AccessUnit<'a>> {
+        if self.current.slices.is_empty() {
+            None
+        } else {
+            self.take()
+        }
+    }
+
+    fn take(&mut self) -> Option<AccessUnit<'a>> {
+        if self.current.is_empty() {
+            None
+        } else {
+            Some(std::mem::take(&mut self.current))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    const STREAM_TEST_25_FPS: &[u8] = include_bytes!("test_data/test-25fps.h265");
+    const STREAM_BEAR: &[u8] = include_bytes!("test_data/bear.h265");
+
+    fn assemble<'a>(nalus: impl Iterator<Item = Nalu<'a>>) -> Vec<AccessUnit<'a>> {
+        let mut assembler = AccessUnitAssembler::default();
+        let mut access_units = vec![];
+
+        for nalu in nalus {
+            access_units.extend(assembler.push(nalu).unwrap());
+        }
+        access_units.extend(assembler.flush());
+
+        access_units
+    }
+
+    fn nalus(stream: &[u8]) -> impl Iterator<Item = Nalu<'_>> {
+        let mut cursor = Cursor::new(stream);
+        std::iter::from_fn(move || Nalu::next(&mut cursor).ok())
+    }
+
+    fn types(au: &AccessUnit) -> Vec<NaluType> {
+        au.nalus.iter().map(|nalu| nalu.header.type_).collect()
+    }
+
+    #[test]
+    fn access_units_test25fps() {
+        let access_units = assemble(nalus(STREAM_TEST_25_FPS));
+        assert_eq!(access_units.len(), 250);
+
+        assert_eq!(
+            types(&access_units[0]),
+            [
+                NaluType::VpsNut,
+                NaluType::SpsNut,
+                NaluType::PpsNut,
+                NaluType::PrefixSeiNut
+            ]
+        );
+        assert!(access_units[0].slices[0].nalu.header.type_.is_idr());
+
+        for au in &access_units {
+            assert_eq!(au.slices.len(), 1);
+            assert!(au.slices[0].header.first_slice_segment_in_pic_flag);
+        }
+    }
+
+    #[test]
+    fn access_units_bear() {
+        let access_units = assemble(nalus(STREAM_BEAR));
+        assert_eq!(access_units.len(), 30);
+
+        assert_eq!(
+            types(&access_units[0]),
+            [
+                NaluType::VpsNut,
+                NaluType::SpsNut,
+                NaluType::PpsNut,
+                NaluType::PrefixSeiNut,
+                NaluType::PrefixSeiNut
+            ]
+        );
+        assert!(access_units[1..].iter().all(|au| au.nalus.is_empty()));
+    }
+
+    #[test]
+    fn access_units_nalu_order() {
+        let nalu = |data: &'static [u8]| Nalu::next(&mut Cursor::new(data)).unwrap();
+
+        let mut assembler = AccessUnitAssembler::default();
+        let mut stream = nalus(STREAM_TEST_25_FPS);
+
+        // VPS, SPS, PPS, SEI and the IDR slice.
+        for nalu in stream.by_ref().take(5) {
+            assert!(assembler.push(nalu).unwrap().is_none());
+        }
+
+        // A suffix SEI, an access unit delimiter of another layer and the end
+        // of sequence belong to the current access unit, and the end of
+        // bitstream completes it.
+        for data in [
+            &[0x00, 0x00, 0x01, 0x50, 0x01, 0x80][..],
+            &[0x00, 0x00, 0x01, 0x46, 0x09, 0x50],
+            &[0x00, 0x00, 0x01, 0x48, 0x01],
+        ] {
+            assert!(assembler.push(nalu(data)).unwrap().is_none());
+        }
+
+        let au = assembler
+            .push(nalu(&[0x00, 0x00, 0x01, 0x4a, 0x01]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(au.slices.len(), 1);
+        assert_eq!(
+            types(&au),
+            [
+                NaluType::VpsNut,
+                NaluType::SpsNut,
+                NaluType::PpsNut,
+                NaluType::PrefixSeiNut,
+                NaluType::SuffixSeiNut,
+                NaluType::AudNut,
+                NaluType::EosNut,
+                NaluType::EobNut
+            ]
+        );
+        assert_eq!(au.nalus[5].header.nuh_layer_id, 1);
+        assert!(assembler.flush().is_none());
+
+        // A base layer prefix SEI after a slice starts a new access unit.
+        assert!(assembler.push(stream.next().unwrap()).unwrap().is_none());
+        let au = assembler
+            .push(nalu(&[0x00, 0x00, 0x01, 0x4e, 0x01, 0x80]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(au.slices.len(), 1);
+        assert!(au.nalus.is_empty());
+        assert_eq!(types(&assembler.flush().unwrap()), [NaluType::PrefixSeiNut]);
+    }
+}
@@ -9,6 +9,8 @@ use std::rc::Rc;
 
 use anyhow::anyhow;
 
+use crate::codec::h265::parser::Pps;
+use crate::codec::h265::parser::Slice;
 use crate::codec::h265::parser::Sps;
 use crate::codec::h265::picture::PictureData;
 use crate::codec::h265::picture::Reference;
@@ -21,9 +23,71 @@ use crate::codec::h265::picture::Reference;
 #[derive(Clone, Debug)]
 pub struct DpbEntry<T>(pub Rc<RefCell<PictureData>>, pub T);
 
+/// A picture of a reference picture set.
+#[derive(Clone, Debug)]
+pub enum RefPicSetEntry<T> {
+    /// A decoded picture stored in the DPB.
+    Decoded(DpbEntry<T>),
+    /// A picture generated for an unavailable reference picture. See 8.3.3.2.
+    ///
+    /// It has no backend handle, and `nonexisting` is true on its
+    /// `PictureData`.
+    Unavailable(Rc<RefCell<PictureData>>),
+}
+
+impl<T> RefPicSetEntry<T> {
+    /// Returns the `PictureData` of the picture.
+    pub fn pic(&self) -> &Rc<RefCell<PictureData>> {
+        match self {
+            RefPicSetEntry::Decoded(entry) => &entry.0,
+            RefPicSetEntry::Unavailable(pic) => pic,
+        }
+    }
+
+    /// Returns the backend handle of the picture, if it was decoded.
+    pub fn handle(&self) -> Option<&T> {
+        match self {
+            RefPicSetEntry::Decoded(entry) => Some(&entry.1),
+            RefPicSetEntry::Unavailable(_) => None,
+        }
+    }
+}
+
+/// The reference picture set of a picture. See 8.3.2.
+///
+/// Entries set to `None` stand for "no reference picture", i.e. a picture
+/// signaled in the RPS that is not in the DPB.
+#[derive(Clone, Debug)]
+pub struct RefPicSet<T> {
+    /// Same as RefPicSetStCurrBefore in the specification.
+    pub st_curr_before: Vec<Option<RefPicSetEntry<T>>>,
+    /// Same as RefPicSetStCurrAfter in the specification.
+    pub st_curr_after: Vec<Option<RefPicSetEntry<T>>>,
+    /// Same as RefPicSetStFoll in the specification.
+    pub st_foll: Vec<Option<RefPicSetEntry<T>>>,
+    /// Same as RefPicSetLtCurr in the specification.
+    pub lt_curr: Vec<Option<RefPicSetEntry<T>>>,
+    /// Same as RefPicSetLtFoll in the specification.
+    pub lt_foll: Vec<Option<RefPicSetEntry<T>>>,
+}
+
+/// The reference picture lists used to decode a slice.
+#[derive(Clone, Debug)]
+pub struct ReferencePicLists<T> {
+    /// Same as RefPicList0 in the specification, after modification. Empty for
+    /// I slices.
+    pub ref_pic_list0: Vec<Option<RefPicSetEntry<T>>>,
+    /// Same as RefPicList1 in the specification, after modification. Empty for
+    /// I and P slices.
+    pub ref_pic_list1: Vec<Option<RefPicSetEntry<T>>>,
+}
+
 pub struct Dpb<T> {
     /// List of `PictureData` and backend handles to decoded pictures.
     entries: Vec<DpbEntry<T>>,
+    /// Pictures generated for unavailable reference pictures. They have no
+    /// backend handle and are never output. See 8.3.3.
+    unavailable: Vec<Rc<RefCell<PictureData>>>,
     /// The maximum number of pictures that can be stored.
     max_num_pics: usize,
 }
@@ -70,6 +134,10 @@ impl<T: Clone> Dpb<T> {
         for mut picture in self.pictures_mut() {
             picture.set_reference(Reference::None);
         }
+
+        for picture in &self.unavailable {
+            picture.borrow_mut().set_reference(Reference::None);
+        }
     }
 
     /// Gets the position of `needle` in the DPB, if any.
@@ -220,7 +288,8 @@ impl<T: Clone> Dpb<T> {
             let retain = pic.needed_for_output || pic.is_ref();
             log::debug!("Retaining pic POC: {}: {}", pic.pic_order_cnt_val, retain);
             retain
-        })
+        });
+        self.unavailable.retain(|pic| pic.borrow().is_ref());
     }
 
     /// Store a picture and its backend handle in the DPB.
@@ -270,6 +339,341 @@ impl<T: Clone> Dpb<T> {
             .cloned()
             .collect()
     }
+
+    /// Finds the picture matching `pred` for an entry of the RPS, among both
+    /// the decoded and the generated pictures.
+    fn find_rps_entry(&self, pred: impl Fn(&PictureData) -> bool) -> Option<RefPicSetEntry<T>> {
+        let decoded = self
+            .entries
+            .iter()
+            .find(|entry| pred(&entry.0.borrow()))
+            .cloned()
+            .map(RefPicSetEntry::Decoded);
+
+        decoded.or_else(|| {
+            self.unavailable
+                .iter()
+                .find(|pic| pred(&pic.borrow()))
+                .cloned()
+                .map(RefPicSetEntry::Unavailable)
+        })
+    }
+
+    /// Finds a long term candidate picture for an entry of the RPS. See 8.3.2.
+    ///
+    /// If `msb_present` is false, only the least significant bits of the POC
+    /// are matched.
+    fn find_lt_ref(
+        &self,
+        poc: i32,
+        msb_present: bool,
+        max_pic_order_cnt_lsb: i32,
+    ) -> Option<RefPicSetEntry<T>> {
+        let mask = if msb_present {
+            -1
+        } else {
+            max_pic_order_cnt_lsb - 1
+        };
+
+        self.find_rps_entry(|p| p.is_ref() && p.pic_order_cnt_val & mask == poc)
+    }
+
+    /// Finds a short term reference picture for an entry of the RPS. See 8.3.2.
+    fn find_st_ref(&self, poc: i32) -> Option<RefPicSetEntry<T>> {
+        self.find_rps_entry(|p| {
+            matches!(p.reference(), Reference::ShortTerm) && p.pic_order_cnt_val == poc
+        })
+    }
+
+    /// Adds a picture generated for an unavailable reference picture. See
+    /// 8.3.3.2.
+    fn store_unavailable_picture(
+        &mut self,
+        poc: i32,
+        max_pic_order_cnt_lsb: i32,
+        reference: Reference,
+    ) -> anyhow::Result<RefPicSetEntry<T>> {
+        if self.entries.len() + self.unavailable.len() >= self.max_num_pics {
+            return Err(anyhow!(
+                "Can't add an unavailable picture to the DPB: DPB is full."
+            ));
+        }
+
+        log::debug!("Generating unavailable reference picture POC {}", poc);
+
+        let pic = PictureData::new_unavailable(poc, poc & (max_pic_order_cnt_lsb - 1), reference);
+        let pic = Rc::new(RefCell::new(pic));
+        self.unavailable.push(pic.clone());
+
+        Ok(RefPicSetEntry::Unavailable(pic))
+    }
+
+    /// Derives the reference picture set of `cur_pic` from `slice`, its first
+    /// slice segment, and marks the pictures in the DPB accordingly. See 8.3.2.
+    ///
+    /// Pictures are only generated for unavailable references when allowed by
+    /// 8.3.3, i.e. when `cur_pic` is a BLA picture or a CRA picture with
+    /// NoRaslOutputFlag set.
+    ///
+    /// This must be called once per picture, before decoding its first slice.
+    /// The reference picture lists of each slice are then built from the
+    /// returned RPS with [`RefPicSet::build_ref_pic_lists`].
+    pub fn build_ref_pic_set(
+        &mut self,
+        slice: &Slice,
+        sps: &Sps,
+        cur_pic: &PictureData,
+    ) -> anyhow::Result<RefPicSet<T>> {
+        let hdr = &slice.header;
+        let poc = cur_pic.pic_order_cnt_val;
+        let max_pic_order_cnt_lsb = 1 << (sps.log2_max_pic_order_cnt_lsb_minus4 + 4);
+
+        // When the current picture is an IRAP picture with NoRaslOutputFlag
+        // equal to 1, all reference pictures currently in the DPB (if any) are
+        // marked as "unused for reference".
+        if cur_pic.is_irap && cur_pic.no_rasl_output_flag {
+            self.mark_all_as_unused_for_ref();
+        }
+
+        let mut poc_st_curr_before = vec![];
+        let mut poc_st_curr_after = vec![];
+        let mut poc_st_foll = vec![];
+        let mut poc_lt_curr = vec![];
+        let mut poc_lt_foll = vec![];
+        let mut curr_delta_poc_msb_present_flag = vec![];
+        let mut foll_delta_poc_msb_present_flag = vec![];
+
+        // IDR pictures have an empty RPS.
+        if !cur_pic.nalu_type.is_idr() {
+            let st_rps = if hdr.short_term_ref_pic_set_sps_flag {
+                sps.short_term_ref_pic_set
+                    .get(usize::from(hdr.curr_rps_idx))
+                    .ok_or(anyhow!("Invalid CurrRpsIdx {}", hdr.curr_rps_idx))?
+            } else {
+                &hdr.short_term_ref_pic_set
+            };
+
+            // Equation 8-5
+            for i in 0..usize::from(st_rps.num_negative_pics) {
+                if st_rps.used_by_curr_pic_s0[i] {
+                    poc_st_curr_before.push(poc + st_rps.delta_poc_s0[i]);
+                } else {
+                    poc_st_foll.push(poc + st_rps.delta_poc_s0[i]);
+                }
+            }
+
+            for i in 0..usize::from(st_rps.num_positive_pics) {
+                if st_rps.used_by_curr_pic_s1[i] {
+                    poc_st_curr_after.push(poc + st_rps.delta_poc_s1[i]);
+                } else {
+                    poc_st_foll.push(poc + st_rps.delta_poc_s1[i]);
+                }
+            }
+
+            for i in 0..usize::from(hdr.num_long_term_sps + hdr.num_long_term_pics) {
+                let mut poc_lt = hdr.poc_lsb_lt[i] as i32;
+                if hdr.delta_poc_msb_present_flag[i] {
+                    poc_lt += poc
+                        - hdr.delta_poc_msb_cycle_lt[i] as i32 * max_pic_order_cnt_lsb
+                        - (poc & (max_pic_order_cnt_lsb - 1));
+                }
+
+                if hdr.used_by_curr_pic_lt[i] {
+                    poc_lt_curr.push(poc_lt);
+                    curr_delta_poc_msb_present_flag.push(hdr.delta_poc_msb_present_flag[i]);
+                } else {
+                    poc_lt_foll.push(poc_lt);
+                    foll_delta_poc_msb_present_flag.push(hdr.delta_poc_msb_present_flag[i]);
+                }
+            }
+        }
+
+        // Equation 8-6: long term references are identified first.
+        let lt_curr = poc_lt_curr
+            .iter()
+            .zip(&curr_delta_poc_msb_present_flag)
+            .map(|(&poc, &msb_present)| self.find_lt_ref(poc, msb_present, max_pic_order_cnt_lsb))
+            .collect::<Vec<_>>();
+
+        let mut lt_foll = poc_lt_foll
+            .iter()
+            .zip(&foll_delta_poc_msb_present_flag)
+            .map(|(&poc, &msb_present)| self.find_lt_ref(poc, msb_present, max_pic_order_cnt_lsb))
+            .collect::<Vec<_>>();
+
+        // All reference pictures in RefPicSetLtCurr or RefPicSetLtFoll are
+        // marked as "used for long-term reference".
+        for entry in lt_curr.iter().chain(&lt_foll).flatten() {
+            entry.pic().borrow_mut().set_reference(Reference::LongTerm);
+        }
+
+        // Equation 8-7
+        let find_st_refs = |pocs: &[i32]| {
+            pocs.iter()
+                .map(|&poc| self.find_st_ref(poc))
+                .collect::<Vec<_>>()
+        };
+
+        let st_curr_before = find_st_refs(&poc_st_curr_before);
+        let st_curr_after = find_st_refs(&poc_st_curr_after);
+        let mut st_foll = find_st_refs(&poc_st_foll);
+
+        // All reference pictures in the DPB that are not included in the RPS
+        // are marked as "unused for reference".
+        let in_rps = |pic: &Rc<RefCell<PictureData>>| {
+            st_curr_before
+                .iter()
+                .chain(&st_curr_after)
+                .chain(&st_foll)
+                .chain(&lt_curr)
+                .chain(&lt_foll)
+                .flatten()
+                .any(|entry| Rc::ptr_eq(entry.pic(), pic))
+        };
+
+        for pic in self
+            .entries
+            .iter()
+            .map(|entry| &entry.0)
+            .chain(&self.unavailable)
+        {
+            if !in_rps(pic) {
+                pic.borrow_mut().set_reference(Reference::None);
+            }
+        }
+
+        // 8.3.3: unavailable pictures are only generated for BLA pictures and
+        // CRA pictures with NoRaslOutputFlag equal to 1.
+        if cur_pic.nalu_type.is_bla() || (cur_pic.nalu_type.is_cra() && cur_pic.no_rasl_output_flag)
+        {
+            for (entry, &poc) in st_foll.iter_mut().zip(&poc_st_foll) {
+                if entry.is_none() {
+                    *entry = Some(self.store_unavailable_picture(
+                        poc,
+                        max_pic_order_cnt_lsb,
+                        Reference::ShortTerm,
+                    )?);
+                }
+            }
+
+            for (entry, &poc) in lt_foll.iter_mut().zip(&poc_lt_foll) {
+                if entry.is_none() {
+                    *entry = Some(self.store_unavailable_picture(
+                        poc,
+                        max_pic_order_cnt_lsb,
+                        Reference::LongTerm,
+                    )?);
+                }
+            }
+        }
+
+        Ok(RefPicSet {
+            st_curr_before,
+            st_curr_after,
+            st_foll,
+            lt_curr,
+            lt_foll,
+        })
+    }
+}
+
+impl<T: Clone> RefPicSet<T> {
+    /// Builds RefPicListTemp0 or RefPicListTemp1 and applies the reference
+    /// picture list modification. See 8.3.4.
+    fn build_ref_pic_list(
+        first: &[Option<RefPicSetEntry<T>>],
+        second: &[Option<RefPicSetEntry<T>>],
+        lt_curr: &[Option<RefPicSetEntry<T>>],
+        num_ref_idx_active: usize,
+        list_entries: Option<&[u32]>,
+    ) -> anyhow::Result<Vec<Option<RefPicSetEntry<T>>>> {
+        let num_rps_curr = first.len() + second.len() + lt_curr.len();
+        if num_rps_curr == 0 {
+            return Err(anyhow!(
+                "Broken stream: no reference pictures for an inter slice"
+            ));
+        }
+
+        // Equations 8-8 and 8-10
+        let num_rps_curr_temp_list = std::cmp::max(num_ref_idx_active, num_rps_curr);
+        let ref_pic_list_temp = first
+            .iter()
+            .chain(second)
+            .chain(lt_curr)
+            .cycle()
+            .take(num_rps_curr_temp_list)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        // Equations 8-9 and 8-11
+        (0..num_ref_idx_active)
+            .map(|r_idx| {
+                let idx = match list_entries {
+                    Some(list_entries) => *list_entries
+                        .get(r_idx)
+                        .ok_or(anyhow!("Missing list_entry for index {}", r_idx))?
+                        as usize,
+                    None => r_idx,
+                };
+
+                ref_pic_list_temp
+                    .get(idx)
+                    .cloned()
+                    .ok_or(anyhow!("Invalid list_entry {}", idx))
+            })
+            .collect()
+    }
+
+    /// Returns the reference picture lists of `slice`, which belongs to the
+    /// picture of this RPS. See 8.3.4.
+    ///
+    /// This must be called for every slice, before decoding it.
+    pub fn build_ref_pic_lists(
+        &self,
+        slice: &Slice,
+        pps: &Pps,
+    ) -> anyhow::Result<ReferencePicLists<T>> {
+        if pps.scc_extension.curr_pic_ref_enabled_flag {
+            return Err(anyhow!(
+                "Using the current picture as a reference is not supported"
+            ));
+        }
+
+        let hdr = &slice.header;
+        let modification = &hdr.ref_pic_list_modification;
+
+        let mut ref_pic_list0 = vec![];
+        let mut ref_pic_list1 = vec![];
+
+        if hdr.type_.is_p() || hdr.type_.is_b() {
+            ref_pic_list0 = Self::build_ref_pic_list(
+                &self.st_curr_before,
+                &self.st_curr_after,
+                &self.lt_curr,
+                usize::from(hdr.num_ref_idx_l0_active_minus1) + 1,
+                modification
+                    .ref_pic_list_modification_flag_l0
+                    .then_some(&modification.list_entry_l0[..]),
+            )?;
+        }
+
+        if hdr.type_.is_b() {
+            ref_pic_list1 = Self::build_ref_pic_list(
+                &self.st_curr_after,
+                &self.st_curr_before,
+                &self.lt_curr,
+                usize::from(hdr.num_ref_idx_l1_active_minus1) + 1,
+                modification
+                    .ref_pic_list_modification_flag_l1
+                    .then_some(&modification.list_entry_l1[..]),
+            )?;
+        }
+
+        Ok(ReferencePicLists {
+            ref_pic_list0,
+            ref_pic_list1,
+        })
+    }
 }
 
 impl<T: Clone> Default for Dpb<T> {
@@ -278,6 +682,7 @@ impl<T: Clone> Default for Dpb<T> {
         // be derived.
         Self {
             entries: Default::default(),
+            unavailable: Default::default(),
             max_num_pics: Default::default(),
         }
     }
@@ -293,7 +698,275 @@ impl<T: Clone> std::fmt::Debug for Dpb<T> {
             .collect::<Vec<_>>();
         f.debug_struct("Dpb")
             .field("pictures", &pics)
+            .field("unavailable", &self.unavailable)
             .field("max_num_pics", &self.max_num_pics)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::codec::h264::nalu::Nalu;
+    use crate::codec::h265::parser::NaluHeader;
+    use crate::codec::h265::parser::NaluType;
+    use crate::codec::h265::parser::Parser;
+    use crate::codec::h265::parser::RefPicListModification;
+    use crate::codec::h265::parser::ShortTermRefPicSet;
+    use crate::codec::h265::parser::SliceHeader;
+    use crate::codec::h265::parser::SliceType;
+
+    const STREAM_BBB: &[u8] = include_bytes!("test_data/bbb.h265");
+    const STREAM_TEST25FPS: &[u8] = include_bytes!("test_data/test-25fps.h265");
+
+    /// The POC of each picture, with the POCs in its reference picture lists.
+    type RefPicListsPocs = Vec<(i32, Vec<i32>, Vec<i32>)>;
+
+    /// Walks `stream` as a decoder would, returning the reference picture
+    /// lists of the first slice of each picture.
+    fn build_stream_ref_pic_lists(stream: &[u8]) -> RefPicListsPocs {
+        let mut cursor = Cursor::new(stream);
+        let mut parser = Parser::default();
+        let mut dpb = Dpb::<i32>::default();
+        dpb.set_max_num_pics(16);
+
+        let mut prev_tid0_pic: Option<PictureData> = None;
+        let mut first_picture = true;
+        let mut lists = vec![];
+
+        let pocs = |list: &[Option<RefPicSetEntry<i32>>]| {
+            list.iter()
+                .map(|entry| entry.as_ref().unwrap().pic().borrow().pic_order_cnt_val)
+                .collect::<Vec<_>>()
+        };
+
+        while let Ok(nalu) = Nalu::<NaluHeader>::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    parser.parse_vps(&nalu).unwrap();
+                }
+                NaluType::SpsNut => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::PpsNut => {
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                type_ if (type_ as u32) < NaluType::RsvIrapVcl22 as u32 => {
+                    let slice = parser.parse_slice_header(nalu).unwrap();
+                    let pps = parser
+                        .get_pps(slice.header.pic_parameter_set_id)
+                        .unwrap()
+                        .clone();
+                    let sps = parser.get_sps(pps.seq_parameter_set_id).unwrap().clone();
+
+                    if !slice.header.first_slice_segment_in_pic_flag {
+                        continue;
+                    }
+
+                    let max_pic_order_cnt_lsb = 1 << (sps.log2_max_pic_order_cnt_lsb_minus4 + 4);
+                    let pic = PictureData::new_from_slice(
+                        &slice,
+                        &pps,
+                        first_picture,
+                        false,
+                        prev_tid0_pic.as_ref(),
+                        max_pic_order_cnt_lsb,
+                        0,
+                    );
+                    first_picture = false;
+
+                    let ref_pic_set = dpb.build_ref_pic_set(&slice, &sps, &pic).unwrap();
+                    let ref_pic_lists = ref_pic_set.build_ref_pic_lists(&slice, &pps).unwrap();
+                    lists.push((
+                        pic.pic_order_cnt_val,
+                        pocs(&ref_pic_lists.ref_pic_list0),
+                        pocs(&ref_pic_lists.ref_pic_list1),
+                    ));
+
+                    dpb.remove_unused();
+                    while dpb.needs_bumping(&sps) {
+                        if dpb.bump(false).is_none() {
+                            break;
+                        }
+                    }
+                    dpb.remove_unused();
+
+                    if pic.valid_for_prev_tid0_pic {
+                        prev_tid0_pic = Some(pic.clone());
+                    }
+
+                    let poc = pic.pic_order_cnt_val;
+                    dpb.store_picture(Rc::new(RefCell::new(pic)), poc).unwrap();
+                }
+                _ => {}
+            }
+        }
+
+        lists
+    }
+
+    #[test]
+    fn ref_pic_lists_bbb() {
+        let lists = build_stream_ref_pic_lists(STREAM_BBB);
+
+        assert_eq!(lists.len(), 60);
+        assert_eq!(&lists[0], &(0, vec![], vec![]));
+        assert_eq!(&lists[1], &(3, vec![0], vec![]));
+        assert_eq!(&lists[3], &(1, vec![0], vec![2, 3]));
+        assert_eq!(&lists[4], &(7, vec![3, 2, 0], vec![]));
+        assert_eq!(&lists[9], &(9, vec![7, 5, 2], vec![11]));
+        assert_eq!(&lists[29], &(30, vec![27, 25, 21], vec![32]));
+    }
+
+    #[test]
+    fn ref_pic_lists_test25fps() {
+        let lists = build_stream_ref_pic_lists(STREAM_TEST25FPS);
+
+        assert_eq!(lists.len(), 250);
+        assert_eq!(&lists[4], &(8, vec![3, 2, 0], vec![]));
+        assert_eq!(&lists[7], &(5, vec![3, 2], vec![6, 8]));
+        assert_eq!(&lists[11], &(9, vec![8, 6], vec![11, 13]));
+    }
+
+    fn new_slice(nalu_type: NaluType, header: SliceHeader) -> Slice<'static> {
+        Slice {
+            header,
+            nalu: Nalu {
+                header: NaluHeader {
+                    type_: nalu_type,
+                    nuh_temporal_id_plus1: 1,
+                    ..Default::default()
+                },
+                data: Default::default(),
+                size: 0,
+                offset: 0,
+            },
+        }
+    }
+
+    #[test]
+    fn ref_pic_lists_modification_and_unavailable_pictures() {
+        let sps = Sps {
+            log2_max_pic_order_cnt_lsb_minus4: 4,
+            ..Default::default()
+        };
+        let pps = Pps::default();
+
+        let mut dpb = Dpb::<i32>::default();
+        dpb.set_max_num_pics(16);
+        for poc in [0, 2, 4, 8] {
+            let mut pic = PictureData::default();
+            pic.pic_order_cnt_val = poc;
+            pic.slice_pic_order_cnt_lsb = poc;
+            dpb.store_picture(Rc::new(RefCell::new(pic)), poc).unwrap();
+        }
+
+        let reference = |dpb: &Dpb<i32>, poc: i32| {
+            *dpb.pictures()
+                .find(|pic| pic.pic_order_cnt_val == poc)
+                .unwrap()
+                .reference()
+        };
+
+        // A B slice of POC 12, using POC 8 as short term and POC 0 as long term
+        // reference. POC 4 is kept for later pictures and POC 2 is dropped.
+        let mut short_term_ref_pic_set = ShortTermRefPicSet {
+            num_negative_pics: 2,
+            ..Default::default()
+        };
+        short_term_ref_pic_set.delta_poc_s0[0] = -4;
+        short_term_ref_pic_set.used_by_curr_pic_s0[0] = true;
+        short_term_ref_pic_set.delta_poc_s0[1] = -8;
+
+        let mut header = SliceHeader {
+            type_: SliceType::B,
+            pic_order_cnt_lsb: 12,
+            short_term_ref_pic_set,
+            num_long_term_pics: 1,
+            num_ref_idx_l0_active_minus1: 2,
+            num_ref_idx_l1_active_minus1: 1,
+            ref_pic_list_modification: RefPicListModification {
+                ref_pic_list_modification_flag_l1: true,
+                list_entry_l1: vec![1, 0],
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        header.used_by_curr_pic_lt[0] = true;
+
+        let slice = new_slice(NaluType::TrailR, header);
+        let cur_pic = PictureData::new_from_slice(&slice, &pps, false, false, None, 256, 0);
+        let ref_pic_set = dpb.build_ref_pic_set(&slice, &sps, &cur_pic).unwrap();
+        let lists = ref_pic_set.build_ref_pic_lists(&slice, &pps).unwrap();
+
+        let pocs = |list: &[Option<RefPicSetEntry<i32>>]| {
+            list.iter()
+                .map(|entry| entry.as_ref().unwrap().pic().borrow().pic_order_cnt_val)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(pocs(&ref_pic_set.st_curr_before), vec![8]);
+        assert!(ref_pic_set.st_curr_after.is_empty());
+        assert_eq!(pocs(&ref_pic_set.st_foll), vec![4]);
+        assert_eq!(pocs(&ref_pic_set.lt_curr), vec![0]);
+        assert!(ref_pic_set.lt_foll.is_empty());
+        assert_eq!(pocs(&lists.ref_pic_list0), vec![8, 0, 8]);
+        assert_eq!(pocs(&lists.ref_pic_list1), vec![0, 8]);
+
+        assert_eq!(reference(&dpb, 0), Reference::LongTerm);
+        assert_eq!(reference(&dpb, 2), Reference::None);
+        assert_eq!(reference(&dpb, 4), Reference::ShortTerm);
+        assert_eq!(reference(&dpb, 8), Reference::ShortTerm);
+
+        // Another slice of the same picture reuses its RPS.
+        let mut header = slice.header.clone();
+        header.type_ = SliceType::P;
+        header.num_ref_idx_l0_active_minus1 = 0;
+        let slice = new_slice(NaluType::TrailR, header);
+        let lists = ref_pic_set.build_ref_pic_lists(&slice, &pps).unwrap();
+        assert_eq!(pocs(&lists.ref_pic_list0), vec![8]);
+        assert!(lists.ref_pic_list1.is_empty());
+
+        // A CRA picture starting a new CVS, whose RPS references POC 17 for its
+        // leading pictures. That picture is unavailable and gets generated.
+        let mut short_term_ref_pic_set = ShortTermRefPicSet {
+            num_negative_pics: 1,
+            ..Default::default()
+        };
+        short_term_ref_pic_set.delta_poc_s0[0] = -3;
+
+        let header = SliceHeader {
+            type_: SliceType::I,
+            pic_order_cnt_lsb: 20,
+            short_term_ref_pic_set,
+            ..Default::default()
+        };
+
+        let slice = new_slice(NaluType::CraNut, header);
+        let cur_pic = PictureData::new_from_slice(&slice, &pps, false, true, None, 256, 0);
+        let ref_pic_set = dpb.build_ref_pic_set(&slice, &sps, &cur_pic).unwrap();
+        let lists = ref_pic_set.build_ref_pic_lists(&slice, &pps).unwrap();
+
+        assert_eq!(ref_pic_set.st_foll.len(), 1);
+        let generated = ref_pic_set.st_foll[0].as_ref().unwrap();
+        assert!(matches!(generated, RefPicSetEntry::Unavailable(_)));
+        assert!(generated.handle().is_none());
+        assert!(generated.pic().borrow().nonexisting);
+        assert_eq!(generated.pic().borrow().pic_order_cnt_val, 17);
+        assert_eq!(*generated.pic().borrow().reference(), Reference::ShortTerm);
+        assert!(lists.ref_pic_list0.is_empty());
+
+        for poc in [0, 2, 4, 8] {
+            assert_eq!(reference(&dpb, poc), Reference::None);
+        }
+
+        // The generated picture is not a decoded picture of the DPB, and is
+        // dropped once unused for reference.
+        assert_eq!(dpb.len(), 4);
+        dpb.mark_all_as_unused_for_ref();
+        dpb.remove_unused();
+        assert!(dpb.find_st_ref(17).is_none());
+    }
+}
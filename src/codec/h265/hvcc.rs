@@ -0,0 +1,468 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Support for the HEVCDecoderConfigurationRecord of ISO/IEC 14496-15, i.e.
+//! the codec private data of H.265 streams stored in MP4 or Matroska files, in
+//! which NAL units are prefixed by their length instead of a start code.
+
+use std::borrow::Cow;
+use std::io::Cursor;
+use std::io::Write;
+
+use anyhow::anyhow;
+use bytes::Buf;
+
+use crate::codec::h264::nalu::Header;
+use crate::codec::h264::nalu::LengthPrefixedNaluIterator;
+use crate::codec::h265::parser::Nalu;
+use crate::codec::h265::parser::NaluHeader;
+use crate::codec::h265::parser::NaluType;
+use crate::codec::h265::parser::Parser;
+use crate::codec::h265::parser::ProfileTierLevel;
+use crate::codec::h265::parser::Sps;
+use crate::codec::h265::synthesizer::profile_in;
+
+/// The only configuration version defined by the specification.
+const CONFIGURATION_VERSION: u8 = 1;
+
+/// An array of NAL units of the same type in the record.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct HvccArray {
+    /// Whether all the NAL units of this type are in the array, as opposed to
+    /// some being in the stream.
+    pub array_completeness: bool,
+    /// The type of the NAL units, usually VPS, SPS, PPS or SEI.
+    pub nal_unit_type: NaluType,
+    /// The NAL units, without length prefix.
+    pub nalus: Vec<Vec<u8>>,
+}
+
+/// A HEVCDecoderConfigurationRecord as per ISO/IEC 14496-15, 8.3.3.1.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct HevcDecoderConfigurationRecord {
+    pub general_profile_space: u8,
+    pub general_tier_flag: bool,
+    pub general_profile_idc: u8,
+    pub general_profile_compatibility_flags: u32,
+    /// The 48 bits following the compatibility flags in profile_tier_level().
+    pub general_constraint_indicator_flags: u64,
+    pub general_level_idc: u8,
+    pub min_spatial_segmentation_idc: u16,
+    pub parallelism_type: u8,
+    pub chroma_format_idc: u8,
+    pub bit_depth_luma_minus8: u8,
+    pub bit_depth_chroma_minus8: u8,
+    /// The average frame rate in frames per 256 seconds, or 0 if unspecified.
+    pub avg_frame_rate: u16,
+    pub constant_frame_rate: u8,
+    pub num_temporal_layers: u8,
+    pub temporal_id_nested: bool,
+    /// The size in bytes of the NAL unit length fields, minus one.
+    pub length_size_minus_one: u8,
+    pub arrays: Vec<HvccArray>,
+}
+
+/// Packs the general constraint flags of `ptl` the way they are laid out in
+/// profile_tier_level(), see H.265 7.3.3.
+fn general_constraint_indicator_flags(ptl: &ProfileTierLevel) -> u64 {
+    let mut flags = 0u64;
+    let mut bit = 47;
+    let mut push = |value: bool, bits: u32| {
+        flags |= u64::from(value) << bit;
+        bit -= bits;
+    };
+
+    push(ptl.general_progressive_source_flag, 1);
+    push(ptl.general_interlaced_source_flag, 1);
+    push(ptl.general_non_packed_constraint_flag, 1);
+    push(ptl.general_frame_only_constraint_flag, 1);
+
+    let idc = ptl.general_profile_idc;
+    let compat = &ptl.general_profile_compatibility_flag;
+    if profile_in(idc, compat, &[4, 5, 6, 7, 8, 9, 10, 11]) {
+        push(ptl.general_max_12bit_constraint_flag, 1);
+        push(ptl.general_max_10bit_constraint_flag, 1);
+        push(ptl.general_max_8bit_constraint_flag, 1);
+        push(ptl.general_max_422chroma_constraint_flag, 1);
+        push(ptl.general_max_420chroma_constraint_flag, 1);
+        push(ptl.general_max_monochrome_constraint_flag, 1);
+        push(ptl.general_intra_constraint_flag, 1);
+        push(ptl.general_one_picture_only_constraint_flag, 1);
+        push(ptl.general_lower_bit_rate_constraint_flag, 1);
+
+        if profile_in(idc, compat, &[5, 9, 10, 11]) {
+            push(ptl.general_max_14bit_constraint_flag, 34);
+        } else {
+            push(false, 34);
+        }
+    } else if profile_in(idc, compat, &[2]) {
+        push(false, 7);
+        push(ptl.general_one_picture_only_constraint_flag, 36);
+    } else {
+        push(false, 43);
+    }
+
+    if profile_in(idc, compat, &[1, 2, 3, 4, 5, 9, 11]) {
+        push(ptl.general_inbld_flag, 0);
+    }
+
+    flags
+}
+
+fn read_nalus(data: &mut &[u8], count: usize) -> anyhow::Result<Vec<Vec<u8>>> {
+    let mut nalus = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        if data.remaining() < 2 {
+            return Err(anyhow!("Broken data: truncated hvcC NALU array"));
+        }
+
+        let len = usize::from(data.get_u16());
+        if data.remaining() < len {
+            return Err(anyhow!("Broken data: truncated hvcC NALU"));
+        }
+
+        nalus.push(data[..len].to_vec());
+        data.advance(len);
+    }
+
+    Ok(nalus)
+}
+
+impl HevcDecoderConfigurationRecord {
+    /// Builds a record for a stream using `sps`, taking the profile, tier,
+    /// level and format information from it.
+    pub fn new(sps: &Sps, length_size: usize, arrays: Vec<HvccArray>) -> anyhow::Result<Self> {
+        if !matches!(length_size, 1 | 2 | 4) {
+            return Err(anyhow!("Invalid NALU length size {}", length_size));
+        }
+
+        let ptl = &sps.profile_tier_level;
+        let general_profile_compatibility_flags = ptl
+            .general_profile_compatibility_flag
+            .iter()
+            .fold(0u32, |flags, &flag| (flags << 1) | u32::from(flag));
+
+        let min_spatial_segmentation_idc = if sps.vui_parameters_present_flag {
+            sps.vui_parameters.min_spatial_segmentation_idc as u16
+        } else {
+            0
+        };
+
+        Ok(Self {
+            general_profile_space: ptl.general_profile_space,
+            general_tier_flag: ptl.general_tier_flag,
+            general_profile_idc: ptl.general_profile_idc,
+            general_profile_compatibility_flags,
+            general_constraint_indicator_flags: general_constraint_indicator_flags(ptl),
+            general_level_idc: ptl.general_level_idc as u8,
+            min_spatial_segmentation_idc,
+            parallelism_type: 0,
+            chroma_format_idc: sps.chroma_format_idc,
+            bit_depth_luma_minus8: sps.bit_depth_luma_minus8,
+            bit_depth_chroma_minus8: sps.bit_depth_chroma_minus8,
+            avg_frame_rate: 0,
+            constant_frame_rate: 0,
+            num_temporal_layers: sps.max_sub_layers_minus1 + 1,
+            temporal_id_nested: sps.temporal_id_nesting_flag,
+            length_size_minus_one: (length_size - 1) as u8,
+            arrays,
+        })
+    }
+
+    /// Parses a record, e.g. from the contents of an MP4 `hvcC` box.
+    pub fn parse(mut data: &[u8]) -> anyhow::Result<Self> {
+        if data.remaining() < 23 {
+            return Err(anyhow!("Broken data: hvcC record is too short"));
+        }
+
+        let version = data.get_u8();
+        if version != CONFIGURATION_VERSION {
+            return Err(anyhow!("Unsupported hvcC version {}", version));
+        }
+
+        let byte = data.get_u8();
+        let general_profile_space = byte >> 6;
+        let general_tier_flag = (byte >> 5) & 1 != 0;
+        let general_profile_idc = byte & 0x1f;
+        let general_profile_compatibility_flags = data.get_u32();
+        let general_constraint_indicator_flags = data.get_uint(6);
+        let general_level_idc = data.get_u8();
+        let min_spatial_segmentation_idc = data.get_u16() & 0xfff;
+        let parallelism_type = data.get_u8() & 0x3;
+        let chroma_format_idc = data.get_u8() & 0x3;
+        let bit_depth_luma_minus8 = data.get_u8() & 0x7;
+        let bit_depth_chroma_minus8 = data.get_u8() & 0x7;
+        let avg_frame_rate = data.get_u16();
+
+        let byte = data.get_u8();
+        let constant_frame_rate = byte >> 6;
+        let num_temporal_layers = (byte >> 3) & 0x7;
+        let temporal_id_nested = (byte >> 2) & 1 != 0;
+        let length_size_minus_one = byte & 0x3;
+
+        if length_size_minus_one == 2 {
+            return Err(anyhow!("Broken data: invalid NALU length size"));
+        }
+
+        let num_arrays = data.get_u8();
+        let mut arrays = Vec::with_capacity(usize::from(num_arrays));
+        for _ in 0..num_arrays {
+            if data.remaining() < 3 {
+                return Err(anyhow!("Broken data: truncated hvcC array"));
+            }
+
+            let byte = data.get_u8();
+            let nal_unit_type = NaluType::n(byte & 0x3f)
+                .ok_or(anyhow!("Invalid NALU type {} in hvcC", byte & 0x3f))?;
+            let num_nalus = usize::from(data.get_u16());
+
+            arrays.push(HvccArray {
+                array_completeness: byte & 0x80 != 0,
+                nal_unit_type,
+                nalus: read_nalus(&mut data, num_nalus)?,
+            });
+        }
+
+        Ok(Self {
+            general_profile_space,
+            general_tier_flag,
+            general_profile_idc,
+            general_profile_compatibility_flags,
+            general_constraint_indicator_flags,
+            general_level_idc,
+            min_spatial_segmentation_idc,
+            parallelism_type,
+            chroma_format_idc,
+            bit_depth_luma_minus8,
+            bit_depth_chroma_minus8,
+            avg_frame_rate,
+            constant_frame_rate,
+            num_temporal_layers,
+            temporal_id_nested,
+            length_size_minus_one,
+            arrays,
+        })
+    }
+
+    /// Writes the record into `writer`.
+    pub fn write_into(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let invalid = |msg| std::io::Error::new(std::io::ErrorKind::InvalidInput, msg);
+
+        writer.write_all(&[
+            CONFIGURATION_VERSION,
+            (self.general_profile_space & 0x3) << 6
+                | u8::from(self.general_tier_flag) << 5
+                | (self.general_profile_idc & 0x1f),
+        ])?;
+        writer.write_all(&self.general_profile_compatibility_flags.to_be_bytes())?;
+        writer.write_all(&self.general_constraint_indicator_flags.to_be_bytes()[2..])?;
+        writer.write_all(&[self.general_level_idc])?;
+        writer.write_all(&(0xf000 | (self.min_spatial_segmentation_idc & 0xfff)).to_be_bytes())?;
+        writer.write_all(&[
+            0xfc | (self.parallelism_type & 0x3),
+            0xfc | (self.chroma_format_idc & 0x3),
+            0xf8 | (self.bit_depth_luma_minus8 & 0x7),
+            0xf8 | (self.bit_depth_chroma_minus8 & 0x7),
+        ])?;
+        writer.write_all(&self.avg_frame_rate.to_be_bytes())?;
+
+        let num_arrays =
+            u8::try_from(self.arrays.len()).map_err(|_| invalid("too many NALU arrays"))?;
+        writer.write_all(&[
+            (self.constant_frame_rate & 0x3) << 6
+                | (self.num_temporal_layers & 0x7) << 3
+                | u8::from(self.temporal_id_nested) << 2
+                | (self.length_size_minus_one & 0x3),
+            num_arrays,
+        ])?;
+
+        for array in &self.arrays {
+            let num_nalus =
+                u16::try_from(array.nalus.len()).map_err(|_| invalid("too many NALUs"))?;
+
+            writer.write_all(&[
+                u8::from(array.array_completeness) << 7 | (array.nal_unit_type as u8 & 0x3f)
+            ])?;
+            writer.write_all(&num_nalus.to_be_bytes())?;
+
+            for nalu in &array.nalus {
+                let len = u16::try_from(nalu.len()).map_err(|_| invalid("NALU too large"))?;
+                writer.write_all(&len.to_be_bytes())?;
+                writer.write_all(nalu)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The size in bytes of the length prefix of the NAL units.
+    pub fn length_size(&self) -> usize {
+        usize::from(self.length_size_minus_one) + 1
+    }
+
+    /// Returns an iterator over the NAL units of a sample of the stream.
+    pub fn nalus<'a>(&self, sample: &'a [u8]) -> LengthPrefixedNaluIterator<'a, NaluHeader> {
+        LengthPrefixedNaluIterator::new(sample, self.length_size())
+    }
+
+    /// Parses the VPSes, SPSes and PPSes of the record with `parser`, in that
+    /// order, so that the slices of the stream can be parsed.
+    pub fn parse_parameter_sets(&self, parser: &mut Parser) -> anyhow::Result<()> {
+        for type_ in [NaluType::VpsNut, NaluType::SpsNut, NaluType::PpsNut] {
+            let nalus = self
+                .arrays
+                .iter()
+                .filter(|array| array.nal_unit_type == type_)
+                .flat_map(|array| array.nalus.iter());
+
+            for data in nalus {
+                let header = NaluHeader::parse(&Cursor::new(data))
+                    .map_err(|e| anyhow!("Invalid parameter set NALU header: {}", e))?;
+                let nalu = Nalu {
+                    header,
+                    data: Cow::Borrowed(data),
+                    size: data.len(),
+                    offset: 0,
+                };
+
+                match type_ {
+                    NaluType::VpsNut => {
+                        parser.parse_vps(&nalu)?;
+                    }
+                    NaluType::SpsNut => {
+                        parser.parse_sps(&nalu)?;
+                    }
+                    _ => {
+                        parser.parse_pps(&nalu)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STREAM_TEST25FPS: &[u8] = include_bytes!("test_data/test-25fps.h265");
+
+    /// Returns the first VPS, SPS and PPS of `stream`, and the stream converted
+    /// to length-prefixed NAL units.
+    fn split_stream(stream: &[u8], length_size: usize) -> (Vec<HvccArray>, Vec<u8>) {
+        let mut cursor = Cursor::new(stream);
+        let mut arrays: Vec<HvccArray> = vec![];
+        let mut out = vec![];
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            let type_ = nalu.header.type_;
+            if matches!(
+                type_,
+                NaluType::VpsNut | NaluType::SpsNut | NaluType::PpsNut
+            ) && !arrays.iter().any(|a| a.nal_unit_type == type_)
+            {
+                arrays.push(HvccArray {
+                    array_completeness: true,
+                    nal_unit_type: type_,
+                    nalus: vec![nalu.as_ref().to_vec()],
+                });
+            }
+
+            let len = nalu.size.to_be_bytes();
+            out.extend_from_slice(&len[len.len() - length_size..]);
+            out.extend_from_slice(nalu.as_ref());
+        }
+
+        (arrays, out)
+    }
+
+    #[test]
+    fn parse_and_write_record() {
+        let (arrays, _) = split_stream(STREAM_TEST25FPS, 4);
+        let sps_data = arrays[1].nalus[0].clone();
+
+        let mut parser = Parser::default();
+        let header = NaluHeader::parse(&Cursor::new(&sps_data)).unwrap();
+        let sps = parser
+            .parse_sps(&Nalu {
+                header,
+                data: Cow::Borrowed(&sps_data),
+                size: sps_data.len(),
+                offset: 0,
+            })
+            .unwrap()
+            .clone();
+
+        let record = HevcDecoderConfigurationRecord::new(&sps, 4, arrays).unwrap();
+        assert_eq!(record.general_profile_idc, 1);
+        assert_eq!(record.num_temporal_layers, 1);
+        assert_eq!(record.length_size(), 4);
+
+        let mut data = vec![];
+        record.write_into(&mut data).unwrap();
+
+        // The profile, tier and level fields are laid out as in the SPS, once
+        // its emulation prevention bytes are removed.
+        let mut rbsp = vec![];
+        for &byte in &sps_data {
+            if !(byte == 3 && rbsp.ends_with(&[0, 0])) {
+                rbsp.push(byte);
+            }
+        }
+        assert_eq!(&data[1..13], &rbsp[3..15]);
+
+        let parsed = HevcDecoderConfigurationRecord::parse(&data).unwrap();
+        assert_eq!(parsed, record);
+
+        let mut parser = Parser::default();
+        parsed.parse_parameter_sets(&mut parser).unwrap();
+        assert!(parser.get_vps(0).is_some());
+        assert_eq!(parser.get_sps(0), Some(&sps));
+        assert!(parser.get_pps(0).is_some());
+
+        assert!(HevcDecoderConfigurationRecord::parse(&data[..data.len() - 1]).is_err());
+        data[0] = 0;
+        assert!(HevcDecoderConfigurationRecord::parse(&data).is_err());
+    }
+
+    #[test]
+    fn parse_samples() {
+        let mut cursor = Cursor::new(STREAM_TEST25FPS);
+        let mut annex_b = vec![];
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            annex_b.push(nalu.as_ref().to_vec());
+        }
+
+        let (arrays, stream) = split_stream(STREAM_TEST25FPS, 2);
+        let mut record = HevcDecoderConfigurationRecord {
+            length_size_minus_one: 1,
+            arrays,
+            ..Default::default()
+        };
+
+        let mut parser = Parser::default();
+        record.parse_parameter_sets(&mut parser).unwrap();
+
+        let mut nalus = vec![];
+        for nalu in record.nalus(&stream) {
+            let nalu = nalu.unwrap();
+            nalus.push(nalu.as_ref().to_vec());
+            if nalu.header.type_.is_irap() || nalu.header.type_ == NaluType::TrailR {
+                parser.parse_slice_header(nalu).unwrap();
+            }
+        }
+        assert_eq!(nalus, annex_b);
+
+        record.length_size_minus_one = 3;
+        assert!(record.nalus(&stream).any(|nalu| nalu.is_err()));
+
+        // A NAL unit shorter than its two byte header.
+        let mut nalus = record.nalus(&[0x00, 0x00, 0x00, 0x01, 0x40]);
+        assert!(nalus.next().unwrap().is_err());
+        assert!(nalus.next().is_none());
+    }
+}
@@ -7,6 +7,7 @@
 //! Parses VPSs, SPSs, PPSs and Slices from NALUs.
 
 use std::collections::BTreeMap;
+use std::io::Cursor;
 
 use anyhow::anyhow;
 use anyhow::Context;
@@ -17,8 +18,14 @@ use enumn::N;
 use crate::codec::h264::nalu;
 use crate::codec::h264::nalu::Header;
 use crate::codec::h264::nalu_reader::NaluReader;
+use crate::codec::h264::nalu_writer::NaluWriter;
+use crate::codec::h264::parser::ContentLightLevelInfo;
+use crate::codec::h264::parser::MasteringDisplayColourVolume;
 use crate::codec::h264::parser::Point;
 use crate::codec::h264::parser::Rect;
+use crate::codec::h264::parser::UserDataRegisteredItuTT35;
+use crate::codec::h264::parser::UserDataUnregistered;
+use crate::codec::h265::synthesizer::Synthesizer;
 
 // Given the max VPS id.
 const MAX_VPS_COUNT: usize = 16;
@@ -45,17 +52,17 @@ const MAX_SHORT_TERM_REF_PIC_SETS: usize = 65;
 const MAX_LONG_TERM_REF_PIC_SETS: usize = 32;
 
 // From table 7-5.
-const DEFAULT_SCALING_LIST_0: [u8; 16] = [16; 16];
+pub(super) const DEFAULT_SCALING_LIST_0: [u8; 16] = [16; 16];
 
 // From Table 7-6.
-const DEFAULT_SCALING_LIST_1: [u8; 64] = [
+pub(super) const DEFAULT_SCALING_LIST_1: [u8; 64] = [
     16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 17, 16, 17, 16, 17, 18, 17, 18, 18, 17, 18, 21, 19, 20,
     21, 20, 19, 21, 24, 22, 22, 24, 24, 22, 22, 24, 25, 25, 27, 30, 27, 25, 25, 29, 31, 35, 35, 31,
     29, 36, 41, 44, 41, 36, 47, 54, 54, 47, 65, 70, 65, 88, 88, 115,
 ];
 
 // From Table 7-6.
-const DEFAULT_SCALING_LIST_2: [u8; 64] = [
+pub(super) const DEFAULT_SCALING_LIST_2: [u8; 64] = [
     16, 16, 16, 16, 16, 16, 16, 16, 16, 16, 17, 17, 17, 17, 17, 18, 18, 18, 18, 18, 18, 20, 20, 20,
     20, 20, 20, 20, 24, 24, 24, 24, 24, 24, 24, 24, 25, 25, 25, 25, 25, 25, 25, 28, 28, 28, 28, 28,
     28, 33, 33, 33, 33, 33, 41, 41, 41, 41, 54, 54, 54, 71, 71, 91,
@@ -185,7 +192,10 @@ impl Header for NaluHeader {
     fn parse<T: AsRef<[u8]>>(
         cursor: &std::io::Cursor<T>,
     ) -> Result<Self, Box<dyn std::error::Error>> {
-        let data = &cursor.chunk()[0..2];
+        let data = cursor
+            .chunk()
+            .get(0..2)
+            .ok_or(anyhow!("NALU too short for its header"))?;
         let mut r = BitReader::new(data);
 
         // Skip forbidden_zero_bit
@@ -1436,10 +1446,11 @@ impl Default for ShortTermRefPicSet {
     }
 }
 
-#[derive(N, Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(N, Clone, Copy, Debug, Default, PartialEq, Eq)]
 /// See table 7-7 in the specification.
 pub enum SliceType {
     B = 0,
+    #[default]
     P = 1,
     I = 2,
 }
@@ -1461,12 +1472,6 @@ impl SliceType {
     }
 }
 
-impl Default for SliceType {
-    fn default() -> Self {
-        Self::P
-    }
-}
-
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct SliceHeader {
     /// When set, specifies that the slice segment is the first slice segment of
@@ -1728,44 +1733,111 @@ pub struct Slice<'a> {
 }
 
 impl<'a> Slice<'a> {
-    /// Sets the header for dependent slices by copying from an independent
-    /// slice.
-    pub fn replace_header(&mut self, header: SliceHeader) -> anyhow::Result<()> {
-        if !self.header.dependent_slice_segment_flag {
-            Err(anyhow!(
-                "Replacing the slice header is only possible for dependent slices"
-            ))
+    /// Replaces the slice segment header with `header` and re-encodes it in
+    /// place, keeping the slice segment data untouched. `sps` and `pps` must be
+    /// the parameter sets referenced by the slice.
+    ///
+    /// For dependent slice segments, `header` is the header of the preceding
+    /// independent slice segment: only the fields that are not coded in a
+    /// dependent slice segment header are copied from it. For independent
+    /// slice segments, all the fields are taken from `header`. In both cases,
+    /// the fields derived by the parser, e.g. `header_bit_size`, are
+    /// recomputed from the new bitstream.
+    pub fn replace_header(
+        &mut self,
+        header: SliceHeader,
+        sps: &Sps,
+        pps: &Pps,
+    ) -> anyhow::Result<()> {
+        let dependent_slice_segment_flag = self.header.dependent_slice_segment_flag;
+        let header = if dependent_slice_segment_flag {
+            Self::inherit_header(&self.header, header)
         } else {
-            let first_slice_segment_in_pic_flag = self.header.first_slice_segment_in_pic_flag;
-            let no_output_of_prior_pics_flag = self.header.no_output_of_prior_pics_flag;
-            let pic_parameter_set_id = self.header.pic_parameter_set_id;
-            let dependent_slice_segment_flag = self.header.dependent_slice_segment_flag;
-            let segment_address = self.header.segment_address;
-
-            let offset_len_minus1 = self.header.offset_len_minus1;
-            let entry_point_offset_minus1 = self.header.entry_point_offset_minus1;
-            let num_pic_total_curr = self.header.num_pic_total_curr;
-            let header_bit_size = self.header.header_bit_size;
-            let n_emulation_prevention_bytes = self.header.n_emulation_prevention_bytes;
-            let curr_rps_idx = self.header.curr_rps_idx;
-            let st_rps_bits = self.header.st_rps_bits;
-
-            self.header = header;
-
-            self.header.first_slice_segment_in_pic_flag = first_slice_segment_in_pic_flag;
-            self.header.no_output_of_prior_pics_flag = no_output_of_prior_pics_flag;
-            self.header.pic_parameter_set_id = pic_parameter_set_id;
-            self.header.dependent_slice_segment_flag = dependent_slice_segment_flag;
-            self.header.segment_address = segment_address;
-            self.header.offset_len_minus1 = offset_len_minus1;
-            self.header.entry_point_offset_minus1 = entry_point_offset_minus1;
-            self.header.num_pic_total_curr = num_pic_total_curr;
-            self.header.header_bit_size = header_bit_size;
-            self.header.n_emulation_prevention_bytes = n_emulation_prevention_bytes;
-            self.header.curr_rps_idx = curr_rps_idx;
-            self.header.st_rps_bits = st_rps_bits;
-
-            Ok(())
+            header
+        };
+
+        if header.pic_parameter_set_id != pps.pic_parameter_set_id
+            || pps.seq_parameter_set_id != sps.seq_parameter_set_id
+        {
+            return Err(anyhow!(
+                "The header does not refer to the given parameter sets"
+            ));
+        }
+
+        // Unescape the slice segment data that follows the current header.
+        let hdr_len = self.nalu.header.len();
+        let mut r = NaluReader::new(&self.nalu.as_ref()[hdr_len..]);
+        r.skip_bits(self.header.header_bit_size as usize - hdr_len * 8)?;
+        let mut slice_data = Vec::new();
+        while let Ok(byte) = r.read_bits::<u8>(8) {
+            slice_data.push(byte);
+        }
+
+        let mut synthesized = Vec::new();
+        Synthesizer::<SliceHeader, _>::synthesize(
+            &self.nalu.header,
+            &header,
+            sps,
+            pps,
+            &mut synthesized,
+            false,
+        )?;
+
+        // Skip the start code and the NALU header, they are written again
+        // below with the escaped payload.
+        let mut data = Vec::new();
+        {
+            let mut writer = NaluWriter::new(&mut data, true);
+            writer.write_raw_header(&synthesized[4..4 + hdr_len])?;
+            for byte in synthesized[4 + hdr_len..].iter().chain(&slice_data) {
+                writer.write_f(8, *byte)?;
+            }
+        }
+
+        let mut parser = Parser::default();
+        parser
+            .active_spses
+            .insert(sps.seq_parameter_set_id, sps.clone());
+        parser
+            .active_ppses
+            .insert(pps.pic_parameter_set_id, pps.clone());
+
+        let mut cursor = Cursor::new(data.as_ref());
+        let nalu = Nalu::next(&mut cursor)
+            .map_err(|err| anyhow!("Failed to find the rewritten NALU: {err}"))?
+            .into_owned();
+        let slice = parser.parse_slice_header(nalu)?;
+
+        // The parser leaves the fields of the independent slice segment unset
+        // in dependent slice segment headers.
+        self.header = if dependent_slice_segment_flag {
+            Self::inherit_header(&slice.header, header)
+        } else {
+            slice.header
+        };
+        self.nalu = slice.nalu;
+
+        Ok(())
+    }
+
+    /// Returns `header` with the fields coded in the dependent slice segment
+    /// header `dependent`, and the fields derived from them, copied over.
+    fn inherit_header(dependent: &SliceHeader, header: SliceHeader) -> SliceHeader {
+        SliceHeader {
+            first_slice_segment_in_pic_flag: dependent.first_slice_segment_in_pic_flag,
+            no_output_of_prior_pics_flag: dependent.no_output_of_prior_pics_flag,
+            pic_parameter_set_id: dependent.pic_parameter_set_id,
+            dependent_slice_segment_flag: dependent.dependent_slice_segment_flag,
+            segment_address: dependent.segment_address,
+            num_entry_point_offsets: dependent.num_entry_point_offsets,
+            offset_len_minus1: dependent.offset_len_minus1,
+            entry_point_offset_minus1: dependent.entry_point_offset_minus1,
+            num_pic_total_curr: dependent.num_pic_total_curr,
+            header_bit_size: dependent.header_bit_size,
+            n_emulation_prevention_bytes: dependent.n_emulation_prevention_bytes,
+            curr_rps_idx: dependent.curr_rps_idx,
+            st_rps_bits: dependent.st_rps_bits,
+            ..header
         }
     }
 }
@@ -2135,11 +2207,246 @@ impl Default for VuiParams {
     }
 }
 
+/// The payload types of the SEI messages that can be parsed, see Annex D.
+const SEI_BUFFERING_PERIOD: u32 = 0;
+const SEI_PIC_TIMING: u32 = 1;
+const SEI_USER_DATA_REGISTERED_ITU_T_T35: u32 = 4;
+const SEI_USER_DATA_UNREGISTERED: u32 = 5;
+const SEI_RECOVERY_POINT: u32 = 6;
+const SEI_ACTIVE_PARAMETER_SETS: u32 = 129;
+const SEI_DECODED_PICTURE_HASH: u32 = 132;
+const SEI_TIME_CODE: u32 = 136;
+const SEI_MASTERING_DISPLAY_COLOUR_VOLUME: u32 = 137;
+const SEI_CONTENT_LIGHT_LEVEL_INFO: u32 = 144;
+const SEI_ALTERNATIVE_TRANSFER_CHARACTERISTICS: u32 = 147;
+
+/// Buffering period SEI message, see D.2.2.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BufferingPeriod {
+    /// Indicates and shall be equal to the sps_seq_parameter_set_id for the
+    /// SPS that is active for the coded picture associated with the buffering
+    /// period SEI message.
+    pub bp_seq_parameter_set_id: u8,
+    /// When set, specifies the presence of the `cpb_delay_offset` and
+    /// `dpb_delay_offset` syntax elements.
+    pub irap_cpb_params_present_flag: bool,
+    /// Specifies an offset to be used in the derivation of the nominal CPB
+    /// removal times of access units following, in decoding order, the CRA
+    /// access unit associated with the buffering period SEI message when the
+    /// RASL access units associated with the CRA access unit are not present.
+    pub cpb_delay_offset: u32,
+    /// Specifies an offset to be used in the derivation of the DPB output
+    /// times of the CRA access unit associated with the buffering period SEI
+    /// message when the RASL access units associated with the CRA access unit
+    /// are not present.
+    pub dpb_delay_offset: u32,
+    /// Indicates whether the nominal CPB removal time of the associated
+    /// access unit is determined relative to the nominal CPB removal time of
+    /// the previous access unit with a buffering period SEI message.
+    pub concatenation_flag: bool,
+    /// Plus 1 specifies a CPB removal delay increment value relative to the
+    /// nominal CPB removal time of the previous access unit with a buffering
+    /// period SEI message, when `concatenation_flag` is set.
+    pub au_cpb_removal_delay_delta_minus1: u32,
+    /// `[ i ]` specifies the default initial CPB removal delay for the i-th
+    /// CPB, for the NAL HRD.
+    pub nal_initial_cpb_removal_delay: [u32; 32],
+    /// `[ i ]` specifies the default initial CPB removal offset for the i-th
+    /// CPB, for the NAL HRD.
+    pub nal_initial_cpb_removal_offset: [u32; 32],
+    /// `[ i ]` specifies the alternative initial CPB removal delay for the
+    /// i-th CPB, for the NAL HRD.
+    pub nal_initial_alt_cpb_removal_delay: [u32; 32],
+    /// `[ i ]` specifies the alternative initial CPB removal offset for the
+    /// i-th CPB, for the NAL HRD.
+    pub nal_initial_alt_cpb_removal_offset: [u32; 32],
+    /// Same as `nal_initial_cpb_removal_delay`, for the VCL HRD.
+    pub vcl_initial_cpb_removal_delay: [u32; 32],
+    /// Same as `nal_initial_cpb_removal_offset`, for the VCL HRD.
+    pub vcl_initial_cpb_removal_offset: [u32; 32],
+    /// Same as `nal_initial_alt_cpb_removal_delay`, for the VCL HRD.
+    pub vcl_initial_alt_cpb_removal_delay: [u32; 32],
+    /// Same as `nal_initial_alt_cpb_removal_offset`, for the VCL HRD.
+    pub vcl_initial_alt_cpb_removal_offset: [u32; 32],
+}
+
+/// Picture timing SEI message, see D.2.3.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct PicTiming {
+    /// Indicates whether a picture should be displayed as a frame or as one
+    /// or more fields, as per Table D.2.
+    pub pic_struct: u8,
+    /// Indicates the scan type of the associated picture, as per Table D.3.
+    pub source_scan_type: u8,
+    /// Indicates that the current picture is indicated to be a duplicate of
+    /// a previous picture in output order.
+    pub duplicate_flag: bool,
+    /// Plus 1 specifies the number of clock ticks between the nominal CPB
+    /// removal time of the access unit associated with the picture timing SEI
+    /// message and the preceding access unit in decoding order that contained
+    /// a buffering period SEI message.
+    pub au_cpb_removal_delay_minus1: u32,
+    /// Used to compute the DPB output time of the picture.
+    pub pic_dpb_output_delay: u32,
+    /// Used to compute the DPB output time of the picture when the HRD
+    /// operates at sub-picture level.
+    pub pic_dpb_output_du_delay: u32,
+    /// Plus 1 specifies the number of decoding units in the access unit.
+    pub num_decoding_units_minus1: u32,
+    /// When set, specifies that `du_common_cpb_removal_delay_increment_minus1`
+    /// is present.
+    pub du_common_cpb_removal_delay_flag: bool,
+    /// Plus 1 specifies the duration, in units of clock sub-ticks, between the
+    /// nominal CPB removal times of any two consecutive decoding units.
+    pub du_common_cpb_removal_delay_increment_minus1: u32,
+    /// `[ i ]` plus 1 specifies the number of NAL units in the i-th decoding
+    /// unit.
+    pub num_nalus_in_du_minus1: Vec<u32>,
+    /// `[ i ]` plus 1 specifies the duration, in units of clock sub-ticks,
+    /// between the nominal CPB removal times of the (i + 1)-th and the i-th
+    /// decoding units.
+    pub du_cpb_removal_delay_increment_minus1: Vec<u32>,
+}
+
+/// Recovery point SEI message, see D.2.8.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct RecoveryPoint {
+    /// Specifies the recovery point of decoded pictures in output order.
+    pub recovery_poc_cnt: i32,
+    /// Indicates whether decoded pictures at and subsequent to the recovery
+    /// point in output order are an exact match to the pictures that would be
+    /// produced by starting the decoding process at the location of a
+    /// previous IRAP access unit.
+    pub exact_match_flag: bool,
+    /// Indicates the presence or absence of a broken link in the NAL unit
+    /// stream at the location of the recovery point SEI message.
+    pub broken_link_flag: bool,
+}
+
+/// Decoded picture hash SEI message, see D.2.20.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodedPictureHash {
+    /// Specifies the method used to calculate the hash: 0 for MD5, 1 for CRC
+    /// and 2 for checksum.
+    pub hash_type: u8,
+    /// The number of colour components the hash was computed for.
+    pub num_components: u8,
+    /// `[ cIdx ]` is the MD5 of the decoded sample values of the colour
+    /// component cIdx.
+    pub picture_md5: [[u8; 16]; 3],
+    /// `[ cIdx ]` is the CRC of the decoded sample values of the colour
+    /// component cIdx.
+    pub picture_crc: [u16; 3],
+    /// `[ cIdx ]` is the checksum of the decoded sample values of the colour
+    /// component cIdx.
+    pub picture_checksum: [u32; 3],
+}
+
+/// Active parameter sets SEI message, see D.2.21.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ActiveParameterSets {
+    /// Identifies the active VPS.
+    pub active_video_parameter_set_id: u8,
+    /// Indicates that each parameter set referenced in the CVS is present
+    /// before it is activated.
+    pub self_contained_cvs_flag: bool,
+    /// Indicates that there is no parameter set update in the CVS.
+    pub no_parameter_set_update_flag: bool,
+    /// Plus 1 indicates the number of `active_seq_parameter_set_id` values.
+    pub num_sps_ids_minus1: u8,
+    /// `[ i ]` indicates a value of sps_seq_parameter_set_id of the SPS that
+    /// may be referred to by any picture of the layers of the CVS.
+    pub active_seq_parameter_set_id: [u8; 16],
+    /// `[ i ]` specifies an index, into the list `active_seq_parameter_set_id`,
+    /// of the SPS referred to by the layer with nuh_layer_id equal to i.
+    pub layer_sps_idx: Vec<u8>,
+}
+
+/// A clock timestamp of the time code SEI message, see D.2.27.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClockTimestamp {
+    /// Used in computing clockTimestamp.
+    pub units_field_based_flag: bool,
+    /// Specifies the method of dropping values of `n_frames`.
+    pub counting_type: u8,
+    /// Specifies that the `seconds_value`, `minutes_value` and `hours_value`
+    /// syntax elements are present.
+    pub full_timestamp_flag: bool,
+    /// Indicates whether the difference between the current and the previous
+    /// clockTimestamp can be interpreted as the time difference between the
+    /// times of origin or capture of the associated pictures.
+    pub discontinuity_flag: bool,
+    /// Specifies the skipping of one or more values of `n_frames` using the
+    /// counting method specified by `counting_type`.
+    pub cnt_dropped_flag: bool,
+    /// Specifies the value of nFrames used to compute clockTimestamp.
+    pub n_frames: u16,
+    /// Specifies that `seconds_value` is present.
+    pub seconds_flag: bool,
+    /// Specifies the value of sS used to compute clockTimestamp.
+    pub seconds_value: u8,
+    /// Specifies that `minutes_value` is present.
+    pub minutes_flag: bool,
+    /// Specifies the value of mM used to compute clockTimestamp.
+    pub minutes_value: u8,
+    /// Specifies that `hours_value` is present.
+    pub hours_flag: bool,
+    /// Specifies the value of hH used to compute clockTimestamp.
+    pub hours_value: u8,
+    /// Specifies the length in bits of `time_offset_value`.
+    pub time_offset_length: u8,
+    /// Specifies the value of tOffset used to compute clockTimestamp.
+    pub time_offset_value: i32,
+}
+
+/// Time code SEI message, see D.2.27.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TimeCode {
+    /// Specifies the number of sets of clock timestamps.
+    pub num_clock_ts: u8,
+    /// `[ i ]` specifies that the i-th clock timestamp is present.
+    pub clock_timestamp_flag: [bool; 3],
+    /// The clock timestamps, as many as `num_clock_ts`.
+    pub clock_timestamp: [ClockTimestamp; 3],
+}
+
+/// Alternative transfer characteristics SEI message, see D.2.38.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AlternativeTransferCharacteristics {
+    /// Provides a preferred alternative value for the
+    /// transfer_characteristics syntax element of the VUI, as per Table E.4.
+    pub preferred_transfer_characteristics: u8,
+}
+
+/// A SEI message, as parsed by [`Parser::parse_sei`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SeiMessage {
+    BufferingPeriod(Box<BufferingPeriod>),
+    PicTiming(PicTiming),
+    UserDataRegisteredItuTT35(UserDataRegisteredItuTT35),
+    UserDataUnregistered(UserDataUnregistered),
+    RecoveryPoint(RecoveryPoint),
+    ActiveParameterSets(ActiveParameterSets),
+    DecodedPictureHash(DecodedPictureHash),
+    TimeCode(TimeCode),
+    MasteringDisplayColourVolume(MasteringDisplayColourVolume),
+    ContentLightLevelInfo(ContentLightLevelInfo),
+    AlternativeTransferCharacteristics(AlternativeTransferCharacteristics),
+    /// A message whose payload type is not parsed. `payload` holds the
+    /// payload bytes, with the emulation prevention bytes removed.
+    Unknown {
+        payload_type: u32,
+        payload: Vec<u8>,
+    },
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Parser {
     active_vpses: BTreeMap<u8, Vps>,
     active_spses: BTreeMap<u8, Sps>,
     active_ppses: BTreeMap<u8, Pps>,
+    /// The SPS used to parse the picture timing SEI messages.
+    last_sps_id: Option<u8>,
 }
 
 impl Parser {
@@ -2292,6 +2599,14 @@ impl Parser {
         profile_present_flag: bool,
         sps_max_sub_layers_minus_1: u8,
     ) -> anyhow::Result<()> {
+        // 7.4.3.1 and 7.4.3.2.1
+        if sps_max_sub_layers_minus_1 > 6 {
+            return Err(anyhow!(
+                "Invalid max_sub_layers_minus1 {}",
+                sps_max_sub_layers_minus_1
+            ));
+        }
+
         if profile_present_flag {
             ptl.general_profile_space = r.read_bits(2)?;
             ptl.general_tier_flag = r.read_bit()?;
@@ -2399,7 +2714,7 @@ impl Parser {
         }
 
         for i in 0..sps_max_sub_layers_minus_1 as usize {
-            if ptl.sub_layer_level_present_flag[i] {
+            if ptl.sub_layer_profile_present_flag[i] {
                 ptl.sub_layer_profile_space[i] = r.read_bits(2)?;
                 ptl.sub_layer_tier_flag[i] = r.read_bit()?;
                 ptl.sub_layer_profile_idc[i] = r.read_bits(5)?;
@@ -2481,12 +2796,12 @@ impl Parser {
                 } else {
                     r.skip_bits(1)?;
                 }
+            }
 
-                if ptl.sub_layer_level_present_flag[i] {
-                    let level: u8 = r.read_bits(8)?;
-                    ptl.sub_layer_level_idc[i] =
-                        Level::n(level).with_context(|| format!("Unsupported level {}", level))?;
-                }
+            if ptl.sub_layer_level_present_flag[i] {
+                let level: u8 = r.read_bits(8)?;
+                ptl.sub_layer_level_idc[i] =
+                    Level::n(level).with_context(|| format!("Unsupported level {}", level))?;
             }
         }
         Ok(())
@@ -2543,7 +2858,13 @@ impl Parser {
                 // in Table 7-5 and Table 7-6 for i = 0..Min( 63, ( 1 << ( 4 + (
                 // sizeId << 1 ) ) ) − 1 ).
                 if !scaling_list_pred_mode_flag {
-                    let scaling_list_pred_matrix_id_delta: u32 = r.read_ue()?;
+                    // The reference list must be one of the previous ones.
+                    let max_delta = if size_id == 3 {
+                        matrix_id as u32 / 3
+                    } else {
+                        matrix_id as u32
+                    };
+                    let scaling_list_pred_matrix_id_delta: u32 = r.read_ue_max(max_delta)?;
                     if scaling_list_pred_matrix_id_delta == 0 {
                         Self::fill_default_scaling_list(sl, size_id, matrix_id);
                     } else {
@@ -2832,6 +3153,11 @@ impl Parser {
             hrd.fixed_pic_rate_general_flag[i] = r.read_bit()?;
             if !hrd.fixed_pic_rate_general_flag[i] {
                 hrd.fixed_pic_rate_within_cvs_flag[i] = r.read_bit()?;
+            } else {
+                // When fixed_pic_rate_general_flag[ i ] is equal to 1, the
+                // value of fixed_pic_rate_within_cvs_flag[ i ] is inferred to
+                // be equal to 1.
+                hrd.fixed_pic_rate_within_cvs_flag[i] = true;
             }
             if hrd.fixed_pic_rate_within_cvs_flag[i] {
                 hrd.elemental_duration_in_tc_minus1[i] = r.read_ue_max(2047)?;
@@ -3175,21 +3501,23 @@ impl Parser {
         sps.extension_present_flag = r.read_bit()?;
         if sps.extension_present_flag {
             sps.range_extension_flag = r.read_bit()?;
+            let multilayer_extension_flag = r.read_bit()?;
+            let three_d_extension_flag = r.read_bit()?;
+            sps.scc_extension_flag = r.read_bit()?;
+            r.skip_bits(4)?; // sps_extension_4bits
+
             if sps.range_extension_flag {
                 Self::parse_sps_range_extension(&mut sps, &mut r)?;
             }
 
-            let multilayer_extension_flag = r.read_bit()?;
             if multilayer_extension_flag {
                 return Err(anyhow!("Multilayer extension not supported."));
             }
 
-            let three_d_extension_flag = r.read_bit()?;
             if three_d_extension_flag {
                 return Err(anyhow!("3D extension not supported."));
             }
 
-            sps.scc_extension_flag = r.read_bit()?;
             if sps.scc_extension_flag {
                 Self::parse_sps_scc_extension(&mut sps, &mut r)?;
             }
@@ -3221,6 +3549,7 @@ impl Parser {
 
         let key = sps.seq_parameter_set_id;
         self.active_spses.insert(key, sps);
+        self.last_sps_id = Some(key);
 
         if self.active_spses.keys().len() > MAX_SPS_COUNT {
             return Err(anyhow!(
@@ -3301,7 +3630,7 @@ impl Parser {
         }
 
         let bit_depth_y = sps.bit_depth_luma_minus8 + 8;
-        let max = u32::from(std::cmp::max(0, bit_depth_y - 10));
+        let max = u32::from(bit_depth_y.saturating_sub(10));
 
         rext.log2_sao_offset_scale_luma = r.read_ue_max(max)?;
         rext.log2_sao_offset_scale_chroma = r.read_ue_max(max)?;
@@ -3455,30 +3784,33 @@ impl Parser {
         pps.extension_present_flag = r.read_bit()?;
         if pps.extension_present_flag {
             pps.range_extension_flag = r.read_bit()?;
+            let multilayer_extension_flag = r.read_bit()?;
+            let three_d_extension_flag = r.read_bit()?;
+            pps.scc_extension_flag = r.read_bit()?;
+            r.skip_bits(4)?; // pps_extension_4bits
 
             if pps.range_extension_flag {
                 Self::parse_pps_range_extension(&mut pps, sps, &mut r)?;
             }
 
-            let multilayer_extension_flag = r.read_bit()?;
             if multilayer_extension_flag {
                 return Err(anyhow!("Multilayer extension is not supported"));
             }
 
-            let three_d_extension_flag = r.read_bit()?;
             if three_d_extension_flag {
                 return Err(anyhow!("3D extension is not supported"));
             }
 
-            pps.scc_extension_flag = r.read_bit()?;
             if pps.scc_extension_flag {
                 Self::parse_pps_scc_extension(&mut pps, sps, &mut r)?;
             }
-
-            r.skip_bits(4)?; // pps_extension_4bits
         }
 
-        pps.temporal_id = nalu.header.nuh_temporal_id_plus1 - 1;
+        pps.temporal_id = nalu
+            .header
+            .nuh_temporal_id_plus1
+            .checked_sub(1)
+            .ok_or(anyhow!("Invalid nuh_temporal_id_plus1 0"))?;
 
         log::debug!(
             "Parsed PPS({}), NAL size was {}",
@@ -3545,7 +3877,7 @@ impl Parser {
                 pwt.luma_weight_l1_flag[i] = r.read_bit()?;
             }
 
-            if sps.chroma_format_idc != 0 {
+            if sps.chroma_array_type != 0 {
                 for i in 0..=usize::from(hdr.num_ref_idx_l1_active_minus1) {
                     pwt.chroma_weight_l1_flag[i] = r.read_bit()?;
                 }
@@ -4046,6 +4378,505 @@ impl Parser {
     }
 
     /// Returns a previously parsed vps given `vps_id`, if any.
+    /// Reads a `u(v)` syntax element of up to 32 bits.
+    fn read_bits_u32(r: &mut NaluReader, num_bits: usize) -> anyhow::Result<u32> {
+        if num_bits == 32 {
+            let value = r.read_bits::<u32>(31)? << 1;
+            Ok(value | r.read_bit()? as u32)
+        } else {
+            Ok(r.read_bits(num_bits)?)
+        }
+    }
+
+    /// Reads `num_bytes` bytes of SEI payload.
+    fn read_payload_bytes(r: &mut NaluReader, num_bytes: usize) -> anyhow::Result<Vec<u8>> {
+        (0..num_bytes).map(|_| Ok(r.read_bits(8)?)).collect()
+    }
+
+    /// Reads a `payloadType` or `payloadSize` value, coded as a run of 0xff
+    /// bytes followed by a last byte. See 7.3.5.
+    fn read_sei_value(r: &mut NaluReader) -> anyhow::Result<u32> {
+        let mut value = 0u32;
+        loop {
+            let byte = r.read_bits::<u32>(8)?;
+            value = value
+                .checked_add(byte)
+                .ok_or(anyhow!("Broken data: SEI payload type or size overflow"))?;
+
+            if byte != 0xff {
+                return Ok(value);
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn parse_initial_cpb_removal_delays(
+        r: &mut NaluReader,
+        hrd: &HrdParams,
+        cpb_cnt: usize,
+        alt_present: bool,
+        delay: &mut [u32; 32],
+        offset: &mut [u32; 32],
+        alt_delay: &mut [u32; 32],
+        alt_offset: &mut [u32; 32],
+    ) -> anyhow::Result<()> {
+        let length = usize::from(hrd.initial_cpb_removal_delay_length_minus1) + 1;
+
+        for i in 0..cpb_cnt {
+            delay[i] = Self::read_bits_u32(r, length)?;
+            offset[i] = Self::read_bits_u32(r, length)?;
+
+            if alt_present {
+                alt_delay[i] = Self::read_bits_u32(r, length)?;
+                alt_offset[i] = Self::read_bits_u32(r, length)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a buffering period SEI message. Returns `None` if the SPS it
+    /// references has not been parsed yet.
+    fn parse_buffering_period(
+        &mut self,
+        r: &mut NaluReader,
+    ) -> anyhow::Result<Option<BufferingPeriod>> {
+        let mut bp = BufferingPeriod {
+            bp_seq_parameter_set_id: r.read_ue_max(MAX_SPS_COUNT as u32 - 1)?,
+            ..Default::default()
+        };
+
+        let Some(sps) = self.get_sps(bp.bp_seq_parameter_set_id) else {
+            return Ok(None);
+        };
+        let hrd = &sps.vui_parameters.hrd;
+
+        if !hrd.sub_pic_hrd_params_present_flag {
+            bp.irap_cpb_params_present_flag = r.read_bit()?;
+        }
+
+        if bp.irap_cpb_params_present_flag {
+            bp.cpb_delay_offset =
+                Self::read_bits_u32(r, usize::from(hrd.au_cpb_removal_delay_length_minus1) + 1)?;
+            bp.dpb_delay_offset =
+                Self::read_bits_u32(r, usize::from(hrd.dpb_output_delay_length_minus1) + 1)?;
+        }
+
+        bp.concatenation_flag = r.read_bit()?;
+        bp.au_cpb_removal_delay_delta_minus1 =
+            Self::read_bits_u32(r, usize::from(hrd.au_cpb_removal_delay_length_minus1) + 1)?;
+
+        // CpbCnt, for HighestTid.
+        let cpb_cnt = hrd.cpb_cnt_minus1[usize::from(sps.max_sub_layers_minus1)] as usize + 1;
+        let alt_present = hrd.sub_pic_hrd_params_present_flag || bp.irap_cpb_params_present_flag;
+
+        if hrd.nal_hrd_parameters_present_flag {
+            Self::parse_initial_cpb_removal_delays(
+                r,
+                hrd,
+                cpb_cnt,
+                alt_present,
+                &mut bp.nal_initial_cpb_removal_delay,
+                &mut bp.nal_initial_cpb_removal_offset,
+                &mut bp.nal_initial_alt_cpb_removal_delay,
+                &mut bp.nal_initial_alt_cpb_removal_offset,
+            )?;
+        }
+
+        if hrd.vcl_hrd_parameters_present_flag {
+            Self::parse_initial_cpb_removal_delays(
+                r,
+                hrd,
+                cpb_cnt,
+                alt_present,
+                &mut bp.vcl_initial_cpb_removal_delay,
+                &mut bp.vcl_initial_cpb_removal_offset,
+                &mut bp.vcl_initial_alt_cpb_removal_delay,
+                &mut bp.vcl_initial_alt_cpb_removal_offset,
+            )?;
+        }
+
+        // The buffering period activates the SPS it references.
+        self.last_sps_id = Some(bp.bp_seq_parameter_set_id);
+
+        Ok(Some(bp))
+    }
+
+    /// Parses a picture timing SEI message. Returns `None` if no SPS has been
+    /// parsed yet.
+    fn parse_pic_timing(&self, r: &mut NaluReader) -> anyhow::Result<Option<PicTiming>> {
+        let Some(sps) = self.last_sps_id.and_then(|sps_id| self.get_sps(sps_id)) else {
+            return Ok(None);
+        };
+        let vui = &sps.vui_parameters;
+        let hrd = &vui.hrd;
+
+        let mut pt = PicTiming::default();
+
+        if vui.frame_field_info_present_flag {
+            pt.pic_struct = r.read_bits(4)?;
+            pt.source_scan_type = r.read_bits(2)?;
+            pt.duplicate_flag = r.read_bit()?;
+        }
+
+        // CpbDpbDelaysPresentFlag
+        if vui.hrd_parameters_present_flag
+            && (hrd.nal_hrd_parameters_present_flag || hrd.vcl_hrd_parameters_present_flag)
+        {
+            pt.au_cpb_removal_delay_minus1 =
+                Self::read_bits_u32(r, usize::from(hrd.au_cpb_removal_delay_length_minus1) + 1)?;
+            pt.pic_dpb_output_delay =
+                Self::read_bits_u32(r, usize::from(hrd.dpb_output_delay_length_minus1) + 1)?;
+
+            if hrd.sub_pic_hrd_params_present_flag {
+                pt.pic_dpb_output_du_delay =
+                    Self::read_bits_u32(r, usize::from(hrd.dpb_output_delay_du_length_minus1) + 1)?;
+            }
+
+            if hrd.sub_pic_hrd_params_present_flag && hrd.sub_pic_cpb_params_in_pic_timing_sei_flag
+            {
+                let increment_length =
+                    usize::from(hrd.du_cpb_removal_delay_increment_length_minus1) + 1;
+
+                pt.num_decoding_units_minus1 = r.read_ue_max(sps.pic_size_in_ctbs_y - 1)?;
+                pt.du_common_cpb_removal_delay_flag = r.read_bit()?;
+                if pt.du_common_cpb_removal_delay_flag {
+                    pt.du_common_cpb_removal_delay_increment_minus1 =
+                        Self::read_bits_u32(r, increment_length)?;
+                }
+
+                for i in 0..=pt.num_decoding_units_minus1 {
+                    pt.num_nalus_in_du_minus1
+                        .push(r.read_ue_max(sps.pic_size_in_ctbs_y - 1)?);
+
+                    if !pt.du_common_cpb_removal_delay_flag && i < pt.num_decoding_units_minus1 {
+                        pt.du_cpb_removal_delay_increment_minus1
+                            .push(Self::read_bits_u32(r, increment_length)?);
+                    }
+                }
+            }
+        }
+
+        Ok(Some(pt))
+    }
+
+    fn parse_user_data_registered_itu_t_t35(
+        r: &mut NaluReader,
+        payload_size: usize,
+    ) -> anyhow::Result<UserDataRegisteredItuTT35> {
+        let mut ud = UserDataRegisteredItuTT35::default();
+        let mut header_size = 1;
+
+        ud.itu_t_t35_country_code = r.read_bits(8)?;
+        if ud.itu_t_t35_country_code == 0xff {
+            ud.itu_t_t35_country_code_extension_byte = r.read_bits(8)?;
+            header_size += 1;
+        }
+
+        let payload_size = payload_size.checked_sub(header_size).ok_or(anyhow!(
+            "Broken data: user data registered SEI payload too small"
+        ))?;
+        ud.itu_t_t35_payload = Self::read_payload_bytes(r, payload_size)?;
+
+        Ok(ud)
+    }
+
+    fn parse_user_data_unregistered(
+        r: &mut NaluReader,
+        payload_size: usize,
+    ) -> anyhow::Result<UserDataUnregistered> {
+        let mut ud = UserDataUnregistered::default();
+
+        let payload_size = payload_size
+            .checked_sub(ud.uuid_iso_iec_11578.len())
+            .ok_or(anyhow!(
+                "Broken data: user data unregistered SEI payload too small"
+            ))?;
+
+        for byte in ud.uuid_iso_iec_11578.iter_mut() {
+            *byte = r.read_bits(8)?;
+        }
+
+        ud.user_data_payload = Self::read_payload_bytes(r, payload_size)?;
+
+        Ok(ud)
+    }
+
+    fn parse_recovery_point(&self, r: &mut NaluReader) -> anyhow::Result<RecoveryPoint> {
+        // The range of recovery_poc_cnt depends on the active SPS.
+        let max_pic_order_cnt_lsb = self
+            .last_sps_id
+            .and_then(|sps_id| self.get_sps(sps_id))
+            .map_or(1 << 16, |sps| {
+                1 << (sps.log2_max_pic_order_cnt_lsb_minus4 + 4)
+            });
+
+        Ok(RecoveryPoint {
+            recovery_poc_cnt: r
+                .read_se_bounded(-max_pic_order_cnt_lsb / 2, max_pic_order_cnt_lsb / 2 - 1)?,
+            exact_match_flag: r.read_bit()?,
+            broken_link_flag: r.read_bit()?,
+        })
+    }
+
+    /// Parses an active parameter sets SEI message. Returns `None` if the VPS
+    /// it references has not been parsed yet.
+    fn parse_active_parameter_sets(
+        &mut self,
+        r: &mut NaluReader,
+    ) -> anyhow::Result<Option<ActiveParameterSets>> {
+        let mut aps = ActiveParameterSets {
+            active_video_parameter_set_id: r.read_bits(4)?,
+            self_contained_cvs_flag: r.read_bit()?,
+            no_parameter_set_update_flag: r.read_bit()?,
+            num_sps_ids_minus1: r.read_ue_max(MAX_SPS_COUNT as u32 - 1)?,
+            ..Default::default()
+        };
+
+        let Some(vps) = self.get_vps(aps.active_video_parameter_set_id) else {
+            return Ok(None);
+        };
+
+        for i in 0..=usize::from(aps.num_sps_ids_minus1) {
+            aps.active_seq_parameter_set_id[i] = r.read_ue_max(MAX_SPS_COUNT as u32 - 1)?;
+        }
+
+        let first_layer = u8::from(vps.base_layer_internal_flag);
+        for _ in first_layer..=vps.max_layers_minus1 {
+            aps.layer_sps_idx
+                .push(r.read_ue_max(u32::from(aps.num_sps_ids_minus1))?);
+        }
+
+        self.last_sps_id = Some(aps.active_seq_parameter_set_id[0]);
+
+        Ok(Some(aps))
+    }
+
+    fn parse_decoded_picture_hash(
+        r: &mut NaluReader,
+        payload_size: usize,
+    ) -> anyhow::Result<DecodedPictureHash> {
+        let mut hash = DecodedPictureHash {
+            hash_type: r.read_bits(8)?,
+            ..Default::default()
+        };
+
+        let hash_size = match hash.hash_type {
+            0 => 16,
+            1 => 2,
+            2 => 4,
+            _ => return Err(anyhow!("Invalid hash_type {}", hash.hash_type)),
+        };
+
+        // The number of components depends on the chroma format of the
+        // active SPS, which can also be deduced from the payload size.
+        let num_components = payload_size.saturating_sub(1) / hash_size;
+        if !matches!(num_components, 1 | 3) {
+            return Err(anyhow!(
+                "Broken data: invalid decoded picture hash payload size {}",
+                payload_size
+            ));
+        }
+        hash.num_components = num_components as u8;
+
+        for c in 0..num_components {
+            match hash.hash_type {
+                0 => {
+                    for byte in hash.picture_md5[c].iter_mut() {
+                        *byte = r.read_bits(8)?;
+                    }
+                }
+                1 => hash.picture_crc[c] = r.read_bits(16)?,
+                _ => hash.picture_checksum[c] = Self::read_bits_u32(r, 32)?,
+            }
+        }
+
+        Ok(hash)
+    }
+
+    fn parse_time_code(r: &mut NaluReader) -> anyhow::Result<TimeCode> {
+        let mut tc = TimeCode {
+            num_clock_ts: r.read_bits(2)?,
+            ..Default::default()
+        };
+
+        for i in 0..usize::from(tc.num_clock_ts) {
+            tc.clock_timestamp_flag[i] = r.read_bit()?;
+            if !tc.clock_timestamp_flag[i] {
+                continue;
+            }
+
+            let ts = &mut tc.clock_timestamp[i];
+            ts.units_field_based_flag = r.read_bit()?;
+            ts.counting_type = r.read_bits(5)?;
+            ts.full_timestamp_flag = r.read_bit()?;
+            ts.discontinuity_flag = r.read_bit()?;
+            ts.cnt_dropped_flag = r.read_bit()?;
+            ts.n_frames = r.read_bits(9)?;
+
+            if ts.full_timestamp_flag {
+                ts.seconds_value = r.read_bits(6)?;
+                ts.minutes_value = r.read_bits(6)?;
+                ts.hours_value = r.read_bits(5)?;
+            } else {
+                ts.seconds_flag = r.read_bit()?;
+                if ts.seconds_flag {
+                    ts.seconds_value = r.read_bits(6)?;
+                    ts.minutes_flag = r.read_bit()?;
+                    if ts.minutes_flag {
+                        ts.minutes_value = r.read_bits(6)?;
+                        ts.hours_flag = r.read_bit()?;
+                        if ts.hours_flag {
+                            ts.hours_value = r.read_bits(5)?;
+                        }
+                    }
+                }
+            }
+
+            ts.time_offset_length = r.read_bits(5)?;
+            if ts.time_offset_length > 0 {
+                // i(v): a two's complement value of time_offset_length bits.
+                let length = usize::from(ts.time_offset_length);
+                let value = i64::from(r.read_bits::<u32>(length)?);
+                let sign = (value >> (length - 1)) & 1;
+                ts.time_offset_value = (value - (sign << length)) as i32;
+            }
+        }
+
+        Ok(tc)
+    }
+
+    fn parse_mastering_display_colour_volume(
+        r: &mut NaluReader,
+    ) -> anyhow::Result<MasteringDisplayColourVolume> {
+        let mut mdcv = MasteringDisplayColourVolume::default();
+
+        for c in 0..3 {
+            mdcv.display_primaries_x[c] = r.read_bits(16)?;
+            mdcv.display_primaries_y[c] = r.read_bits(16)?;
+        }
+
+        mdcv.white_point_x = r.read_bits(16)?;
+        mdcv.white_point_y = r.read_bits(16)?;
+        mdcv.max_display_mastering_luminance = Self::read_bits_u32(r, 32)?;
+        mdcv.min_display_mastering_luminance = Self::read_bits_u32(r, 32)?;
+
+        Ok(mdcv)
+    }
+
+    fn parse_content_light_level_info(r: &mut NaluReader) -> anyhow::Result<ContentLightLevelInfo> {
+        Ok(ContentLightLevelInfo {
+            max_content_light_level: r.read_bits(16)?,
+            max_pic_average_light_level: r.read_bits(16)?,
+        })
+    }
+
+    /// Parses the SEI messages of a prefix or suffix SEI NALU. Payload types
+    /// that are not handled are returned as [`SeiMessage::Unknown`].
+    ///
+    /// Picture timing messages are parsed using the SPS referenced by the last
+    /// buffering period or active parameter sets message, or else the last
+    /// parsed SPS. Messages that depend on a parameter set that has not been
+    /// parsed yet are returned raw as well.
+    pub fn parse_sei(&mut self, nalu: &Nalu) -> anyhow::Result<Vec<SeiMessage>> {
+        let prefix = match nalu.header.type_ {
+            NaluType::PrefixSeiNut => true,
+            NaluType::SuffixSeiNut => false,
+            _ => {
+                return Err(anyhow!(
+                    "Invalid NALU type, expected {:?} or {:?}, got {:?}",
+                    NaluType::PrefixSeiNut,
+                    NaluType::SuffixSeiNut,
+                    nalu.header.type_
+                ))
+            }
+        };
+
+        let data = nalu.as_ref();
+        // Skip the header
+        let rbsp = &data[nalu.header.len()..];
+        let mut r = NaluReader::new(rbsp);
+
+        // The number of bits read so far, excluding emulation prevention bytes.
+        let bits_read = |r: &NaluReader| rbsp.len() * 8 - r.num_bits_left() - r.num_epb() * 8;
+
+        let mut messages = Vec::new();
+
+        loop {
+            let payload_type = Self::read_sei_value(&mut r)?;
+            let payload_size = Self::read_sei_value(&mut r)? as usize;
+            let payload_start = bits_read(&r);
+            let payload_reader = r.clone();
+
+            let message = match (prefix, payload_type) {
+                (true, SEI_BUFFERING_PERIOD) => self
+                    .parse_buffering_period(&mut r)?
+                    .map(|bp| SeiMessage::BufferingPeriod(Box::new(bp))),
+                (true, SEI_PIC_TIMING) => self.parse_pic_timing(&mut r)?.map(SeiMessage::PicTiming),
+                (_, SEI_USER_DATA_REGISTERED_ITU_T_T35) => {
+                    Some(SeiMessage::UserDataRegisteredItuTT35(
+                        Self::parse_user_data_registered_itu_t_t35(&mut r, payload_size)?,
+                    ))
+                }
+                (_, SEI_USER_DATA_UNREGISTERED) => Some(SeiMessage::UserDataUnregistered(
+                    Self::parse_user_data_unregistered(&mut r, payload_size)?,
+                )),
+                (true, SEI_RECOVERY_POINT) => Some(SeiMessage::RecoveryPoint(
+                    self.parse_recovery_point(&mut r)?,
+                )),
+                (true, SEI_ACTIVE_PARAMETER_SETS) => self
+                    .parse_active_parameter_sets(&mut r)?
+                    .map(SeiMessage::ActiveParameterSets),
+                (false, SEI_DECODED_PICTURE_HASH) => Some(SeiMessage::DecodedPictureHash(
+                    Self::parse_decoded_picture_hash(&mut r, payload_size)?,
+                )),
+                (true, SEI_TIME_CODE) => Some(SeiMessage::TimeCode(Self::parse_time_code(&mut r)?)),
+                (true, SEI_MASTERING_DISPLAY_COLOUR_VOLUME) => {
+                    Some(SeiMessage::MasteringDisplayColourVolume(
+                        Self::parse_mastering_display_colour_volume(&mut r)?,
+                    ))
+                }
+                (true, SEI_CONTENT_LIGHT_LEVEL_INFO) => Some(SeiMessage::ContentLightLevelInfo(
+                    Self::parse_content_light_level_info(&mut r)?,
+                )),
+                (true, SEI_ALTERNATIVE_TRANSFER_CHARACTERISTICS) => {
+                    Some(SeiMessage::AlternativeTransferCharacteristics(
+                        AlternativeTransferCharacteristics {
+                            preferred_transfer_characteristics: r.read_bits(8)?,
+                        },
+                    ))
+                }
+                _ => None,
+            };
+
+            let message = match message {
+                Some(message) => message,
+                None => {
+                    r = payload_reader;
+                    SeiMessage::Unknown {
+                        payload_type,
+                        payload: Self::read_payload_bytes(&mut r, payload_size)?,
+                    }
+                }
+            };
+
+            // Skip the reserved bits and the payload extension, if any.
+            let payload_bits_read = bits_read(&r) - payload_start;
+            let payload_bits_left = (payload_size * 8)
+                .checked_sub(payload_bits_read)
+                .ok_or(anyhow!("Broken data: SEI payload larger than its size"))?;
+            r.skip_bits(payload_bits_left)?;
+
+            messages.push(message);
+
+            if !r.has_more_rsbp_data() {
+                break;
+            }
+        }
+
+        Ok(messages)
+    }
+
     pub fn get_vps(&self, vps_id: u8) -> Option<&Vps> {
         self.active_vpses.get(&vps_id)
     }
@@ -4066,11 +4897,23 @@ mod tests {
     use std::io::Cursor;
 
     use crate::codec::h264::nalu::Nalu;
+    use crate::codec::h265::parser::AlternativeTransferCharacteristics;
+    use crate::codec::h265::parser::ClockTimestamp;
+    use crate::codec::h265::parser::ContentLightLevelInfo;
+    use crate::codec::h265::parser::DecodedPictureHash;
     use crate::codec::h265::parser::Level;
+    use crate::codec::h265::parser::MasteringDisplayColourVolume;
     use crate::codec::h265::parser::NaluHeader;
     use crate::codec::h265::parser::NaluType;
     use crate::codec::h265::parser::Parser;
+    use crate::codec::h265::parser::Pps;
+    use crate::codec::h265::parser::RecoveryPoint;
+    use crate::codec::h265::parser::SeiMessage;
+    use crate::codec::h265::parser::SliceHeader;
     use crate::codec::h265::parser::SliceType;
+    use crate::codec::h265::parser::Sps;
+    use crate::codec::h265::parser::TimeCode;
+    use crate::codec::h265::synthesizer::Synthesizer;
 
     const STREAM_BEAR: &[u8] = include_bytes!("test_data/bear.h265");
     const STREAM_BEAR_NUM_NALUS: usize = 35;
@@ -4124,7 +4967,7 @@ mod tests {
         bitstream: &[u8],
         nalu_type: NaluType,
         mut nskip: i32,
-    ) -> Option<Nalu<NaluHeader>> {
+    ) -> Option<Nalu<'_, NaluHeader>> {
         let mut cursor = Cursor::new(bitstream);
         while let Ok(nalu) = Nalu::<NaluHeader>::next(&mut cursor) {
             if nalu.header.type_ == nalu_type {
@@ -4613,6 +5456,11 @@ mod tests {
         assert_eq!(pps.log2_parallel_merge_level_minus2, 0);
         assert!(!pps.slice_segment_header_extension_present_flag);
         assert!(!pps.extension_present_flag);
+
+        // nuh_temporal_id_plus1 shall not be 0.
+        let mut pps_nalu = pps_nalu;
+        pps_nalu.header.nuh_temporal_id_plus1 = 0;
+        assert!(parser.parse_pps(&pps_nalu).is_err());
     }
 
     /// A custom test for slice header parsing with data manually extracted from
@@ -4740,4 +5588,192 @@ mod tests {
         // Subtract 2 bytes to account for the header size.
         assert_eq!(hdr.header_bit_size - 16, 80);
     }
+
+    #[test]
+    fn parse_sei_bear() {
+        let mut parser = Parser::default();
+        let mut cursor = Cursor::new(STREAM_BEAR);
+        for _ in 0..3 {
+            let nalu = Nalu::<NaluHeader>::next(&mut cursor).unwrap();
+            dispatch_parse_call(&mut parser, nalu).unwrap();
+        }
+
+        let sei_nalu = find_nalu_by_type(STREAM_BEAR, NaluType::PrefixSeiNut, 0).unwrap();
+        let messages = parser.parse_sei(&sei_nalu).unwrap();
+        assert_eq!(messages.len(), 1);
+        let SeiMessage::UserDataUnregistered(user_data) = &messages[0] else {
+            panic!("unexpected SEI message {:?}", messages[0]);
+        };
+        assert_eq!(
+            user_data.uuid_iso_iec_11578,
+            [
+                0x2c, 0xa2, 0xde, 0x09, 0xb5, 0x17, 0x47, 0xdb, 0xbb, 0x55, 0xa4, 0xfe, 0x7f, 0xc2,
+                0xfc, 0x4e
+            ]
+        );
+        assert_eq!(user_data.user_data_payload.len(), 810 - 16);
+        assert!(user_data.user_data_payload.starts_with(b"x265"));
+
+        let sei_nalu = find_nalu_by_type(STREAM_BEAR, NaluType::PrefixSeiNut, 1).unwrap();
+        assert_eq!(
+            parser.parse_sei(&sei_nalu).unwrap(),
+            vec![SeiMessage::RecoveryPoint(RecoveryPoint {
+                recovery_poc_cnt: 0,
+                exact_match_flag: true,
+                broken_link_flag: false,
+            })]
+        );
+
+        // Slices are not SEI NALUs.
+        let slice_nalu = find_nalu_by_type(STREAM_BEAR, NaluType::IdrWRadl, 0).unwrap();
+        assert!(parser.parse_sei(&slice_nalu).is_err());
+    }
+
+    #[test]
+    fn parse_sei_messages() {
+        // A prefix SEI NALU with HDR metadata, a time code and an unknown
+        // message, including emulation prevention bytes within the payloads.
+        const PREFIX_SEI: [u8; 57] = [
+            0x00, 0x00, 0x00, 0x01, 0x4e, 0x01, 0x89, 0x18, 0x33, 0xc2, 0x86, 0xc4, 0x1d, 0x4c,
+            0x0b, 0xb8, 0x84, 0xd0, 0x3e, 0x80, 0x3d, 0x13, 0x40, 0x42, 0x00, 0x98, 0x96, 0x80,
+            0x00, 0x00, 0x03, 0x00, 0x32, 0x90, 0x04, 0x03, 0xe8, 0x01, 0x90, 0x93, 0x01, 0x12,
+            0x88, 0x06, 0x60, 0x40, 0xc3, 0xc7, 0xa8, 0x9c, 0xc8, 0x03, 0x00, 0x00, 0x03, 0x01,
+            0x80,
+        ];
+
+        let mut cursor = Cursor::new(&PREFIX_SEI[..]);
+        let nalu = Nalu::<NaluHeader>::next(&mut cursor).unwrap();
+        let messages = Parser::default().parse_sei(&nalu).unwrap();
+
+        let mut clock_timestamp: [ClockTimestamp; 3] = Default::default();
+        clock_timestamp[0] = ClockTimestamp {
+            full_timestamp_flag: true,
+            n_frames: 24,
+            seconds_value: 30,
+            minutes_value: 15,
+            hours_value: 10,
+            time_offset_length: 4,
+            time_offset_value: -2,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            messages,
+            vec![
+                SeiMessage::MasteringDisplayColourVolume(MasteringDisplayColourVolume {
+                    display_primaries_x: [13250, 7500, 34000],
+                    display_primaries_y: [34500, 3000, 16000],
+                    white_point_x: 15635,
+                    white_point_y: 16450,
+                    max_display_mastering_luminance: 10000000,
+                    min_display_mastering_luminance: 50,
+                }),
+                SeiMessage::ContentLightLevelInfo(ContentLightLevelInfo {
+                    max_content_light_level: 1000,
+                    max_pic_average_light_level: 400,
+                }),
+                SeiMessage::AlternativeTransferCharacteristics(
+                    AlternativeTransferCharacteristics {
+                        preferred_transfer_characteristics: 18,
+                    }
+                ),
+                SeiMessage::TimeCode(TimeCode {
+                    num_clock_ts: 1,
+                    clock_timestamp_flag: [true, false, false],
+                    clock_timestamp,
+                }),
+                SeiMessage::Unknown {
+                    payload_type: 200,
+                    payload: vec![0x00, 0x00, 0x01],
+                },
+            ]
+        );
+
+        // A suffix SEI NALU with a MD5 decoded picture hash.
+        const SUFFIX_SEI: [u8; 59] = [
+            0x00, 0x00, 0x00, 0x01, 0x50, 0x01, 0x84, 0x31, 0x00, 0x00, 0x03, 0x01, 0x02, 0x03,
+            0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0a, 0x0b, 0x0c, 0x0d, 0x0e, 0x0f, 0x10, 0x11,
+            0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x1b, 0x1c, 0x1d, 0x1e, 0x1f,
+            0x20, 0x21, 0x22, 0x23, 0x24, 0x25, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x2b, 0x2c, 0x2d,
+            0x2e, 0x2f, 0x80,
+        ];
+
+        let mut cursor = Cursor::new(&SUFFIX_SEI[..]);
+        let nalu = Nalu::<NaluHeader>::next(&mut cursor).unwrap();
+        let messages = Parser::default().parse_sei(&nalu).unwrap();
+
+        let mut picture_md5 = [[0u8; 16]; 3];
+        for (i, byte) in picture_md5.iter_mut().flatten().enumerate() {
+            *byte = i as u8;
+        }
+
+        assert_eq!(
+            messages,
+            vec![SeiMessage::DecodedPictureHash(DecodedPictureHash {
+                hash_type: 0,
+                num_components: 3,
+                picture_md5,
+                ..Default::default()
+            })]
+        );
+    }
+
+    /// chroma_weight_l1_flag is only present when ChromaArrayType is not 0,
+    /// which is not the case for 4:4:4 streams coded as separate planes.
+    #[test]
+    fn parse_pred_weight_table_separate_colour_planes() {
+        const STREAM: &[u8] = include_bytes!("test_data/64x64-I-P-B-P.h265");
+        let mut parser = Parser::default();
+
+        let vps_nalu = find_nalu_by_type(STREAM, NaluType::VpsNut, 0).unwrap();
+        parser.parse_vps(&vps_nalu).unwrap();
+
+        let sps_nalu = find_nalu_by_type(STREAM, NaluType::SpsNut, 0).unwrap();
+        let mut sps = parser.parse_sps(&sps_nalu).unwrap().clone();
+        sps.chroma_format_idc = 3;
+        sps.separate_colour_plane_flag = true;
+        let mut buf = Vec::new();
+        Synthesizer::<'_, Sps, _>::synthesize(&sps, &mut buf, true).unwrap();
+        let sps_nalu = Nalu::next(&mut Cursor::new(buf.as_slice())).unwrap();
+        let sps = parser.parse_sps(&sps_nalu).unwrap().clone();
+        assert_eq!(sps.chroma_array_type, 0);
+
+        let pps_nalu = find_nalu_by_type(STREAM, NaluType::PpsNut, 0).unwrap();
+        let mut pps = parser.parse_pps(&pps_nalu).unwrap().clone();
+        pps.weighted_bipred_flag = true;
+        let mut buf = Vec::new();
+        Synthesizer::<'_, Pps, _>::synthesize(&pps, &mut buf, true).unwrap();
+        let pps_nalu = Nalu::next(&mut Cursor::new(buf.as_slice())).unwrap();
+        let pps = parser.parse_pps(&pps_nalu).unwrap().clone();
+
+        let nalu_header = find_nalu_by_type(STREAM, NaluType::TrailR, 0)
+            .unwrap()
+            .header;
+        let mut hdr = SliceHeader {
+            type_: SliceType::B,
+            qp_delta: 5,
+            ..Default::default()
+        };
+        hdr.pred_weight_table.luma_log2_weight_denom = 6;
+        hdr.pred_weight_table.luma_weight_l0_flag[0] = true;
+        hdr.pred_weight_table.delta_luma_weight_l0[0] = 3;
+        hdr.pred_weight_table.luma_weight_l1_flag[0] = true;
+        hdr.pred_weight_table.luma_offset_l1[0] = -2;
+
+        let mut buf = Vec::new();
+        Synthesizer::<'_, SliceHeader, _>::synthesize(
+            &nalu_header,
+            &hdr,
+            &sps,
+            &pps,
+            &mut buf,
+            true,
+        )
+        .unwrap();
+        let slice_nalu = Nalu::next(&mut Cursor::new(buf.as_slice())).unwrap();
+        let slice = parser.parse_slice_header(slice_nalu).unwrap();
+
+        assert_eq!(slice.header.pred_weight_table, hdr.pred_weight_table);
+        assert_eq!(slice.header.qp_delta, hdr.qp_delta);
+    }
 }
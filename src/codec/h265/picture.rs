@@ -36,9 +36,32 @@ pub struct PictureData {
     pub pic_latency_cnt: i32,
     pub needed_for_output: bool,
     pub short_term_ref_pic_set_size_bits: u32,
+
+    // Generated by the decoding process for unavailable reference pictures.
+    // Not for decode or output.
+    pub nonexisting: bool,
 }
 
 impl PictureData {
+    /// Instantiates a picture to stand for an unavailable reference picture.
+    ///
+    /// See 8.3.3.2 Generation of one unavailable picture.
+    pub fn new_unavailable(
+        pic_order_cnt_val: i32,
+        slice_pic_order_cnt_lsb: i32,
+        reference: Reference,
+    ) -> Self {
+        Self {
+            pic_order_cnt_val,
+            slice_pic_order_cnt_lsb,
+            pic_order_cnt_msb: pic_order_cnt_val - slice_pic_order_cnt_lsb,
+            pic_output_flag: false,
+            reference,
+            nonexisting: true,
+            ..Default::default()
+        }
+    }
+
     /// Instantiates a new `PictureData` from a slice.
     ///
     /// See 8.1.3 Decoding process for a coded picture with nuh_layer_id equal
@@ -140,6 +163,7 @@ impl PictureData {
             pic_latency_cnt: 0,
             needed_for_output: false,
             short_term_ref_pic_set_size_bits: hdr.st_rps_bits,
+            nonexisting: false,
         }
     }
 
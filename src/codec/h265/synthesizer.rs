@@ -0,0 +1,2265 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+use std::io::Write;
+
+use thiserror::Error;
+
+use crate::codec::h264::nalu_writer::NaluWriter;
+use crate::codec::h264::nalu_writer::NaluWriterError;
+use crate::codec::h265::parser::HrdParams;
+use crate::codec::h265::parser::NaluHeader;
+use crate::codec::h265::parser::NaluType;
+use crate::codec::h265::parser::Pps;
+use crate::codec::h265::parser::PredWeightTable;
+use crate::codec::h265::parser::ProfileTierLevel;
+use crate::codec::h265::parser::RefPicListModification;
+use crate::codec::h265::parser::ScalingLists;
+use crate::codec::h265::parser::ShortTermRefPicSet;
+use crate::codec::h265::parser::SliceHeader;
+use crate::codec::h265::parser::Sps;
+use crate::codec::h265::parser::SublayerHrdParameters;
+use crate::codec::h265::parser::Vps;
+use crate::codec::h265::parser::DEFAULT_SCALING_LIST_0;
+use crate::codec::h265::parser::DEFAULT_SCALING_LIST_1;
+use crate::codec::h265::parser::DEFAULT_SCALING_LIST_2;
+
+mod private {
+    pub trait NaluStruct {}
+}
+
+impl private::NaluStruct for Vps {}
+
+impl private::NaluStruct for Sps {}
+
+impl private::NaluStruct for Pps {}
+
+impl private::NaluStruct for SliceHeader {}
+
+#[derive(Error, Debug)]
+pub enum SynthesizerError {
+    #[error("tried to synthesize unsupported settings")]
+    Unsupported,
+    #[error(transparent)]
+    NaluWriter(#[from] NaluWriterError),
+}
+
+pub type SynthesizerResult<T> = Result<T, SynthesizerError>;
+
+/// A helper to output typed NALUs to [`std::io::Write`] using [`NaluWriter`].
+pub struct Synthesizer<'n, N: private::NaluStruct, W: Write> {
+    writer: NaluWriter<W>,
+    nalu: &'n N,
+}
+
+/// Extended Sample Aspect Ratio - H.265 Table E-1
+const EXTENDED_SAR: u32 = 255;
+
+/// Returns the number of bits of a `u(v)` element that indexes one of `count` entries, i.e.
+/// Ceil( Log2( `count` ) ).
+fn ceil_log2(count: u32) -> usize {
+    (32 - count.saturating_sub(1).leading_zeros()) as usize
+}
+
+/// Returns `true` if `profile_idc` or any of `compatibility_flag` signals one of `profiles`.
+pub(super) fn profile_in(
+    profile_idc: u8,
+    compatibility_flag: &[bool; 32],
+    profiles: &[u8],
+) -> bool {
+    profiles
+        .iter()
+        .any(|&p| profile_idc == p || compatibility_flag[usize::from(p)])
+}
+
+impl<N: private::NaluStruct, W: Write> Synthesizer<'_, N, W> {
+    fn u<T: Into<u32>>(&mut self, bits: usize, value: T) -> SynthesizerResult<()> {
+        self.writer.write_u(bits, value)?;
+        Ok(())
+    }
+
+    fn f<T: Into<u32>>(&mut self, bits: usize, value: T) -> SynthesizerResult<()> {
+        self.writer.write_f(bits, value)?;
+        Ok(())
+    }
+
+    fn ue<T: Into<u32>>(&mut self, value: T) -> SynthesizerResult<()> {
+        self.writer.write_ue(value)?;
+        Ok(())
+    }
+
+    fn se<T: Into<i32>>(&mut self, value: T) -> SynthesizerResult<()> {
+        self.writer.write_se(value)?;
+        Ok(())
+    }
+
+    /// Writes `bits` zero bits, e.g. for the `reserved_zero_Xbits` syntax elements.
+    fn reserved_zero_bits(&mut self, mut bits: usize) -> SynthesizerResult<()> {
+        while bits > 0 {
+            let n = std::cmp::min(bits, 32);
+            self.f(n, 0u32)?;
+            bits -= n;
+        }
+
+        Ok(())
+    }
+
+    fn nalu_header(&mut self, header: &NaluHeader) -> SynthesizerResult<()> {
+        // H.265 7.3.1.2, forbidden_zero_bit is always 0.
+        let type_ = header.type_ as u8;
+        let layer_id = header.nuh_layer_id & 0b111111;
+
+        self.writer.write_raw_header(&[
+            (type_ & 0b111111) << 1 | layer_id >> 5,
+            (layer_id & 0b11111) << 3 | (header.nuh_temporal_id_plus1 & 0b111),
+        ])?;
+
+        Ok(())
+    }
+
+    fn profile_tier_level(
+        &mut self,
+        ptl: &ProfileTierLevel,
+        profile_present_flag: bool,
+        max_sub_layers_minus1: u8,
+    ) -> SynthesizerResult<()> {
+        // H.265 7.3.3
+        if profile_present_flag {
+            self.u(2, ptl.general_profile_space)?;
+            self.u(1, ptl.general_tier_flag)?;
+            self.u(5, ptl.general_profile_idc)?;
+
+            for flag in ptl.general_profile_compatibility_flag {
+                self.u(1, flag)?;
+            }
+
+            self.u(1, ptl.general_progressive_source_flag)?;
+            self.u(1, ptl.general_interlaced_source_flag)?;
+            self.u(1, ptl.general_non_packed_constraint_flag)?;
+            self.u(1, ptl.general_frame_only_constraint_flag)?;
+
+            let idc = ptl.general_profile_idc;
+            let compat = &ptl.general_profile_compatibility_flag;
+            if profile_in(idc, compat, &[4, 5, 6, 7, 8, 9, 10, 11]) {
+                self.u(1, ptl.general_max_12bit_constraint_flag)?;
+                self.u(1, ptl.general_max_10bit_constraint_flag)?;
+                self.u(1, ptl.general_max_8bit_constraint_flag)?;
+                self.u(1, ptl.general_max_422chroma_constraint_flag)?;
+                self.u(1, ptl.general_max_420chroma_constraint_flag)?;
+                self.u(1, ptl.general_max_monochrome_constraint_flag)?;
+                self.u(1, ptl.general_intra_constraint_flag)?;
+                self.u(1, ptl.general_one_picture_only_constraint_flag)?;
+                self.u(1, ptl.general_lower_bit_rate_constraint_flag)?;
+
+                if profile_in(idc, compat, &[5, 9, 10, 11]) {
+                    self.u(1, ptl.general_max_14bit_constraint_flag)?;
+                    self.reserved_zero_bits(33)?;
+                } else {
+                    self.reserved_zero_bits(34)?;
+                }
+            } else if profile_in(idc, compat, &[2]) {
+                self.reserved_zero_bits(7)?;
+                self.u(1, ptl.general_one_picture_only_constraint_flag)?;
+                self.reserved_zero_bits(35)?;
+            } else {
+                self.reserved_zero_bits(43)?;
+            }
+
+            if profile_in(idc, compat, &[1, 2, 3, 4, 5, 9, 11]) {
+                self.u(1, ptl.general_inbld_flag)?;
+            } else {
+                self.reserved_zero_bits(1)?;
+            }
+        }
+
+        self.u(8, ptl.general_level_idc as u8)?;
+
+        let max_sub_layers_minus1 = usize::from(max_sub_layers_minus1);
+        for i in 0..max_sub_layers_minus1 {
+            self.u(1, ptl.sub_layer_profile_present_flag[i])?;
+            self.u(1, ptl.sub_layer_level_present_flag[i])?;
+        }
+
+        if max_sub_layers_minus1 > 0 {
+            for _ in max_sub_layers_minus1..8 {
+                self.reserved_zero_bits(2)?;
+            }
+        }
+
+        for i in 0..max_sub_layers_minus1 {
+            if ptl.sub_layer_profile_present_flag[i] {
+                self.u(2, ptl.sub_layer_profile_space[i])?;
+                self.u(1, ptl.sub_layer_tier_flag[i])?;
+                self.u(5, ptl.sub_layer_profile_idc[i])?;
+
+                for flag in ptl.sub_layer_profile_compatibility_flag[i] {
+                    self.u(1, flag)?;
+                }
+
+                self.u(1, ptl.sub_layer_progressive_source_flag[i])?;
+                self.u(1, ptl.sub_layer_interlaced_source_flag[i])?;
+                self.u(1, ptl.sub_layer_non_packed_constraint_flag[i])?;
+                self.u(1, ptl.sub_layer_frame_only_constraint_flag[i])?;
+
+                let idc = ptl.sub_layer_profile_idc[i];
+                let compat = &ptl.sub_layer_profile_compatibility_flag[i];
+                if profile_in(idc, compat, &[4, 5, 6, 7, 8, 9, 10, 11]) {
+                    self.u(1, ptl.sub_layer_max_12bit_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_max_10bit_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_max_8bit_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_max_422chroma_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_max_420chroma_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_max_monochrome_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_intra_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_one_picture_only_constraint_flag[i])?;
+                    self.u(1, ptl.sub_layer_lower_bit_rate_constraint_flag[i])?;
+
+                    if profile_in(idc, compat, &[5, 9, 10, 11]) {
+                        self.u(1, ptl.sub_layer_max_14bit_constraint_flag[i])?;
+                        self.reserved_zero_bits(33)?;
+                    } else {
+                        self.reserved_zero_bits(34)?;
+                    }
+                } else if profile_in(idc, compat, &[2]) {
+                    self.reserved_zero_bits(7)?;
+                    self.u(1, ptl.sub_layer_one_picture_only_constraint_flag[i])?;
+                    self.reserved_zero_bits(35)?;
+                } else {
+                    self.reserved_zero_bits(43)?;
+                }
+
+                if profile_in(idc, compat, &[1, 2, 3, 4, 5, 9, 11]) {
+                    self.u(1, ptl.sub_layer_inbld_flag[i])?;
+                } else {
+                    self.reserved_zero_bits(1)?;
+                }
+            }
+
+            if ptl.sub_layer_level_present_flag[i] {
+                self.u(8, ptl.sub_layer_level_idc[i] as u8)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the scaling list of `size_id` and `matrix_id` along with its DC coefficient, if
+    /// the list has one.
+    fn scaling_list_entry(
+        sl: &ScalingLists,
+        size_id: usize,
+        matrix_id: usize,
+    ) -> (&[u8], Option<i16>) {
+        match size_id {
+            0 => (&sl.scaling_list_4x4[matrix_id][..], None),
+            1 => (&sl.scaling_list_8x8[matrix_id][..], None),
+            2 => (
+                &sl.scaling_list_16x16[matrix_id][..],
+                Some(sl.scaling_list_dc_coef_minus8_16x16[matrix_id]),
+            ),
+            3 => (
+                &sl.scaling_list_32x32[matrix_id][..],
+                Some(sl.scaling_list_dc_coef_minus8_32x32[matrix_id]),
+            ),
+            _ => unreachable!(),
+        }
+    }
+
+    fn default_scaling_list(size_id: usize, matrix_id: usize) -> &'static [u8] {
+        // H.265 Table 7-5 and Table 7-6
+        match (size_id, matrix_id) {
+            (0, _) => &DEFAULT_SCALING_LIST_0[..],
+            (_, 0..=2) => &DEFAULT_SCALING_LIST_1[..],
+            _ => &DEFAULT_SCALING_LIST_2[..],
+        }
+    }
+
+    fn scaling_list_data(&mut self, sl: &ScalingLists) -> SynthesizerResult<()> {
+        // H.265 7.3.4
+        for size_id in 0..4 {
+            let step = if size_id == 3 { 3 } else { 1 };
+
+            for matrix_id in (0..6).step_by(step) {
+                let (list, dc_coef_minus8) = Self::scaling_list_entry(sl, size_id, matrix_id);
+
+                // Lists that are equal to the default one, or to a previous list of the same
+                // size, are predicted instead of coded explicitly.
+                let pred_matrix_id_delta = if list == Self::default_scaling_list(size_id, matrix_id)
+                    && dc_coef_minus8.is_none_or(|dc| dc == 8)
+                {
+                    Some(0)
+                } else {
+                    (0..matrix_id)
+                        .step_by(step)
+                        .rev()
+                        .find(|&ref_matrix_id| {
+                            Self::scaling_list_entry(sl, size_id, ref_matrix_id)
+                                == (list, dc_coef_minus8)
+                        })
+                        .map(|ref_matrix_id| ((matrix_id - ref_matrix_id) / step) as u32)
+                };
+
+                if let Some(delta) = pred_matrix_id_delta {
+                    self.u(1, /* scaling_list_pred_mode_flag */ false)?;
+                    self.ue(/* scaling_list_pred_matrix_id_delta */ delta)?;
+                    continue;
+                }
+
+                self.u(1, /* scaling_list_pred_mode_flag */ true)?;
+
+                let mut next_coef = 8i32;
+                if let Some(dc_coef_minus8) = dc_coef_minus8 {
+                    self.se(dc_coef_minus8)?;
+                    next_coef = i32::from(dc_coef_minus8) + 8;
+                }
+
+                for &coef in list {
+                    // The decoder accumulates the deltas modulo 256, so pick the delta within
+                    // -128..=127.
+                    let mut delta = i32::from(coef) - next_coef;
+                    if delta > 127 {
+                        delta -= 256;
+                    } else if delta < -128 {
+                        delta += 256;
+                    }
+
+                    self.se(/* scaling_list_delta_coef */ delta)?;
+                    next_coef = i32::from(coef);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn sub_layer_hrd_parameters(
+        &mut self,
+        hrd: &SublayerHrdParameters,
+        cpb_cnt: u32,
+        sub_pic_hrd_params_present_flag: bool,
+    ) -> SynthesizerResult<()> {
+        // H.265 E.2.3
+        for i in 0..cpb_cnt as usize {
+            self.ue(hrd.bit_rate_value_minus1[i])?;
+            self.ue(hrd.cpb_size_value_minus1[i])?;
+            if sub_pic_hrd_params_present_flag {
+                self.ue(hrd.cpb_size_du_value_minus1[i])?;
+                self.ue(hrd.bit_rate_du_value_minus1[i])?;
+            }
+
+            self.u(1, hrd.cbr_flag[i])?;
+        }
+
+        Ok(())
+    }
+
+    fn hrd_parameters(
+        &mut self,
+        hrd: &HrdParams,
+        common_inf_present_flag: bool,
+        max_sub_layers_minus1: u8,
+    ) -> SynthesizerResult<()> {
+        // H.265 E.2.2
+        if common_inf_present_flag {
+            self.u(1, hrd.nal_hrd_parameters_present_flag)?;
+            self.u(1, hrd.vcl_hrd_parameters_present_flag)?;
+
+            if hrd.nal_hrd_parameters_present_flag || hrd.vcl_hrd_parameters_present_flag {
+                self.u(1, hrd.sub_pic_hrd_params_present_flag)?;
+                if hrd.sub_pic_hrd_params_present_flag {
+                    self.u(8, hrd.tick_divisor_minus2)?;
+                    self.u(5, hrd.du_cpb_removal_delay_increment_length_minus1)?;
+                    self.u(1, hrd.sub_pic_cpb_params_in_pic_timing_sei_flag)?;
+                    self.u(5, hrd.dpb_output_delay_du_length_minus1)?;
+                }
+
+                self.u(4, hrd.bit_rate_scale)?;
+                self.u(4, hrd.cpb_size_scale)?;
+                if hrd.sub_pic_hrd_params_present_flag {
+                    self.u(4, hrd.cpb_size_du_scale)?;
+                }
+
+                self.u(5, hrd.initial_cpb_removal_delay_length_minus1)?;
+                self.u(5, hrd.au_cpb_removal_delay_length_minus1)?;
+                self.u(5, hrd.dpb_output_delay_length_minus1)?;
+            }
+        }
+
+        for i in 0..=usize::from(max_sub_layers_minus1) {
+            self.u(1, hrd.fixed_pic_rate_general_flag[i])?;
+            if !hrd.fixed_pic_rate_general_flag[i] {
+                self.u(1, hrd.fixed_pic_rate_within_cvs_flag[i])?;
+            }
+
+            if hrd.fixed_pic_rate_general_flag[i] || hrd.fixed_pic_rate_within_cvs_flag[i] {
+                self.ue(hrd.elemental_duration_in_tc_minus1[i])?;
+            } else {
+                self.u(1, hrd.low_delay_hrd_flag[i])?;
+            }
+
+            if !hrd.low_delay_hrd_flag[i] {
+                self.ue(hrd.cpb_cnt_minus1[i])?;
+            }
+
+            if hrd.nal_hrd_parameters_present_flag {
+                self.sub_layer_hrd_parameters(
+                    &hrd.nal_hrd[i],
+                    hrd.cpb_cnt_minus1[i] + 1,
+                    hrd.sub_pic_hrd_params_present_flag,
+                )?;
+            }
+
+            if hrd.vcl_hrd_parameters_present_flag {
+                self.sub_layer_hrd_parameters(
+                    &hrd.vcl_hrd[i],
+                    hrd.cpb_cnt_minus1[i] + 1,
+                    hrd.sub_pic_hrd_params_present_flag,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn st_ref_pic_set(
+        &mut self,
+        sps: &Sps,
+        st: &ShortTermRefPicSet,
+        st_rps_idx: u8,
+    ) -> SynthesizerResult<()> {
+        // H.265 7.3.7
+        if st_rps_idx != 0 {
+            self.u(1, st.inter_ref_pic_set_prediction_flag)?;
+        }
+
+        if !st.inter_ref_pic_set_prediction_flag {
+            self.ue(st.num_negative_pics)?;
+            self.ue(st.num_positive_pics)?;
+
+            let mut prev = 0;
+            for i in 0..usize::from(st.num_negative_pics) {
+                self.ue(
+                    /* delta_poc_s0_minus1 */ (prev - st.delta_poc_s0[i] - 1) as u32,
+                )?;
+                self.u(1, st.used_by_curr_pic_s0[i])?;
+                prev = st.delta_poc_s0[i];
+            }
+
+            let mut prev = 0;
+            for i in 0..usize::from(st.num_positive_pics) {
+                self.ue(
+                    /* delta_poc_s1_minus1 */ (st.delta_poc_s1[i] - prev - 1) as u32,
+                )?;
+                self.u(1, st.used_by_curr_pic_s1[i])?;
+                prev = st.delta_poc_s1[i];
+            }
+
+            return Ok(());
+        }
+
+        if st_rps_idx == sps.num_short_term_ref_pic_sets {
+            self.ue(st.delta_idx_minus1)?;
+        }
+
+        self.u(1, st.delta_rps_sign)?;
+        self.ue(st.abs_delta_rps_minus1)?;
+
+        let ref_rps_idx = st_rps_idx
+            .checked_sub(st.delta_idx_minus1 + 1)
+            .ok_or(SynthesizerError::Unsupported)?;
+        let ref_st = sps
+            .short_term_ref_pic_set
+            .get(usize::from(ref_rps_idx))
+            .ok_or(SynthesizerError::Unsupported)?;
+        let delta_rps = (1 - 2 * st.delta_rps_sign as i32) * (st.abs_delta_rps_minus1 as i32 + 1);
+
+        let negative = &st.delta_poc_s0[..usize::from(st.num_negative_pics)];
+        let positive = &st.delta_poc_s1[..usize::from(st.num_positive_pics)];
+
+        // Only the derived set is kept, so find out which pictures of the reference set (and the
+        // reference picture itself, at index NumDeltaPocs) were carried over by (7-61) and (7-62).
+        let mut num_used = 0;
+        for j in 0..=ref_st.num_delta_pocs as usize {
+            let num_negative = usize::from(ref_st.num_negative_pics);
+            let d_poc = if j < num_negative {
+                ref_st.delta_poc_s0[j] + delta_rps
+            } else if j < ref_st.num_delta_pocs as usize {
+                ref_st.delta_poc_s1[j - num_negative] + delta_rps
+            } else {
+                delta_rps
+            };
+
+            let used_by_curr_pic_flag = if d_poc < 0 {
+                negative
+                    .iter()
+                    .position(|&poc| poc == d_poc)
+                    .map(|i| st.used_by_curr_pic_s0[i])
+            } else if d_poc > 0 {
+                positive
+                    .iter()
+                    .position(|&poc| poc == d_poc)
+                    .map(|i| st.used_by_curr_pic_s1[i])
+            } else {
+                None
+            };
+
+            match used_by_curr_pic_flag {
+                Some(used_by_curr_pic_flag) => {
+                    self.u(1, used_by_curr_pic_flag)?;
+                    if !used_by_curr_pic_flag {
+                        self.u(1, /* use_delta_flag */ true)?;
+                    }
+
+                    num_used += 1;
+                }
+                None => {
+                    self.u(1, /* used_by_curr_pic_flag */ false)?;
+                    self.u(1, /* use_delta_flag */ false)?;
+                }
+            }
+        }
+
+        // The set cannot be expressed as a prediction from the reference set.
+        if num_used != st.num_delta_pocs {
+            return Err(SynthesizerError::Unsupported);
+        }
+
+        Ok(())
+    }
+
+    fn rbsp_trailing_bits(&mut self) -> SynthesizerResult<()> {
+        self.f(1, 1u32)?;
+
+        while !self.writer.aligned() {
+            self.f(1, 0u32)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'n, W: Write> Synthesizer<'n, Vps, W> {
+    pub fn synthesize(vps: &'n Vps, writer: W, ep_enabled: bool) -> SynthesizerResult<()> {
+        let mut s = Self {
+            writer: NaluWriter::<W>::new(writer, ep_enabled),
+            nalu: vps,
+        };
+
+        s.nalu_header(&NaluHeader {
+            type_: NaluType::VpsNut,
+            nuh_layer_id: 0,
+            nuh_temporal_id_plus1: 1,
+        })?;
+        s.video_parameter_set_rbsp()?;
+        s.rbsp_trailing_bits()
+    }
+
+    fn video_parameter_set_rbsp(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.1
+        let vps = self.nalu;
+
+        self.u(4, vps.video_parameter_set_id)?;
+        self.u(1, vps.base_layer_internal_flag)?;
+        self.u(1, vps.base_layer_available_flag)?;
+        self.u(6, vps.max_layers_minus1)?;
+        self.u(3, vps.max_sub_layers_minus1)?;
+        self.u(1, vps.temporal_id_nesting_flag)?;
+        self.u(16, /* vps_reserved_0xffff_16bits */ 0xffffu32)?;
+
+        self.profile_tier_level(&vps.profile_tier_level, true, vps.max_sub_layers_minus1)?;
+
+        self.u(1, vps.sub_layer_ordering_info_present_flag)?;
+        let start = if vps.sub_layer_ordering_info_present_flag {
+            0
+        } else {
+            vps.max_sub_layers_minus1
+        };
+
+        for i in usize::from(start)..=usize::from(vps.max_sub_layers_minus1) {
+            self.ue(vps.max_dec_pic_buffering_minus1[i])?;
+            self.ue(vps.max_num_reorder_pics[i])?;
+            self.ue(vps.max_latency_increase_plus1[i])?;
+        }
+
+        self.u(6, vps.max_layer_id)?;
+        self.ue(vps.num_layer_sets_minus1)?;
+
+        // layer_id_included_flag is not kept by the parser.
+        if vps.num_layer_sets_minus1 > 0 {
+            return Err(SynthesizerError::Unsupported);
+        }
+
+        self.u(1, vps.timing_info_present_flag)?;
+        if vps.timing_info_present_flag {
+            self.u(32, vps.num_units_in_tick)?;
+            self.u(32, vps.time_scale)?;
+            self.u(1, vps.poc_proportional_to_timing_flag)?;
+            if vps.poc_proportional_to_timing_flag {
+                self.ue(vps.num_ticks_poc_diff_one_minus1)?;
+            }
+
+            self.ue(vps.num_hrd_parameters)?;
+            for i in 0..vps.num_hrd_parameters as usize {
+                self.ue(vps.hrd_layer_set_idx[i])?;
+
+                // cprms_present_flag[ 0 ] is inferred to be equal to 1.
+                let cprms_present_flag = vps.cprms_present_flag.get(i).copied().unwrap_or(true);
+                if i > 0 {
+                    self.u(1, cprms_present_flag)?;
+                }
+
+                self.hrd_parameters(
+                    &vps.hrd_parameters[i],
+                    cprms_present_flag,
+                    vps.max_sub_layers_minus1,
+                )?;
+            }
+        }
+
+        // vps_extension( ) is not kept by the parser.
+        if vps.extension_flag {
+            return Err(SynthesizerError::Unsupported);
+        }
+
+        self.u(1, vps.extension_flag)?;
+
+        Ok(())
+    }
+}
+
+impl<'n, W: Write> Synthesizer<'n, Sps, W> {
+    pub fn synthesize(sps: &'n Sps, writer: W, ep_enabled: bool) -> SynthesizerResult<()> {
+        let mut s = Self {
+            writer: NaluWriter::<W>::new(writer, ep_enabled),
+            nalu: sps,
+        };
+
+        s.nalu_header(&NaluHeader {
+            type_: NaluType::SpsNut,
+            nuh_layer_id: 0,
+            nuh_temporal_id_plus1: 1,
+        })?;
+        s.seq_parameter_set_rbsp()?;
+        s.rbsp_trailing_bits()
+    }
+
+    fn vui_parameters(&mut self) -> SynthesizerResult<()> {
+        // H.265 E.2.1
+        let sps = self.nalu;
+        let vui = &sps.vui_parameters;
+
+        self.u(1, vui.aspect_ratio_info_present_flag)?;
+        if vui.aspect_ratio_info_present_flag {
+            self.u(8, vui.aspect_ratio_idc)?;
+            if vui.aspect_ratio_idc == EXTENDED_SAR {
+                self.u(16, vui.sar_width)?;
+                self.u(16, vui.sar_height)?;
+            }
+        }
+
+        self.u(1, vui.overscan_info_present_flag)?;
+        if vui.overscan_info_present_flag {
+            self.u(1, vui.overscan_appropriate_flag)?;
+        }
+
+        self.u(1, vui.video_signal_type_present_flag)?;
+        if vui.video_signal_type_present_flag {
+            self.u(3, vui.video_format)?;
+            self.u(1, vui.video_full_range_flag)?;
+            self.u(1, vui.colour_description_present_flag)?;
+            if vui.colour_description_present_flag {
+                self.u(8, vui.colour_primaries)?;
+                self.u(8, vui.transfer_characteristics)?;
+                self.u(8, vui.matrix_coeffs)?;
+            }
+        }
+
+        self.u(1, vui.chroma_loc_info_present_flag)?;
+        if vui.chroma_loc_info_present_flag {
+            self.ue(vui.chroma_sample_loc_type_top_field)?;
+            self.ue(vui.chroma_sample_loc_type_bottom_field)?;
+        }
+
+        self.u(1, vui.neutral_chroma_indication_flag)?;
+        self.u(1, vui.field_seq_flag)?;
+        self.u(1, vui.frame_field_info_present_flag)?;
+
+        self.u(1, vui.default_display_window_flag)?;
+        if vui.default_display_window_flag {
+            self.ue(vui.def_disp_win_left_offset)?;
+            self.ue(vui.def_disp_win_right_offset)?;
+            self.ue(vui.def_disp_win_top_offset)?;
+            self.ue(vui.def_disp_win_bottom_offset)?;
+        }
+
+        self.u(1, vui.timing_info_present_flag)?;
+        if vui.timing_info_present_flag {
+            self.u(32, vui.num_units_in_tick)?;
+            self.u(32, vui.time_scale)?;
+            self.u(1, vui.poc_proportional_to_timing_flag)?;
+            if vui.poc_proportional_to_timing_flag {
+                self.ue(vui.num_ticks_poc_diff_one_minus1)?;
+            }
+
+            self.u(1, vui.hrd_parameters_present_flag)?;
+            if vui.hrd_parameters_present_flag {
+                self.hrd_parameters(&vui.hrd, true, sps.max_sub_layers_minus1)?;
+            }
+        }
+
+        self.u(1, vui.bitstream_restriction_flag)?;
+        if vui.bitstream_restriction_flag {
+            self.u(1, vui.tiles_fixed_structure_flag)?;
+            self.u(1, vui.motion_vectors_over_pic_boundaries_flag)?;
+            self.u(1, vui.restricted_ref_pic_lists_flag)?;
+            self.ue(vui.min_spatial_segmentation_idc)?;
+            self.ue(vui.max_bytes_per_pic_denom)?;
+            self.ue(vui.max_bits_per_min_cu_denom)?;
+            self.ue(vui.log2_max_mv_length_horizontal)?;
+            self.ue(vui.log2_max_mv_length_vertical)?;
+        }
+
+        Ok(())
+    }
+
+    fn sps_range_extension(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.2.2
+        let ext = &self.nalu.range_extension;
+
+        self.u(1, ext.transform_skip_rotation_enabled_flag)?;
+        self.u(1, ext.transform_skip_context_enabled_flag)?;
+        self.u(1, ext.implicit_rdpcm_enabled_flag)?;
+        self.u(1, ext.explicit_rdpcm_enabled_flag)?;
+        self.u(1, ext.extended_precision_processing_flag)?;
+        self.u(1, ext.intra_smoothing_disabled_flag)?;
+        self.u(1, ext.high_precision_offsets_enabled_flag)?;
+        self.u(1, ext.persistent_rice_adaptation_enabled_flag)?;
+        self.u(1, ext.cabac_bypass_alignment_enabled_flag)?;
+
+        Ok(())
+    }
+
+    fn sps_scc_extension(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.2.3
+        let sps = self.nalu;
+        let scc = &sps.scc_extension;
+
+        self.u(1, scc.curr_pic_ref_enabled_flag)?;
+        self.u(1, scc.palette_mode_enabled_flag)?;
+        if scc.palette_mode_enabled_flag {
+            self.ue(scc.palette_max_size)?;
+            self.ue(scc.delta_palette_max_predictor_size)?;
+            self.u(1, scc.palette_predictor_initializers_present_flag)?;
+            if scc.palette_predictor_initializers_present_flag {
+                self.ue(scc.num_palette_predictor_initializer_minus1)?;
+
+                let num_comps = if sps.chroma_format_idc == 0 { 1 } else { 3 };
+                for comp in 0..num_comps {
+                    let num_bits = if comp == 0 {
+                        sps.bit_depth_luma_minus8 + 8
+                    } else {
+                        sps.bit_depth_chroma_minus8 + 8
+                    };
+
+                    for i in 0..=usize::from(scc.num_palette_predictor_initializer_minus1) {
+                        self.u(
+                            usize::from(num_bits),
+                            scc.palette_predictor_initializer[comp][i],
+                        )?;
+                    }
+                }
+            }
+        }
+
+        self.u(2, scc.motion_vector_resolution_control_idc)?;
+        self.u(1, scc.intra_boundary_filtering_disabled_flag)?;
+
+        Ok(())
+    }
+
+    fn seq_parameter_set_rbsp(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.2.1
+        let sps = self.nalu;
+
+        self.u(4, sps.video_parameter_set_id)?;
+        self.u(3, sps.max_sub_layers_minus1)?;
+        self.u(1, sps.temporal_id_nesting_flag)?;
+
+        self.profile_tier_level(&sps.profile_tier_level, true, sps.max_sub_layers_minus1)?;
+
+        self.ue(sps.seq_parameter_set_id)?;
+        self.ue(sps.chroma_format_idc)?;
+        if sps.chroma_format_idc == 3 {
+            self.u(1, sps.separate_colour_plane_flag)?;
+        }
+
+        self.ue(sps.pic_width_in_luma_samples)?;
+        self.ue(sps.pic_height_in_luma_samples)?;
+
+        self.u(1, sps.conformance_window_flag)?;
+        if sps.conformance_window_flag {
+            self.ue(sps.conf_win_left_offset)?;
+            self.ue(sps.conf_win_right_offset)?;
+            self.ue(sps.conf_win_top_offset)?;
+            self.ue(sps.conf_win_bottom_offset)?;
+        }
+
+        self.ue(sps.bit_depth_luma_minus8)?;
+        self.ue(sps.bit_depth_chroma_minus8)?;
+        self.ue(sps.log2_max_pic_order_cnt_lsb_minus4)?;
+
+        self.u(1, sps.sub_layer_ordering_info_present_flag)?;
+        let start = if sps.sub_layer_ordering_info_present_flag {
+            0
+        } else {
+            sps.max_sub_layers_minus1
+        };
+
+        for i in usize::from(start)..=usize::from(sps.max_sub_layers_minus1) {
+            self.ue(sps.max_dec_pic_buffering_minus1[i])?;
+            self.ue(sps.max_num_reorder_pics[i])?;
+            self.ue(sps.max_latency_increase_plus1[i])?;
+        }
+
+        self.ue(sps.log2_min_luma_coding_block_size_minus3)?;
+        self.ue(sps.log2_diff_max_min_luma_coding_block_size)?;
+        self.ue(sps.log2_min_luma_transform_block_size_minus2)?;
+        self.ue(sps.log2_diff_max_min_luma_transform_block_size)?;
+        self.ue(sps.max_transform_hierarchy_depth_inter)?;
+        self.ue(sps.max_transform_hierarchy_depth_intra)?;
+
+        self.u(1, sps.scaling_list_enabled_flag)?;
+        if sps.scaling_list_enabled_flag {
+            self.u(1, sps.scaling_list_data_present_flag)?;
+            if sps.scaling_list_data_present_flag {
+                self.scaling_list_data(&sps.scaling_list)?;
+            }
+        }
+
+        self.u(1, sps.amp_enabled_flag)?;
+        self.u(1, sps.sample_adaptive_offset_enabled_flag)?;
+
+        self.u(1, sps.pcm_enabled_flag)?;
+        if sps.pcm_enabled_flag {
+            self.u(4, sps.pcm_sample_bit_depth_luma_minus1)?;
+            self.u(4, sps.pcm_sample_bit_depth_chroma_minus1)?;
+            self.ue(sps.log2_min_pcm_luma_coding_block_size_minus3)?;
+            self.ue(sps.log2_diff_max_min_pcm_luma_coding_block_size)?;
+            self.u(1, sps.pcm_loop_filter_disabled_flag)?;
+        }
+
+        self.ue(sps.num_short_term_ref_pic_sets)?;
+        for i in 0..sps.num_short_term_ref_pic_sets {
+            let st = sps
+                .short_term_ref_pic_set
+                .get(usize::from(i))
+                .ok_or(SynthesizerError::Unsupported)?;
+            self.st_ref_pic_set(sps, st, i)?;
+        }
+
+        self.u(1, sps.long_term_ref_pics_present_flag)?;
+        if sps.long_term_ref_pics_present_flag {
+            self.ue(sps.num_long_term_ref_pics_sps)?;
+            for i in 0..usize::from(sps.num_long_term_ref_pics_sps) {
+                self.u(
+                    usize::from(sps.log2_max_pic_order_cnt_lsb_minus4) + 4,
+                    sps.lt_ref_pic_poc_lsb_sps[i],
+                )?;
+                self.u(1, sps.used_by_curr_pic_lt_sps_flag[i])?;
+            }
+        }
+
+        self.u(1, sps.temporal_mvp_enabled_flag)?;
+        self.u(1, sps.strong_intra_smoothing_enabled_flag)?;
+
+        self.u(1, sps.vui_parameters_present_flag)?;
+        if sps.vui_parameters_present_flag {
+            self.vui_parameters()?;
+        }
+
+        self.u(1, sps.extension_present_flag)?;
+        if sps.extension_present_flag {
+            self.u(1, sps.range_extension_flag)?;
+            self.u(1, /* sps_multilayer_extension_flag */ false)?;
+            self.u(1, /* sps_3d_extension_flag */ false)?;
+            self.u(1, sps.scc_extension_flag)?;
+            self.u(4, /* sps_extension_4bits */ 0u32)?;
+
+            if sps.range_extension_flag {
+                self.sps_range_extension()?;
+            }
+
+            if sps.scc_extension_flag {
+                self.sps_scc_extension()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'n, W: Write> Synthesizer<'n, Pps, W> {
+    pub fn synthesize(pps: &'n Pps, writer: W, ep_enabled: bool) -> SynthesizerResult<()> {
+        let mut s = Self {
+            writer: NaluWriter::<W>::new(writer, ep_enabled),
+            nalu: pps,
+        };
+
+        s.nalu_header(&NaluHeader {
+            type_: NaluType::PpsNut,
+            nuh_layer_id: 0,
+            nuh_temporal_id_plus1: pps.temporal_id + 1,
+        })?;
+        s.pic_parameter_set_rbsp()?;
+        s.rbsp_trailing_bits()
+    }
+
+    fn pps_range_extension(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.3.2
+        let pps = self.nalu;
+        let ext = &pps.range_extension;
+
+        if pps.transform_skip_enabled_flag {
+            self.ue(ext.log2_max_transform_skip_block_size_minus2)?;
+        }
+
+        self.u(1, ext.cross_component_prediction_enabled_flag)?;
+        self.u(1, ext.chroma_qp_offset_list_enabled_flag)?;
+        if ext.chroma_qp_offset_list_enabled_flag {
+            self.ue(ext.diff_cu_chroma_qp_offset_depth)?;
+            self.ue(ext.chroma_qp_offset_list_len_minus1)?;
+            for i in 0..=ext.chroma_qp_offset_list_len_minus1 as usize {
+                self.se(ext.cb_qp_offset_list[i])?;
+                self.se(ext.cr_qp_offset_list[i])?;
+            }
+        }
+
+        self.ue(ext.log2_sao_offset_scale_luma)?;
+        self.ue(ext.log2_sao_offset_scale_chroma)?;
+
+        Ok(())
+    }
+
+    fn pps_scc_extension(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.3.3
+        let scc = &self.nalu.scc_extension;
+
+        self.u(1, scc.curr_pic_ref_enabled_flag)?;
+        self.u(1, scc.residual_adaptive_colour_transform_enabled_flag)?;
+        if scc.residual_adaptive_colour_transform_enabled_flag {
+            self.u(1, scc.slice_act_qp_offsets_present_flag)?;
+            self.se(scc.act_y_qp_offset_plus5)?;
+            self.se(scc.act_cb_qp_offset_plus5)?;
+            self.se(scc.act_cr_qp_offset_plus3)?;
+        }
+
+        self.u(1, scc.palette_predictor_initializers_present_flag)?;
+        if scc.palette_predictor_initializers_present_flag {
+            self.ue(scc.num_palette_predictor_initializers)?;
+            if scc.num_palette_predictor_initializers > 0 {
+                self.u(1, scc.monochrome_palette_flag)?;
+                self.ue(scc.luma_bit_depth_entry_minus8)?;
+                if !scc.monochrome_palette_flag {
+                    self.ue(scc.chroma_bit_depth_entry_minus8)?;
+                }
+
+                let num_comps = if scc.monochrome_palette_flag { 1 } else { 3 };
+                for comp in 0..num_comps {
+                    let num_bits = if comp == 0 {
+                        scc.luma_bit_depth_entry_minus8 + 8
+                    } else {
+                        scc.chroma_bit_depth_entry_minus8 + 8
+                    };
+
+                    for i in 0..usize::from(scc.num_palette_predictor_initializers) {
+                        self.u(
+                            usize::from(num_bits),
+                            scc.palette_predictor_initializer[comp][i],
+                        )?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pic_parameter_set_rbsp(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.3.1
+        let pps = self.nalu;
+
+        self.ue(pps.pic_parameter_set_id)?;
+        self.ue(pps.seq_parameter_set_id)?;
+        self.u(1, pps.dependent_slice_segments_enabled_flag)?;
+        self.u(1, pps.output_flag_present_flag)?;
+        self.u(3, pps.num_extra_slice_header_bits)?;
+        self.u(1, pps.sign_data_hiding_enabled_flag)?;
+        self.u(1, pps.cabac_init_present_flag)?;
+        self.ue(pps.num_ref_idx_l0_default_active_minus1)?;
+        self.ue(pps.num_ref_idx_l1_default_active_minus1)?;
+        self.se(pps.init_qp_minus26)?;
+        self.u(1, pps.constrained_intra_pred_flag)?;
+        self.u(1, pps.transform_skip_enabled_flag)?;
+
+        self.u(1, pps.cu_qp_delta_enabled_flag)?;
+        if pps.cu_qp_delta_enabled_flag {
+            self.ue(pps.diff_cu_qp_delta_depth)?;
+        }
+
+        self.se(pps.cb_qp_offset)?;
+        self.se(pps.cr_qp_offset)?;
+        self.u(1, pps.slice_chroma_qp_offsets_present_flag)?;
+        self.u(1, pps.weighted_pred_flag)?;
+        self.u(1, pps.weighted_bipred_flag)?;
+        self.u(1, pps.transquant_bypass_enabled_flag)?;
+        self.u(1, pps.tiles_enabled_flag)?;
+        self.u(1, pps.entropy_coding_sync_enabled_flag)?;
+
+        if pps.tiles_enabled_flag {
+            self.ue(pps.num_tile_columns_minus1)?;
+            self.ue(pps.num_tile_rows_minus1)?;
+
+            self.u(1, pps.uniform_spacing_flag)?;
+            if !pps.uniform_spacing_flag {
+                for i in 0..usize::from(pps.num_tile_columns_minus1) {
+                    self.ue(pps.column_width_minus1[i])?;
+                }
+
+                for i in 0..usize::from(pps.num_tile_rows_minus1) {
+                    self.ue(pps.row_height_minus1[i])?;
+                }
+            }
+
+            self.u(1, pps.loop_filter_across_tiles_enabled_flag)?;
+        }
+
+        self.u(1, pps.loop_filter_across_slices_enabled_flag)?;
+
+        self.u(1, pps.deblocking_filter_control_present_flag)?;
+        if pps.deblocking_filter_control_present_flag {
+            self.u(1, pps.deblocking_filter_override_enabled_flag)?;
+            self.u(1, pps.deblocking_filter_disabled_flag)?;
+            if !pps.deblocking_filter_disabled_flag {
+                self.se(pps.beta_offset_div2)?;
+                self.se(pps.tc_offset_div2)?;
+            }
+        }
+
+        self.u(1, pps.scaling_list_data_present_flag)?;
+        if pps.scaling_list_data_present_flag {
+            self.scaling_list_data(&pps.scaling_list)?;
+        }
+
+        self.u(1, pps.lists_modification_present_flag)?;
+        self.ue(pps.log2_parallel_merge_level_minus2)?;
+        self.u(1, pps.slice_segment_header_extension_present_flag)?;
+
+        self.u(1, pps.extension_present_flag)?;
+        if pps.extension_present_flag {
+            self.u(1, pps.range_extension_flag)?;
+            self.u(1, /* pps_multilayer_extension_flag */ false)?;
+            self.u(1, /* pps_3d_extension_flag */ false)?;
+            self.u(1, pps.scc_extension_flag)?;
+            self.u(4, /* pps_extension_4bits */ 0u32)?;
+
+            if pps.range_extension_flag {
+                self.pps_range_extension()?;
+            }
+
+            if pps.scc_extension_flag {
+                self.pps_scc_extension()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'n, W: Write> Synthesizer<'n, SliceHeader, W> {
+    /// Writes a slice segment NALU header followed by the `slice_segment_header()` of `header`.
+    ///
+    /// `nalu_header` must describe a VCL NALU. The syntax elements present in the header are
+    /// selected using `sps` and `pps`. The header ends with `byte_alignment()`, so the
+    /// `slice_segment_data()` can be appended to the output as is.
+    pub fn synthesize(
+        nalu_header: &NaluHeader,
+        header: &'n SliceHeader,
+        sps: &Sps,
+        pps: &Pps,
+        writer: W,
+        ep_enabled: bool,
+    ) -> SynthesizerResult<()> {
+        if !matches!(
+            nalu_header.type_,
+            NaluType::TrailN
+                | NaluType::TrailR
+                | NaluType::TsaN
+                | NaluType::TsaR
+                | NaluType::StsaN
+                | NaluType::StsaR
+                | NaluType::RadlN
+                | NaluType::RadlR
+                | NaluType::RaslN
+                | NaluType::RaslR
+                | NaluType::BlaWLp
+                | NaluType::BlaWRadl
+                | NaluType::BlaNLp
+                | NaluType::IdrWRadl
+                | NaluType::IdrNLp
+                | NaluType::CraNut
+        ) {
+            return Err(SynthesizerError::Unsupported);
+        }
+
+        let mut s = Self {
+            writer: NaluWriter::<W>::new(writer, ep_enabled),
+            nalu: header,
+        };
+
+        s.nalu_header(nalu_header)?;
+        s.slice_segment_header(nalu_header.type_, sps, pps)?;
+        s.byte_alignment()
+    }
+
+    /// Computes NumPicTotalCurr as per (7-55).
+    fn num_pic_total_curr(&self, sps: &Sps, pps: &Pps) -> SynthesizerResult<u32> {
+        let header = self.nalu;
+        let rps = if header.short_term_ref_pic_set_sps_flag {
+            sps.short_term_ref_pic_set
+                .get(usize::from(header.short_term_ref_pic_set_idx))
+                .ok_or(SynthesizerError::Unsupported)?
+        } else {
+            &header.short_term_ref_pic_set
+        };
+
+        let num_lt = usize::from(header.num_long_term_sps) + usize::from(header.num_long_term_pics);
+        let used_by_curr_pic_lt = header
+            .used_by_curr_pic_lt
+            .get(..num_lt)
+            .ok_or(SynthesizerError::Unsupported)?;
+
+        let num_pic_total_curr = rps.used_by_curr_pic_s0[..usize::from(rps.num_negative_pics)]
+            .iter()
+            .chain(&rps.used_by_curr_pic_s1[..usize::from(rps.num_positive_pics)])
+            .chain(used_by_curr_pic_lt)
+            .filter(|used| **used)
+            .count() as u32;
+
+        if pps.scc_extension.curr_pic_ref_enabled_flag {
+            Ok(num_pic_total_curr + 1)
+        } else {
+            Ok(num_pic_total_curr)
+        }
+    }
+
+    fn ref_pic_lists_modification(
+        &mut self,
+        rplm: &RefPicListModification,
+        num_pic_total_curr: u32,
+    ) -> SynthesizerResult<()> {
+        // H.265 7.3.6.2
+        let header = self.nalu;
+        let num_bits = ceil_log2(num_pic_total_curr);
+
+        self.u(1, rplm.ref_pic_list_modification_flag_l0)?;
+        if rplm.ref_pic_list_modification_flag_l0 {
+            let num_entries = usize::from(header.num_ref_idx_l0_active_minus1) + 1;
+            let entries = rplm
+                .list_entry_l0
+                .get(..num_entries)
+                .ok_or(SynthesizerError::Unsupported)?;
+
+            for entry in entries {
+                self.u(num_bits, *entry)?;
+            }
+        }
+
+        if header.type_.is_b() {
+            self.u(1, rplm.ref_pic_list_modification_flag_l1)?;
+            if rplm.ref_pic_list_modification_flag_l1 {
+                let num_entries = usize::from(header.num_ref_idx_l1_active_minus1) + 1;
+                let entries = rplm
+                    .list_entry_l1
+                    .get(..num_entries)
+                    .ok_or(SynthesizerError::Unsupported)?;
+
+                for entry in entries {
+                    self.u(num_bits, *entry)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn pred_weight_table(&mut self, pwt: &PredWeightTable, sps: &Sps) -> SynthesizerResult<()> {
+        // H.265 7.3.6.3
+        let header = self.nalu;
+
+        self.ue(pwt.luma_log2_weight_denom)?;
+        if sps.chroma_array_type != 0 {
+            self.se(pwt.delta_chroma_log2_weight_denom)?;
+        }
+
+        let num_l0 = usize::from(header.num_ref_idx_l0_active_minus1) + 1;
+        for i in 0..num_l0 {
+            self.u(1, pwt.luma_weight_l0_flag[i])?;
+        }
+
+        if sps.chroma_array_type != 0 {
+            for i in 0..num_l0 {
+                self.u(1, pwt.chroma_weight_l0_flag[i])?;
+            }
+        }
+
+        for i in 0..num_l0 {
+            if pwt.luma_weight_l0_flag[i] {
+                self.se(pwt.delta_luma_weight_l0[i])?;
+                self.se(pwt.luma_offset_l0[i])?;
+            }
+
+            if sps.chroma_array_type != 0 && pwt.chroma_weight_l0_flag[i] {
+                for j in 0..2 {
+                    self.se(pwt.delta_chroma_weight_l0[i][j])?;
+                    self.se(pwt.delta_chroma_offset_l0[i][j])?;
+                }
+            }
+        }
+
+        if header.type_.is_b() {
+            let num_l1 = usize::from(header.num_ref_idx_l1_active_minus1) + 1;
+            for i in 0..num_l1 {
+                self.u(1, pwt.luma_weight_l1_flag[i])?;
+            }
+
+            if sps.chroma_array_type != 0 {
+                for i in 0..num_l1 {
+                    self.u(1, pwt.chroma_weight_l1_flag[i])?;
+                }
+            }
+
+            for i in 0..num_l1 {
+                if pwt.luma_weight_l1_flag[i] {
+                    self.se(pwt.delta_luma_weight_l1[i])?;
+                    self.se(pwt.luma_offset_l1[i])?;
+                }
+
+                if sps.chroma_array_type != 0 && pwt.chroma_weight_l1_flag[i] {
+                    for j in 0..2 {
+                        self.se(pwt.delta_chroma_weight_l1[i][j])?;
+                        self.se(pwt.delta_chroma_offset_l1[i][j])?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn long_term_ref_pics(&mut self, sps: &Sps) -> SynthesizerResult<()> {
+        let header = self.nalu;
+
+        if sps.num_long_term_ref_pics_sps > 0 {
+            self.ue(header.num_long_term_sps)?;
+        }
+
+        self.ue(header.num_long_term_pics)?;
+
+        let num_long_term_sps = usize::from(header.num_long_term_sps);
+        let num_lt = num_long_term_sps + usize::from(header.num_long_term_pics);
+        if num_lt > header.lt_idx_sps.len() {
+            return Err(SynthesizerError::Unsupported);
+        }
+
+        for i in 0..num_lt {
+            if i < num_long_term_sps {
+                if sps.num_long_term_ref_pics_sps > 1 {
+                    let num_bits = ceil_log2(u32::from(sps.num_long_term_ref_pics_sps));
+                    self.u(num_bits, header.lt_idx_sps[i])?;
+                }
+            } else {
+                let num_bits = usize::from(sps.log2_max_pic_order_cnt_lsb_minus4) + 4;
+                self.u(num_bits, header.poc_lsb_lt[i])?;
+                self.u(1, header.used_by_curr_pic_lt[i])?;
+            }
+
+            self.u(1, header.delta_poc_msb_present_flag[i])?;
+            if header.delta_poc_msb_present_flag[i] {
+                // DeltaPocMsbCycleLt is kept instead of the coded value, undo (7-52).
+                let mut delta_poc_msb_cycle_lt = header.delta_poc_msb_cycle_lt[i];
+                if i != 0 && i != num_long_term_sps {
+                    delta_poc_msb_cycle_lt = delta_poc_msb_cycle_lt
+                        .checked_sub(header.delta_poc_msb_cycle_lt[i - 1])
+                        .ok_or(SynthesizerError::Unsupported)?;
+                }
+
+                self.ue(delta_poc_msb_cycle_lt)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn slice_segment_header(
+        &mut self,
+        nalu_type: NaluType,
+        sps: &Sps,
+        pps: &Pps,
+    ) -> SynthesizerResult<()> {
+        // H.265 7.3.6.1
+        let header = self.nalu;
+
+        self.u(1, header.first_slice_segment_in_pic_flag)?;
+        if nalu_type.is_irap() {
+            self.u(1, header.no_output_of_prior_pics_flag)?;
+        }
+
+        self.ue(header.pic_parameter_set_id)?;
+
+        if !header.first_slice_segment_in_pic_flag {
+            if pps.dependent_slice_segments_enabled_flag {
+                self.u(1, header.dependent_slice_segment_flag)?;
+            }
+
+            self.u(ceil_log2(sps.pic_size_in_ctbs_y), header.segment_address)?;
+        }
+
+        if !header.dependent_slice_segment_flag {
+            for _ in 0..pps.num_extra_slice_header_bits {
+                self.u(1, /* slice_reserved_flag */ false)?;
+            }
+
+            self.ue(header.type_ as u32)?;
+
+            if pps.output_flag_present_flag {
+                self.u(1, header.pic_output_flag)?;
+            }
+
+            if sps.separate_colour_plane_flag {
+                self.u(2, header.colour_plane_id)?;
+            }
+
+            if !matches!(nalu_type, NaluType::IdrWRadl | NaluType::IdrNLp) {
+                self.u(
+                    usize::from(sps.log2_max_pic_order_cnt_lsb_minus4) + 4,
+                    header.pic_order_cnt_lsb,
+                )?;
+
+                self.u(1, header.short_term_ref_pic_set_sps_flag)?;
+                if !header.short_term_ref_pic_set_sps_flag {
+                    self.st_ref_pic_set(
+                        sps,
+                        &header.short_term_ref_pic_set,
+                        sps.num_short_term_ref_pic_sets,
+                    )?;
+                } else if sps.num_short_term_ref_pic_sets > 1 {
+                    self.u(
+                        ceil_log2(u32::from(sps.num_short_term_ref_pic_sets)),
+                        header.short_term_ref_pic_set_idx,
+                    )?;
+                }
+
+                if sps.long_term_ref_pics_present_flag {
+                    self.long_term_ref_pics(sps)?;
+                }
+
+                if sps.temporal_mvp_enabled_flag {
+                    self.u(1, header.temporal_mvp_enabled_flag)?;
+                }
+            }
+
+            if sps.sample_adaptive_offset_enabled_flag {
+                self.u(1, header.sao_luma_flag)?;
+                if sps.chroma_array_type != 0 {
+                    self.u(1, header.sao_chroma_flag)?;
+                }
+            }
+
+            if header.type_.is_p() || header.type_.is_b() {
+                self.u(1, header.num_ref_idx_active_override_flag)?;
+                if header.num_ref_idx_active_override_flag {
+                    self.ue(header.num_ref_idx_l0_active_minus1)?;
+                    if header.type_.is_b() {
+                        self.ue(header.num_ref_idx_l1_active_minus1)?;
+                    }
+                }
+
+                let num_pic_total_curr = self.num_pic_total_curr(sps, pps)?;
+                if pps.lists_modification_present_flag && num_pic_total_curr > 1 {
+                    self.ref_pic_lists_modification(
+                        &header.ref_pic_list_modification,
+                        num_pic_total_curr,
+                    )?;
+                }
+
+                if header.type_.is_b() {
+                    self.u(1, header.mvd_l1_zero_flag)?;
+                }
+
+                if pps.cabac_init_present_flag {
+                    self.u(1, header.cabac_init_flag)?;
+                }
+
+                if header.temporal_mvp_enabled_flag {
+                    if header.type_.is_b() {
+                        self.u(1, header.collocated_from_l0_flag)?;
+                    }
+
+                    if (header.collocated_from_l0_flag && header.num_ref_idx_l0_active_minus1 > 0)
+                        || (!header.collocated_from_l0_flag
+                            && header.num_ref_idx_l1_active_minus1 > 0)
+                    {
+                        self.ue(header.collocated_ref_idx)?;
+                    }
+                }
+
+                if (pps.weighted_pred_flag && header.type_.is_p())
+                    || (pps.weighted_bipred_flag && header.type_.is_b())
+                {
+                    self.pred_weight_table(&header.pred_weight_table, sps)?;
+                }
+
+                self.ue(header.five_minus_max_num_merge_cand)?;
+
+                if sps.scc_extension.motion_vector_resolution_control_idc == 2 {
+                    self.u(1, header.use_integer_mv_flag)?;
+                }
+            }
+
+            self.se(header.qp_delta)?;
+
+            if pps.slice_chroma_qp_offsets_present_flag {
+                self.se(header.cb_qp_offset)?;
+                self.se(header.cr_qp_offset)?;
+            }
+
+            if pps.scc_extension.slice_act_qp_offsets_present_flag {
+                self.se(header.slice_act_y_qp_offset)?;
+                self.se(header.slice_act_cb_qp_offset)?;
+                self.se(header.slice_act_cr_qp_offset)?;
+            }
+
+            if pps.range_extension.chroma_qp_offset_list_enabled_flag {
+                self.u(1, header.cu_chroma_qp_offset_enabled_flag)?;
+            }
+
+            if pps.deblocking_filter_override_enabled_flag {
+                self.u(1, header.deblocking_filter_override_flag)?;
+            }
+
+            if header.deblocking_filter_override_flag {
+                self.u(1, header.deblocking_filter_disabled_flag)?;
+                if !header.deblocking_filter_disabled_flag {
+                    self.se(header.beta_offset_div2)?;
+                    self.se(header.tc_offset_div2)?;
+                }
+            }
+
+            if pps.loop_filter_across_slices_enabled_flag
+                && (header.sao_luma_flag
+                    || header.sao_chroma_flag
+                    || !header.deblocking_filter_disabled_flag)
+            {
+                self.u(1, header.loop_filter_across_slices_enabled_flag)?;
+            }
+        }
+
+        if pps.tiles_enabled_flag || pps.entropy_coding_sync_enabled_flag {
+            self.ue(header.num_entry_point_offsets)?;
+            if header.num_entry_point_offsets > 0 {
+                self.ue(header.offset_len_minus1)?;
+
+                let offsets = header
+                    .entry_point_offset_minus1
+                    .get(..header.num_entry_point_offsets as usize)
+                    .ok_or(SynthesizerError::Unsupported)?;
+
+                for offset in offsets {
+                    self.u(usize::from(header.offset_len_minus1) + 1, *offset)?;
+                }
+            }
+        }
+
+        if pps.slice_segment_header_extension_present_flag {
+            // The extension data bytes are not kept by the parser.
+            self.ue(/* slice_segment_header_extension_length */ 0u32)?;
+        }
+
+        Ok(())
+    }
+
+    fn byte_alignment(&mut self) -> SynthesizerResult<()> {
+        // H.265 7.3.2.12
+        self.rbsp_trailing_bits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::codec::h264::nalu_reader::NaluReader;
+    use crate::codec::h265::parser::Level;
+    use crate::codec::h265::parser::Nalu;
+    use crate::codec::h265::parser::Parser;
+    use crate::codec::h265::parser::SliceType;
+
+    const STREAM_TEST25FPS: &[u8] = include_bytes!("test_data/test-25fps.h265");
+
+    /// Re-synthesizes every parameter set of `stream` and checks that the output is identical to
+    /// the original NALU and parses back to the same structure.
+    fn parameter_sets_round_trip(stream: &[u8]) {
+        let mut cursor = Cursor::new(stream);
+        let mut parser = Parser::default();
+        let mut reparser = Parser::default();
+        let mut num_parameter_sets = 0;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            let mut buf = Vec::<u8>::new();
+
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    let vps = parser.parse_vps(&nalu).unwrap();
+                    Synthesizer::<'_, Vps, _>::synthesize(vps, &mut buf, true).unwrap();
+                }
+                NaluType::SpsNut => {
+                    let sps = parser.parse_sps(&nalu).unwrap();
+                    Synthesizer::<'_, Sps, _>::synthesize(sps, &mut buf, true).unwrap();
+                }
+                NaluType::PpsNut => {
+                    let pps = parser.parse_pps(&nalu).unwrap();
+                    Synthesizer::<'_, Pps, _>::synthesize(pps, &mut buf, true).unwrap();
+                }
+                _ => continue,
+            }
+
+            assert_eq!(&buf[4..], nalu.as_ref());
+
+            let mut synthesized = Cursor::new(&buf[..]);
+            let nalu2 = Nalu::next(&mut synthesized).unwrap();
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    let vps = reparser.parse_vps(&nalu2).unwrap();
+                    assert_eq!(Some(vps), parser.get_vps(vps.video_parameter_set_id));
+                }
+                NaluType::SpsNut => {
+                    let sps = reparser.parse_sps(&nalu2).unwrap();
+                    assert_eq!(Some(sps), parser.get_sps(sps.seq_parameter_set_id));
+                }
+                _ => {
+                    let pps = reparser.parse_pps(&nalu2).unwrap();
+                    assert_eq!(Some(pps), parser.get_pps(pps.pic_parameter_set_id));
+                }
+            }
+
+            num_parameter_sets += 1;
+        }
+
+        assert!(num_parameter_sets >= 3);
+    }
+
+    #[test]
+    fn synthesize_parameter_sets_test25fps() {
+        parameter_sets_round_trip(STREAM_TEST25FPS);
+    }
+
+    #[test]
+    fn synthesize_parameter_sets_bear() {
+        parameter_sets_round_trip(include_bytes!("test_data/bear.h265"));
+    }
+
+    #[test]
+    fn synthesize_parameter_sets_bbb() {
+        parameter_sets_round_trip(include_bytes!("test_data/bbb.h265"));
+    }
+
+    #[test]
+    fn synthesize_parameter_sets_64x64() {
+        parameter_sets_round_trip(include_bytes!("test_data/64x64-I-P-B-P.h265"));
+    }
+
+    #[test]
+    fn synthesize_parameter_sets_extensions() {
+        let mut cursor = Cursor::new(STREAM_TEST25FPS);
+        let mut parser = Parser::default();
+        let mut vps = None;
+        let mut sps = None;
+        let mut pps = None;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => vps = Some(parser.parse_vps(&nalu).unwrap().clone()),
+                NaluType::SpsNut => sps = Some(parser.parse_sps(&nalu).unwrap().clone()),
+                NaluType::PpsNut => pps = Some(parser.parse_pps(&nalu).unwrap().clone()),
+                _ => (),
+            }
+        }
+
+        let (mut vps, mut sps, mut pps) = (vps.unwrap(), sps.unwrap(), pps.unwrap());
+
+        let mut hrd = HrdParams {
+            nal_hrd_parameters_present_flag: true,
+            sub_pic_hrd_params_present_flag: true,
+            tick_divisor_minus2: 10,
+            du_cpb_removal_delay_increment_length_minus1: 7,
+            dpb_output_delay_du_length_minus1: 9,
+            bit_rate_scale: 3,
+            cpb_size_scale: 5,
+            cpb_size_du_scale: 2,
+            fixed_pic_rate_general_flag: [true, false, false, false, false, false, false],
+            fixed_pic_rate_within_cvs_flag: [true, false, false, false, false, false, false],
+            elemental_duration_in_tc_minus1: [3, 0, 0, 0, 0, 0, 0],
+            low_delay_hrd_flag: [false, true, false, false, false, false, false],
+            cpb_cnt_minus1: [1, 0, 0, 0, 0, 0, 0],
+            ..Default::default()
+        };
+        hrd.nal_hrd[0].bit_rate_value_minus1[..2].copy_from_slice(&[1000, 2000]);
+        hrd.nal_hrd[0].cpb_size_value_minus1[..2].copy_from_slice(&[3000, 4000]);
+        hrd.nal_hrd[0].cpb_size_du_value_minus1[..2].copy_from_slice(&[5, 6]);
+        hrd.nal_hrd[0].bit_rate_du_value_minus1[..2].copy_from_slice(&[7, 8]);
+        hrd.nal_hrd[0].cbr_flag[1] = true;
+        hrd.nal_hrd[1].bit_rate_value_minus1[0] = 12345;
+
+        vps.max_sub_layers_minus1 = 1;
+        vps.sub_layer_ordering_info_present_flag = true;
+        vps.max_dec_pic_buffering_minus1[..2].copy_from_slice(&[3, 4]);
+        vps.max_num_reorder_pics[..2].copy_from_slice(&[1, 2]);
+        vps.profile_tier_level.sub_layer_level_present_flag[0] = true;
+        vps.profile_tier_level.sub_layer_level_idc[0] = Level::L2;
+        vps.timing_info_present_flag = true;
+        vps.num_units_in_tick = 1001;
+        vps.time_scale = 0x8000_0000;
+        vps.poc_proportional_to_timing_flag = true;
+        vps.num_ticks_poc_diff_one_minus1 = 1;
+        vps.num_hrd_parameters = 2;
+        vps.hrd_layer_set_idx = vec![0, 0];
+        vps.cprms_present_flag = vec![true, false];
+        vps.hrd_parameters = vec![
+            hrd.clone(),
+            HrdParams {
+                fixed_pic_rate_within_cvs_flag: [true, true, false, false, false, false, false],
+                elemental_duration_in_tc_minus1: [2, 2, 0, 0, 0, 0, 0],
+                ..Default::default()
+            },
+        ];
+
+        sps.max_sub_layers_minus1 = 1;
+        sps.sub_layer_ordering_info_present_flag = true;
+        sps.max_dec_pic_buffering_minus1[..2].copy_from_slice(&[4, 4]);
+        sps.max_num_reorder_pics[..2].copy_from_slice(&[0, 0]);
+        sps.profile_tier_level.sub_layer_profile_present_flag[0] = true;
+        sps.profile_tier_level.sub_layer_profile_idc[0] = 4;
+        sps.profile_tier_level.sub_layer_profile_compatibility_flag[0][4] = true;
+        sps.profile_tier_level.sub_layer_max_12bit_constraint_flag[0] = true;
+        sps.profile_tier_level.sub_layer_inbld_flag[0] = true;
+
+        let mut scaling_list = ScalingLists {
+            scaling_list_dc_coef_minus8_16x16: [8; 6],
+            scaling_list_dc_coef_minus8_32x32: [8, 0, 0, 8, 0, 0],
+            scaling_list_4x4: [DEFAULT_SCALING_LIST_0; 6],
+            scaling_list_8x8: [
+                DEFAULT_SCALING_LIST_1,
+                DEFAULT_SCALING_LIST_1,
+                DEFAULT_SCALING_LIST_1,
+                DEFAULT_SCALING_LIST_2,
+                DEFAULT_SCALING_LIST_2,
+                DEFAULT_SCALING_LIST_2,
+            ],
+            scaling_list_16x16: [
+                DEFAULT_SCALING_LIST_1,
+                DEFAULT_SCALING_LIST_1,
+                DEFAULT_SCALING_LIST_1,
+                DEFAULT_SCALING_LIST_2,
+                DEFAULT_SCALING_LIST_2,
+                DEFAULT_SCALING_LIST_2,
+            ],
+            scaling_list_32x32: [
+                DEFAULT_SCALING_LIST_1,
+                [0; 64],
+                [0; 64],
+                DEFAULT_SCALING_LIST_2,
+                [0; 64],
+                [0; 64],
+            ],
+        };
+        scaling_list.scaling_list_4x4[1] = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16];
+        scaling_list.scaling_list_16x16[2] = [40; 64];
+        scaling_list.scaling_list_dc_coef_minus8_16x16[2] = 4;
+        scaling_list.scaling_list_16x16[4] = [40; 64];
+        scaling_list.scaling_list_dc_coef_minus8_16x16[4] = 4;
+        for (i, coef) in scaling_list.scaling_list_32x32[3].iter_mut().enumerate() {
+            *coef = if i % 2 == 0 { 1 } else { 255 };
+        }
+        scaling_list.scaling_list_dc_coef_minus8_32x32[3] = -7;
+
+        sps.scaling_list_enabled_flag = true;
+        sps.scaling_list_data_present_flag = true;
+        sps.scaling_list = scaling_list.clone();
+
+        let mut explicit = ShortTermRefPicSet {
+            num_negative_pics: 2,
+            num_positive_pics: 1,
+            num_delta_pocs: 3,
+            ..Default::default()
+        };
+        explicit.delta_poc_s0[..2].copy_from_slice(&[-1, -2]);
+        explicit.used_by_curr_pic_s0[..2].copy_from_slice(&[true, true]);
+        explicit.delta_poc_s1[0] = 1;
+        explicit.used_by_curr_pic_s1[0] = true;
+
+        // Predicted from `explicit` with deltaRps = -1, dropping its positive picture.
+        let mut predicted = ShortTermRefPicSet {
+            inter_ref_pic_set_prediction_flag: true,
+            delta_rps_sign: true,
+            num_negative_pics: 3,
+            num_delta_pocs: 3,
+            ..Default::default()
+        };
+        predicted.delta_poc_s0[..3].copy_from_slice(&[-1, -2, -3]);
+        predicted.used_by_curr_pic_s0[..3].copy_from_slice(&[true, false, true]);
+
+        sps.num_short_term_ref_pic_sets = 2;
+        sps.short_term_ref_pic_set = vec![explicit, predicted];
+
+        sps.long_term_ref_pics_present_flag = true;
+        sps.num_long_term_ref_pics_sps = 2;
+        sps.lt_ref_pic_poc_lsb_sps[..2].copy_from_slice(&[5, 9]);
+        sps.used_by_curr_pic_lt_sps_flag[1] = true;
+
+        sps.vui_parameters_present_flag = true;
+        sps.vui_parameters.aspect_ratio_info_present_flag = true;
+        sps.vui_parameters.aspect_ratio_idc = EXTENDED_SAR;
+        sps.vui_parameters.sar_width = 4;
+        sps.vui_parameters.sar_height = 3;
+        sps.vui_parameters.default_display_window_flag = true;
+        sps.vui_parameters.def_disp_win_bottom_offset = 8;
+        sps.vui_parameters.timing_info_present_flag = true;
+        sps.vui_parameters.num_units_in_tick = 1;
+        sps.vui_parameters.time_scale = 25;
+        sps.vui_parameters.hrd_parameters_present_flag = true;
+        sps.vui_parameters.hrd = hrd;
+        sps.vui_parameters.bitstream_restriction_flag = true;
+        sps.vui_parameters.min_spatial_segmentation_idc = 4;
+
+        sps.extension_present_flag = true;
+        sps.range_extension_flag = true;
+        sps.range_extension.implicit_rdpcm_enabled_flag = true;
+        sps.range_extension.cabac_bypass_alignment_enabled_flag = true;
+        sps.scc_extension_flag = true;
+        sps.scc_extension.palette_mode_enabled_flag = true;
+        sps.scc_extension.palette_max_size = 8;
+        sps.scc_extension.delta_palette_max_predictor_size = 4;
+        sps.scc_extension
+            .palette_predictor_initializers_present_flag = true;
+        sps.scc_extension.num_palette_predictor_initializer_minus1 = 1;
+        sps.scc_extension.palette_predictor_initializer[0][..2].copy_from_slice(&[16, 235]);
+        sps.scc_extension.palette_predictor_initializer[2][..2].copy_from_slice(&[128, 1]);
+        sps.scc_extension.motion_vector_resolution_control_idc = 2;
+
+        pps.tiles_enabled_flag = true;
+        pps.num_tile_columns_minus1 = 1;
+        pps.num_tile_rows_minus1 = 1;
+        pps.uniform_spacing_flag = false;
+        pps.column_width_minus1[..2].copy_from_slice(&[0, sps.pic_width_in_ctbs_y - 2]);
+        pps.row_height_minus1[..2].copy_from_slice(&[0, sps.pic_height_in_ctbs_y - 2]);
+        pps.loop_filter_across_tiles_enabled_flag = false;
+        pps.deblocking_filter_control_present_flag = true;
+        pps.beta_offset_div2 = -3;
+        pps.tc_offset_div2 = 2;
+        pps.scaling_list_data_present_flag = true;
+        pps.scaling_list = scaling_list;
+        pps.transform_skip_enabled_flag = true;
+        pps.extension_present_flag = true;
+        pps.range_extension_flag = true;
+        pps.range_extension
+            .log2_max_transform_skip_block_size_minus2 = 1;
+        pps.range_extension.chroma_qp_offset_list_enabled_flag = true;
+        pps.range_extension.diff_cu_chroma_qp_offset_depth = 1;
+        pps.range_extension.chroma_qp_offset_list_len_minus1 = 1;
+        pps.range_extension.cb_qp_offset_list[..2].copy_from_slice(&[-12, 3]);
+        pps.range_extension.cr_qp_offset_list[..2].copy_from_slice(&[12, -3]);
+        pps.scc_extension_flag = true;
+        pps.scc_extension
+            .residual_adaptive_colour_transform_enabled_flag = true;
+        pps.scc_extension.act_y_qp_offset_plus5 = -7;
+        pps.scc_extension.act_cr_qp_offset_plus3 = 15;
+        pps.scc_extension
+            .palette_predictor_initializers_present_flag = true;
+        pps.scc_extension.num_palette_predictor_initializers = 2;
+        pps.scc_extension.palette_predictor_initializer[1][..2].copy_from_slice(&[7, 77]);
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, Vps, _>::synthesize(&vps, &mut buf, true).unwrap();
+        Synthesizer::<'_, Sps, _>::synthesize(&sps, &mut buf, true).unwrap();
+        Synthesizer::<'_, Pps, _>::synthesize(&pps, &mut buf, true).unwrap();
+
+        let write_to_file = std::option_env!("CROS_CODECS_TEST_WRITE_TO_FILE") == Some("true");
+        if write_to_file {
+            let mut out = std::fs::File::create("vps_sps_pps.h265").unwrap();
+            out.write_all(&buf).unwrap();
+            out.flush().unwrap();
+        }
+
+        let mut cursor = Cursor::new(&buf[..]);
+        let mut parser = Parser::default();
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        assert_eq!(parser.parse_vps(&nalu).unwrap(), &vps);
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        assert_eq!(parser.parse_sps(&nalu).unwrap(), &sps);
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        assert_eq!(parser.parse_pps(&nalu).unwrap(), &pps);
+    }
+
+    /// Returns the RBSP of `nalu`, i.e. its payload without emulation prevention bytes.
+    fn rbsp(nalu: &[u8]) -> Vec<u8> {
+        let mut reader = NaluReader::new(nalu);
+        let mut rbsp = Vec::new();
+        while let Ok(byte) = reader.read_bits::<u8>(8) {
+            rbsp.push(byte);
+        }
+
+        rbsp
+    }
+
+    /// Re-synthesizes every slice segment header of `stream` and checks that the output matches
+    /// the original header bits, then rewrites each slice in place and checks that it is left
+    /// unchanged.
+    fn slice_headers_round_trip(stream: &[u8]) {
+        let mut cursor = Cursor::new(stream);
+        let mut parser = Parser::default();
+        let mut num_slices = 0;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    parser.parse_vps(&nalu).unwrap();
+                }
+                NaluType::SpsNut => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::PpsNut => {
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                NaluType::AudNut
+                | NaluType::EosNut
+                | NaluType::EobNut
+                | NaluType::FdNut
+                | NaluType::PrefixSeiNut
+                | NaluType::SuffixSeiNut => (),
+                _ => {
+                    let mut slice = parser.parse_slice_header(nalu).unwrap();
+                    let pps = parser
+                        .get_pps(slice.header.pic_parameter_set_id)
+                        .unwrap()
+                        .clone();
+                    let sps = parser.get_sps(pps.seq_parameter_set_id).unwrap().clone();
+
+                    let mut buf = Vec::<u8>::new();
+                    Synthesizer::<'_, SliceHeader, _>::synthesize(
+                        &slice.nalu.header,
+                        &slice.header,
+                        &sps,
+                        &pps,
+                        &mut buf,
+                        false,
+                    )
+                    .unwrap();
+
+                    let header_size = slice.header.header_bit_size as usize / 8;
+                    assert_eq!(&buf[4..], &rbsp(slice.nalu.as_ref())[..header_size]);
+
+                    let header = slice.header.clone();
+                    let data = slice.nalu.as_ref().to_vec();
+                    slice.replace_header(header.clone(), &sps, &pps).unwrap();
+                    assert_eq!(slice.header, header);
+                    assert_eq!(slice.nalu.as_ref(), &data[..]);
+
+                    num_slices += 1;
+                }
+            }
+        }
+
+        assert!(num_slices > 0);
+    }
+
+    #[test]
+    fn synthesize_slice_headers_test25fps() {
+        slice_headers_round_trip(STREAM_TEST25FPS);
+    }
+
+    #[test]
+    fn synthesize_slice_headers_bear() {
+        slice_headers_round_trip(include_bytes!("test_data/bear.h265"));
+    }
+
+    #[test]
+    fn synthesize_slice_headers_bbb() {
+        slice_headers_round_trip(include_bytes!("test_data/bbb.h265"));
+    }
+
+    #[test]
+    fn synthesize_slice_headers_64x64() {
+        slice_headers_round_trip(include_bytes!("test_data/64x64-I-P-B-P.h265"));
+    }
+
+    #[test]
+    fn synthesize_slice_header_ref_pics() {
+        let mut cursor = Cursor::new(STREAM_TEST25FPS);
+        let mut parser = Parser::default();
+        let mut slice = None;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    parser.parse_vps(&nalu).unwrap();
+                }
+                NaluType::SpsNut => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::PpsNut => {
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                NaluType::TrailR | NaluType::TrailN if slice.is_none() => {
+                    slice = Some(parser.parse_slice_header(nalu).unwrap());
+                }
+                _ => (),
+            }
+        }
+
+        let slice = slice.unwrap();
+        let mut pps = parser
+            .get_pps(slice.header.pic_parameter_set_id)
+            .unwrap()
+            .clone();
+        let mut sps = parser.get_sps(pps.seq_parameter_set_id).unwrap().clone();
+
+        sps.long_term_ref_pics_present_flag = true;
+        sps.num_long_term_ref_pics_sps = 2;
+        sps.lt_ref_pic_poc_lsb_sps[..2].copy_from_slice(&[3, 7]);
+        sps.used_by_curr_pic_lt_sps_flag[..2].copy_from_slice(&[false, true]);
+
+        pps.lists_modification_present_flag = true;
+        pps.weighted_bipred_flag = true;
+        pps.entropy_coding_sync_enabled_flag = true;
+        pps.slice_segment_header_extension_present_flag = true;
+
+        let mut header = slice.header.clone();
+        header.type_ = SliceType::B;
+
+        header.short_term_ref_pic_set_sps_flag = false;
+        header.short_term_ref_pic_set = ShortTermRefPicSet {
+            num_negative_pics: 1,
+            num_positive_pics: 1,
+            num_delta_pocs: 2,
+            ..Default::default()
+        };
+        header.short_term_ref_pic_set.delta_poc_s0[0] = -1;
+        header.short_term_ref_pic_set.used_by_curr_pic_s0[0] = true;
+        header.short_term_ref_pic_set.delta_poc_s1[0] = 2;
+        header.short_term_ref_pic_set.used_by_curr_pic_s1[0] = true;
+
+        // One long-term picture from the SPS and two signalled in the slice header.
+        header.num_long_term_sps = 1;
+        header.num_long_term_pics = 2;
+        header.lt_idx_sps[0] = 1;
+        header.poc_lsb_lt[..3].copy_from_slice(&[7, 12, 13]);
+        header.used_by_curr_pic_lt[..3].copy_from_slice(&[true, true, false]);
+        header.delta_poc_msb_present_flag[..3].copy_from_slice(&[true, true, true]);
+        header.delta_poc_msb_cycle_lt[..3].copy_from_slice(&[1, 2, 5]);
+
+        header.num_ref_idx_active_override_flag = true;
+        header.num_ref_idx_l0_active_minus1 = 1;
+        header.num_ref_idx_l1_active_minus1 = 0;
+        header
+            .ref_pic_list_modification
+            .ref_pic_list_modification_flag_l0 = true;
+        header.ref_pic_list_modification.list_entry_l0 = vec![3, 0];
+        header
+            .ref_pic_list_modification
+            .ref_pic_list_modification_flag_l1 = true;
+        header.ref_pic_list_modification.list_entry_l1 = vec![2];
+
+        let pwt = &mut header.pred_weight_table;
+        pwt.luma_log2_weight_denom = 6;
+        pwt.delta_chroma_log2_weight_denom = -1;
+        pwt.luma_weight_l0_flag[1] = true;
+        pwt.delta_luma_weight_l0[1] = 5;
+        pwt.luma_offset_l0[1] = -3;
+        pwt.chroma_weight_l0_flag[0] = true;
+        pwt.delta_chroma_weight_l0[0] = [-2, 4];
+        pwt.delta_chroma_offset_l0[0] = [100, -100];
+        pwt.chroma_weight_l1_flag[0] = true;
+        pwt.delta_chroma_weight_l1[0] = [1, -1];
+        pwt.delta_chroma_offset_l1[0] = [-7, 9];
+
+        header.num_entry_point_offsets = 2;
+        header.offset_len_minus1 = 9;
+        header.entry_point_offset_minus1 = [0; 32];
+        header.entry_point_offset_minus1[..2].copy_from_slice(&[100, 513]);
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, Sps, _>::synthesize(&sps, &mut buf, true).unwrap();
+        Synthesizer::<'_, Pps, _>::synthesize(&pps, &mut buf, true).unwrap();
+        Synthesizer::<'_, SliceHeader, _>::synthesize(
+            &slice.nalu.header,
+            &header,
+            &sps,
+            &pps,
+            &mut buf,
+            true,
+        )
+        .unwrap();
+
+        let mut cursor = Cursor::new(&buf[..]);
+        let mut parser = Parser::default();
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        parser.parse_sps(&nalu).unwrap();
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        parser.parse_pps(&nalu).unwrap();
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        let reparsed = parser.parse_slice_header(nalu).unwrap().header;
+
+        assert_eq!(reparsed.num_pic_total_curr, 4);
+
+        // Fields derived by the parser.
+        header.num_pic_total_curr = reparsed.num_pic_total_curr;
+        header.header_bit_size = reparsed.header_bit_size;
+        header.n_emulation_prevention_bytes = reparsed.n_emulation_prevention_bytes;
+        header.curr_rps_idx = reparsed.curr_rps_idx;
+        header.st_rps_bits = reparsed.st_rps_bits;
+        header.pred_weight_table.chroma_log2_weight_denom =
+            reparsed.pred_weight_table.chroma_log2_weight_denom;
+        assert_eq!(reparsed, header);
+
+        // A list entry that cannot be coded with the available pictures.
+        header.ref_pic_list_modification.list_entry_l1.clear();
+        let err = Synthesizer::<'_, SliceHeader, _>::synthesize(
+            &slice.nalu.header,
+            &header,
+            &sps,
+            &pps,
+            Vec::new(),
+            true,
+        );
+        assert!(matches!(err, Err(SynthesizerError::Unsupported)));
+    }
+
+    #[test]
+    fn synthesize_pred_weight_table_without_chroma() {
+        let mut cursor = Cursor::new(STREAM_TEST25FPS);
+        let mut parser = Parser::default();
+        let mut slice = None;
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    parser.parse_vps(&nalu).unwrap();
+                }
+                NaluType::SpsNut => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::PpsNut => {
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                NaluType::TrailR | NaluType::TrailN if slice.is_none() => {
+                    slice = Some(parser.parse_slice_header(nalu).unwrap());
+                }
+                _ => (),
+            }
+        }
+
+        let slice = slice.unwrap();
+        let mut pps = parser
+            .get_pps(slice.header.pic_parameter_set_id)
+            .unwrap()
+            .clone();
+        let mut sps = parser.get_sps(pps.seq_parameter_set_id).unwrap().clone();
+
+        // ChromaArrayType is 0 when the colour planes are coded separately.
+        sps.chroma_format_idc = 3;
+        sps.separate_colour_plane_flag = true;
+        sps.chroma_array_type = 0;
+        pps.weighted_bipred_flag = true;
+
+        let mut header = slice.header.clone();
+        header.type_ = SliceType::B;
+
+        // The chroma weights must be ignored.
+        let pwt = &mut header.pred_weight_table;
+        pwt.luma_log2_weight_denom = 6;
+        pwt.luma_weight_l0_flag[0] = true;
+        pwt.delta_luma_weight_l0[0] = 5;
+        pwt.chroma_weight_l0_flag[0] = true;
+        pwt.delta_chroma_weight_l0[0] = [-2, 4];
+        pwt.chroma_weight_l1_flag[0] = true;
+        pwt.delta_chroma_offset_l1[0] = [-7, 9];
+
+        let mut buf = Vec::<u8>::new();
+        Synthesizer::<'_, Sps, _>::synthesize(&sps, &mut buf, true).unwrap();
+        Synthesizer::<'_, Pps, _>::synthesize(&pps, &mut buf, true).unwrap();
+        Synthesizer::<'_, SliceHeader, _>::synthesize(
+            &slice.nalu.header,
+            &header,
+            &sps,
+            &pps,
+            &mut buf,
+            true,
+        )
+        .unwrap();
+
+        let mut cursor = Cursor::new(&buf[..]);
+        let mut parser = Parser::default();
+
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        parser.parse_sps(&nalu).unwrap();
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        parser.parse_pps(&nalu).unwrap();
+        let nalu = Nalu::next(&mut cursor).unwrap();
+        let reparsed = parser.parse_slice_header(nalu).unwrap().header;
+
+        let pwt = &reparsed.pred_weight_table;
+        assert_eq!(pwt.luma_log2_weight_denom, 6);
+        assert_eq!(pwt.delta_luma_weight_l0[0], 5);
+        assert!(!pwt.chroma_weight_l0_flag[0]);
+        assert!(!pwt.chroma_weight_l1_flag[0]);
+        assert_eq!(reparsed.qp_delta, header.qp_delta);
+        assert_eq!(
+            reparsed.loop_filter_across_slices_enabled_flag,
+            header.loop_filter_across_slices_enabled_flag
+        );
+    }
+
+    #[test]
+    fn replace_slice_header() {
+        let mut cursor = Cursor::new(include_bytes!("test_data/64x64-I-P-B-P.h265").as_slice());
+        let mut parser = Parser::default();
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    parser.parse_vps(&nalu).unwrap();
+                }
+                NaluType::SpsNut => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::PpsNut => {
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                NaluType::IdrWRadl | NaluType::IdrNLp => {
+                    let mut slice = parser.parse_slice_header(nalu).unwrap();
+                    let pps = parser
+                        .get_pps(slice.header.pic_parameter_set_id)
+                        .unwrap()
+                        .clone();
+                    let sps = parser.get_sps(pps.seq_parameter_set_id).unwrap().clone();
+
+                    let header_size = slice.header.header_bit_size as usize / 8;
+                    let slice_data = rbsp(slice.nalu.as_ref())[header_size..].to_vec();
+
+                    let mut header = slice.header.clone();
+                    header.qp_delta += 3;
+                    header.no_output_of_prior_pics_flag = !header.no_output_of_prior_pics_flag;
+                    slice.replace_header(header.clone(), &sps, &pps).unwrap();
+
+                    assert_eq!(slice.header.qp_delta, header.qp_delta);
+                    assert_eq!(
+                        slice.header.no_output_of_prior_pics_flag,
+                        header.no_output_of_prior_pics_flag
+                    );
+
+                    let header_size = slice.header.header_bit_size as usize / 8;
+                    assert_eq!(&rbsp(slice.nalu.as_ref())[header_size..], &slice_data[..]);
+
+                    // The PPS does not match the one referenced by the header.
+                    let mut other_pps = pps.clone();
+                    other_pps.pic_parameter_set_id += 1;
+                    assert!(slice.replace_header(header, &sps, &other_pps).is_err());
+
+                    return;
+                }
+                _ => (),
+            }
+        }
+
+        panic!("No IDR slice found");
+    }
+
+    #[test]
+    fn replace_dependent_slice_header() {
+        let mut cursor = Cursor::new(include_bytes!("test_data/64x64-I-P-B-P.h265").as_slice());
+        let mut parser = Parser::default();
+
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::VpsNut => {
+                    parser.parse_vps(&nalu).unwrap();
+                }
+                NaluType::SpsNut => {
+                    parser.parse_sps(&nalu).unwrap();
+                }
+                NaluType::PpsNut => {
+                    // Enable dependent slice segments, which the stream does not use.
+                    let mut pps = parser.parse_pps(&nalu).unwrap().clone();
+                    pps.dependent_slice_segments_enabled_flag = true;
+                    let mut buf = Vec::new();
+                    Synthesizer::<'_, Pps, _>::synthesize(&pps, &mut buf, true).unwrap();
+                    let nalu = Nalu::next(&mut Cursor::new(buf.as_slice())).unwrap();
+                    parser.parse_pps(&nalu).unwrap();
+                }
+                NaluType::IdrWRadl | NaluType::IdrNLp => {
+                    let independent = parser.parse_slice_header(nalu).unwrap();
+                    let pps = parser
+                        .get_pps(independent.header.pic_parameter_set_id)
+                        .unwrap()
+                        .clone();
+                    let sps = parser.get_sps(pps.seq_parameter_set_id).unwrap().clone();
+
+                    let header = SliceHeader {
+                        first_slice_segment_in_pic_flag: false,
+                        pic_parameter_set_id: pps.pic_parameter_set_id,
+                        dependent_slice_segment_flag: true,
+                        segment_address: sps.pic_size_in_ctbs_y - 1,
+                        ..Default::default()
+                    };
+                    let mut buf = Vec::new();
+                    Synthesizer::<'_, SliceHeader, _>::synthesize(
+                        &independent.nalu.header,
+                        &header,
+                        &sps,
+                        &pps,
+                        &mut buf,
+                        true,
+                    )
+                    .unwrap();
+                    buf.extend_from_slice(&[0xaa, 0x55, 0x80]);
+
+                    let nalu = Nalu::next(&mut Cursor::new(buf.as_slice())).unwrap();
+                    let mut dependent = parser.parse_slice_header(nalu).unwrap();
+                    let header_bit_size = dependent.header.header_bit_size;
+                    dependent
+                        .replace_header(independent.header.clone(), &sps, &pps)
+                        .unwrap();
+
+                    // The coded fields are kept, the others are inherited.
+                    assert_eq!(dependent.nalu.as_ref(), &buf[4..]);
+                    assert!(dependent.header.dependent_slice_segment_flag);
+                    assert!(!dependent.header.first_slice_segment_in_pic_flag);
+                    assert_eq!(dependent.header.segment_address, header.segment_address);
+                    assert_eq!(dependent.header.header_bit_size, header_bit_size);
+                    assert_eq!(dependent.header.type_, independent.header.type_);
+                    assert_eq!(dependent.header.qp_delta, independent.header.qp_delta);
+
+                    return;
+                }
+                _ => (),
+            }
+        }
+
+        panic!("No IDR slice found");
+    }
+}
@@ -3,5 +3,8 @@
 // found in the LICENSE file.
 
 mod bool_decoder;
+pub mod bool_encoder;
 pub mod parser;
 mod probs;
+pub mod references;
+pub mod synthesizer;
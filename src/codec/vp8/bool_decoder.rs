@@ -14,7 +14,7 @@ const LOTS_OF_BITS: u32 = 0x40000000;
 const U8_BITS: usize = u8::BITS as usize;
 const BD_VALUE_SIZE: usize = std::mem::size_of::<usize>() * U8_BITS;
 
-const NORM: [u8; 256] = [
+pub(super) const NORM: [u8; 256] = [
     0, 7, 6, 6, 5, 5, 5, 5, 4, 4, 4, 4, 4, 4, 4, 4, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3,
     2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
@@ -26,7 +26,7 @@ const NORM: [u8; 256] = [
 ];
 
 /// Some bits are "encoded" with a 50/50 probability.
-const DEFAULT_PROBABILITY: u8 = 128;
+pub(super) const DEFAULT_PROBABILITY: u8 = 128;
 
 /// A capture of the state of the boolean decoder.
 ///
@@ -68,6 +68,32 @@ impl<T: AsRef<[u8]>> BoolDecoder<T> {
         }
     }
 
+    /// Creates an instance resuming the decoding of `data` from `state`,
+    /// captured after reading `pos` bits of it, e.g. from the `bd_*` and
+    /// `header_size` members of a parsed frame header.
+    #[cfg(test)]
+    pub fn resume(data: T, state: &BoolDecoderState, pos: usize) -> Self {
+        // The top byte of `value` is the only one modified by the decoding,
+        // the `state.count` bits below it are still those of `data`.
+        let next = pos + U8_BITS;
+        let mut value = state.value << (BD_VALUE_SIZE - U8_BITS);
+        if state.count > 0 {
+            let byte = data.as_ref().get(next / U8_BITS).copied().unwrap_or(0);
+            let mask = (1usize << state.count) - 1;
+            value |= (byte as usize & mask) << (BD_VALUE_SIZE - U8_BITS - state.count as usize);
+        }
+
+        let mut data = Cursor::new(data);
+        data.set_position(next.div_ceil(U8_BITS) as u64);
+
+        Self {
+            data,
+            range: state.range,
+            value,
+            count: state.count,
+        }
+    }
+
     /// Fills more bits from `data` to `value`. We shall keep at least 8 bits of the current `data`
     /// in `value`.
     ///
@@ -301,4 +327,24 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn resume_bools_with_parities_and_increasing_probabilities() {
+        for start in 0..NUM_BITS_TO_TEST {
+            let mut bd = BoolDecoder::new(&DATA_PARITIES_AND_INCREASING_PROBABILITIES[..]);
+            for i in 0..start {
+                bd.read_bool_with_prob(i as u8).unwrap();
+            }
+
+            let pos = bd.pos();
+            let state = BoolDecoderState::from(bd);
+            let mut bd =
+                BoolDecoder::resume(&DATA_PARITIES_AND_INCREASING_PROBABILITIES[..], &state, pos);
+            assert_eq!(bd.pos(), pos);
+
+            for i in start..NUM_BITS_TO_TEST {
+                assert_eq!(bd.read_bool_with_prob(i as u8), Ok(i % 2 == 1));
+            }
+        }
+    }
 }
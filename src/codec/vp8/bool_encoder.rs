@@ -0,0 +1,249 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A VP8 boolean encoder based on the implementation in libvpx. This is the counterpart of
+//! the boolean decoder used by [`super::parser::Parser`].
+
+use thiserror::Error;
+
+use crate::codec::vp8::bool_decoder::DEFAULT_PROBABILITY;
+use crate::codec::vp8::bool_decoder::NORM;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BoolEncoderError {
+    #[error("value {0} does not fit in {1} bits")]
+    ValueTooLarge(u32, usize),
+    #[error("could not convert value to the literal type")]
+    CannotConvert,
+}
+
+pub type BoolEncoderResult<T> = std::result::Result<T, BoolEncoderError>;
+
+/// The encoder state.
+///
+/// The output is kept in memory until [`BoolEncoder::finish`] is called, as encoding a bool may
+/// carry over into the bytes that were already produced.
+pub struct BoolEncoder {
+    data: Vec<u8>,
+    range: u32,
+    bottom: u32,
+    count: i32,
+}
+
+impl Default for BoolEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl BoolEncoder {
+    /// Creates a new instance.
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            range: 255,
+            bottom: 0,
+            count: -24,
+        }
+    }
+
+    /// Adds one to the bytes already written, as the result of `bottom` overflowing.
+    fn propagate_carry(&mut self) {
+        for byte in self.data.iter_mut().rev() {
+            if *byte == 0xff {
+                *byte = 0;
+            } else {
+                *byte += 1;
+                break;
+            }
+        }
+    }
+
+    /// Writes the next bit to the coded stream. The probability of the bit to
+    /// be zero is probability / 256.
+    fn write_bit(&mut self, bit: bool, probability: u8) {
+        let split = 1 + (((self.range - 1) * u32::from(probability)) >> 8);
+
+        if bit {
+            self.bottom = self.bottom.wrapping_add(split);
+            self.range -= split;
+        } else {
+            self.range = split;
+        }
+
+        let mut shift = i32::from(NORM[self.range as usize]);
+        self.range <<= shift;
+        self.count += shift;
+
+        if self.count >= 0 {
+            let offset = shift - self.count;
+
+            if (self.bottom << (offset - 1)) & 0x8000_0000 != 0 {
+                self.propagate_carry();
+            }
+
+            self.data.push((self.bottom >> (24 - offset)) as u8);
+            self.bottom <<= offset;
+            shift = self.count;
+            self.bottom &= 0xff_ffff;
+            self.count -= 8;
+        }
+
+        self.bottom <<= shift;
+    }
+
+    /// Writes a "literal", that is, a "num_bits"-wide unsigned value whose bits
+    /// come high- to low-order, with each bit encoded at probability 1/2.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `nbits > 31`.
+    fn write_literal(&mut self, value: u32, nbits: usize) -> BoolEncoderResult<()> {
+        assert!(nbits <= 31);
+
+        if value >> nbits != 0 {
+            return Err(BoolEncoderError::ValueTooLarge(value, nbits));
+        }
+
+        for bit in (0..nbits).rev() {
+            self.write_bit((value >> bit) & 1 != 0, DEFAULT_PROBABILITY);
+        }
+
+        Ok(())
+    }
+
+    /// Writes a boolean to the coded stream with an even probability.
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_bit(value, DEFAULT_PROBABILITY)
+    }
+
+    /// Writes a boolean to the coded stream. The probability of `value` to be
+    /// false is probability / 256, e.g., when probability is 0x80, the chance
+    /// is 1/2 (i.e., 0x80 / 256).
+    pub fn write_bool_with_prob(&mut self, value: bool, probability: u8) {
+        self.write_bit(value, probability)
+    }
+
+    /// Writes an unsigned literal to the coded stream.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `nbits > 31`.
+    pub fn write_uint<U: TryInto<u32>>(&mut self, value: U, nbits: usize) -> BoolEncoderResult<()> {
+        let value = value
+            .try_into()
+            .map_err(|_| BoolEncoderError::CannotConvert)?;
+
+        self.write_literal(value, nbits)
+    }
+
+    /// Writes a literal with sign to the coded stream. This is the counterpart
+    /// of `BoolDecoder::read_sint`: the magnitude is written as a "num_bits"-wide
+    /// literal, followed by an extra bit for the sign.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `nbits > 31`.
+    pub fn write_sint<U: TryInto<i32>>(&mut self, value: U, nbits: usize) -> BoolEncoderResult<()> {
+        let value = value
+            .try_into()
+            .map_err(|_| BoolEncoderError::CannotConvert)?;
+
+        self.write_literal(value.unsigned_abs(), nbits)?;
+        self.write_bool(value < 0);
+
+        Ok(())
+    }
+
+    /// Flushes the state of the encoder and returns the coded stream.
+    pub fn finish(mut self) -> Vec<u8> {
+        // Same as libvpx's vp8_stop_encode(), pad with enough bits for the
+        // decoder to be able to read all the symbols.
+        for _ in 0..32 {
+            self.write_bit(false, DEFAULT_PROBABILITY);
+        }
+
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::vp8::bool_decoder::BoolDecoder;
+
+    const NUM_BITS_TO_TEST: usize = 100;
+
+    #[test]
+    fn encode_bools_with_even_probabilities() {
+        for value in [false, true] {
+            let mut be = BoolEncoder::new();
+            for _ in 0..NUM_BITS_TO_TEST {
+                be.write_bool_with_prob(value, 0x80);
+            }
+
+            let data = be.finish();
+            let mut bd = BoolDecoder::new(&data[..]);
+            for _ in 0..NUM_BITS_TO_TEST {
+                assert_eq!(bd.read_bool_with_prob(0x80), Ok(value));
+            }
+        }
+    }
+
+    #[test]
+    fn encode_bools_with_parities_and_increasing_probabilities() {
+        let mut be = BoolEncoder::new();
+        for i in 0..NUM_BITS_TO_TEST {
+            be.write_bool_with_prob(i % 2 != 0, i as u8);
+        }
+
+        // Same data as in the bool decoder tests, followed by the padding.
+        let data = be.finish();
+        assert_eq!(
+            &data[..20],
+            &[
+                0x00, 0x02, 0x08, 0x31, 0x8e, 0xca, 0xab, 0xe2, 0xc8, 0x31, 0x12, 0xb3, 0x2c, 0x19,
+                0x90, 0xc6, 0x6a, 0xeb, 0x17, 0x52,
+            ]
+        );
+
+        let mut bd = BoolDecoder::new(&data[..]);
+        for i in 0..NUM_BITS_TO_TEST {
+            assert_eq!(bd.read_bool_with_prob(i as u8), Ok(i % 2 != 0));
+        }
+    }
+
+    #[test]
+    fn encode_literals() {
+        let mut be = BoolEncoder::new();
+        be.write_uint(0u32, 1).unwrap();
+        be.write_uint(0x7fffffffu32, 31).unwrap();
+        be.write_sint(-1, 1).unwrap();
+        be.write_sint(-0x7fffffff, 31).unwrap();
+        be.write_sint(42, 7).unwrap();
+        be.write_uint(0xabu8, 8).unwrap();
+        be.write_bool_with_prob(true, 1);
+        be.write_bool_with_prob(false, 255);
+
+        assert_eq!(
+            be.write_uint(4u32, 2),
+            Err(BoolEncoderError::ValueTooLarge(4, 2))
+        );
+        assert_eq!(
+            be.write_sint(-64, 6),
+            Err(BoolEncoderError::ValueTooLarge(64, 6))
+        );
+
+        let data = be.finish();
+        let mut bd = BoolDecoder::new(&data[..]);
+        assert_eq!(bd.read_uint::<u32>(1), Ok(0));
+        assert_eq!(bd.read_uint::<u32>(31), Ok(0x7fffffff));
+        assert_eq!(bd.read_sint::<i32>(1), Ok(-1));
+        assert_eq!(bd.read_sint::<i32>(31), Ok(-0x7fffffff));
+        assert_eq!(bd.read_sint::<i32>(7), Ok(42));
+        assert_eq!(bd.read_uint::<u8>(8), Ok(0xab));
+        assert_eq!(bd.read_bool_with_prob(1), Ok(true));
+        assert_eq!(bd.read_bool_with_prob(255), Ok(false));
+    }
+}
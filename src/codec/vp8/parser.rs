@@ -131,7 +131,7 @@ pub struct Header {
     pub sharpness_level: u8,
     /// Determines the number of separate partitions containing the DCT
     /// coefficients of the macroblocks.
-    log2_nbr_of_dct_partitions: u8,
+    pub log2_nbr_of_dct_partitions: u8,
 
     pub partition_size: [u32; 8],
 
@@ -356,17 +356,17 @@ impl Parser {
                     *value = 0;
                 }
             }
+        }
 
-            if seg.update_mb_segmentation_map {
-                for value in seg.segment_prob.iter_mut() {
-                    let update = bd.read_bool()?;
-                    if update {
-                        *value = bd.read_uint(8)?;
-                    } else {
-                        // segment_prob defaults to 255 if update flag is
-                        // zero (Section 9.3, 5)
-                        *value = 255;
-                    }
+        if seg.update_mb_segmentation_map {
+            for value in seg.segment_prob.iter_mut() {
+                let update = bd.read_bool()?;
+                if update {
+                    *value = bd.read_uint(8)?;
+                } else {
+                    // segment_prob defaults to 255 if update flag is
+                    // zero (Section 9.3, 5)
+                    *value = 255;
                 }
             }
         }
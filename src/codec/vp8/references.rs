@@ -0,0 +1,231 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Management of the VP8 reference buffers. This applies the buffer refresh
+//! and copy rules signaled in the frame headers, in the same order as libvpx.
+
+use anyhow::anyhow;
+
+use crate::codec::vp8::parser::Frame;
+use crate::codec::vp8::parser::Header;
+
+/// The reference buffers an inter frame predicts from, along with the sign
+/// biases to apply to their motion vectors.
+///
+/// `T` is the handle to a physical buffer, which is cloned whenever a buffer
+/// is referenced from several slots.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReferenceBuffers<T> {
+    /// The last frame reference.
+    pub last: Option<T>,
+    /// The golden frame reference.
+    pub golden: Option<T>,
+    /// The alternate frame reference.
+    pub alt_ref: Option<T>,
+    /// The sign bias of the golden frame.
+    pub sign_bias_golden: bool,
+    /// The sign bias of the alternate frame.
+    pub sign_bias_alternate: bool,
+}
+
+impl<T> Default for ReferenceBuffers<T> {
+    fn default() -> Self {
+        Self {
+            last: None,
+            golden: None,
+            alt_ref: None,
+            sign_bias_golden: false,
+            sign_bias_alternate: false,
+        }
+    }
+}
+
+/// Keeps track of the buffers held by the last, golden and alternate
+/// references across frames.
+#[derive(Clone, Debug)]
+pub struct ReferenceManager<T> {
+    refs: ReferenceBuffers<T>,
+}
+
+impl<T> Default for ReferenceManager<T> {
+    fn default() -> Self {
+        Self {
+            refs: Default::default(),
+        }
+    }
+}
+
+impl<T: Clone> ReferenceManager<T> {
+    /// Returns the current references, i.e. the ones the next frame predicts
+    /// from.
+    pub fn references(&self) -> &ReferenceBuffers<T> {
+        &self.refs
+    }
+
+    /// Drops all the references, e.g. when seeking.
+    pub fn clear(&mut self) {
+        self.refs = Default::default();
+    }
+
+    /// Updates the references after `frame` has been decoded into `decoded`,
+    /// and returns the new references.
+    ///
+    /// As in libvpx, the copies into the alternate and golden references are
+    /// applied first, in that order, followed by the refreshes with the
+    /// decoded frame. The sign biases are the ones signaled by `frame`.
+    pub fn update(&mut self, frame: &Frame, decoded: T) -> anyhow::Result<&ReferenceBuffers<T>> {
+        let hdr = &frame.header;
+
+        if hdr.key_frame {
+            self.refs = ReferenceBuffers {
+                last: Some(decoded.clone()),
+                golden: Some(decoded.clone()),
+                alt_ref: Some(decoded),
+                sign_bias_golden: false,
+                sign_bias_alternate: false,
+            };
+
+            return Ok(&self.refs);
+        }
+
+        if self.refs.last.is_none() {
+            return Err(anyhow!("Broken data: inter frame without a key frame"));
+        }
+
+        self.apply_copies(hdr)?;
+
+        if hdr.refresh_golden_frame {
+            self.refs.golden = Some(decoded.clone());
+        }
+
+        if hdr.refresh_alternate_frame {
+            self.refs.alt_ref = Some(decoded.clone());
+        }
+
+        if hdr.refresh_last {
+            self.refs.last = Some(decoded);
+        }
+
+        self.refs.sign_bias_golden = hdr.sign_bias_golden;
+        self.refs.sign_bias_alternate = hdr.sign_bias_alternate;
+
+        Ok(&self.refs)
+    }
+
+    fn apply_copies(&mut self, hdr: &Header) -> anyhow::Result<()> {
+        match hdr.copy_buffer_to_alternate {
+            0 => (),
+            1 => self.refs.alt_ref = self.refs.last.clone(),
+            2 => self.refs.alt_ref = self.refs.golden.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Broken data: invalid copy_buffer_to_alternate {}",
+                    hdr.copy_buffer_to_alternate
+                ))
+            }
+        }
+
+        match hdr.copy_buffer_to_golden {
+            0 => (),
+            1 => self.refs.golden = self.refs.last.clone(),
+            2 => self.refs.golden = self.refs.alt_ref.clone(),
+            _ => {
+                return Err(anyhow!(
+                    "Broken data: invalid copy_buffer_to_golden {}",
+                    hdr.copy_buffer_to_golden
+                ))
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::vp8::parser::Parser;
+
+    const VP8_TEST_0_INTRA: &[u8] = include_bytes!("test_data/vp8-parser-test-0-intra.bin");
+    const VP8_TEST_0_INTER: &[u8] = include_bytes!("test_data/vp8-parser-test-0-inter.bin");
+
+    #[test]
+    fn gst_frames() {
+        let mut parser = Parser::default();
+        let mut manager = ReferenceManager::default();
+
+        let inter = parser.parse_frame(VP8_TEST_0_INTER).unwrap();
+        assert!(manager.update(&inter, 0).is_err());
+
+        let intra = parser.parse_frame(VP8_TEST_0_INTRA).unwrap();
+        let refs = manager.update(&intra, 1).unwrap();
+        assert_eq!(
+            (refs.last, refs.golden, refs.alt_ref),
+            (Some(1), Some(1), Some(1))
+        );
+
+        let inter = parser.parse_frame(VP8_TEST_0_INTER).unwrap();
+        let refs = manager.update(&inter, 2).unwrap();
+        assert_eq!(refs.last, Some(2));
+    }
+
+    #[test]
+    fn copy_before_refresh() {
+        let mut parser = Parser::default();
+        let mut manager = ReferenceManager::default();
+
+        let mut frame = parser.parse_frame(VP8_TEST_0_INTRA).unwrap();
+        manager.update(&frame, 1).unwrap();
+
+        // Refresh the last and alternate references.
+        frame.header = Header {
+            refresh_last: true,
+            refresh_alternate_frame: true,
+            sign_bias_alternate: true,
+            ..Default::default()
+        };
+        let refs = manager.update(&frame, 2).unwrap();
+        assert_eq!(
+            (refs.last, refs.golden, refs.alt_ref),
+            (Some(2), Some(1), Some(2))
+        );
+        assert!(!refs.sign_bias_golden);
+        assert!(refs.sign_bias_alternate);
+
+        // The golden frame is copied into the alternate reference, which is
+        // then copied into the golden reference before it is refreshed.
+        frame.header = Header {
+            refresh_last: true,
+            copy_buffer_to_alternate: 2,
+            copy_buffer_to_golden: 2,
+            ..Default::default()
+        };
+        let refs = manager.update(&frame, 3).unwrap();
+        assert_eq!(
+            (refs.last, refs.golden, refs.alt_ref),
+            (Some(3), Some(1), Some(1))
+        );
+        assert!(!refs.sign_bias_alternate);
+
+        // The last frame is copied into the golden reference before being
+        // refreshed, while the alternate reference is refreshed.
+        frame.header = Header {
+            refresh_last: true,
+            refresh_alternate_frame: true,
+            copy_buffer_to_golden: 1,
+            ..Default::default()
+        };
+        let refs = manager.update(&frame, 4).unwrap();
+        assert_eq!(
+            (refs.last, refs.golden, refs.alt_ref),
+            (Some(4), Some(3), Some(4))
+        );
+
+        frame.header.copy_buffer_to_golden = 3;
+        assert!(manager.update(&frame, 5).is_err());
+
+        manager.clear();
+        assert_eq!(manager.references(), &ReferenceBuffers::default());
+    }
+}
@@ -0,0 +1,594 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::io::Write;
+
+use thiserror::Error;
+
+use crate::codec::vp8::bool_encoder::BoolEncoder;
+use crate::codec::vp8::bool_encoder::BoolEncoderError;
+use crate::codec::vp8::parser::Header;
+use crate::codec::vp8::parser::MbLfAdjustments;
+use crate::codec::vp8::parser::QuantIndices;
+use crate::codec::vp8::parser::Segmentation;
+use crate::codec::vp8::probs::COEFF_DEFAULT_PROBS;
+use crate::codec::vp8::probs::COEFF_UPDATE_PROBS;
+use crate::codec::vp8::probs::MV_DEFAULT_PROBS;
+use crate::codec::vp8::probs::MV_UPDATE_PROBS;
+use crate::codec::vp8::probs::NK_UV_MODE_PROBS;
+use crate::codec::vp8::probs::NK_Y_MODE_PROBS;
+
+/// The start code of the key frames' uncompressed data chunk.
+const START_CODE: [u8; 3] = [0x9d, 0x01, 0x2a];
+
+mod private {
+    pub trait HeaderStruct {}
+}
+
+impl private::HeaderStruct for Header {}
+
+#[derive(Error, Debug)]
+pub enum SynthesizerError {
+    #[error("tried to synthesize unsupported settings")]
+    Unsupported,
+    #[error("invalid syntax element value {0}")]
+    InvalidSyntaxElementValue(&'static str),
+    #[error(transparent)]
+    BoolEncoder(#[from] BoolEncoderError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type SynthesizerResult<T> = Result<T, SynthesizerError>;
+
+/// A writer for VP8 frame headers. It serializes the structures produced by
+/// [`crate::codec::vp8::parser::Parser`] back to the bitstream syntax.
+///
+/// Probability updates are coded against the defaults of RFC 6386, i.e. the
+/// state of the decoder after a key frame or after a frame with
+/// `refresh_entropy_probs` unset. An update is signaled for every probability
+/// of the header that differs from its default value.
+pub struct Synthesizer<'h, H: private::HeaderStruct, W: Write> {
+    writer: W,
+    hdr: &'h H,
+    bd: BoolEncoder,
+}
+
+impl<'h, H, W> Synthesizer<'h, H, W>
+where
+    H: private::HeaderStruct,
+    W: Write,
+{
+    fn new(writer: W, hdr: &'h H) -> Self {
+        Self {
+            writer,
+            hdr,
+            bd: BoolEncoder::new(),
+        }
+    }
+
+    fn flag(&mut self, value: bool) {
+        self.bd.write_bool(value)
+    }
+
+    fn uint<U: TryInto<u32>>(&mut self, value: U, bits: usize) -> SynthesizerResult<()> {
+        Ok(self.bd.write_uint(value, bits)?)
+    }
+
+    fn sint<U: TryInto<i32>>(&mut self, value: U, bits: usize) -> SynthesizerResult<()> {
+        Ok(self.bd.write_sint(value, bits)?)
+    }
+
+    /// Writes an optional signed value, preceded by its update flag. Zero
+    /// values are not sent, as this is what the decoder infers when the flag
+    /// is not set.
+    fn optional_sint(&mut self, value: i8, bits: usize) -> SynthesizerResult<()> {
+        self.flag(value != 0);
+        if value != 0 {
+            self.sint(value, bits)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'h, W: Write> Synthesizer<'h, Header, W> {
+    /// Writes the uncompressed data chunk of `hdr` followed by a first
+    /// partition made of its frame header only, and returns the number of
+    /// bytes written.
+    ///
+    /// The segmentation and loop filter adjustments are not part of `hdr` but
+    /// are kept live across frames, hence they are passed separately, see
+    /// [`crate::codec::vp8::parser::Parser::segmentation`] and
+    /// [`crate::codec::vp8::parser::Parser::mb_lf_adjust`].
+    ///
+    /// The `first_part_size` written in the frame tag is the size of the
+    /// synthesized partition, not `hdr.first_part_size`. Use
+    /// [`Self::synthesize_with_macroblock_data`] to code the macroblock data
+    /// in the same partition.
+    pub fn synthesize(
+        hdr: &'h Header,
+        segmentation: &Segmentation,
+        mb_lf_adjust: &MbLfAdjustments,
+        writer: W,
+    ) -> SynthesizerResult<usize> {
+        Self::synthesize_with_macroblock_data(hdr, segmentation, mb_lf_adjust, writer, |_| Ok(()))
+    }
+
+    /// Same as [`Self::synthesize`], but `macroblock_data` is called with the
+    /// bool encoder right after the frame header, so that the rest of the
+    /// first partition is coded in the same arithmetic-coded stream. The
+    /// encoder is only flushed afterwards.
+    pub fn synthesize_with_macroblock_data<F>(
+        hdr: &'h Header,
+        segmentation: &Segmentation,
+        mb_lf_adjust: &MbLfAdjustments,
+        writer: W,
+        macroblock_data: F,
+    ) -> SynthesizerResult<usize>
+    where
+        F: FnOnce(&mut BoolEncoder) -> SynthesizerResult<()>,
+    {
+        let mut s = Self::new(writer, hdr);
+
+        s.frame_header(segmentation, mb_lf_adjust)?;
+        macroblock_data(&mut s.bd)?;
+
+        let data = std::mem::take(&mut s.bd).finish();
+        let chunk_size = s.uncompressed_data_chunk(data.len())?;
+        s.writer.write_all(&data)?;
+
+        Ok(chunk_size + data.len())
+    }
+
+    /// Writes the sizes of the DCT partitions of `hdr`, as found right after
+    /// the first partition. The size of the last partition is implied and
+    /// thus not written.
+    pub fn synthesize_partition_sizes(hdr: &'h Header, mut writer: W) -> SynthesizerResult<()> {
+        let num_partitions = hdr.num_dct_partitions();
+        if num_partitions > hdr.partition_size.len() {
+            return Err(SynthesizerError::InvalidSyntaxElementValue(
+                "log2_nbr_of_dct_partitions",
+            ));
+        }
+
+        for size in &hdr.partition_size[..num_partitions - 1] {
+            if *size >= 1 << 24 {
+                return Err(SynthesizerError::InvalidSyntaxElementValue(
+                    "partition_size",
+                ));
+            }
+
+            writer.write_all(&size.to_le_bytes()[..3])?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the frame tag for a first partition of `first_part_size` bytes
+    /// and, for key frames, the start code and the dimensions. Returns the
+    /// number of bytes written.
+    fn uncompressed_data_chunk(&mut self, first_part_size: usize) -> SynthesizerResult<usize> {
+        let hdr = self.hdr;
+
+        if hdr.version > 7 {
+            return Err(SynthesizerError::InvalidSyntaxElementValue("version"));
+        }
+
+        if first_part_size >= 1 << 19 {
+            return Err(SynthesizerError::InvalidSyntaxElementValue(
+                "first_part_size",
+            ));
+        }
+
+        let frame_tag = u32::from(!hdr.key_frame)
+            | u32::from(hdr.version) << 1
+            | u32::from(hdr.show_frame) << 4
+            | (first_part_size as u32) << 5;
+
+        self.writer.write_all(&frame_tag.to_le_bytes()[..3])?;
+
+        if !hdr.key_frame {
+            return Ok(3);
+        }
+
+        if hdr.width >= 1 << 14 || hdr.height >= 1 << 14 {
+            return Err(SynthesizerError::InvalidSyntaxElementValue("width/height"));
+        }
+
+        if hdr.horiz_scale_code > 3 || hdr.vert_scale_code > 3 {
+            return Err(SynthesizerError::InvalidSyntaxElementValue("scale_code"));
+        }
+
+        let width = hdr.width | u16::from(hdr.horiz_scale_code) << 14;
+        let height = hdr.height | u16::from(hdr.vert_scale_code) << 14;
+
+        self.writer.write_all(&START_CODE)?;
+        self.writer.write_all(&width.to_le_bytes())?;
+        self.writer.write_all(&height.to_le_bytes())?;
+
+        Ok(10)
+    }
+
+    fn update_segmentation(&mut self, seg: &Segmentation) -> SynthesizerResult<()> {
+        self.flag(seg.segmentation_enabled);
+        if !seg.segmentation_enabled {
+            return Ok(());
+        }
+
+        self.flag(seg.update_mb_segmentation_map);
+        self.flag(seg.update_segment_feature_data);
+
+        if seg.update_segment_feature_data {
+            self.flag(seg.segment_feature_mode);
+
+            for value in seg.quantizer_update_value {
+                self.optional_sint(value, 7)?;
+            }
+
+            for value in seg.lf_update_value {
+                self.optional_sint(value, 6)?;
+            }
+        }
+
+        if seg.update_mb_segmentation_map {
+            // The decoder infers 255 when the update flag is not set.
+            for prob in seg.segment_prob {
+                self.flag(prob != 255);
+                if prob != 255 {
+                    self.uint(prob, 8)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mb_lf_adjustments(&mut self, adj: &MbLfAdjustments) -> SynthesizerResult<()> {
+        self.flag(adj.loop_filter_adj_enable);
+        if !adj.loop_filter_adj_enable {
+            return Ok(());
+        }
+
+        self.flag(adj.mode_ref_lf_delta_update);
+        if !adj.mode_ref_lf_delta_update {
+            return Ok(());
+        }
+
+        // The deltas are kept live across frames, so send all of them in
+        // order not to depend on the decoder's state.
+        for value in adj.ref_frame_delta.into_iter().chain(adj.mb_mode_delta) {
+            self.flag(true);
+            self.sint(value, 6)?;
+        }
+
+        Ok(())
+    }
+
+    fn quant_indices(&mut self, q: &QuantIndices) -> SynthesizerResult<()> {
+        self.uint(q.y_ac_qi, 7)?;
+
+        for delta in [
+            q.y_dc_delta,
+            q.y2_dc_delta,
+            q.y2_ac_delta,
+            q.uv_dc_delta,
+            q.uv_ac_delta,
+        ] {
+            self.optional_sint(delta, 4)?;
+        }
+
+        Ok(())
+    }
+
+    fn token_prob_update(&mut self) -> SynthesizerResult<()> {
+        let coeff_prob = &self.hdr.coeff_prob;
+
+        for (i, vi) in coeff_prob.iter().enumerate() {
+            for (j, vj) in vi.iter().enumerate() {
+                for (k, vk) in vj.iter().enumerate() {
+                    for (l, prob) in vk.iter().enumerate() {
+                        let update = *prob != COEFF_DEFAULT_PROBS[i][j][k][l];
+                        self.bd
+                            .write_bool_with_prob(update, COEFF_UPDATE_PROBS[i][j][k][l]);
+                        if update {
+                            self.uint(*prob, 8)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn mv_prob_update(&mut self) -> SynthesizerResult<()> {
+        let mv_prob = &self.hdr.mv_prob;
+
+        for (i, vi) in mv_prob.iter().enumerate() {
+            for (j, prob) in vi.iter().enumerate() {
+                let update = *prob != MV_DEFAULT_PROBS[i][j];
+                self.bd.write_bool_with_prob(update, MV_UPDATE_PROBS[i][j]);
+                if !update {
+                    continue;
+                }
+
+                // Updated probabilities are coded using 7 bits, so only 1 and
+                // the even values can be represented.
+                let mv_prob_update = match *prob {
+                    1 => 0,
+                    prob if prob != 0 && prob % 2 == 0 => prob >> 1,
+                    _ => return Err(SynthesizerError::InvalidSyntaxElementValue("mv_prob")),
+                };
+
+                self.uint(mv_prob_update, 7)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn frame_header(
+        &mut self,
+        segmentation: &Segmentation,
+        mb_lf_adjust: &MbLfAdjustments,
+    ) -> SynthesizerResult<()> {
+        let hdr = self.hdr;
+
+        if hdr.key_frame {
+            self.flag(hdr.color_space);
+            self.flag(hdr.clamping_type);
+        }
+
+        self.update_segmentation(segmentation)?;
+
+        self.flag(hdr.filter_type);
+        self.uint(hdr.loop_filter_level, 6)?;
+        self.uint(hdr.sharpness_level, 3)?;
+
+        self.mb_lf_adjustments(mb_lf_adjust)?;
+
+        self.uint(hdr.log2_nbr_of_dct_partitions, 2)?;
+
+        self.quant_indices(&hdr.quant_indices)?;
+
+        if hdr.key_frame {
+            self.flag(hdr.refresh_entropy_probs);
+        } else {
+            self.flag(hdr.refresh_golden_frame);
+            self.flag(hdr.refresh_alternate_frame);
+
+            if !hdr.refresh_golden_frame {
+                self.uint(hdr.copy_buffer_to_golden, 2)?;
+            }
+
+            if !hdr.refresh_alternate_frame {
+                self.uint(hdr.copy_buffer_to_alternate, 2)?;
+            }
+
+            self.flag(hdr.sign_bias_golden);
+            self.flag(hdr.sign_bias_alternate);
+            self.flag(hdr.refresh_entropy_probs);
+            self.flag(hdr.refresh_last);
+        }
+
+        self.token_prob_update()?;
+
+        self.flag(hdr.mb_no_coeff_skip);
+        if hdr.mb_no_coeff_skip {
+            self.uint(hdr.prob_skip_false, 8)?;
+        }
+
+        if !hdr.key_frame {
+            self.uint(hdr.prob_intra, 8)?;
+            self.uint(hdr.prob_last, 8)?;
+            self.uint(hdr.prob_golden, 8)?;
+
+            let intra_16x16_prob_update_flag = hdr.mode_probs.intra_16x16_prob != NK_Y_MODE_PROBS;
+            self.flag(intra_16x16_prob_update_flag);
+            if intra_16x16_prob_update_flag {
+                for prob in hdr.mode_probs.intra_16x16_prob {
+                    self.uint(prob, 8)?;
+                }
+            }
+
+            let intra_chroma_prob_update_flag =
+                hdr.mode_probs.intra_chroma_prob != NK_UV_MODE_PROBS;
+            self.flag(intra_chroma_prob_update_flag);
+            if intra_chroma_prob_update_flag {
+                for prob in hdr.mode_probs.intra_chroma_prob {
+                    self.uint(prob, 8)?;
+                }
+            }
+
+            self.mv_prob_update()?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::vp8::bool_decoder::BoolDecoder;
+    use crate::codec::vp8::bool_decoder::BoolDecoderState;
+    use crate::codec::vp8::parser::Parser;
+
+    const VP8_TEST_0_INTRA: &[u8] = include_bytes!("test_data/vp8-parser-test-0-intra.bin");
+    const VP8_TEST_0_INTER: &[u8] = include_bytes!("test_data/vp8-parser-test-0-inter.bin");
+
+    /// Synthesizes the header of `hdr` in place of the one of `frame`, then
+    /// checks that a fresh parser gets back the same header and state, and
+    /// that the macroblock data of `frame` can still be decoded after it.
+    fn synthesize_and_compare(
+        frame: &[u8],
+        hdr: &Header,
+        segmentation: &Segmentation,
+        mb_lf_adjust: &MbLfAdjustments,
+    ) {
+        let first_part_start = usize::from(hdr.data_chunk_size);
+        let first_part_end = first_part_start + hdr.first_part_size as usize;
+        let first_part = &frame[first_part_start..first_part_end];
+
+        // Read the rest of the first partition as plain bools, which the bool
+        // encoder codes back into the same stream.
+        let state = BoolDecoderState {
+            range: hdr.bd_range,
+            value: hdr.bd_value,
+            count: hdr.bd_count,
+        };
+        let mut bd = BoolDecoder::resume(first_part, &state, hdr.header_size as usize);
+        let mb_data = (0..first_part.len() * 8 - hdr.header_size as usize)
+            .map(|_| bd.read_bool().unwrap())
+            .collect::<Vec<_>>();
+
+        let mut buf = Vec::new();
+        let size = Synthesizer::<'_, Header, _>::synthesize_with_macroblock_data(
+            hdr,
+            segmentation,
+            mb_lf_adjust,
+            &mut buf,
+            |bd| {
+                for &bit in &mb_data {
+                    bd.write_bool(bit);
+                }
+                Ok(())
+            },
+        )
+        .unwrap();
+        assert_eq!(size, buf.len());
+
+        let mut partition_sizes = Vec::new();
+        Synthesizer::<'_, Header, _>::synthesize_partition_sizes(hdr, &mut partition_sizes)
+            .unwrap();
+        let partitions_start = first_part_end + partition_sizes.len();
+        assert_eq!(&frame[first_part_end..partitions_start], &partition_sizes);
+
+        buf.extend_from_slice(&frame[first_part_end..]);
+
+        let mut parser = Parser::default();
+        let reparsed = parser.parse_frame(&buf).unwrap().header;
+
+        // The bool decoder state after the frame header depends on how it was
+        // coded, so it is checked by decoding the macroblock data below.
+        let mut expected = hdr.clone();
+        expected.first_part_size = (size - first_part_start) as u32;
+        expected.bd_range = reparsed.bd_range;
+        expected.bd_value = reparsed.bd_value;
+        expected.bd_count = reparsed.bd_count;
+        expected.header_size = reparsed.header_size;
+        assert_eq!(&reparsed, &expected);
+        assert_eq!(parser.segmentation(), segmentation);
+        assert_eq!(parser.mb_lf_adjust(), mb_lf_adjust);
+
+        let state = BoolDecoderState {
+            range: reparsed.bd_range,
+            value: reparsed.bd_value,
+            count: reparsed.bd_count,
+        };
+        let first_part = &buf[first_part_start..size];
+        let mut bd = BoolDecoder::resume(first_part, &state, reparsed.header_size as usize);
+        for &bit in &mb_data {
+            assert_eq!(bd.read_bool(), Ok(bit));
+        }
+    }
+
+    #[test]
+    fn synthesize_intra() {
+        let mut parser = Parser::default();
+        let frame = parser.parse_frame(VP8_TEST_0_INTRA).unwrap();
+
+        synthesize_and_compare(
+            VP8_TEST_0_INTRA,
+            &frame.header,
+            parser.segmentation(),
+            parser.mb_lf_adjust(),
+        );
+    }
+
+    #[test]
+    fn synthesize_inter() {
+        let mut parser = Parser::default();
+        let frame = parser.parse_frame(VP8_TEST_0_INTER).unwrap();
+
+        synthesize_and_compare(
+            VP8_TEST_0_INTER,
+            &frame.header,
+            parser.segmentation(),
+            parser.mb_lf_adjust(),
+        );
+    }
+
+    #[test]
+    fn synthesize_inter_params() {
+        let mut parser = Parser::default();
+        let mut hdr = parser.parse_frame(VP8_TEST_0_INTER).unwrap().header;
+
+        hdr.show_frame = false;
+        hdr.loop_filter_level = 42;
+        hdr.sharpness_level = 5;
+        hdr.quant_indices = QuantIndices {
+            y_ac_qi: 100,
+            y_dc_delta: -15,
+            y2_dc_delta: 0,
+            y2_ac_delta: 7,
+            uv_dc_delta: 1,
+            uv_ac_delta: -1,
+        };
+        hdr.refresh_golden_frame = false;
+        hdr.copy_buffer_to_golden = 2;
+        hdr.refresh_alternate_frame = false;
+        hdr.copy_buffer_to_alternate = 1;
+        hdr.sign_bias_alternate = true;
+        hdr.coeff_prob[3][7][2][10] = 1;
+        hdr.mode_probs.intra_16x16_prob = [1, 2, 3, 4];
+        hdr.mv_prob[0][0] = 1;
+        hdr.mv_prob[1][18] = 254;
+
+        let segmentation = Segmentation {
+            segmentation_enabled: true,
+            update_mb_segmentation_map: true,
+            update_segment_feature_data: true,
+            segment_feature_mode: true,
+            quantizer_update_value: [0, -127, 127, 3],
+            lf_update_value: [-63, 0, 63, 1],
+            segment_prob: [255, 0, 128],
+        };
+
+        let mb_lf_adjust = MbLfAdjustments {
+            loop_filter_adj_enable: true,
+            mode_ref_lf_delta_update: true,
+            ref_frame_delta: [0, 1, -2, 63],
+            mb_mode_delta: [-63, 0, 5, -5],
+        };
+
+        synthesize_and_compare(VP8_TEST_0_INTER, &hdr, &segmentation, &mb_lf_adjust);
+
+        // Values that cannot be represented.
+        hdr.mv_prob[1][18] = 3;
+        assert!(matches!(
+            Synthesizer::<'_, Header, _>::synthesize(
+                &hdr,
+                &segmentation,
+                &mb_lf_adjust,
+                &mut Vec::new()
+            ),
+            Err(SynthesizerError::InvalidSyntaxElementValue("mv_prob"))
+        ));
+
+        hdr.quant_indices.y_dc_delta = 16;
+        assert!(matches!(
+            Synthesizer::<'_, Header, _>::synthesize(
+                &hdr,
+                &segmentation,
+                &mb_lf_adjust,
+                &mut Vec::new()
+            ),
+            Err(SynthesizerError::BoolEncoder(
+                BoolEncoderError::ValueTooLarge(16, 4)
+            ))
+        ));
+    }
+}
@@ -2,5 +2,11 @@
 // Use of this source code is governed by a BSD-style license that can be
 // found in the LICENSE file.
 
+pub mod bool_decoder;
+pub mod frame_context;
 pub mod lookups;
 pub mod parser;
+pub mod probs;
+pub mod references;
+pub mod synthesizer;
+pub mod vpcc;
@@ -0,0 +1,185 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A VP9 boolean decoder, as per "9.2 Boolean decoding process".
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BoolDecoderError {
+    #[error("end of input reached")]
+    EndOfInput,
+    #[error("the marker bit is not zero")]
+    InvalidMarker,
+    #[error("the padding bits are not zero")]
+    NonZeroPadding,
+}
+
+pub type BoolDecoderResult<T> = std::result::Result<T, BoolDecoderError>;
+
+/// The decoder state.
+///
+/// The bits of the data are consumed one at a time, as the decoding process
+/// of the specification does. This is only used for the compressed header,
+/// for which this is fast enough.
+pub struct BoolDecoder<'a> {
+    data: &'a [u8],
+    /// The position of the next bit to read from `data`.
+    pos: usize,
+    /// Same as BoolValue in the specification.
+    value: u32,
+    /// Same as BoolRange in the specification.
+    range: u32,
+    /// Same as BoolMaxBits in the specification.
+    max_bits: usize,
+}
+
+impl<'a> BoolDecoder<'a> {
+    /// Creates a new instance over `data`, which is the whole payload of the
+    /// coded bools (e.g. `header_size_in_bytes` bytes for the compressed
+    /// header). This is init_bool() in the specification.
+    pub fn new(data: &'a [u8]) -> BoolDecoderResult<Self> {
+        if data.is_empty() {
+            return Err(BoolDecoderError::EndOfInput);
+        }
+
+        let mut bd = Self {
+            data,
+            pos: 8,
+            value: u32::from(data[0]),
+            range: 255,
+            max_bits: 8 * data.len() - 8,
+        };
+
+        if bd.read_bool()? {
+            return Err(BoolDecoderError::InvalidMarker);
+        }
+
+        Ok(bd)
+    }
+
+    /// Reads the next bit of `data`, returning zero once `max_bits` bits have
+    /// been consumed.
+    fn read_data_bit(&mut self) -> u32 {
+        if self.max_bits == 0 {
+            return 0;
+        }
+
+        let bit = (self.data[self.pos / 8] >> (7 - self.pos % 8)) & 1;
+        self.pos += 1;
+        self.max_bits -= 1;
+
+        u32::from(bit)
+    }
+
+    /// Reads a boolean from the coded stream. The probability of the boolean
+    /// to be false is probability / 256.
+    pub fn read_bool_with_prob(&mut self, probability: u8) -> BoolDecoderResult<bool> {
+        let split = 1 + (((self.range - 1) * u32::from(probability)) >> 8);
+
+        let bit = if self.value < split {
+            self.range = split;
+            false
+        } else {
+            self.range -= split;
+            self.value -= split;
+            true
+        };
+
+        while self.range < 128 {
+            self.value = (self.value << 1) | self.read_data_bit();
+            self.range <<= 1;
+        }
+
+        Ok(bit)
+    }
+
+    /// Reads a boolean from the coded stream with an even probability.
+    pub fn read_bool(&mut self) -> BoolDecoderResult<bool> {
+        self.read_bool_with_prob(128)
+    }
+
+    /// Reads a "literal", that is, a `nbits`-wide unsigned value whose bits
+    /// come high- to low-order, with each bit encoded at probability 1/2. This
+    /// is L(n) in the specification.
+    ///
+    /// # Panics
+    ///
+    /// Will panic if `nbits > 32`.
+    pub fn read_literal(&mut self, nbits: usize) -> BoolDecoderResult<u32> {
+        assert!(nbits <= 32);
+
+        let mut ret = 0u32;
+        for _ in 0..nbits {
+            ret = (ret << 1) | u32::from(self.read_bool()?);
+        }
+
+        Ok(ret)
+    }
+
+    /// Consumes the decoder, checking that the padding bits are zero. This is
+    /// exit_bool() in the specification.
+    pub fn finish(mut self) -> BoolDecoderResult<()> {
+        while self.max_bits > 0 {
+            if self.read_data_bit() != 0 {
+                return Err(BoolDecoderError::NonZeroPadding);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::vp8::bool_encoder::BoolEncoder;
+
+    #[test]
+    fn decode_bools_and_literals() {
+        // The arithmetic coding is the same as VP8's.
+        let mut be = BoolEncoder::new();
+        be.write_bool(false);
+        for i in 0..100 {
+            be.write_bool_with_prob(i % 3 == 0, i as u8 + 1);
+        }
+        be.write_uint(0xabcdu32, 16).unwrap();
+        be.write_uint(5u32, 3).unwrap();
+        let data = be.finish();
+
+        let mut bd = BoolDecoder::new(&data).unwrap();
+        for i in 0..100 {
+            assert_eq!(bd.read_bool_with_prob(i as u8 + 1), Ok(i % 3 == 0));
+        }
+        assert_eq!(bd.read_literal(16), Ok(0xabcd));
+        assert_eq!(bd.read_literal(3), Ok(5));
+        assert_eq!(bd.finish(), Ok(()));
+    }
+
+    #[test]
+    fn invalid_marker_and_padding() {
+        let mut be = BoolEncoder::new();
+        be.write_bool(true);
+        let data = be.finish();
+        assert_eq!(
+            BoolDecoder::new(&data).err(),
+            Some(BoolDecoderError::InvalidMarker)
+        );
+
+        let mut be = BoolEncoder::new();
+        be.write_bool(false);
+        be.write_bool(true);
+        let mut data = be.finish();
+        data.push(0x01);
+
+        let mut bd = BoolDecoder::new(&data).unwrap();
+        assert_eq!(bd.read_bool(), Ok(true));
+        assert_eq!(bd.finish(), Err(BoolDecoderError::NonZeroPadding));
+
+        assert_eq!(
+            BoolDecoder::new(&[]).err(),
+            Some(BoolDecoderError::EndOfInput)
+        );
+    }
+}
@@ -0,0 +1,778 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! VP9 probability contexts. This implements the bookkeeping that a stateless
+//! decoder needs to perform between frames: the four saved frame contexts,
+//! forward updates from the compressed header and backward adaptation as per
+//! "8.4 Probability adaptation process".
+
+use anyhow::anyhow;
+
+use crate::codec::vp9::parser::update_prob;
+use crate::codec::vp9::parser::CoefProbs;
+use crate::codec::vp9::parser::CompressedHeader;
+use crate::codec::vp9::parser::FrameType;
+use crate::codec::vp9::parser::Header;
+use crate::codec::vp9::parser::InterpolationFilter;
+use crate::codec::vp9::parser::TxMode;
+use crate::codec::vp9::parser::BLOCK_SIZE_GROUPS;
+use crate::codec::vp9::parser::BLOCK_TYPES;
+use crate::codec::vp9::parser::CLASS0_SIZE;
+use crate::codec::vp9::parser::COEF_BANDS;
+use crate::codec::vp9::parser::COMP_MODE_CONTEXTS;
+use crate::codec::vp9::parser::INTERP_FILTER_CONTEXTS;
+use crate::codec::vp9::parser::INTER_MODES;
+use crate::codec::vp9::parser::INTER_MODE_CONTEXTS;
+use crate::codec::vp9::parser::INTRA_MODES;
+use crate::codec::vp9::parser::IS_INTER_CONTEXTS;
+use crate::codec::vp9::parser::MV_CLASSES;
+use crate::codec::vp9::parser::MV_FR_SIZE;
+use crate::codec::vp9::parser::MV_JOINTS;
+use crate::codec::vp9::parser::MV_OFFSET_BITS;
+use crate::codec::vp9::parser::PARTITION_CONTEXTS;
+use crate::codec::vp9::parser::PARTITION_TYPES;
+use crate::codec::vp9::parser::PREV_COEF_CONTEXTS;
+use crate::codec::vp9::parser::REF_CONTEXTS;
+use crate::codec::vp9::parser::REF_TYPES;
+use crate::codec::vp9::parser::SKIP_CONTEXTS;
+use crate::codec::vp9::parser::SWITCHABLE_FILTERS;
+use crate::codec::vp9::parser::TX_SIZES;
+use crate::codec::vp9::parser::TX_SIZE_CONTEXTS;
+use crate::codec::vp9::parser::UNCONSTRAINED_NODES;
+use crate::codec::vp9::probs::*;
+
+/// The number of frame contexts that can be saved.
+pub const FRAME_CONTEXTS: usize = 4;
+
+const COEF_COUNT_SAT: u32 = 24;
+const COEF_MAX_UPDATE_FACTOR: u32 = 112;
+const COEF_MAX_UPDATE_FACTOR_AFTER_KEY: u32 = 128;
+const MODE_MV_COUNT_SAT: u32 = 20;
+const MODE_MV_MAX_UPDATE_FACTOR: u32 = 128;
+
+// The trees used to adapt the probabilities of the non binary symbols. A value
+// lower than or equal to 0 is a leaf holding the negated symbol, any other
+// value is the index of the next node.
+const INTRA_MODE_TREE: [i8; 18] = [
+    0, 2, -9, 4, -1, 6, 8, 12, -2, 10, -4, -5, -3, 14, -8, 16, -6, -7,
+];
+const INTER_MODE_TREE: [i8; 6] = [-2, 2, 0, 4, -1, -3];
+const PARTITION_TREE: [i8; 6] = [0, 2, -1, 4, -2, -3];
+const INTERP_FILTER_TREE: [i8; 4] = [0, 2, -1, -2];
+const MV_JOINT_TREE: [i8; 6] = [0, 2, -1, 4, -2, -3];
+const MV_CLASS_TREE: [i8; 20] = [
+    0, 2, -1, 4, 6, 8, -2, -3, 10, 12, -4, -5, -6, 14, 16, 18, -7, -8, -9, -10,
+];
+const MV_FR_TREE: [i8; 6] = [0, 2, -1, 4, -2, -3];
+
+/// The probabilities used to decode a frame, and saved for later frames.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameContext {
+    pub tx_probs_8x8: [[u8; TX_SIZES - 3]; TX_SIZE_CONTEXTS],
+    pub tx_probs_16x16: [[u8; TX_SIZES - 2]; TX_SIZE_CONTEXTS],
+    pub tx_probs_32x32: [[u8; TX_SIZES - 1]; TX_SIZE_CONTEXTS],
+    pub coef_probs: CoefProbs,
+    pub skip_prob: [u8; SKIP_CONTEXTS],
+    pub inter_mode_probs: [[u8; INTER_MODES - 1]; INTER_MODE_CONTEXTS],
+    pub interp_filter_probs: [[u8; SWITCHABLE_FILTERS - 1]; INTERP_FILTER_CONTEXTS],
+    pub is_inter_prob: [u8; IS_INTER_CONTEXTS],
+    pub comp_mode_prob: [u8; COMP_MODE_CONTEXTS],
+    pub single_ref_prob: [[u8; 2]; REF_CONTEXTS],
+    pub comp_ref_prob: [u8; REF_CONTEXTS],
+    pub y_mode_probs: [[u8; INTRA_MODES - 1]; BLOCK_SIZE_GROUPS],
+    pub uv_mode_probs: [[u8; INTRA_MODES - 1]; INTRA_MODES],
+    pub partition_probs: [[u8; PARTITION_TYPES - 1]; PARTITION_CONTEXTS],
+    pub mv_joint_probs: [u8; MV_JOINTS - 1],
+    pub mv_sign_prob: [u8; 2],
+    pub mv_class_probs: [[u8; MV_CLASSES - 1]; 2],
+    pub mv_class0_bit_prob: [u8; 2],
+    pub mv_bits_prob: [[u8; MV_OFFSET_BITS]; 2],
+    pub mv_class0_fr_probs: [[[u8; MV_FR_SIZE - 1]; CLASS0_SIZE]; 2],
+    pub mv_fr_probs: [[u8; MV_FR_SIZE - 1]; 2],
+    pub mv_class0_hp_prob: [u8; 2],
+    pub mv_hp_prob: [u8; 2],
+}
+
+impl Default for FrameContext {
+    /// Returns the default probabilities, as loaded by
+    /// setup_past_independence().
+    fn default() -> Self {
+        Self {
+            tx_probs_8x8: DEFAULT_TX_PROBS_8X8,
+            tx_probs_16x16: DEFAULT_TX_PROBS_16X16,
+            tx_probs_32x32: DEFAULT_TX_PROBS_32X32,
+            coef_probs: DEFAULT_COEF_PROBS,
+            skip_prob: DEFAULT_SKIP_PROB,
+            inter_mode_probs: DEFAULT_INTER_MODE_PROBS,
+            interp_filter_probs: DEFAULT_INTERP_FILTER_PROBS,
+            is_inter_prob: DEFAULT_IS_INTER_PROB,
+            comp_mode_prob: DEFAULT_COMP_MODE_PROB,
+            single_ref_prob: DEFAULT_SINGLE_REF_PROB,
+            comp_ref_prob: DEFAULT_COMP_REF_PROB,
+            y_mode_probs: DEFAULT_Y_MODE_PROBS,
+            uv_mode_probs: DEFAULT_UV_MODE_PROBS,
+            partition_probs: DEFAULT_PARTITION_PROBS,
+            mv_joint_probs: DEFAULT_MV_JOINT_PROBS,
+            mv_sign_prob: DEFAULT_MV_SIGN_PROB,
+            mv_class_probs: DEFAULT_MV_CLASS_PROBS,
+            mv_class0_bit_prob: DEFAULT_MV_CLASS0_BIT_PROB,
+            mv_bits_prob: DEFAULT_MV_BITS_PROB,
+            mv_class0_fr_probs: DEFAULT_MV_CLASS0_FR_PROBS,
+            mv_fr_probs: DEFAULT_MV_FR_PROBS,
+            mv_class0_hp_prob: DEFAULT_MV_CLASS0_HP_PROB,
+            mv_hp_prob: DEFAULT_MV_HP_PROB,
+        }
+    }
+}
+
+/// The number of times each symbol was decoded in a frame, as per "8.4.2
+/// Syntax element counting process". These are usually reported by the
+/// hardware after decoding a frame, and drive the backward adaptation.
+///
+/// Binary symbols are indexed by their value.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SymbolCounts {
+    /// Indexed by block size group and luma intra mode.
+    pub intra_mode: [[u32; INTRA_MODES]; BLOCK_SIZE_GROUPS],
+    /// Indexed by luma intra mode and chroma intra mode.
+    pub uv_mode: [[u32; INTRA_MODES]; INTRA_MODES],
+    pub partition: [[u32; PARTITION_TYPES]; PARTITION_CONTEXTS],
+    /// Indexed by context and [`InterpolationFilter`].
+    pub interp_filter: [[u32; SWITCHABLE_FILTERS]; INTERP_FILTER_CONTEXTS],
+    /// Indexed by context and inter mode, in the NEARESTMV, NEARMV, ZEROMV,
+    /// NEWMV order.
+    pub inter_mode: [[u32; INTER_MODES]; INTER_MODE_CONTEXTS],
+    /// Indexed by context and transform size.
+    pub tx_8x8: [[u32; TX_SIZES - 2]; TX_SIZE_CONTEXTS],
+    /// Indexed by context and transform size.
+    pub tx_16x16: [[u32; TX_SIZES - 1]; TX_SIZE_CONTEXTS],
+    /// Indexed by context and transform size.
+    pub tx_32x32: [[u32; TX_SIZES]; TX_SIZE_CONTEXTS],
+    pub skip: [[u32; 2]; SKIP_CONTEXTS],
+    pub is_inter: [[u32; 2]; IS_INTER_CONTEXTS],
+    pub comp_mode: [[u32; 2]; COMP_MODE_CONTEXTS],
+    pub single_ref: [[[u32; 2]; 2]; REF_CONTEXTS],
+    pub comp_ref: [[u32; 2]; REF_CONTEXTS],
+    pub mv_joint: [u32; MV_JOINTS],
+    pub mv_sign: [[u32; 2]; 2],
+    pub mv_class: [[u32; MV_CLASSES]; 2],
+    pub mv_class0_bit: [[u32; CLASS0_SIZE]; 2],
+    pub mv_bits: [[[u32; 2]; MV_OFFSET_BITS]; 2],
+    pub mv_class0_fr: [[[u32; MV_FR_SIZE]; CLASS0_SIZE]; 2],
+    pub mv_fr: [[u32; MV_FR_SIZE]; 2],
+    pub mv_class0_hp: [[u32; 2]; 2],
+    pub mv_hp: [[u32; 2]; 2],
+    /// Indexed by transform size, plane type, reference, band, context and
+    /// token, where the token is either ZERO_TOKEN, ONE_TOKEN or any larger
+    /// token.
+    #[allow(clippy::type_complexity)]
+    pub token: [[[[[[u32; UNCONSTRAINED_NODES]; PREV_COEF_CONTEXTS]; COEF_BANDS]; REF_TYPES];
+        BLOCK_TYPES]; TX_SIZES],
+    /// Indexed like `token`, then by the value of more_coefs.
+    #[allow(clippy::type_complexity)]
+    pub more_coefs:
+        [[[[[[u32; 2]; PREV_COEF_CONTEXTS]; COEF_BANDS]; REF_TYPES]; BLOCK_TYPES]; TX_SIZES],
+}
+
+/// An implementation of merge_prob as per "8.4.2 Merge prob process".
+fn merge_prob(pre_prob: u8, ct0: u32, ct1: u32, count_sat: u32, max_update_factor: u32) -> u8 {
+    let den = ct0 + ct1;
+    let prob = if den == 0 {
+        128
+    } else {
+        ((u64::from(ct0) * 256 + u64::from(den >> 1)) / u64::from(den)).clamp(1, 255) as u32
+    };
+
+    let count = std::cmp::min(den, count_sat);
+    let factor = max_update_factor * count / count_sat;
+
+    ((u32::from(pre_prob) * (256 - factor) + prob * factor + 128) >> 8) as u8
+}
+
+fn merge_mode_mv_prob(pre_prob: u8, counts: &[u32; 2]) -> u8 {
+    merge_prob(
+        pre_prob,
+        counts[0],
+        counts[1],
+        MODE_MV_COUNT_SAT,
+        MODE_MV_MAX_UPDATE_FACTOR,
+    )
+}
+
+/// An implementation of merge_probs as per "8.4.2 Merge probs process".
+/// Returns the total count of the symbols below node `i`.
+fn merge_probs(tree: &[i8], i: usize, pre_probs: &[u8], counts: &[u32], probs: &mut [u8]) -> u32 {
+    let node_count = |node: i8, probs: &mut [u8]| {
+        if node <= 0 {
+            counts[usize::from(node.unsigned_abs())]
+        } else {
+            merge_probs(tree, node as usize, pre_probs, counts, probs)
+        }
+    };
+
+    let left = node_count(tree[i], probs);
+    let right = node_count(tree[i + 1], probs);
+
+    probs[i >> 1] = merge_prob(
+        pre_probs[i >> 1],
+        left,
+        right,
+        MODE_MV_COUNT_SAT,
+        MODE_MV_MAX_UPDATE_FACTOR,
+    );
+
+    left + right
+}
+
+fn merge_tree_probs(tree: &[i8], pre_probs: &[u8], counts: &[u32], probs: &mut [u8]) {
+    merge_probs(tree, 0, pre_probs, counts, probs);
+}
+
+/// Applies the deltas of the compressed header to `probs`.
+fn update_probs(probs: &mut [u8], deltas: &[u8]) {
+    for (prob, &delta) in probs.iter_mut().zip(deltas) {
+        *prob = update_prob(delta, *prob);
+    }
+}
+
+/// Replaces `probs` with the motion vector probabilities of the compressed
+/// header that are updated.
+fn update_mv_probs(probs: &mut [u8], new_probs: &[u8]) {
+    for (prob, &new_prob) in probs.iter_mut().zip(new_probs) {
+        if new_prob != 0 {
+            *prob = new_prob;
+        }
+    }
+}
+
+impl FrameContext {
+    /// Applies the forward updates signaled in the compressed header of a
+    /// frame.
+    pub fn apply_compressed_header(&mut self, chdr: &CompressedHeader) {
+        update_probs(
+            self.tx_probs_8x8.as_flattened_mut(),
+            chdr.tx_probs_8x8.as_flattened(),
+        );
+        update_probs(
+            self.tx_probs_16x16.as_flattened_mut(),
+            chdr.tx_probs_16x16.as_flattened(),
+        );
+        update_probs(
+            self.tx_probs_32x32.as_flattened_mut(),
+            chdr.tx_probs_32x32.as_flattened(),
+        );
+        update_probs(
+            self.coef_probs
+                .as_flattened_mut()
+                .as_flattened_mut()
+                .as_flattened_mut()
+                .as_flattened_mut()
+                .as_flattened_mut(),
+            chdr.coef_probs
+                .as_flattened()
+                .as_flattened()
+                .as_flattened()
+                .as_flattened()
+                .as_flattened(),
+        );
+        update_probs(&mut self.skip_prob, &chdr.skip_prob);
+        update_probs(
+            self.inter_mode_probs.as_flattened_mut(),
+            chdr.inter_mode_probs.as_flattened(),
+        );
+        update_probs(
+            self.interp_filter_probs.as_flattened_mut(),
+            chdr.interp_filter_probs.as_flattened(),
+        );
+        update_probs(&mut self.is_inter_prob, &chdr.is_inter_prob);
+        update_probs(&mut self.comp_mode_prob, &chdr.comp_mode_prob);
+        update_probs(
+            self.single_ref_prob.as_flattened_mut(),
+            chdr.single_ref_prob.as_flattened(),
+        );
+        update_probs(&mut self.comp_ref_prob, &chdr.comp_ref_prob);
+        update_probs(
+            self.y_mode_probs.as_flattened_mut(),
+            chdr.y_mode_probs.as_flattened(),
+        );
+        update_probs(
+            self.partition_probs.as_flattened_mut(),
+            chdr.partition_probs.as_flattened(),
+        );
+
+        update_mv_probs(&mut self.mv_joint_probs, &chdr.mv_joint_probs);
+        update_mv_probs(&mut self.mv_sign_prob, &chdr.mv_sign_prob);
+        update_mv_probs(
+            self.mv_class_probs.as_flattened_mut(),
+            chdr.mv_class_probs.as_flattened(),
+        );
+        update_mv_probs(&mut self.mv_class0_bit_prob, &chdr.mv_class0_bit_prob);
+        update_mv_probs(
+            self.mv_bits_prob.as_flattened_mut(),
+            chdr.mv_bits_prob.as_flattened(),
+        );
+        update_mv_probs(
+            self.mv_class0_fr_probs
+                .as_flattened_mut()
+                .as_flattened_mut(),
+            chdr.mv_class0_fr_probs.as_flattened().as_flattened(),
+        );
+        update_mv_probs(
+            self.mv_fr_probs.as_flattened_mut(),
+            chdr.mv_fr_probs.as_flattened(),
+        );
+        update_mv_probs(&mut self.mv_class0_hp_prob, &chdr.mv_class0_hp_prob);
+        update_mv_probs(&mut self.mv_hp_prob, &chdr.mv_hp_prob);
+    }
+
+    /// Adapts the coefficient probabilities as per "8.4.3 Coefficient
+    /// probability adaptation process". `pre` holds the probabilities before
+    /// the forward updates of the frame were applied.
+    pub fn adapt_coef_probs(
+        &mut self,
+        pre: &FrameContext,
+        counts: &SymbolCounts,
+        frame_is_intra: bool,
+        last_frame_type: Option<FrameType>,
+    ) {
+        let update_factor = if !frame_is_intra && last_frame_type == Some(FrameType::KeyFrame) {
+            COEF_MAX_UPDATE_FACTOR_AFTER_KEY
+        } else {
+            COEF_MAX_UPDATE_FACTOR
+        };
+
+        for t in 0..TX_SIZES {
+            for i in 0..BLOCK_TYPES {
+                for j in 0..REF_TYPES {
+                    for k in 0..COEF_BANDS {
+                        let max_l = if k == 0 { 3 } else { PREV_COEF_CONTEXTS };
+                        for l in 0..max_l {
+                            let token = &counts.token[t][i][j][k][l];
+                            let more_coefs = &counts.more_coefs[t][i][j][k][l];
+                            let branch_counts = [
+                                [more_coefs[0], more_coefs[1]],
+                                [token[0], token[1] + token[2]],
+                                [token[1], token[2]],
+                            ];
+
+                            for (m, [ct0, ct1]) in branch_counts.into_iter().enumerate() {
+                                self.coef_probs[t][i][j][k][l][m] = merge_prob(
+                                    pre.coef_probs[t][i][j][k][l][m],
+                                    ct0,
+                                    ct1,
+                                    COEF_COUNT_SAT,
+                                    update_factor,
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Adapts the probabilities of the other symbols as per "8.4.4 Non
+    /// coefficient probability adaptation process". `pre` holds the
+    /// probabilities before the forward updates of the frame were applied.
+    pub fn adapt_noncoef_probs(
+        &mut self,
+        pre: &FrameContext,
+        counts: &SymbolCounts,
+        hdr: &Header,
+        tx_mode: TxMode,
+    ) {
+        for i in 0..IS_INTER_CONTEXTS {
+            self.is_inter_prob[i] = merge_mode_mv_prob(pre.is_inter_prob[i], &counts.is_inter[i]);
+        }
+
+        for i in 0..COMP_MODE_CONTEXTS {
+            self.comp_mode_prob[i] =
+                merge_mode_mv_prob(pre.comp_mode_prob[i], &counts.comp_mode[i]);
+        }
+
+        for i in 0..REF_CONTEXTS {
+            self.comp_ref_prob[i] = merge_mode_mv_prob(pre.comp_ref_prob[i], &counts.comp_ref[i]);
+
+            for j in 0..2 {
+                self.single_ref_prob[i][j] =
+                    merge_mode_mv_prob(pre.single_ref_prob[i][j], &counts.single_ref[i][j]);
+            }
+        }
+
+        for i in 0..INTER_MODE_CONTEXTS {
+            merge_tree_probs(
+                &INTER_MODE_TREE,
+                &pre.inter_mode_probs[i],
+                &counts.inter_mode[i],
+                &mut self.inter_mode_probs[i],
+            );
+        }
+
+        for i in 0..BLOCK_SIZE_GROUPS {
+            merge_tree_probs(
+                &INTRA_MODE_TREE,
+                &pre.y_mode_probs[i],
+                &counts.intra_mode[i],
+                &mut self.y_mode_probs[i],
+            );
+        }
+
+        for i in 0..INTRA_MODES {
+            merge_tree_probs(
+                &INTRA_MODE_TREE,
+                &pre.uv_mode_probs[i],
+                &counts.uv_mode[i],
+                &mut self.uv_mode_probs[i],
+            );
+        }
+
+        for i in 0..PARTITION_CONTEXTS {
+            merge_tree_probs(
+                &PARTITION_TREE,
+                &pre.partition_probs[i],
+                &counts.partition[i],
+                &mut self.partition_probs[i],
+            );
+        }
+
+        if hdr.interpolation_filter == InterpolationFilter::Switchable {
+            for i in 0..INTERP_FILTER_CONTEXTS {
+                merge_tree_probs(
+                    &INTERP_FILTER_TREE,
+                    &pre.interp_filter_probs[i],
+                    &counts.interp_filter[i],
+                    &mut self.interp_filter_probs[i],
+                );
+            }
+        }
+
+        if tx_mode == TxMode::TxModeSelect {
+            for i in 0..TX_SIZE_CONTEXTS {
+                let c = &counts.tx_8x8[i];
+                self.tx_probs_8x8[i][0] = merge_mode_mv_prob(pre.tx_probs_8x8[i][0], c);
+
+                let c = &counts.tx_16x16[i];
+                let branch_counts = [[c[0], c[1] + c[2]], [c[1], c[2]]];
+                for (j, branch) in branch_counts.iter().enumerate() {
+                    self.tx_probs_16x16[i][j] =
+                        merge_mode_mv_prob(pre.tx_probs_16x16[i][j], branch);
+                }
+
+                let c = &counts.tx_32x32[i];
+                let branch_counts = [
+                    [c[0], c[1] + c[2] + c[3]],
+                    [c[1], c[2] + c[3]],
+                    [c[2], c[3]],
+                ];
+                for (j, branch) in branch_counts.iter().enumerate() {
+                    self.tx_probs_32x32[i][j] =
+                        merge_mode_mv_prob(pre.tx_probs_32x32[i][j], branch);
+                }
+            }
+        }
+
+        for i in 0..SKIP_CONTEXTS {
+            self.skip_prob[i] = merge_mode_mv_prob(pre.skip_prob[i], &counts.skip[i]);
+        }
+
+        merge_tree_probs(
+            &MV_JOINT_TREE,
+            &pre.mv_joint_probs,
+            &counts.mv_joint,
+            &mut self.mv_joint_probs,
+        );
+
+        for i in 0..2 {
+            self.mv_sign_prob[i] = merge_mode_mv_prob(pre.mv_sign_prob[i], &counts.mv_sign[i]);
+
+            merge_tree_probs(
+                &MV_CLASS_TREE,
+                &pre.mv_class_probs[i],
+                &counts.mv_class[i],
+                &mut self.mv_class_probs[i],
+            );
+
+            self.mv_class0_bit_prob[i] =
+                merge_mode_mv_prob(pre.mv_class0_bit_prob[i], &counts.mv_class0_bit[i]);
+
+            for j in 0..MV_OFFSET_BITS {
+                self.mv_bits_prob[i][j] =
+                    merge_mode_mv_prob(pre.mv_bits_prob[i][j], &counts.mv_bits[i][j]);
+            }
+
+            for j in 0..CLASS0_SIZE {
+                merge_tree_probs(
+                    &MV_FR_TREE,
+                    &pre.mv_class0_fr_probs[i][j],
+                    &counts.mv_class0_fr[i][j],
+                    &mut self.mv_class0_fr_probs[i][j],
+                );
+            }
+
+            merge_tree_probs(
+                &MV_FR_TREE,
+                &pre.mv_fr_probs[i],
+                &counts.mv_fr[i],
+                &mut self.mv_fr_probs[i],
+            );
+
+            if hdr.allow_high_precision_mv {
+                self.mv_class0_hp_prob[i] =
+                    merge_mode_mv_prob(pre.mv_class0_hp_prob[i], &counts.mv_class0_hp[i]);
+                self.mv_hp_prob[i] = merge_mode_mv_prob(pre.mv_hp_prob[i], &counts.mv_hp[i]);
+            }
+        }
+    }
+}
+
+/// The frame contexts saved across frames, with the reset and refresh
+/// semantics of the specification.
+///
+/// For each frame, [`FrameContexts::setup_frame`] returns the probabilities
+/// to decode it with, and [`FrameContexts::finish_frame`] updates the saved
+/// contexts once the frame is decoded.
+#[derive(Clone, Debug, Default)]
+pub struct FrameContexts {
+    saved: [FrameContext; FRAME_CONTEXTS],
+    /// The type of the last frame that went through `finish_frame`.
+    last_frame_type: Option<FrameType>,
+}
+
+impl FrameContexts {
+    /// Returns the saved frame context `idx`.
+    pub fn get(&self, idx: usize) -> Option<&FrameContext> {
+        self.saved.get(idx)
+    }
+
+    /// Whether the frame resets its probabilities to the default values.
+    fn frame_is_intra_or_resilient(hdr: &Header) -> bool {
+        hdr.frame_type == FrameType::KeyFrame || hdr.intra_only || hdr.error_resilient_mode
+    }
+
+    /// Returns the index of the frame context the frame loads and refreshes.
+    /// This is `frame_context_idx`, except for frames that reset it to 0.
+    pub fn context_idx(hdr: &Header) -> usize {
+        if Self::frame_is_intra_or_resilient(hdr) {
+            0
+        } else {
+            usize::from(hdr.frame_context_idx)
+        }
+    }
+
+    /// Resets the saved contexts as requested by the frame header, then
+    /// returns the probabilities to decode the frame with, i.e. the loaded
+    /// frame context with the forward updates of `chdr` applied.
+    ///
+    /// This must not be called for frames with `show_existing_frame` set.
+    pub fn setup_frame(&mut self, hdr: &Header, chdr: &CompressedHeader) -> FrameContext {
+        // See setup_past_independence() and the semantics of
+        // reset_frame_context.
+        if Self::frame_is_intra_or_resilient(hdr) {
+            if hdr.frame_type == FrameType::KeyFrame
+                || hdr.error_resilient_mode
+                || hdr.reset_frame_context == 3
+            {
+                self.saved = Default::default();
+            } else if hdr.reset_frame_context == 2 {
+                self.saved[usize::from(hdr.frame_context_idx)] = Default::default();
+            }
+        }
+
+        let mut ctx = self.saved[Self::context_idx(hdr)].clone();
+        ctx.apply_compressed_header(chdr);
+        ctx
+    }
+
+    /// Updates the saved contexts after `hdr` has been decoded with `ctx`, the
+    /// context returned by [`FrameContexts::setup_frame`]. If backward
+    /// adaptation is enabled for the frame, `counts` must contain the symbol
+    /// counts of the frame.
+    ///
+    /// Returns the adapted context, i.e. the one that was saved if
+    /// `refresh_frame_context` is set.
+    pub fn finish_frame(
+        &mut self,
+        hdr: &Header,
+        chdr: &CompressedHeader,
+        mut ctx: FrameContext,
+        counts: Option<&SymbolCounts>,
+    ) -> anyhow::Result<FrameContext> {
+        let idx = Self::context_idx(hdr);
+
+        if !hdr.error_resilient_mode && !hdr.frame_parallel_decoding_mode {
+            let counts = counts.ok_or(anyhow!(
+                "Symbol counts are required to adapt the probabilities"
+            ))?;
+
+            let frame_is_intra = hdr.frame_type == FrameType::KeyFrame || hdr.intra_only;
+            let pre = &self.saved[idx];
+
+            ctx.adapt_coef_probs(pre, counts, frame_is_intra, self.last_frame_type);
+            if !frame_is_intra {
+                ctx.adapt_noncoef_probs(pre, counts, hdr, chdr.tx_mode);
+            }
+        }
+
+        if hdr.refresh_frame_context {
+            self.saved[idx] = ctx.clone();
+        }
+
+        self.last_frame_type = Some(hdr.frame_type);
+
+        Ok(ctx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn merge() {
+        // No counts leave the probability untouched.
+        assert_eq!(merge_prob(128, 0, 0, 20, 128), 128);
+        // Saturated counts move the probability halfway to the observed one.
+        assert_eq!(merge_prob(100, 20, 0, 20, 128), 178);
+
+        let mut probs = [0; PARTITION_TYPES - 1];
+        merge_tree_probs(&PARTITION_TREE, &[100; 3], &[10, 0, 0, 0], &mut probs);
+        assert_eq!(probs, [139, 100, 100]);
+    }
+
+    #[test]
+    fn forward_updates() {
+        let mut chdr = CompressedHeader::default();
+        chdr.skip_prob[1] = 10;
+        chdr.mv_sign_prob[1] = 77;
+
+        let mut ctx = FrameContext::default();
+        ctx.apply_compressed_header(&chdr);
+
+        let mut expected = FrameContext::default();
+        expected.skip_prob[1] = update_prob(10, DEFAULT_SKIP_PROB[1]);
+        expected.mv_sign_prob[1] = 77;
+        assert_ne!(expected.skip_prob[1], DEFAULT_SKIP_PROB[1]);
+        assert_eq!(ctx, expected);
+    }
+
+    #[test]
+    fn save_and_reset_contexts() {
+        let mut contexts = FrameContexts::default();
+        let mut chdr = CompressedHeader::default();
+        chdr.skip_prob[0] = 20;
+
+        // An inter frame saving its probabilities into context 2.
+        let hdr = Header {
+            frame_type: FrameType::InterFrame,
+            frame_context_idx: 2,
+            refresh_frame_context: true,
+            frame_parallel_decoding_mode: true,
+            ..Default::default()
+        };
+        let ctx = contexts.setup_frame(&hdr, &chdr);
+        contexts
+            .finish_frame(&hdr, &chdr, ctx.clone(), None)
+            .unwrap();
+        assert_eq!(contexts.get(2), Some(&ctx));
+        assert_eq!(contexts.get(0), Some(&FrameContext::default()));
+
+        // The next frame loads it.
+        let loaded = contexts.setup_frame(&hdr, &CompressedHeader::default());
+        assert_eq!(loaded, ctx);
+
+        // An intra only frame only resetting context 1 and refreshing context 0.
+        let intra_hdr = Header {
+            frame_type: FrameType::InterFrame,
+            intra_only: true,
+            reset_frame_context: 2,
+            frame_context_idx: 1,
+            ..hdr.clone()
+        };
+        let ctx = contexts.setup_frame(&intra_hdr, &chdr);
+        contexts
+            .finish_frame(&intra_hdr, &chdr, ctx.clone(), None)
+            .unwrap();
+        assert_eq!(contexts.get(0), Some(&ctx));
+        assert_eq!(contexts.get(1), Some(&FrameContext::default()));
+        assert_ne!(contexts.get(2), Some(&FrameContext::default()));
+
+        // A key frame resets everything.
+        let key_hdr = Header {
+            frame_type: FrameType::KeyFrame,
+            refresh_frame_context: false,
+            ..hdr.clone()
+        };
+        let ctx = contexts.setup_frame(&key_hdr, &CompressedHeader::default());
+        assert_eq!(ctx, FrameContext::default());
+        for i in 0..FRAME_CONTEXTS {
+            assert_eq!(contexts.get(i), Some(&FrameContext::default()));
+        }
+    }
+
+    #[test]
+    fn backward_adaptation() {
+        let mut contexts = FrameContexts::default();
+        let mut chdr = CompressedHeader::default();
+        chdr.skip_prob[0] = 20;
+        chdr.coef_probs[0][0][0][1][0][0] = 20;
+
+        let hdr = Header {
+            frame_type: FrameType::KeyFrame,
+            refresh_frame_context: true,
+            ..Default::default()
+        };
+
+        // Adaptation requires the symbol counts.
+        let ctx = contexts.setup_frame(&hdr, &chdr);
+        assert!(contexts
+            .finish_frame(&hdr, &chdr, ctx.clone(), None)
+            .is_err());
+
+        // Without any symbol, the coefficient probabilities go back to the
+        // ones prior to the forward updates, while the others are kept as
+        // intra frames do not adapt them.
+        let mut counts = SymbolCounts::default();
+        let adapted = contexts
+            .finish_frame(&hdr, &chdr, ctx.clone(), Some(&counts))
+            .unwrap();
+        assert_eq!(adapted.coef_probs, DEFAULT_COEF_PROBS);
+        assert_eq!(adapted.skip_prob, ctx.skip_prob);
+        assert_eq!(contexts.get(0), Some(&adapted));
+
+        // An inter frame following the key frame.
+        let hdr = Header {
+            frame_type: FrameType::InterFrame,
+            ..hdr
+        };
+        let ctx = contexts.setup_frame(&hdr, &CompressedHeader::default());
+        let pre_skip_prob = contexts.get(0).unwrap().skip_prob[0];
+        counts.skip[0] = [20, 0];
+        counts.token[0][0][0][1][0] = [24, 0, 0];
+        counts.more_coefs[0][0][0][1][0] = [0, 24];
+        let adapted = contexts
+            .finish_frame(&hdr, &chdr, ctx, Some(&counts))
+            .unwrap();
+
+        let pre = &DEFAULT_COEF_PROBS[0][0][0][1][0];
+        assert_eq!(
+            adapted.coef_probs[0][0][0][1][0],
+            [
+                merge_prob(pre[0], 0, 24, COEF_COUNT_SAT, 128),
+                merge_prob(pre[1], 24, 0, COEF_COUNT_SAT, 128),
+                merge_prob(pre[2], 0, 0, COEF_COUNT_SAT, 128),
+            ]
+        );
+        assert_eq!(adapted.coef_probs[0][0][0][1][0][2], pre[2]);
+        assert_eq!(
+            adapted.skip_prob[0],
+            merge_prob(
+                pre_skip_prob,
+                20,
+                0,
+                MODE_MV_COUNT_SAT,
+                MODE_MV_MAX_UPDATE_FACTOR
+            )
+        );
+    }
+}
@@ -7,12 +7,16 @@ use anyhow::Context;
 use bitreader::BitReader;
 use enumn::N;
 
+use crate::codec::vp9::bool_decoder::BoolDecoder;
+
 use crate::codec::vp9::lookups::AC_QLOOKUP;
 use crate::codec::vp9::lookups::AC_QLOOKUP_10;
 use crate::codec::vp9::lookups::AC_QLOOKUP_12;
 use crate::codec::vp9::lookups::DC_QLOOKUP;
 use crate::codec::vp9::lookups::DC_QLOOKUP_10;
 use crate::codec::vp9::lookups::DC_QLOOKUP_12;
+use crate::codec::vp9::lookups::INV_MAP_TABLE;
+use crate::codec::vp9::references::ReferenceSlots;
 
 pub const REFS_PER_FRAME: usize = 3;
 
@@ -51,6 +55,37 @@ pub const MAX_TILE_WIDTH_B64: u32 = 64;
 /// The number of pictures in the DPB
 pub const NUM_REF_FRAMES: usize = 8;
 
+pub const TX_SIZES: usize = 4;
+pub const TX_SIZE_CONTEXTS: usize = 2;
+pub const BLOCK_TYPES: usize = 2;
+pub const REF_TYPES: usize = 2;
+pub const COEF_BANDS: usize = 6;
+pub const PREV_COEF_CONTEXTS: usize = 6;
+pub const UNCONSTRAINED_NODES: usize = 3;
+pub const SKIP_CONTEXTS: usize = 3;
+pub const INTER_MODE_CONTEXTS: usize = 7;
+pub const INTER_MODES: usize = 4;
+pub const INTERP_FILTER_CONTEXTS: usize = 4;
+pub const SWITCHABLE_FILTERS: usize = 3;
+pub const IS_INTER_CONTEXTS: usize = 4;
+pub const COMP_MODE_CONTEXTS: usize = 5;
+pub const REF_CONTEXTS: usize = 5;
+pub const BLOCK_SIZE_GROUPS: usize = 4;
+pub const INTRA_MODES: usize = 10;
+pub const PARTITION_CONTEXTS: usize = 16;
+pub const PARTITION_TYPES: usize = 4;
+pub const MV_JOINTS: usize = 4;
+pub const MV_CLASSES: usize = 11;
+pub const CLASS0_SIZE: usize = 2;
+pub const MV_OFFSET_BITS: usize = 10;
+pub const MV_FR_SIZE: usize = 4;
+
+pub const MAX_PROB: u8 = 255;
+
+/// The probability used to signal whether a probability is updated in the
+/// compressed header.
+const DIFF_UPDATE_PROB: u8 = 252;
+
 /// A clamp such that min <= x <= max
 fn clamp<U: PartialOrd>(x: U, low: U, high: U) -> U {
     if x > high {
@@ -125,6 +160,24 @@ pub enum ColorRange {
     FullSwing = 1,
 }
 
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, N)]
+pub enum TxMode {
+    #[default]
+    Only4x4 = 0,
+    Allow8x8 = 1,
+    Allow16x16 = 2,
+    Allow32x32 = 3,
+    TxModeSelect = 4,
+}
+
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, N)]
+pub enum ReferenceMode {
+    #[default]
+    SingleReference = 0,
+    CompoundReference = 1,
+    ReferenceModeSelect = 2,
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub struct LoopFilterParams {
     /// Indicates the loop filter strength.
@@ -308,10 +361,13 @@ impl Segmentation {
     }
 }
 
+/// The dimensions of a reference frame slot.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
-struct FrameSize {
-    width: u32,
-    height: u32,
+pub struct FrameSize {
+    /// The width of the frame in pixels.
+    pub width: u32,
+    /// The height of the frame in pixels.
+    pub height: u32,
 }
 
 pub struct Frame<'a> {
@@ -319,6 +375,10 @@ pub struct Frame<'a> {
     bitstream: &'a [u8],
     /// The frame header.
     pub header: Header,
+    /// The compressed header. Left to its default value if
+    /// `header.show_existing_frame` is set, or if the frame was built with
+    /// [`Frame::new`].
+    pub compressed_header: CompressedHeader,
     /// The offset into T
     offset: usize,
     /// The size of the data in T
@@ -330,6 +390,7 @@ impl<'a> Frame<'a> {
         Self {
             bitstream,
             header,
+            compressed_header: Default::default(),
             offset,
             size,
         }
@@ -493,6 +554,113 @@ impl Header {
     }
 }
 
+/// Coefficient probabilities, indexed by transform size, plane type,
+/// reference, band, context and node.
+pub type CoefProbs = [[[[[[u8; UNCONSTRAINED_NODES]; PREV_COEF_CONTEXTS]; COEF_BANDS]; REF_TYPES];
+    BLOCK_TYPES]; TX_SIZES];
+
+/// A VP9 compressed header, as per "6.3 Compressed header syntax".
+///
+/// Probabilities are not applied to any frame context. Instead, each `u8`
+/// probability entry holds the delta decoded for it by diff_update_prob(),
+/// after the inv_map_table lookup, or 0 if the probability is not updated by
+/// this frame. Use [`update_prob`] to compute the updated probability. Motion
+/// vector probabilities hold the updated probability itself, or 0 if not
+/// updated. This is the layout expected by V4L2's
+/// `v4l2_ctrl_vp9_compressed_hdr`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CompressedHeader {
+    /// Specifies how the transform size is determined.
+    pub tx_mode: TxMode,
+    /// Deltas for the probabilities of the 8x8 transform sizes.
+    pub tx_probs_8x8: [[u8; TX_SIZES - 3]; TX_SIZE_CONTEXTS],
+    /// Deltas for the probabilities of the 16x16 transform sizes.
+    pub tx_probs_16x16: [[u8; TX_SIZES - 2]; TX_SIZE_CONTEXTS],
+    /// Deltas for the probabilities of the 32x32 transform sizes.
+    pub tx_probs_32x32: [[u8; TX_SIZES - 1]; TX_SIZE_CONTEXTS],
+    /// Deltas for the coefficient probabilities. Only the first 3 contexts are
+    /// used in band 0.
+    pub coef_probs: CoefProbs,
+    /// Deltas for the probabilities of the skip flag.
+    pub skip_prob: [u8; SKIP_CONTEXTS],
+    /// Deltas for the probabilities of the inter modes.
+    pub inter_mode_probs: [[u8; INTER_MODES - 1]; INTER_MODE_CONTEXTS],
+    /// Deltas for the probabilities of the switchable interpolation filters.
+    pub interp_filter_probs: [[u8; SWITCHABLE_FILTERS - 1]; INTERP_FILTER_CONTEXTS],
+    /// Deltas for the probabilities of the is_inter flag.
+    pub is_inter_prob: [u8; IS_INTER_CONTEXTS],
+    /// Specifies the type of inter prediction used by the frame.
+    pub reference_mode: ReferenceMode,
+    /// The reference frame used by all compound predictions. Computed by
+    /// setup_compound_reference_mode() when compound prediction is allowed.
+    pub comp_fixed_ref: usize,
+    /// The two reference frames that can be paired with `comp_fixed_ref`.
+    pub comp_var_ref: [usize; 2],
+    /// Deltas for the probabilities of the compound prediction flag.
+    pub comp_mode_prob: [u8; COMP_MODE_CONTEXTS],
+    /// Deltas for the probabilities of the reference frame in single
+    /// prediction.
+    pub single_ref_prob: [[u8; 2]; REF_CONTEXTS],
+    /// Deltas for the probabilities of the reference frame in compound
+    /// prediction.
+    pub comp_ref_prob: [u8; REF_CONTEXTS],
+    /// Deltas for the probabilities of the luma intra modes in inter frames.
+    pub y_mode_probs: [[u8; INTRA_MODES - 1]; BLOCK_SIZE_GROUPS],
+    /// Deltas for the probabilities of the partition types.
+    pub partition_probs: [[u8; PARTITION_TYPES - 1]; PARTITION_CONTEXTS],
+    /// Updated probabilities of the motion vector joints.
+    pub mv_joint_probs: [u8; MV_JOINTS - 1],
+    /// Updated probabilities of the motion vector signs.
+    pub mv_sign_prob: [u8; 2],
+    /// Updated probabilities of the motion vector classes.
+    pub mv_class_probs: [[u8; MV_CLASSES - 1]; 2],
+    /// Updated probabilities of the motion vector class0 bit.
+    pub mv_class0_bit_prob: [u8; 2],
+    /// Updated probabilities of the motion vector integer bits.
+    pub mv_bits_prob: [[u8; MV_OFFSET_BITS]; 2],
+    /// Updated probabilities of the motion vector class0 fractional parts.
+    pub mv_class0_fr_probs: [[[u8; MV_FR_SIZE - 1]; CLASS0_SIZE]; 2],
+    /// Updated probabilities of the motion vector fractional parts.
+    pub mv_fr_probs: [[u8; MV_FR_SIZE - 1]; 2],
+    /// Updated probabilities of the motion vector class0 high precision bit.
+    pub mv_class0_hp_prob: [u8; 2],
+    /// Updated probabilities of the motion vector high precision bit.
+    pub mv_hp_prob: [u8; 2],
+}
+
+/// An implementation of inv_recenter_nonneg as per "6.3.6 Inv recenter noneg
+/// syntax".
+fn inv_recenter_nonneg(v: u32, m: u32) -> u32 {
+    if v > 2 * m {
+        v
+    } else if v & 1 != 0 {
+        m - ((v + 1) >> 1)
+    } else {
+        m + (v >> 1)
+    }
+}
+
+/// Returns the probability resulting from applying `delta`, as stored in
+/// [`CompressedHeader`], to `prob`. This is the part of inv_remap_prob() that
+/// follows the inv_map_table lookup, as per "6.3.5 Inv remap prob syntax".
+///
+/// `prob` is returned unchanged if `delta` is 0.
+pub fn update_prob(delta: u8, prob: u8) -> u8 {
+    if delta == 0 {
+        return prob;
+    }
+
+    let v = u32::from(delta);
+    let m = u32::from(prob) - 1;
+    let max_prob = u32::from(MAX_PROB);
+
+    if (m << 1) <= max_prob {
+        (1 + inv_recenter_nonneg(v, m)) as u8
+    } else {
+        (max_prob - inv_recenter_nonneg(v, max_prob - 1 - m)) as u8
+    }
+}
+
 /// The VP9 superframe header as per Annex B, B.2.1, B.2.2
 struct SuperframeHeader {
     /// Indicates the number of frames within this superframe. NOTE - It is
@@ -520,7 +688,7 @@ pub struct Parser {
     lf: LoopFilterParams,
     seg: SegmentationParams,
 
-    reference_frame_sz: [FrameSize; REF_FRAMES],
+    ref_slots: ReferenceSlots,
 }
 
 impl Parser {
@@ -738,9 +906,15 @@ impl Parser {
             found_ref = r.read_bool()?;
 
             if found_ref {
-                let idx = hdr.ref_frame_idx[i] as usize;
-                hdr.width = self.reference_frame_sz[idx].width;
-                hdr.height = self.reference_frame_sz[idx].height;
+                // The slot may be empty if parsing started mid-stream.
+                let idx = usize::from(hdr.ref_frame_idx[i]);
+                let size = self
+                    .ref_slots
+                    .get(idx)
+                    .map(|slot| slot.size.clone())
+                    .unwrap_or_default();
+                hdr.width = size.width;
+                hdr.height = size.height;
                 break;
             }
         }
@@ -899,7 +1073,7 @@ impl Parser {
                     seg.feature_enabled[i][j] = r.read_bool()?;
                     if seg.feature_enabled[i][j] {
                         let bits_to_read = SEGMENTATION_FEATURE_BITS[j];
-                        let mut feature_value = r.read_i16(bits_to_read)?;
+                        let mut feature_value = i16::from(r.read_u8(bits_to_read)?);
 
                         if SEGMENTATION_FEATURE_SIGNED[j] {
                             let feature_sign = r.read_bool()?;
@@ -918,7 +1092,7 @@ impl Parser {
         Ok(())
     }
 
-    fn calc_min_log2_tile_cols(sb64_cols: u32) -> u8 {
+    pub(super) fn calc_min_log2_tile_cols(sb64_cols: u32) -> u8 {
         let mut min_log2 = 0;
 
         while (MAX_TILE_WIDTH_B64 << min_log2) < sb64_cols {
@@ -928,7 +1102,7 @@ impl Parser {
         min_log2
     }
 
-    fn calc_max_log2_tile_cols(sb64_cols: u32) -> u8 {
+    pub(super) fn calc_max_log2_tile_cols(sb64_cols: u32) -> u8 {
         let mut max_log2 = 1;
 
         while (sb64_cols >> max_log2) >= MIN_TILE_WIDTH_B64 {
@@ -963,6 +1137,253 @@ impl Parser {
         Ok(())
     }
 
+    /// An implementation of decode_term_subexp as per "6.3.4 Decode term
+    /// subexp syntax".
+    fn decode_term_subexp(bd: &mut BoolDecoder) -> anyhow::Result<u8> {
+        if !bd.read_bool()? {
+            return Ok(bd.read_literal(4)? as u8);
+        }
+
+        if !bd.read_bool()? {
+            return Ok(bd.read_literal(4)? as u8 + 16);
+        }
+
+        if !bd.read_bool()? {
+            return Ok(bd.read_literal(5)? as u8 + 32);
+        }
+
+        let v = bd.read_literal(7)? as u8;
+        if v < 65 {
+            return Ok(v + 64);
+        }
+
+        let bit = bd.read_literal(1)? as u8;
+        Ok((v << 1) - 1 + bit)
+    }
+
+    /// An implementation of diff_update_prob as per "6.3.3 Diff update prob
+    /// syntax". Returns the remapped delta, or 0 if there is no update.
+    fn diff_update_prob(bd: &mut BoolDecoder) -> anyhow::Result<u8> {
+        if bd.read_bool_with_prob(DIFF_UPDATE_PROB)? {
+            let delta_prob = Self::decode_term_subexp(bd)?;
+            Ok(INV_MAP_TABLE[usize::from(delta_prob)])
+        } else {
+            Ok(0)
+        }
+    }
+
+    fn diff_update_probs(bd: &mut BoolDecoder, probs: &mut [u8]) -> anyhow::Result<()> {
+        for prob in probs {
+            *prob = Self::diff_update_prob(bd)?;
+        }
+
+        Ok(())
+    }
+
+    /// An implementation of update_mv_prob as per "6.3.17 Update mv prob
+    /// syntax". Returns the new probability, or 0 if there is no update.
+    fn update_mv_probs(bd: &mut BoolDecoder, probs: &mut [u8]) -> anyhow::Result<()> {
+        for prob in probs {
+            *prob = if bd.read_bool_with_prob(DIFF_UPDATE_PROB)? {
+                (bd.read_literal(7)? as u8) << 1 | 1
+            } else {
+                0
+            };
+        }
+
+        Ok(())
+    }
+
+    fn read_tx_mode(
+        bd: &mut BoolDecoder,
+        hdr: &Header,
+        chdr: &mut CompressedHeader,
+    ) -> anyhow::Result<()> {
+        if hdr.lossless {
+            chdr.tx_mode = TxMode::Only4x4;
+            return Ok(());
+        }
+
+        let mut tx_mode = bd.read_literal(2)?;
+        if tx_mode == TxMode::Allow32x32 as u32 {
+            tx_mode += bd.read_literal(1)?;
+        }
+
+        chdr.tx_mode = TxMode::n(tx_mode).ok_or(anyhow!("Broken data: invalid tx_mode"))?;
+
+        if chdr.tx_mode == TxMode::TxModeSelect {
+            for i in 0..TX_SIZE_CONTEXTS {
+                Self::diff_update_probs(bd, &mut chdr.tx_probs_8x8[i])?;
+            }
+            for i in 0..TX_SIZE_CONTEXTS {
+                Self::diff_update_probs(bd, &mut chdr.tx_probs_16x16[i])?;
+            }
+            for i in 0..TX_SIZE_CONTEXTS {
+                Self::diff_update_probs(bd, &mut chdr.tx_probs_32x32[i])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn read_coef_probs(bd: &mut BoolDecoder, chdr: &mut CompressedHeader) -> anyhow::Result<()> {
+        let max_tx_size = match chdr.tx_mode {
+            TxMode::Only4x4 => 0,
+            TxMode::Allow8x8 => 1,
+            TxMode::Allow16x16 => 2,
+            TxMode::Allow32x32 | TxMode::TxModeSelect => 3,
+        };
+
+        for tx_size in chdr.coef_probs.iter_mut().take(max_tx_size + 1) {
+            let update_probs = bd.read_literal(1)? != 0;
+            if !update_probs {
+                continue;
+            }
+
+            for block_type in tx_size.iter_mut() {
+                for ref_type in block_type.iter_mut() {
+                    for (band, contexts) in ref_type.iter_mut().enumerate() {
+                        let max_l = if band == 0 { 3 } else { PREV_COEF_CONTEXTS };
+                        for context in contexts.iter_mut().take(max_l) {
+                            Self::diff_update_probs(bd, context)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// An implementation of setup_compound_reference_mode as per "6.3.18
+    /// Setup compound reference mode syntax".
+    fn setup_compound_reference_mode(hdr: &Header, chdr: &mut CompressedHeader) {
+        let sign_bias = &hdr.ref_frame_sign_bias;
+
+        if sign_bias[LAST_FRAME] == sign_bias[GOLDEN_FRAME] {
+            chdr.comp_fixed_ref = ALTREF_FRAME;
+            chdr.comp_var_ref = [LAST_FRAME, GOLDEN_FRAME];
+        } else if sign_bias[LAST_FRAME] == sign_bias[ALTREF_FRAME] {
+            chdr.comp_fixed_ref = GOLDEN_FRAME;
+            chdr.comp_var_ref = [LAST_FRAME, ALTREF_FRAME];
+        } else {
+            chdr.comp_fixed_ref = LAST_FRAME;
+            chdr.comp_var_ref = [GOLDEN_FRAME, ALTREF_FRAME];
+        }
+    }
+
+    fn read_frame_reference_mode(
+        bd: &mut BoolDecoder,
+        hdr: &Header,
+        chdr: &mut CompressedHeader,
+    ) -> anyhow::Result<()> {
+        let compound_reference_allowed = (1..REFS_PER_FRAME)
+            .any(|i| hdr.ref_frame_sign_bias[i + 1] != hdr.ref_frame_sign_bias[1]);
+
+        chdr.reference_mode = if compound_reference_allowed {
+            Self::setup_compound_reference_mode(hdr, chdr);
+
+            let non_single_reference = bd.read_literal(1)? != 0;
+            if !non_single_reference {
+                ReferenceMode::SingleReference
+            } else if bd.read_literal(1)? == 0 {
+                ReferenceMode::CompoundReference
+            } else {
+                ReferenceMode::ReferenceModeSelect
+            }
+        } else {
+            ReferenceMode::SingleReference
+        };
+
+        if chdr.reference_mode == ReferenceMode::ReferenceModeSelect {
+            Self::diff_update_probs(bd, &mut chdr.comp_mode_prob)?;
+        }
+
+        if chdr.reference_mode != ReferenceMode::CompoundReference {
+            for probs in &mut chdr.single_ref_prob {
+                Self::diff_update_probs(bd, probs)?;
+            }
+        }
+
+        if chdr.reference_mode != ReferenceMode::SingleReference {
+            Self::diff_update_probs(bd, &mut chdr.comp_ref_prob)?;
+        }
+
+        Ok(())
+    }
+
+    fn read_mv_probs(
+        bd: &mut BoolDecoder,
+        hdr: &Header,
+        chdr: &mut CompressedHeader,
+    ) -> anyhow::Result<()> {
+        Self::update_mv_probs(bd, &mut chdr.mv_joint_probs)?;
+
+        for i in 0..2 {
+            Self::update_mv_probs(bd, &mut chdr.mv_sign_prob[i..=i])?;
+            Self::update_mv_probs(bd, &mut chdr.mv_class_probs[i])?;
+            Self::update_mv_probs(bd, &mut chdr.mv_class0_bit_prob[i..=i])?;
+            Self::update_mv_probs(bd, &mut chdr.mv_bits_prob[i])?;
+        }
+
+        for i in 0..2 {
+            for j in 0..CLASS0_SIZE {
+                Self::update_mv_probs(bd, &mut chdr.mv_class0_fr_probs[i][j])?;
+            }
+            Self::update_mv_probs(bd, &mut chdr.mv_fr_probs[i])?;
+        }
+
+        if hdr.allow_high_precision_mv {
+            for i in 0..2 {
+                Self::update_mv_probs(bd, &mut chdr.mv_class0_hp_prob[i..=i])?;
+                Self::update_mv_probs(bd, &mut chdr.mv_hp_prob[i..=i])?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses the compressed header of a frame as per "6.3 Compressed header
+    /// syntax". `data` must contain exactly `header_size_in_bytes` bytes.
+    pub fn parse_compressed_header(hdr: &Header, data: &[u8]) -> anyhow::Result<CompressedHeader> {
+        let mut bd = BoolDecoder::new(data)?;
+        let mut chdr = CompressedHeader::default();
+
+        Self::read_tx_mode(&mut bd, hdr, &mut chdr)?;
+        Self::read_coef_probs(&mut bd, &mut chdr)?;
+        Self::diff_update_probs(&mut bd, &mut chdr.skip_prob)?;
+
+        let frame_is_intra = matches!(hdr.frame_type, FrameType::KeyFrame) || hdr.intra_only;
+        if !frame_is_intra {
+            for probs in &mut chdr.inter_mode_probs {
+                Self::diff_update_probs(&mut bd, probs)?;
+            }
+
+            if hdr.interpolation_filter == InterpolationFilter::Switchable {
+                for probs in &mut chdr.interp_filter_probs {
+                    Self::diff_update_probs(&mut bd, probs)?;
+                }
+            }
+
+            Self::diff_update_probs(&mut bd, &mut chdr.is_inter_prob)?;
+            Self::read_frame_reference_mode(&mut bd, hdr, &mut chdr)?;
+
+            for probs in &mut chdr.y_mode_probs {
+                Self::diff_update_probs(&mut bd, probs)?;
+            }
+
+            for probs in &mut chdr.partition_probs {
+                Self::diff_update_probs(&mut bd, probs)?;
+            }
+
+            Self::read_mv_probs(&mut bd, hdr, &mut chdr)?;
+        }
+
+        bd.finish()?;
+
+        Ok(chdr)
+    }
+
     fn parse_frame_header(
         &mut self,
         resource: impl AsRef<[u8]>,
@@ -1047,6 +1468,16 @@ impl Parser {
                 }
 
                 self.parse_frame_size_with_refs(&mut r, &mut hdr)?;
+
+                // Reject illegal references, unless parsing started mid-stream
+                // and they are unknown.
+                let refs_known = hdr
+                    .ref_frame_idx
+                    .iter()
+                    .all(|&idx| self.ref_slots.get(usize::from(idx)).is_some());
+                if refs_known {
+                    self.ref_slots.scale_factors(&hdr)?;
+                }
                 hdr.allow_high_precision_mv = r.read_bool()?;
                 Self::read_interpolation_filter(&mut r, &mut hdr)?;
             }
@@ -1076,15 +1507,9 @@ impl Parser {
         hdr.lf = self.lf.clone();
         hdr.seg = self.seg.clone();
 
-        for i in 0..REF_FRAMES {
-            let flag = 1 << i;
-            if hdr.refresh_frame_flags & flag != 0 {
-                self.reference_frame_sz[i].width = hdr.width;
-                self.reference_frame_sz[i].height = hdr.height;
-            }
-        }
+        self.ref_slots.update(&hdr);
 
-        hdr.uncompressed_header_size_in_bytes = (r.position() as u16 + 7) / 8;
+        hdr.uncompressed_header_size_in_bytes = (r.position() as u16).div_ceil(8);
 
         Ok(hdr)
     }
@@ -1098,8 +1523,25 @@ impl Parser {
     ) -> anyhow::Result<Frame<'a>> {
         let header = self.parse_frame_header(bitstream, offset)?;
 
+        let compressed_header = if header.show_existing_frame {
+            CompressedHeader::default()
+        } else {
+            let start = offset + usize::from(header.uncompressed_header_size_in_bytes);
+            let end = start + usize::from(header.header_size_in_bytes);
+            let data = bitstream
+                .get(start..end)
+                .filter(|_| end <= offset + size)
+                .ok_or(anyhow!(
+                    "Broken data: the compressed header is out of bounds"
+                ))?;
+
+            Self::parse_compressed_header(&header, data)
+                .context("Failed to parse the compressed header")?
+        };
+
         Ok(Frame {
             header,
+            compressed_header,
             bitstream,
             offset,
             size,
@@ -1127,12 +1569,22 @@ impl Parser {
 
 #[cfg(test)]
 mod tests {
+    use crate::codec::vp8::bool_encoder::BoolEncoder;
+    use crate::codec::vp9::lookups::INV_MAP_TABLE;
+    use crate::codec::vp9::parser::update_prob;
     use crate::codec::vp9::parser::BitDepth;
     use crate::codec::vp9::parser::ColorSpace;
     use crate::codec::vp9::parser::FrameType;
+    use crate::codec::vp9::parser::Header;
     use crate::codec::vp9::parser::InterpolationFilter;
     use crate::codec::vp9::parser::Parser;
     use crate::codec::vp9::parser::Profile;
+    use crate::codec::vp9::parser::ReferenceMode;
+    use crate::codec::vp9::parser::TxMode;
+    use crate::codec::vp9::parser::ALTREF_FRAME;
+    use crate::codec::vp9::parser::DIFF_UPDATE_PROB;
+    use crate::codec::vp9::parser::GOLDEN_FRAME;
+    use crate::codec::vp9::parser::LAST_FRAME;
     use crate::codec::vp9::parser::MAX_SEGMENTS;
     use crate::codec::vp9::parser::SEG_LVL_MAX;
     use crate::utils::IvfIterator;
@@ -1251,6 +1703,13 @@ mod tests {
                 assert_eq!(h.header_size_in_bytes, 120);
 
                 assert!(!h.lossless);
+
+                let c = &frames[0].compressed_header;
+                assert_eq!(c.tx_mode, TxMode::TxModeSelect);
+                assert_eq!(c.tx_probs_16x16, [[0, 254], [0, 189]]);
+                assert_eq!(c.skip_prob, [85, 0, 0]);
+                assert_eq!(c.coef_probs[0][0][0][0][2], [29, 29, 41]);
+                assert_eq!(c.reference_mode, ReferenceMode::SingleReference);
             } else if frame_n == 1 {
                 assert_eq!(frames.len(), 2);
 
@@ -1342,6 +1801,17 @@ mod tests {
 
                 assert!(!h.lossless);
 
+                let c = &frames[0].compressed_header;
+                assert_eq!(c.tx_mode, TxMode::TxModeSelect);
+                assert_eq!(c.tx_probs_16x16, [[150, 0], [0, 163]]);
+                assert_eq!(c.is_inter_prob, [20, 0, 0, 0]);
+                assert_eq!(c.y_mode_probs[0][2], 59);
+                assert_eq!(c.reference_mode, ReferenceMode::SingleReference);
+
+                let c = &frames[1].compressed_header;
+                assert_eq!(c.skip_prob, [0, 0, 85]);
+                assert_eq!(c.reference_mode, ReferenceMode::ReferenceModeSelect);
+
                 let h = &frames[1].header;
 
                 assert!(matches!(h.profile, Profile::Profile0));
@@ -1427,4 +1897,128 @@ mod tests {
             }
         }
     }
+
+    /// Writes the counterpart of diff_update_prob() for `delta_prob`, the value
+    /// returned by decode_term_subexp().
+    fn write_diff_update_prob(be: &mut BoolEncoder, delta_prob: Option<u8>) {
+        let Some(v) = delta_prob else {
+            be.write_bool_with_prob(false, DIFF_UPDATE_PROB);
+            return;
+        };
+
+        be.write_bool_with_prob(true, DIFF_UPDATE_PROB);
+        if v < 16 {
+            be.write_bool(false);
+            be.write_uint(v, 4).unwrap();
+        } else if v < 32 {
+            be.write_bool(true);
+            be.write_bool(false);
+            be.write_uint(v - 16, 4).unwrap();
+        } else if v < 64 {
+            be.write_bool(true);
+            be.write_bool(true);
+            be.write_bool(false);
+            be.write_uint(v - 32, 5).unwrap();
+        } else {
+            be.write_bool(true);
+            be.write_bool(true);
+            be.write_bool(true);
+            if v < 129 {
+                be.write_uint(v - 64, 7).unwrap();
+            } else {
+                let v = u32::from(v) + 1;
+                be.write_uint(v >> 1, 7).unwrap();
+                be.write_uint(v & 1, 1).unwrap();
+            }
+        }
+    }
+
+    fn write_no_updates(be: &mut BoolEncoder, count: usize) {
+        for _ in 0..count {
+            be.write_bool_with_prob(false, DIFF_UPDATE_PROB);
+        }
+    }
+
+    #[test]
+    fn parse_compressed_header() {
+        let hdr = Header {
+            frame_type: FrameType::InterFrame,
+            interpolation_filter: InterpolationFilter::Switchable,
+            allow_high_precision_mv: true,
+            ref_frame_sign_bias: [0, 0, 0, 1],
+            ..Default::default()
+        };
+
+        let mut be = BoolEncoder::new();
+        // Marker bit.
+        be.write_bool(false);
+
+        // TX_MODE_SELECT, then the 2 + 4 + 6 transform size probabilities.
+        be.write_uint(3u32, 2).unwrap();
+        be.write_uint(1u32, 1).unwrap();
+        write_diff_update_prob(&mut be, Some(5));
+        write_no_updates(&mut be, 4);
+        write_diff_update_prob(&mut be, Some(200));
+        write_no_updates(&mut be, 6);
+
+        // Only the 4x4 coefficient probabilities are updated.
+        be.write_uint(1u32, 1).unwrap();
+        write_diff_update_prob(&mut be, Some(40));
+        write_no_updates(&mut be, 2 * 2 * (3 + 5 * 6) * 3 - 1);
+        for _ in 0..3 {
+            be.write_uint(0u32, 1).unwrap();
+        }
+
+        // Skip probabilities.
+        write_no_updates(&mut be, 1);
+        write_diff_update_prob(&mut be, Some(100));
+        write_no_updates(&mut be, 1);
+
+        // Inter mode, interpolation filter and is_inter probabilities.
+        write_no_updates(&mut be, 7 * 3 + 4 * 2 + 4);
+
+        // REFERENCE_MODE_SELECT, then the compound mode, single and compound
+        // reference probabilities.
+        be.write_uint(1u32, 1).unwrap();
+        be.write_uint(1u32, 1).unwrap();
+        write_no_updates(&mut be, 5 + 5 * 2 + 4);
+        write_diff_update_prob(&mut be, Some(254));
+
+        // Y mode and partition probabilities.
+        write_no_updates(&mut be, 4 * 9 + 16 * 3);
+
+        // Motion vector probabilities.
+        be.write_bool_with_prob(true, DIFF_UPDATE_PROB);
+        be.write_uint(10u32, 7).unwrap();
+        write_no_updates(&mut be, 2 + 2 * (1 + 10 + 1 + 10) + 2 * (2 * 3 + 3) + 3);
+        be.write_bool_with_prob(true, DIFF_UPDATE_PROB);
+        be.write_uint(127u32, 7).unwrap();
+
+        let data = be.finish();
+        let c = Parser::parse_compressed_header(&hdr, &data).unwrap();
+
+        assert_eq!(c.tx_mode, TxMode::TxModeSelect);
+        assert_eq!(c.tx_probs_8x8, [[INV_MAP_TABLE[5]], [0]]);
+        assert_eq!(c.tx_probs_16x16, [[0, 0], [0, INV_MAP_TABLE[200]]]);
+        assert_eq!(c.tx_probs_32x32, [[0; 3]; 2]);
+        assert_eq!(c.coef_probs[0][0][0][0][0], [INV_MAP_TABLE[40], 0, 0]);
+        assert_eq!(c.skip_prob, [0, INV_MAP_TABLE[100], 0]);
+        assert_eq!(c.reference_mode, ReferenceMode::ReferenceModeSelect);
+        assert_eq!(c.comp_fixed_ref, ALTREF_FRAME);
+        assert_eq!(c.comp_var_ref, [LAST_FRAME, GOLDEN_FRAME]);
+        assert_eq!(c.comp_ref_prob, [0, 0, 0, 0, INV_MAP_TABLE[254]]);
+        assert_eq!(c.mv_joint_probs, [21, 0, 0]);
+        assert_eq!(c.mv_hp_prob, [0, 255]);
+
+        // The compressed header cannot be empty.
+        assert!(Parser::parse_compressed_header(&hdr, &[]).is_err());
+    }
+
+    #[test]
+    fn update_probs() {
+        assert_eq!(update_prob(0, 100), 100);
+        assert_eq!(update_prob(7, 128), 124);
+        assert_eq!(update_prob(7, 200), 204);
+        assert_eq!(update_prob(254, 1), 255);
+    }
 }
@@ -0,0 +1,275 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Tracking of the VP9 reference frame slots. This keeps the properties of the
+//! frames held by the eight reference slots, so that the references of inter
+//! frames can be validated and their scale factors computed as per "8.5.2.3
+//! Motion vector scaling process".
+
+use anyhow::anyhow;
+
+use crate::codec::vp9::parser::BitDepth;
+use crate::codec::vp9::parser::FrameSize;
+use crate::codec::vp9::parser::FrameType;
+use crate::codec::vp9::parser::Header;
+use crate::codec::vp9::parser::REFS_PER_FRAME;
+use crate::codec::vp9::parser::REF_FRAMES;
+
+/// The precision of the scale factors.
+pub const REF_SCALE_SHIFT: u32 = 14;
+/// The scale factor of a reference with the same size as the current frame.
+pub const REF_NO_SCALE: u32 = 1 << REF_SCALE_SHIFT;
+
+/// The properties of a frame held in a reference slot.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReferenceSlot {
+    /// The dimensions of the frame.
+    pub size: FrameSize,
+    /// The chroma subsampling of the frame.
+    pub subsampling_x: bool,
+    /// The chroma subsampling of the frame.
+    pub subsampling_y: bool,
+    /// The bit depth of the frame.
+    pub bit_depth: BitDepth,
+}
+
+impl ReferenceSlot {
+    fn from_header(hdr: &Header) -> Self {
+        Self {
+            size: FrameSize {
+                width: hdr.width,
+                height: hdr.height,
+            },
+            subsampling_x: hdr.subsampling_x,
+            subsampling_y: hdr.subsampling_y,
+            bit_depth: hdr.bit_depth,
+        }
+    }
+}
+
+/// The scale factors to apply to the motion vectors pointing into a
+/// reference, as per "8.5.2.3 Motion vector scaling process".
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ScaleFactors {
+    /// The horizontal scale factor, in units of 1 / 2^REF_SCALE_SHIFT.
+    pub x_scale: u32,
+    /// The vertical scale factor, in units of 1 / 2^REF_SCALE_SHIFT.
+    pub y_scale: u32,
+}
+
+impl ScaleFactors {
+    /// Computes the scale factors of `reference` for a frame of size `frame`,
+    /// or returns `None` if the reference is more than twice as large or
+    /// sixteen times smaller than the frame in either dimension.
+    pub fn new(reference: &FrameSize, frame: &FrameSize) -> Option<Self> {
+        if 2 * frame.width < reference.width
+            || 2 * frame.height < reference.height
+            || frame.width > 16 * reference.width
+            || frame.height > 16 * reference.height
+        {
+            return None;
+        }
+
+        Some(Self {
+            x_scale: (reference.width << REF_SCALE_SHIFT) / frame.width,
+            y_scale: (reference.height << REF_SCALE_SHIFT) / frame.height,
+        })
+    }
+
+    /// Whether the reference has a different size than the frame.
+    pub fn is_scaled(&self) -> bool {
+        self.x_scale != REF_NO_SCALE || self.y_scale != REF_NO_SCALE
+    }
+}
+
+/// The state of the reference frame slots, updated after each frame according
+/// to its `refresh_frame_flags`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ReferenceSlots {
+    slots: [Option<ReferenceSlot>; REF_FRAMES],
+}
+
+impl ReferenceSlots {
+    /// Returns the frame held by slot `idx`, if any.
+    pub fn get(&self, idx: usize) -> Option<&ReferenceSlot> {
+        self.slots.get(idx)?.as_ref()
+    }
+
+    /// Validates the references of the frame described by `hdr` against the
+    /// current state of the slots, and returns the scale factors of its
+    /// LAST, GOLDEN and ALTREF references.
+    ///
+    /// A reference is `None` if its size is out of the limits of scaled
+    /// motion compensation, in which case the frame must not predict from it.
+    /// An error is returned if a reference slot is empty or holds a frame
+    /// with another bit depth or chroma subsampling, or if no reference can
+    /// be predicted from. Intra frames do not use any reference.
+    pub fn scale_factors(
+        &self,
+        hdr: &Header,
+    ) -> anyhow::Result<[Option<ScaleFactors>; REFS_PER_FRAME]> {
+        let mut scale_factors = [None; REFS_PER_FRAME];
+
+        if hdr.frame_type == FrameType::KeyFrame || hdr.intra_only {
+            return Ok(scale_factors);
+        }
+
+        let frame_size = FrameSize {
+            width: hdr.width,
+            height: hdr.height,
+        };
+
+        for (i, &idx) in hdr.ref_frame_idx.iter().enumerate() {
+            let slot = self
+                .get(usize::from(idx))
+                .ok_or(anyhow!("Broken data: reference slot {} is empty", idx))?;
+
+            if slot.bit_depth != hdr.bit_depth
+                || slot.subsampling_x != hdr.subsampling_x
+                || slot.subsampling_y != hdr.subsampling_y
+            {
+                return Err(anyhow!(
+                    "Broken data: reference slot {} has an incompatible format",
+                    idx
+                ));
+            }
+
+            scale_factors[i] = ScaleFactors::new(&slot.size, &frame_size);
+        }
+
+        if scale_factors.iter().all(Option::is_none) {
+            return Err(anyhow!(
+                "Broken data: no reference of a valid size for a {}x{} frame",
+                hdr.width,
+                hdr.height
+            ));
+        }
+
+        Ok(scale_factors)
+    }
+
+    /// Stores the frame described by `hdr` into the slots selected by its
+    /// `refresh_frame_flags`.
+    pub fn update(&mut self, hdr: &Header) {
+        if hdr.show_existing_frame {
+            return;
+        }
+
+        for (i, slot) in self.slots.iter_mut().enumerate() {
+            if hdr.refresh_frame_flags & (1 << i) != 0 {
+                *slot = Some(ReferenceSlot::from_header(hdr));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(frame_type: FrameType, width: u32, height: u32, refresh_frame_flags: u8) -> Header {
+        Header {
+            frame_type,
+            width,
+            height,
+            refresh_frame_flags,
+            subsampling_x: true,
+            subsampling_y: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn scale_factors() {
+        let frame = FrameSize {
+            width: 320,
+            height: 240,
+        };
+        let same = ScaleFactors::new(&frame.clone(), &frame).unwrap();
+        assert!(!same.is_scaled());
+
+        let twice = FrameSize {
+            width: 640,
+            height: 480,
+        };
+        let downscaled = ScaleFactors::new(&twice, &frame).unwrap();
+        assert_eq!(downscaled.x_scale, 2 * REF_NO_SCALE);
+        assert_eq!(downscaled.y_scale, 2 * REF_NO_SCALE);
+
+        let too_large = FrameSize {
+            width: 642,
+            height: 480,
+        };
+        assert_eq!(ScaleFactors::new(&too_large, &frame), None);
+
+        let sixteenth = FrameSize {
+            width: 20,
+            height: 15,
+        };
+        assert_eq!(
+            ScaleFactors::new(&sixteenth, &frame).unwrap().x_scale,
+            REF_NO_SCALE / 16
+        );
+
+        let too_small = FrameSize {
+            width: 20,
+            height: 14,
+        };
+        assert_eq!(ScaleFactors::new(&too_small, &frame), None);
+    }
+
+    #[test]
+    fn track_references() {
+        let mut slots = ReferenceSlots::default();
+
+        let mut hdr = header(FrameType::InterFrame, 320, 240, 0);
+        assert!(slots.scale_factors(&hdr).is_err());
+
+        let key = header(FrameType::KeyFrame, 1280, 720, 0xff);
+        assert_eq!(slots.scale_factors(&key).unwrap(), [None; REFS_PER_FRAME]);
+        slots.update(&key);
+        assert_eq!(slots.get(7).unwrap().size.width, 1280);
+
+        // Slot 1 now holds a frame with a quarter of the key frame's size.
+        let small = header(FrameType::InterFrame, 320, 180, 0b10);
+        slots.update(&small);
+
+        hdr.width = 640;
+        hdr.height = 360;
+        hdr.ref_frame_idx = [1, 0, 2];
+        let scale_factors = slots.scale_factors(&hdr).unwrap();
+        assert_eq!(
+            scale_factors[0],
+            Some(ScaleFactors {
+                x_scale: REF_NO_SCALE / 2,
+                y_scale: REF_NO_SCALE / 2,
+            })
+        );
+        assert_eq!(
+            scale_factors[1],
+            Some(ScaleFactors {
+                x_scale: 2 * REF_NO_SCALE,
+                y_scale: 2 * REF_NO_SCALE,
+            })
+        );
+
+        // The key frame is too large to predict a 320x180 frame from, the
+        // small one is fine.
+        hdr.width = 320;
+        hdr.height = 180;
+        let scale_factors = slots.scale_factors(&hdr).unwrap();
+        assert!(!scale_factors[0].unwrap().is_scaled());
+        assert_eq!(scale_factors[1], None);
+        assert_eq!(scale_factors[2], None);
+
+        // No reference can be used anymore.
+        hdr.ref_frame_idx = [0, 2, 3];
+        assert!(slots.scale_factors(&hdr).is_err());
+
+        // The bit depth must match.
+        hdr.ref_frame_idx = [1, 1, 1];
+        hdr.bit_depth = BitDepth::Depth10;
+        assert!(slots.scale_factors(&hdr).is_err());
+    }
+}
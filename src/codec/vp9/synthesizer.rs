@@ -0,0 +1,638 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+use std::io::Write;
+
+use thiserror::Error;
+
+use crate::codec::vp9::parser::BitDepth;
+use crate::codec::vp9::parser::ColorRange;
+use crate::codec::vp9::parser::ColorSpace;
+use crate::codec::vp9::parser::FrameSize;
+use crate::codec::vp9::parser::FrameType;
+use crate::codec::vp9::parser::Header;
+use crate::codec::vp9::parser::InterpolationFilter;
+use crate::codec::vp9::parser::LoopFilterParams;
+use crate::codec::vp9::parser::Parser;
+use crate::codec::vp9::parser::Profile;
+use crate::codec::vp9::parser::QuantizationParams;
+use crate::codec::vp9::parser::SegmentationParams;
+use crate::codec::vp9::parser::FRAME_MARKER;
+use crate::codec::vp9::parser::LAST_FRAME;
+use crate::codec::vp9::parser::MAX_MODE_LF_DELTAS;
+use crate::codec::vp9::parser::MAX_REF_LF_DELTAS;
+use crate::codec::vp9::parser::MAX_SEGMENTS;
+use crate::codec::vp9::parser::PREDICTION_PROBS;
+use crate::codec::vp9::parser::REFS_PER_FRAME;
+use crate::codec::vp9::parser::REF_FRAMES;
+use crate::codec::vp9::parser::SEG_LVL_MAX;
+use crate::codec::vp9::parser::SEG_TREE_PROBS;
+use crate::codec::vp9::parser::SYNC_CODE;
+use crate::utils::BitWriter;
+use crate::utils::BitWriterError;
+
+mod private {
+    pub trait HeaderStruct {}
+}
+
+impl private::HeaderStruct for Header {}
+
+#[derive(Error, Debug)]
+pub enum SynthesizerError {
+    #[error("tried to synthesize unsupported settings")]
+    Unsupported,
+    #[error("invalid syntax element value {0}")]
+    InvalidSyntaxElementValue(&'static str),
+    #[error(transparent)]
+    BitWriter(#[from] BitWriterError),
+}
+
+pub type SynthesizerResult<T> = Result<T, SynthesizerError>;
+
+/// A writer for VP9 headers. It serializes the structures produced by
+/// [`crate::codec::vp9::parser::Parser`] back to the bitstream syntax.
+pub struct Synthesizer<'h, H: private::HeaderStruct, W: Write> {
+    writer: BitWriter<W>,
+    hdr: &'h H,
+    /// The number of bits written so far.
+    num_bits: usize,
+}
+
+impl<'h, H, W> Synthesizer<'h, H, W>
+where
+    H: private::HeaderStruct,
+    W: Write,
+{
+    fn new(writer: W, hdr: &'h H) -> Self {
+        Self {
+            writer: BitWriter::new(writer),
+            hdr,
+            num_bits: 0,
+        }
+    }
+
+    fn f<T: Into<u32>>(&mut self, bits: usize, value: T) -> SynthesizerResult<()> {
+        self.num_bits += self.writer.write_f(bits, value)?;
+        Ok(())
+    }
+
+    /// Writes the magnitude of `value` using `bits` bits followed by its sign bit, as done for
+    /// the signed syntax elements of VP9, e.g. `delta_q` or `loop_filter_ref_deltas`.
+    fn s<T: Into<i32>>(
+        &mut self,
+        bits: usize,
+        value: T,
+        element: &'static str,
+    ) -> SynthesizerResult<()> {
+        let value: i32 = value.into();
+        let magnitude = value.unsigned_abs();
+
+        if magnitude >= 1 << bits {
+            return Err(SynthesizerError::InvalidSyntaxElementValue(element));
+        }
+
+        self.f(bits, magnitude)?;
+        self.f(1, value < 0)
+    }
+
+    /// Writes VP9 6.2 trailing_bits()
+    fn trailing_bits(&mut self) -> SynthesizerResult<()> {
+        while !self.num_bits.is_multiple_of(8) {
+            self.f(1, /* zero_bit */ false)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'h, W: Write> Synthesizer<'h, Header, W> {
+    /// Writes the `uncompressed_header()` of `hdr` followed by `trailing_bits()`, and returns
+    /// the number of bytes written, i.e. `uncompressed_header_size_in_bytes`.
+    ///
+    /// `ref_frame_sizes` holds the dimensions of the reference frame slots before `hdr` is
+    /// decoded. Inter frames signal their size through the first reference in
+    /// `hdr.ref_frame_idx` that has the same dimensions, if any. As the compressed header comes
+    /// after the uncompressed one, `hdr.header_size_in_bytes` must already be known.
+    pub fn synthesize(
+        hdr: &'h Header,
+        ref_frame_sizes: &[FrameSize; REF_FRAMES],
+        writer: W,
+    ) -> SynthesizerResult<u16> {
+        let mut s = Self::new(writer, hdr);
+
+        s.uncompressed_header(ref_frame_sizes)?;
+        s.trailing_bits()?;
+
+        u16::try_from(s.num_bits / 8).map_err(|_| SynthesizerError::Unsupported)
+    }
+
+    fn frame_sync_code(&mut self) -> SynthesizerResult<()> {
+        self.f(24, SYNC_CODE)
+    }
+
+    fn color_config(&mut self) -> SynthesizerResult<()> {
+        let hdr = self.hdr;
+        let high_bitdepth = matches!(hdr.profile, Profile::Profile2 | Profile::Profile3);
+
+        if high_bitdepth {
+            if matches!(hdr.bit_depth, BitDepth::Depth8) {
+                return Err(SynthesizerError::InvalidSyntaxElementValue("bit_depth"));
+            }
+
+            self.f(
+                1,
+                /* ten_or_twelve_bit */ hdr.bit_depth == BitDepth::Depth12,
+            )?;
+        } else if !matches!(hdr.bit_depth, BitDepth::Depth8) {
+            return Err(SynthesizerError::InvalidSyntaxElementValue("bit_depth"));
+        }
+
+        self.f(3, hdr.color_space as u32)?;
+
+        let has_subsampling = matches!(hdr.profile, Profile::Profile1 | Profile::Profile3);
+        if hdr.color_space != ColorSpace::CsSrgb {
+            self.f(1, hdr.color_range as u32)?;
+
+            if has_subsampling {
+                if hdr.subsampling_x && hdr.subsampling_y {
+                    return Err(SynthesizerError::InvalidSyntaxElementValue("subsampling"));
+                }
+
+                self.f(1, hdr.subsampling_x)?;
+                self.f(1, hdr.subsampling_y)?;
+                self.f(1, /* reserved_zero */ false)?;
+            } else if !hdr.subsampling_x || !hdr.subsampling_y {
+                return Err(SynthesizerError::InvalidSyntaxElementValue("subsampling"));
+            }
+        } else {
+            if hdr.color_range != ColorRange::FullSwing {
+                return Err(SynthesizerError::InvalidSyntaxElementValue("color_range"));
+            }
+
+            // 4:4:4 sRGB can only be signalled in profiles 1 and 3.
+            if !has_subsampling || hdr.subsampling_x || hdr.subsampling_y {
+                return Err(SynthesizerError::InvalidSyntaxElementValue("color_space"));
+            }
+
+            self.f(1, /* reserved_zero */ false)?;
+        }
+
+        Ok(())
+    }
+
+    fn frame_size(&mut self) -> SynthesizerResult<()> {
+        let hdr = self.hdr;
+
+        let frame_width_minus_1 = u16::try_from(hdr.width.wrapping_sub(1))
+            .map_err(|_| SynthesizerError::InvalidSyntaxElementValue("width"))?;
+        let frame_height_minus_1 = u16::try_from(hdr.height.wrapping_sub(1))
+            .map_err(|_| SynthesizerError::InvalidSyntaxElementValue("height"))?;
+
+        self.f(16, frame_width_minus_1)?;
+        self.f(16, frame_height_minus_1)
+    }
+
+    fn render_size(&mut self) -> SynthesizerResult<()> {
+        let hdr = self.hdr;
+
+        self.f(1, hdr.render_and_frame_size_different)?;
+        if hdr.render_and_frame_size_different {
+            let render_width_minus_1 = u16::try_from(hdr.render_width.wrapping_sub(1))
+                .map_err(|_| SynthesizerError::InvalidSyntaxElementValue("render_width"))?;
+            let render_height_minus_1 = u16::try_from(hdr.render_height.wrapping_sub(1))
+                .map_err(|_| SynthesizerError::InvalidSyntaxElementValue("render_height"))?;
+
+            self.f(16, render_width_minus_1)?;
+            self.f(16, render_height_minus_1)?;
+        }
+
+        Ok(())
+    }
+
+    fn frame_size_with_refs(
+        &mut self,
+        ref_frame_sizes: &[FrameSize; REF_FRAMES],
+    ) -> SynthesizerResult<()> {
+        let hdr = self.hdr;
+        let mut found_ref = false;
+
+        for i in 0..REFS_PER_FRAME {
+            let ref_frame_size = ref_frame_sizes
+                .get(usize::from(hdr.ref_frame_idx[i]))
+                .ok_or(SynthesizerError::InvalidSyntaxElementValue("ref_frame_idx"))?;
+
+            found_ref = ref_frame_size.width == hdr.width && ref_frame_size.height == hdr.height;
+            self.f(1, found_ref)?;
+
+            if found_ref {
+                break;
+            }
+        }
+
+        if !found_ref {
+            self.frame_size()?;
+        }
+
+        self.render_size()
+    }
+
+    fn interpolation_filter(&mut self) -> SynthesizerResult<()> {
+        // Inverse of the literal_to_type mapping of VP9 6.2.7.
+        let literal = match self.hdr.interpolation_filter {
+            InterpolationFilter::Switchable => {
+                return self.f(1, /* is_filter_switchable */ true);
+            }
+            InterpolationFilter::EightTapSmooth => 0u32,
+            InterpolationFilter::EightTap => 1,
+            InterpolationFilter::EightTapSharp => 2,
+            InterpolationFilter::Bilinear => 3,
+        };
+
+        self.f(1, /* is_filter_switchable */ false)?;
+        self.f(2, literal)
+    }
+
+    fn loop_filter_params(&mut self, lf: &LoopFilterParams) -> SynthesizerResult<()> {
+        self.f(6, lf.level)?;
+        self.f(3, lf.sharpness)?;
+        self.f(1, lf.delta_enabled)?;
+
+        if lf.delta_enabled {
+            self.f(1, lf.delta_update)?;
+            if lf.delta_update {
+                for i in 0..MAX_REF_LF_DELTAS {
+                    self.f(1, lf.update_ref_delta[i])?;
+                    if lf.update_ref_delta[i] {
+                        self.s(6, lf.ref_deltas[i], "loop_filter_ref_deltas")?;
+                    }
+                }
+
+                for i in 0..MAX_MODE_LF_DELTAS {
+                    self.f(1, lf.update_mode_delta[i])?;
+                    if lf.update_mode_delta[i] {
+                        self.s(6, lf.mode_deltas[i], "loop_filter_mode_deltas")?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn delta_q(&mut self, value: i8) -> SynthesizerResult<()> {
+        self.f(1, /* delta_coded */ value != 0)?;
+        if value != 0 {
+            self.s(4, value, "delta_q")?;
+        }
+
+        Ok(())
+    }
+
+    fn quantization_params(&mut self, quant: &QuantizationParams) -> SynthesizerResult<()> {
+        self.f(8, quant.base_q_idx)?;
+        self.delta_q(quant.delta_q_y_dc)?;
+        self.delta_q(quant.delta_q_uv_dc)?;
+        self.delta_q(quant.delta_q_uv_ac)
+    }
+
+    fn prob(&mut self, prob: u8) -> SynthesizerResult<()> {
+        // A probability of 255 is implied when it is not coded.
+        self.f(1, /* prob_coded */ prob != 255)?;
+        if prob != 255 {
+            self.f(8, prob)?;
+        }
+
+        Ok(())
+    }
+
+    fn segmentation_params(&mut self, seg: &SegmentationParams) -> SynthesizerResult<()> {
+        const SEGMENTATION_FEATURE_BITS: [usize; SEG_LVL_MAX] = [8, 6, 2, 0];
+        const SEGMENTATION_FEATURE_SIGNED: [bool; SEG_LVL_MAX] = [true, true, false, false];
+
+        self.f(1, seg.enabled)?;
+        if !seg.enabled {
+            return Ok(());
+        }
+
+        self.f(1, seg.update_map)?;
+        if seg.update_map {
+            for i in 0..SEG_TREE_PROBS {
+                self.prob(seg.tree_probs[i])?;
+            }
+
+            self.f(1, seg.temporal_update)?;
+            if seg.temporal_update {
+                for i in 0..PREDICTION_PROBS {
+                    self.prob(seg.pred_probs[i])?;
+                }
+            }
+        }
+
+        self.f(1, seg.update_data)?;
+        if seg.update_data {
+            self.f(1, seg.abs_or_delta_update)?;
+            for i in 0..MAX_SEGMENTS {
+                for j in 0..SEG_LVL_MAX {
+                    self.f(1, seg.feature_enabled[i][j])?;
+                    if !seg.feature_enabled[i][j] {
+                        continue;
+                    }
+
+                    let bits = SEGMENTATION_FEATURE_BITS[j];
+                    let value = seg.feature_data[i][j];
+                    if SEGMENTATION_FEATURE_SIGNED[j] {
+                        self.s(bits, value, "feature_value")?;
+                    } else {
+                        if !(0..1 << bits).contains(&value) {
+                            return Err(SynthesizerError::InvalidSyntaxElementValue(
+                                "feature_value",
+                            ));
+                        }
+
+                        self.f(bits, value as u32)?;
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn tile_info(&mut self) -> SynthesizerResult<()> {
+        let hdr = self.hdr;
+
+        let mi_cols = (hdr.width + 7) >> 3;
+        let sb64_cols = (mi_cols + 7) >> 3;
+        let min_log2_tile_cols = Parser::calc_min_log2_tile_cols(sb64_cols);
+        let max_log2_tile_cols = Parser::calc_max_log2_tile_cols(sb64_cols);
+
+        if hdr.tile_cols_log2 < min_log2_tile_cols || hdr.tile_cols_log2 > max_log2_tile_cols {
+            return Err(SynthesizerError::InvalidSyntaxElementValue(
+                "tile_cols_log2",
+            ));
+        }
+
+        for _ in min_log2_tile_cols..hdr.tile_cols_log2 {
+            self.f(1, /* increment_tile_cols_log2 */ true)?;
+        }
+
+        if hdr.tile_cols_log2 < max_log2_tile_cols {
+            self.f(1, /* increment_tile_cols_log2 */ false)?;
+        }
+
+        match hdr.tile_rows_log2 {
+            0 => self.f(1, /* tile_rows_log2 */ false),
+            1 => {
+                self.f(1, /* tile_rows_log2 */ true)?;
+                self.f(1, /* increment_tile_rows_log2 */ false)
+            }
+            2 => {
+                self.f(1, /* tile_rows_log2 */ true)?;
+                self.f(1, /* increment_tile_rows_log2 */ true)
+            }
+            _ => Err(SynthesizerError::InvalidSyntaxElementValue(
+                "tile_rows_log2",
+            )),
+        }
+    }
+
+    fn uncompressed_header(
+        &mut self,
+        ref_frame_sizes: &[FrameSize; REF_FRAMES],
+    ) -> SynthesizerResult<()> {
+        // VP9 6.2
+        let hdr = self.hdr;
+
+        self.f(2, FRAME_MARKER)?;
+
+        let profile = hdr.profile as u32;
+        self.f(1, /* profile_low_bit */ profile & 1)?;
+        self.f(1, /* profile_high_bit */ profile >> 1)?;
+        if matches!(hdr.profile, Profile::Profile3) {
+            self.f(1, /* reserved_zero */ false)?;
+        }
+
+        self.f(1, hdr.show_existing_frame)?;
+        if hdr.show_existing_frame {
+            return self.f(3, hdr.frame_to_show_map_idx);
+        }
+
+        self.f(1, hdr.frame_type as u32)?;
+        self.f(1, hdr.show_frame)?;
+        self.f(1, hdr.error_resilient_mode)?;
+
+        if matches!(hdr.frame_type, FrameType::KeyFrame) {
+            self.frame_sync_code()?;
+            self.color_config()?;
+            self.frame_size()?;
+            self.render_size()?;
+        } else {
+            if !hdr.show_frame {
+                self.f(1, hdr.intra_only)?;
+            } else if hdr.intra_only {
+                return Err(SynthesizerError::InvalidSyntaxElementValue("intra_only"));
+            }
+
+            if !hdr.error_resilient_mode {
+                self.f(2, hdr.reset_frame_context)?;
+            }
+
+            if hdr.intra_only {
+                self.frame_sync_code()?;
+
+                if !matches!(hdr.profile, Profile::Profile0) {
+                    self.color_config()?;
+                } else if hdr.bit_depth != BitDepth::Depth8
+                    || !hdr.subsampling_x
+                    || !hdr.subsampling_y
+                {
+                    // Profile 0 intra-only frames are always 8 bit 4:2:0.
+                    return Err(SynthesizerError::InvalidSyntaxElementValue("color_config"));
+                }
+
+                self.f(8, hdr.refresh_frame_flags)?;
+                self.frame_size()?;
+                self.render_size()?;
+            } else {
+                self.f(8, hdr.refresh_frame_flags)?;
+
+                for i in 0..REFS_PER_FRAME {
+                    self.f(3, hdr.ref_frame_idx[i])?;
+                    self.f(1, hdr.ref_frame_sign_bias[LAST_FRAME + i])?;
+                }
+
+                self.frame_size_with_refs(ref_frame_sizes)?;
+                self.f(1, hdr.allow_high_precision_mv)?;
+                self.interpolation_filter()?;
+            }
+        }
+
+        if !hdr.error_resilient_mode {
+            self.f(1, hdr.refresh_frame_context)?;
+            self.f(1, hdr.frame_parallel_decoding_mode)?;
+        }
+
+        self.f(2, hdr.frame_context_idx)?;
+
+        self.loop_filter_params(&hdr.lf)?;
+        self.quantization_params(&hdr.quant)?;
+        self.segmentation_params(&hdr.seg)?;
+        self.tile_info()?;
+
+        self.f(16, hdr.header_size_in_bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec::vp9::parser::SEG_LVL_ALT_L;
+    use crate::codec::vp9::parser::SEG_LVL_REF_FRAME;
+    use crate::codec::vp9::parser::SEG_LVL_SKIP;
+    use crate::utils::IvfIterator;
+
+    /// Re-synthesizes the uncompressed header of every frame in the IVF `stream` and checks that
+    /// it is identical to the original one.
+    fn uncompressed_headers_round_trip(stream: &[u8]) {
+        let mut parser = Parser::default();
+        let mut ref_frame_sizes: [FrameSize; REF_FRAMES] = Default::default();
+        let mut num_frames = 0;
+
+        for packet in IvfIterator::new(stream) {
+            for frame in parser.parse_chunk(packet.as_ref()).unwrap() {
+                let hdr = &frame.header;
+
+                let mut buf = Vec::<u8>::new();
+                let size =
+                    Synthesizer::<'_, Header, _>::synthesize(hdr, &ref_frame_sizes, &mut buf)
+                        .unwrap();
+
+                assert_eq!(usize::from(size), buf.len());
+                assert_eq!(&buf[..], &frame.as_ref()[..buf.len()]);
+
+                if !hdr.show_existing_frame {
+                    assert_eq!(size, hdr.uncompressed_header_size_in_bytes);
+                }
+
+                for (i, ref_frame_size) in ref_frame_sizes.iter_mut().enumerate() {
+                    if hdr.refresh_frame_flags & (1 << i) != 0 {
+                        *ref_frame_size = FrameSize {
+                            width: hdr.width,
+                            height: hdr.height,
+                        };
+                    }
+                }
+
+                num_frames += 1;
+            }
+        }
+
+        assert!(num_frames > 0);
+    }
+
+    #[test]
+    fn synthesize_test25fps() {
+        uncompressed_headers_round_trip(include_bytes!("test_data/test-25fps.vp9"));
+    }
+
+    #[test]
+    fn synthesize_resolution_change() {
+        uncompressed_headers_round_trip(include_bytes!(
+            "test_data/resolution_change_500frames-vp9.ivf"
+        ));
+    }
+
+    #[test]
+    fn synthesize_show_existing_frame() {
+        uncompressed_headers_round_trip(include_bytes!(
+            "test_data/vp90-2-10-show-existing-frame.vp9.ivf"
+        ));
+        uncompressed_headers_round_trip(include_bytes!(
+            "test_data/vp90-2-10-show-existing-frame2.vp9.ivf"
+        ));
+    }
+
+    #[test]
+    fn synthesize_key_frame_params() {
+        let mut hdr = Header {
+            profile: Profile::Profile1,
+            bit_depth: BitDepth::Depth8,
+            subsampling_x: true,
+            subsampling_y: false,
+            color_space: ColorSpace::Bt709,
+            color_range: ColorRange::FullSwing,
+            frame_type: FrameType::KeyFrame,
+            show_frame: true,
+            width: 1920,
+            height: 1080,
+            render_and_frame_size_different: true,
+            render_width: 1280,
+            render_height: 720,
+            refresh_frame_flags: 0xff,
+            refresh_frame_context: true,
+            frame_context_idx: 2,
+            tile_cols_log2: 2,
+            tile_rows_log2: 1,
+            header_size_in_bytes: 1234,
+            ..Default::default()
+        };
+
+        hdr.lf = LoopFilterParams {
+            level: 36,
+            sharpness: 5,
+            delta_enabled: true,
+            delta_update: true,
+            update_ref_delta: [true; MAX_REF_LF_DELTAS],
+            ref_deltas: [1, 0, -1, -63],
+            update_mode_delta: [true; MAX_MODE_LF_DELTAS],
+            mode_deltas: [63, -2],
+        };
+
+        hdr.quant = QuantizationParams {
+            base_q_idx: 120,
+            delta_q_y_dc: -15,
+            delta_q_uv_dc: 3,
+            delta_q_uv_ac: 0,
+        };
+
+        hdr.seg = SegmentationParams {
+            enabled: true,
+            update_map: true,
+            tree_probs: [1, 255, 128, 4, 255, 99, 200],
+            pred_probs: [255; PREDICTION_PROBS],
+            temporal_update: false,
+            update_data: true,
+            abs_or_delta_update: true,
+            ..Default::default()
+        };
+        hdr.seg.feature_enabled[0][0] = true;
+        hdr.seg.feature_data[0][0] = -255;
+        hdr.seg.feature_enabled[3][SEG_LVL_ALT_L] = true;
+        hdr.seg.feature_data[3][SEG_LVL_ALT_L] = 63;
+        hdr.seg.feature_enabled[5][SEG_LVL_REF_FRAME] = true;
+        hdr.seg.feature_data[5][SEG_LVL_REF_FRAME] = 3;
+        hdr.seg.feature_enabled[7][SEG_LVL_SKIP] = true;
+
+        let mut buf = Vec::<u8>::new();
+        let size =
+            Synthesizer::<'_, Header, _>::synthesize(&hdr, &Default::default(), &mut buf).unwrap();
+        // An all-zero compressed header is valid and updates no probability.
+        buf.resize(buf.len() + usize::from(hdr.header_size_in_bytes), 0);
+
+        let mut parser = Parser::default();
+        let frames = parser.parse_chunk(&buf).unwrap();
+        assert_eq!(frames.len(), 1);
+
+        hdr.uncompressed_header_size_in_bytes = size;
+        assert_eq!(frames[0].header, hdr);
+
+        // Segment feature values must fit their bit width.
+        hdr.seg.feature_data[5][SEG_LVL_REF_FRAME] = 4;
+        let err = Synthesizer::<'_, Header, _>::synthesize(&hdr, &Default::default(), Vec::new());
+        assert!(matches!(
+            err,
+            Err(SynthesizerError::InvalidSyntaxElementValue("feature_value"))
+        ));
+    }
+}
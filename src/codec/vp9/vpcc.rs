@@ -0,0 +1,132 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Support for the VPCodecConfigurationRecord of the "VP Codec ISO Media File
+//! Format Binding" specification, i.e. the contents of the `vpcC` box of VP9
+//! streams stored in MP4 files.
+
+use std::io::Write;
+
+use anyhow::anyhow;
+use bytes::Buf;
+
+/// A VPCodecConfigurationRecord, without the version and flags of the `vpcC`
+/// box containing it.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct VpCodecConfigurationRecord {
+    pub profile: u8,
+    pub level: u8,
+    pub bit_depth: u8,
+    /// 0 and 1 for 4:2:0 with vertical and colocated chroma respectively, 2
+    /// for 4:2:2 and 3 for 4:4:4.
+    pub chroma_subsampling: u8,
+    pub video_full_range_flag: bool,
+    /// The color primaries, transfer characteristics and matrix coefficients,
+    /// as defined in ISO/IEC 23091-4.
+    pub colour_primaries: u8,
+    pub transfer_characteristics: u8,
+    pub matrix_coefficients: u8,
+    /// Unused for VP8 and VP9.
+    pub codec_initialization_data: Vec<u8>,
+}
+
+impl VpCodecConfigurationRecord {
+    /// Parses a record, e.g. from the contents of an MP4 `vpcC` box once its
+    /// version and flags are skipped.
+    pub fn parse(mut data: &[u8]) -> anyhow::Result<Self> {
+        if data.remaining() < 8 {
+            return Err(anyhow!("Broken data: vpcC record is too short"));
+        }
+
+        let profile = data.get_u8();
+        let level = data.get_u8();
+        let byte = data.get_u8();
+        let colour_primaries = data.get_u8();
+        let transfer_characteristics = data.get_u8();
+        let matrix_coefficients = data.get_u8();
+
+        let len = usize::from(data.get_u16());
+        if data.remaining() < len {
+            return Err(anyhow!("Broken data: truncated vpcC initialization data"));
+        }
+
+        Ok(Self {
+            profile,
+            level,
+            bit_depth: byte >> 4,
+            chroma_subsampling: (byte >> 1) & 0x7,
+            video_full_range_flag: byte & 0x1 != 0,
+            colour_primaries,
+            transfer_characteristics,
+            matrix_coefficients,
+            codec_initialization_data: data[..len].to_vec(),
+        })
+    }
+
+    /// Writes the record into `writer`.
+    pub fn write_into(&self, writer: &mut impl Write) -> std::io::Result<()> {
+        let len = u16::try_from(self.codec_initialization_data.len()).map_err(|_| {
+            std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                "initialization data too large",
+            )
+        })?;
+
+        writer.write_all(&[
+            self.profile,
+            self.level,
+            self.bit_depth << 4
+                | (self.chroma_subsampling & 0x7) << 1
+                | u8::from(self.video_full_range_flag),
+            self.colour_primaries,
+            self.transfer_characteristics,
+            self.matrix_coefficients,
+        ])?;
+        writer.write_all(&len.to_be_bytes())?;
+        writer.write_all(&self.codec_initialization_data)
+    }
+
+    /// Returns the codec string identifying the stream, e.g. for the `codecs`
+    /// MIME type parameter, in its short form "vp09.PP.LL.DD".
+    pub fn codec_string(&self) -> String {
+        format!(
+            "vp09.{:02}.{:02}.{:02}",
+            self.profile, self.level, self.bit_depth
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_write_record() {
+        const RECORD: &[u8] = &[0x02, 0x1f, 0xa3, 0x09, 0x10, 0x09, 0x00, 0x00];
+
+        let record = VpCodecConfigurationRecord::parse(RECORD).unwrap();
+        assert_eq!(
+            record,
+            VpCodecConfigurationRecord {
+                profile: 2,
+                level: 31,
+                bit_depth: 10,
+                chroma_subsampling: 1,
+                video_full_range_flag: true,
+                colour_primaries: 9,
+                transfer_characteristics: 16,
+                matrix_coefficients: 9,
+                codec_initialization_data: vec![],
+            }
+        );
+        assert_eq!(record.codec_string(), "vp09.02.31.10");
+
+        let mut data = vec![];
+        record.write_into(&mut data).unwrap();
+        assert_eq!(data, RECORD);
+
+        assert!(VpCodecConfigurationRecord::parse(&RECORD[..7]).is_err());
+        assert!(VpCodecConfigurationRecord::parse(&[0, 0, 0, 0, 0, 0, 0, 1]).is_err());
+    }
+}
@@ -0,0 +1,10 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Demuxers extracting the encoded samples and codec configuration of the
+//! video tracks of container files, so they can be fed to the parsers of the
+//! [crate::codec] module.
+
+pub mod matroska;
+pub mod mp4;
@@ -0,0 +1,1171 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A minimal read-only demuxer of Matroska and WebM files, as specified in RFC
+//! 9559, for the video codecs supported by this crate.
+//!
+//! The whole file is expected in memory. The frames are returned as found in
+//! the blocks, so that VP8, VP9 and AV1 frames can be given to their parser as
+//! is, and H.264 and H.265 frames are made of length-prefixed NAL units.
+
+use thiserror::Error;
+
+use crate::codec::av1::av1c::Av1CodecConfigurationRecord;
+use crate::codec::h264::avcc::AvcDecoderConfigurationRecord;
+use crate::codec::h265::hvcc::HevcDecoderConfigurationRecord;
+
+const EBML: u32 = 0x1a45_dfa3;
+const DOC_TYPE: u32 = 0x4282;
+const SEGMENT: u32 = 0x1853_8067;
+const INFO: u32 = 0x1549_a966;
+const TIMESTAMP_SCALE: u32 = 0x2a_d7b1;
+const TRACKS: u32 = 0x1654_ae6b;
+const TRACK_ENTRY: u32 = 0xae;
+const TRACK_NUMBER: u32 = 0xd7;
+const TRACK_TYPE: u32 = 0x83;
+const CODEC_ID: u32 = 0x86;
+const CODEC_PRIVATE: u32 = 0x63a2;
+const DEFAULT_DURATION: u32 = 0x23_e383;
+const CONTENT_ENCODINGS: u32 = 0x6d80;
+const VIDEO: u32 = 0xe0;
+const PIXEL_WIDTH: u32 = 0xb0;
+const PIXEL_HEIGHT: u32 = 0xba;
+const CLUSTER: u32 = 0x1f43_b675;
+const CLUSTER_TIMESTAMP: u32 = 0xe7;
+const SIMPLE_BLOCK: u32 = 0xa3;
+const BLOCK_GROUP: u32 = 0xa0;
+const BLOCK: u32 = 0xa1;
+const BLOCK_DURATION: u32 = 0x9b;
+const REFERENCE_BLOCK: u32 = 0xfb;
+const VOID: u32 = 0xec;
+const CRC32: u32 = 0xbf;
+
+/// The elements that can be found in a Cluster, used to find the end of
+/// Clusters of unknown size.
+const CLUSTER_CHILDREN: &[u32] = &[
+    CLUSTER_TIMESTAMP,
+    SIMPLE_BLOCK,
+    BLOCK_GROUP,
+    // Position, PrevSize, SilentTracks and EncryptedBlock.
+    0xa7,
+    0xab,
+    0x5854,
+    0xaf,
+    VOID,
+    CRC32,
+];
+
+/// The default TimestampScale, i.e. timestamps in milliseconds.
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+#[derive(Error, Debug)]
+pub enum MatroskaError {
+    #[error("not an EBML file")]
+    NotEbml,
+    #[error("unsupported document type {0:?}")]
+    UnsupportedDocType(String),
+    #[error("invalid variable size integer at offset {0}")]
+    InvalidVint(usize),
+    #[error("truncated element {0:#x}")]
+    TruncatedElement(u32),
+    #[error("missing element {0:#x}")]
+    MissingElement(u32),
+    #[error("invalid element {0:#x}: {1}")]
+    InvalidElement(u32, &'static str),
+    #[error("unknown track {0}")]
+    UnknownTrack(u64),
+    #[error("unsupported feature: {0}")]
+    UnsupportedFeature(&'static str),
+    #[error("invalid {0} codec private data: {1}")]
+    InvalidCodecPrivate(String, anyhow::Error),
+}
+
+pub type MatroskaResult<T> = std::result::Result<T, MatroskaError>;
+
+/// Reads the variable size integer at the start of `data` as per section 4 of
+/// RFC 8794, returning its value with the length marker cleared, and its
+/// length.
+fn read_vint(data: &[u8], max_len: usize) -> Option<(u64, usize)> {
+    let &first = data.first()?;
+    let len = first.leading_zeros() as usize + 1;
+    if len > max_len {
+        return None;
+    }
+
+    let bytes = data.get(..len)?;
+    let value = bytes[1..]
+        .iter()
+        .fold(u64::from(first) & (0xff >> len), |acc, &b| {
+            (acc << 8) | u64::from(b)
+        });
+
+    Some((value, len))
+}
+
+/// An EBML element.
+struct Element<'a> {
+    id: u32,
+    /// The position of the data of the element in the file.
+    offset: usize,
+    data: &'a [u8],
+}
+
+impl<'a> Element<'a> {
+    fn children(&self) -> ElementIterator<'a> {
+        ElementIterator::new(self.data, self.offset)
+    }
+
+    fn read_uint(&self) -> MatroskaResult<u64> {
+        if self.data.len() > 8 {
+            return Err(MatroskaError::InvalidElement(self.id, "integer too large"));
+        }
+
+        Ok(self
+            .data
+            .iter()
+            .fold(0u64, |acc, &b| (acc << 8) | u64::from(b)))
+    }
+
+    fn read_string(&self) -> String {
+        let len = self
+            .data
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(self.data.len());
+        String::from_utf8_lossy(&self.data[..len]).into_owned()
+    }
+}
+
+/// Iterator over the EBML elements contained in a buffer. The iteration stops
+/// after the first error.
+struct ElementIterator<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// The position of `data` in the file.
+    base: usize,
+}
+
+impl<'a> ElementIterator<'a> {
+    fn new(data: &'a [u8], base: usize) -> Self {
+        Self { data, pos: 0, base }
+    }
+
+    fn read_element(&mut self) -> MatroskaResult<Element<'a>> {
+        let data = &self.data[self.pos..];
+        let invalid_vint = |pos| MatroskaError::InvalidVint(self.base + pos);
+
+        // IDs keep their length marker.
+        let (_, id_len) = read_vint(data, 4).ok_or_else(|| invalid_vint(self.pos))?;
+        let id = data[..id_len]
+            .iter()
+            .fold(0u32, |acc, &b| (acc << 8) | u32::from(b));
+
+        let (size, size_len) =
+            read_vint(&data[id_len..], 8).ok_or_else(|| invalid_vint(self.pos + id_len))?;
+        let header_len = id_len + size_len;
+        let unknown_size = size == (1 << (7 * size_len)) - 1;
+
+        let size = if !unknown_size {
+            usize::try_from(size)
+                .ok()
+                .filter(|&size| size <= data.len() - header_len)
+                .ok_or(MatroskaError::TruncatedElement(id))?
+        } else if id == CLUSTER {
+            cluster_size(&data[header_len..], self.base + self.pos + header_len)?
+        } else if id == SEGMENT {
+            data.len() - header_len
+        } else {
+            return Err(MatroskaError::InvalidElement(id, "unknown size"));
+        };
+
+        let offset = self.base + self.pos + header_len;
+        self.pos += header_len + size;
+
+        Ok(Element {
+            id,
+            offset,
+            data: &data[header_len..header_len + size],
+        })
+    }
+}
+
+impl<'a> Iterator for ElementIterator<'a> {
+    type Item = MatroskaResult<Element<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let element = self.read_element();
+        if element.is_err() {
+            self.pos = self.data.len();
+        }
+
+        Some(element)
+    }
+}
+
+/// Returns the size of a Cluster of unknown size whose data starts at `data`,
+/// i.e. up to the first element that cannot be one of its children.
+fn cluster_size(data: &[u8], base: usize) -> MatroskaResult<usize> {
+    let mut children = ElementIterator::new(data, base);
+
+    loop {
+        let pos = children.pos;
+        if pos >= data.len() {
+            return Ok(pos);
+        }
+
+        let (_, id_len) =
+            read_vint(&data[pos..], 4).ok_or(MatroskaError::InvalidVint(base + pos))?;
+        let id = data[pos..pos + id_len]
+            .iter()
+            .fold(0u32, |acc, &b| (acc << 8) | u32::from(b));
+        if !CLUSTER_CHILDREN.contains(&id) {
+            return Ok(pos);
+        }
+
+        children.next().transpose()?;
+    }
+}
+
+/// The codec of a track, with its configuration record for the codecs that
+/// have one, to be handed to the parser of the codec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrackCodec {
+    /// `V_VP8`.
+    Vp8,
+    /// `V_VP9`, whose codec private data only holds optional features.
+    Vp9,
+    /// `V_AV1`, with the AV1CodecConfigurationRecord of the codec private data.
+    Av1(Av1CodecConfigurationRecord),
+    /// `V_MPEG4/ISO/AVC`, with the AVCDecoderConfigurationRecord of the codec
+    /// private data.
+    Avc(AvcDecoderConfigurationRecord),
+    /// `V_MPEGH/ISO/HEVC`, with the HEVCDecoderConfigurationRecord of the
+    /// codec private data.
+    Hevc(HevcDecoderConfigurationRecord),
+}
+
+/// The position and timing of a frame.
+#[derive(Clone, Debug)]
+struct FrameInfo {
+    offset: usize,
+    len: usize,
+    timestamp: i64,
+    duration: Option<u64>,
+    is_keyframe: bool,
+    is_invisible: bool,
+}
+
+/// A track of the file.
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub number: u64,
+    /// The type of the track, e.g. 1 for video tracks.
+    pub track_type: u64,
+    pub codec_id: String,
+    pub codec_private: Option<Vec<u8>>,
+    /// `None` for unsupported codecs, e.g. audio.
+    pub codec: Option<TrackCodec>,
+    /// The dimensions of video tracks, or 0.
+    pub width: u64,
+    pub height: u64,
+    /// The duration of each frame in nanoseconds, if constant.
+    pub default_duration: Option<u64>,
+    frames: Vec<FrameInfo>,
+}
+
+impl Track {
+    /// The number of frames of the track.
+    pub fn num_frames(&self) -> usize {
+        self.frames.len()
+    }
+}
+
+/// A frame of a track.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Frame<'a> {
+    pub data: &'a [u8],
+    /// The presentation timestamp in nanoseconds.
+    pub timestamp: i64,
+    /// The duration in nanoseconds, if known.
+    pub duration: Option<u64>,
+    /// Whether decoding can start from this frame.
+    pub is_keyframe: bool,
+    /// Whether the frame should be decoded but not displayed.
+    pub is_invisible: bool,
+}
+
+/// A read-only demuxer of Matroska and WebM files held in memory.
+pub struct MatroskaDemuxer<'a> {
+    data: &'a [u8],
+    doc_type: String,
+    timestamp_scale: u64,
+    tracks: Vec<Track>,
+}
+
+impl<'a> MatroskaDemuxer<'a> {
+    /// Parses the EBML header and the first Segment of the file in `data`.
+    pub fn new(data: &'a [u8]) -> MatroskaResult<Self> {
+        let mut elements = ElementIterator::new(data, 0);
+
+        let header = match elements.next() {
+            Some(Ok(header)) if header.id == EBML => header,
+            _ => return Err(MatroskaError::NotEbml),
+        };
+
+        let mut doc_type = String::from("matroska");
+        for child in header.children() {
+            let child = child?;
+            if child.id == DOC_TYPE {
+                doc_type = child.read_string();
+            }
+        }
+
+        if !matches!(doc_type.as_str(), "matroska" | "webm") {
+            return Err(MatroskaError::UnsupportedDocType(doc_type));
+        }
+
+        let mut segment = None;
+        for element in elements {
+            let element = element?;
+            if element.id == SEGMENT {
+                segment = Some(element);
+                break;
+            }
+        }
+        let segment = segment.ok_or(MatroskaError::MissingElement(SEGMENT))?;
+
+        let mut demuxer = Self {
+            data,
+            doc_type,
+            timestamp_scale: DEFAULT_TIMESTAMP_SCALE,
+            tracks: vec![],
+        };
+
+        for element in segment.children() {
+            let element = element?;
+
+            match element.id {
+                INFO => {
+                    for child in element.children() {
+                        let child = child?;
+                        if child.id == TIMESTAMP_SCALE {
+                            demuxer.timestamp_scale = child.read_uint()?;
+                        }
+                    }
+
+                    if demuxer.timestamp_scale == 0 {
+                        return Err(MatroskaError::InvalidElement(
+                            TIMESTAMP_SCALE,
+                            "zero timestamp scale",
+                        ));
+                    }
+                }
+                TRACKS => {
+                    for child in element.children() {
+                        let child = child?;
+                        if child.id == TRACK_ENTRY {
+                            demuxer.tracks.push(parse_track_entry(&child)?);
+                        }
+                    }
+                }
+                CLUSTER => demuxer.parse_cluster(&element)?,
+                _ => (),
+            }
+        }
+
+        Ok(demuxer)
+    }
+
+    /// Returns the DocType of the file, i.e. "matroska" or "webm".
+    pub fn doc_type(&self) -> &str {
+        &self.doc_type
+    }
+
+    /// Returns the tracks of the file.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Returns the track with number `number`.
+    pub fn track(&self, number: u64) -> Option<&Track> {
+        self.tracks.iter().find(|track| track.number == number)
+    }
+
+    /// Returns an iterator over the frames of the track with number `number`,
+    /// in decoding order.
+    pub fn frames(&self, number: u64) -> MatroskaResult<Frames<'a, '_>> {
+        let track = self
+            .track(number)
+            .ok_or(MatroskaError::UnknownTrack(number))?;
+
+        Ok(Frames {
+            data: self.data,
+            frames: track.frames.iter(),
+        })
+    }
+
+    /// Converts a timestamp in TimestampScale units into nanoseconds.
+    fn to_ns(&self, timestamp: i64) -> i64 {
+        timestamp.saturating_mul(self.timestamp_scale as i64)
+    }
+
+    fn parse_cluster(&mut self, cluster: &Element) -> MatroskaResult<()> {
+        let mut cluster_timestamp = None;
+
+        for child in cluster.children() {
+            let child = child?;
+
+            match child.id {
+                CLUSTER_TIMESTAMP => {
+                    let timestamp = i64::try_from(child.read_uint()?).map_err(|_| {
+                        MatroskaError::InvalidElement(child.id, "timestamp too large")
+                    })?;
+                    cluster_timestamp = Some(timestamp);
+                }
+                SIMPLE_BLOCK | BLOCK_GROUP => {
+                    let cluster_timestamp = cluster_timestamp
+                        .ok_or(MatroskaError::MissingElement(CLUSTER_TIMESTAMP))?;
+
+                    if child.id == SIMPLE_BLOCK {
+                        self.parse_block(&child, cluster_timestamp, None, None)?;
+                        continue;
+                    }
+
+                    let mut block = None;
+                    let mut duration = None;
+                    let mut has_reference = false;
+                    for group_child in child.children() {
+                        let group_child = group_child?;
+                        match group_child.id {
+                            BLOCK => block = Some(group_child),
+                            BLOCK_DURATION => duration = Some(group_child.read_uint()?),
+                            REFERENCE_BLOCK => has_reference = true,
+                            _ => (),
+                        }
+                    }
+
+                    let block = block.ok_or(MatroskaError::MissingElement(BLOCK))?;
+                    self.parse_block(&block, cluster_timestamp, Some(!has_reference), duration)?;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Parses a SimpleBlock, or a Block whose keyframe flag and duration are
+    /// given by its BlockGroup, as per section 10 of RFC 9559.
+    fn parse_block(
+        &mut self,
+        block: &Element,
+        cluster_timestamp: i64,
+        is_keyframe: Option<bool>,
+        duration: Option<u64>,
+    ) -> MatroskaResult<()> {
+        let data = block.data;
+        let truncated = || MatroskaError::TruncatedElement(block.id);
+
+        let (number, len) = read_vint(data, 8).ok_or(MatroskaError::InvalidVint(block.offset))?;
+        let header = data.get(len..len + 3).ok_or_else(truncated)?;
+        let relative_timestamp = i16::from_be_bytes([header[0], header[1]]);
+        let flags = header[2];
+        let mut pos = len + 3;
+
+        let timestamp = cluster_timestamp
+            .checked_add(i64::from(relative_timestamp))
+            .ok_or(MatroskaError::InvalidElement(
+                block.id,
+                "timestamp too large",
+            ))?;
+        let timestamp = self.to_ns(timestamp);
+        let duration =
+            duration.map(|duration| self.to_ns(i64::try_from(duration).unwrap_or(i64::MAX)) as u64);
+
+        let track = self
+            .tracks
+            .iter_mut()
+            .find(|track| track.number == number)
+            .ok_or(MatroskaError::UnknownTrack(number))?;
+
+        let is_keyframe = is_keyframe.unwrap_or(flags & 0x80 != 0);
+        let is_invisible = flags & 0x08 != 0;
+
+        let sizes = match (flags >> 1) & 0x3 {
+            0 => vec![data.len() - pos],
+            lacing => {
+                let num_frames = usize::from(*data.get(pos).ok_or_else(truncated)?) + 1;
+                pos += 1;
+                read_lace_sizes(block, lacing, num_frames, &mut pos)?
+            }
+        };
+
+        for (i, &len) in sizes.iter().enumerate() {
+            // Laced frames follow each other by the default duration.
+            let default_duration = track.default_duration.unwrap_or(0);
+            track.frames.push(FrameInfo {
+                offset: block.offset + pos,
+                len,
+                timestamp: timestamp.saturating_add(
+                    i64::try_from((i as u64).saturating_mul(default_duration)).unwrap_or(i64::MAX),
+                ),
+                duration: duration.or(track.default_duration),
+                // Only the first frame of a lace can be a keyframe.
+                is_keyframe: is_keyframe && i == 0,
+                is_invisible,
+            });
+            pos += len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads the sizes of the `num_frames` frames of a laced block, as per
+/// section 10.3 of RFC 9559. `pos` is the position of the lace sizes in the
+/// block, and is updated to the position of the first frame.
+fn read_lace_sizes(
+    block: &Element,
+    lacing: u8,
+    num_frames: usize,
+    pos: &mut usize,
+) -> MatroskaResult<Vec<usize>> {
+    let data = block.data;
+    let truncated = || MatroskaError::TruncatedElement(block.id);
+    let mut sizes = Vec::with_capacity(num_frames);
+    let mut laced = 0usize;
+
+    // Each lace must fit in the bytes left in the block.
+    let mut push_size = |size: usize, pos: usize| -> MatroskaResult<()> {
+        laced = laced
+            .checked_add(size)
+            .filter(|&laced| laced <= data.len() - pos)
+            .ok_or_else(truncated)?;
+        sizes.push(size);
+        Ok(())
+    };
+
+    match lacing {
+        // Xiph lacing.
+        1 => {
+            for _ in 0..num_frames - 1 {
+                let mut size = 0;
+                loop {
+                    let byte = *data.get(*pos).ok_or_else(truncated)?;
+                    *pos += 1;
+                    size += usize::from(byte);
+                    if byte != 0xff {
+                        break;
+                    }
+                }
+                push_size(size, *pos)?;
+            }
+        }
+        // EBML lacing, in which sizes are coded as differences with the
+        // previous one.
+        3 => {
+            let mut size = 0i64;
+            for i in 0..num_frames - 1 {
+                let (value, len) = read_vint(data.get(*pos..).ok_or_else(truncated)?, 8)
+                    .ok_or(MatroskaError::InvalidVint(block.offset + *pos))?;
+                *pos += len;
+
+                // The value has at most 56 bits, and the previous size fits
+                // in the block.
+                size = if i == 0 {
+                    value as i64
+                } else {
+                    size.checked_add(value as i64 - ((1i64 << (7 * len - 1)) - 1))
+                        .ok_or(MatroskaError::InvalidElement(block.id, "invalid lace size"))?
+                };
+                let size = usize::try_from(size)
+                    .map_err(|_| MatroskaError::InvalidElement(block.id, "negative lace size"))?;
+                push_size(size, *pos)?;
+            }
+        }
+        // Fixed-size lacing.
+        _ => {
+            let remaining = data.len().checked_sub(*pos).ok_or_else(truncated)?;
+            if remaining % num_frames != 0 {
+                return Err(MatroskaError::InvalidElement(
+                    block.id,
+                    "invalid fixed lace size",
+                ));
+            }
+            return Ok(vec![remaining / num_frames; num_frames]);
+        }
+    }
+
+    let last = data
+        .len()
+        .checked_sub(*pos)
+        .and_then(|remaining| remaining.checked_sub(laced))
+        .ok_or_else(truncated)?;
+    sizes.push(last);
+
+    Ok(sizes)
+}
+
+fn parse_track_entry(entry: &Element) -> MatroskaResult<Track> {
+    let mut track = Track {
+        number: 0,
+        track_type: 0,
+        codec_id: String::new(),
+        codec_private: None,
+        codec: None,
+        width: 0,
+        height: 0,
+        default_duration: None,
+        frames: vec![],
+    };
+
+    for child in entry.children() {
+        let child = child?;
+
+        match child.id {
+            TRACK_NUMBER => track.number = child.read_uint()?,
+            TRACK_TYPE => track.track_type = child.read_uint()?,
+            CODEC_ID => track.codec_id = child.read_string(),
+            CODEC_PRIVATE => track.codec_private = Some(child.data.to_vec()),
+            DEFAULT_DURATION => track.default_duration = Some(child.read_uint()?),
+            CONTENT_ENCODINGS => {
+                return Err(MatroskaError::UnsupportedFeature(
+                    "compressed or encrypted tracks",
+                ))
+            }
+            VIDEO => {
+                for video_child in child.children() {
+                    let video_child = video_child?;
+                    match video_child.id {
+                        PIXEL_WIDTH => track.width = video_child.read_uint()?,
+                        PIXEL_HEIGHT => track.height = video_child.read_uint()?,
+                        _ => (),
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    if track.number == 0 {
+        return Err(MatroskaError::MissingElement(TRACK_NUMBER));
+    }
+
+    let codec_private = || {
+        track
+            .codec_private
+            .as_deref()
+            .ok_or(MatroskaError::MissingElement(CODEC_PRIVATE))
+    };
+    let invalid = |e: anyhow::Error| MatroskaError::InvalidCodecPrivate(track.codec_id.clone(), e);
+
+    let codec = match track.codec_id.as_str() {
+        "V_VP8" => Some(TrackCodec::Vp8),
+        "V_VP9" => Some(TrackCodec::Vp9),
+        "V_AV1" => Some(TrackCodec::Av1(
+            Av1CodecConfigurationRecord::parse(codec_private()?).map_err(invalid)?,
+        )),
+        "V_MPEG4/ISO/AVC" => Some(TrackCodec::Avc(
+            AvcDecoderConfigurationRecord::parse(codec_private()?)
+                .map_err(|e| invalid(e.into()))?,
+        )),
+        "V_MPEGH/ISO/HEVC" => Some(TrackCodec::Hevc(
+            HevcDecoderConfigurationRecord::parse(codec_private()?).map_err(invalid)?,
+        )),
+        _ => None,
+    };
+    track.codec = codec;
+
+    Ok(track)
+}
+
+/// Iterator over the frames of a track.
+pub struct Frames<'a, 'd> {
+    data: &'a [u8],
+    frames: std::slice::Iter<'d, FrameInfo>,
+}
+
+impl<'a, 'd> Iterator for Frames<'a, 'd> {
+    type Item = Frame<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let info = self.frames.next()?;
+
+        Some(Frame {
+            // The bounds were checked when parsing the blocks.
+            data: &self.data[info.offset..info.offset + info.len],
+            timestamp: info.timestamp,
+            duration: info.duration,
+            is_keyframe: info.is_keyframe,
+            is_invisible: info.is_invisible,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::codec::h264::parser::Nalu;
+    use crate::codec::h264::parser::NaluType;
+    use crate::codec::h264::parser::Parser as H264Parser;
+    use crate::codec::vp8::parser::Parser as Vp8Parser;
+    use crate::codec::vp9::parser::Parser as Vp9Parser;
+    use crate::utils::IvfIterator;
+
+    const STREAM_H264: &[u8] = include_bytes!("../codec/h264/test_data/test-25fps.h264");
+    const STREAM_AV1: &[u8] = include_bytes!("../codec/av1/test_data/test-25fps.ivf.av1");
+    const STREAM_VP8: &[u8] = include_bytes!("../codec/vp8/test_data/test-25fps.vp8");
+    const STREAM_VP9: &[u8] = include_bytes!("../codec/vp9/test_data/test-25fps.vp9");
+
+    /// 40ms per frame, in nanoseconds.
+    const FRAME_DURATION: u64 = 40_000_000;
+
+    fn id_bytes(id: u32) -> Vec<u8> {
+        let bytes = id.to_be_bytes();
+        let skip = bytes.iter().position(|&b| b != 0).unwrap();
+        bytes[skip..].to_vec()
+    }
+
+    /// Writes an element, with a one-byte size if possible.
+    fn element(id: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = id_bytes(id);
+        if payload.len() < 0x7f {
+            data.push(0x80 | payload.len() as u8);
+        } else {
+            data.push(0x01);
+            data.extend_from_slice(&(payload.len() as u64).to_be_bytes()[1..]);
+        }
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn unknown_size_element(id: u32, payload: &[u8]) -> Vec<u8> {
+        [
+            &id_bytes(id)[..],
+            &[0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+            payload,
+        ]
+        .concat()
+    }
+
+    fn uint(id: u32, value: u64) -> Vec<u8> {
+        element(id, &value.to_be_bytes())
+    }
+
+    fn ebml_header(doc_type: &str) -> Vec<u8> {
+        element(
+            EBML,
+            &[
+                uint(0x4286, 1),
+                element(DOC_TYPE, doc_type.as_bytes()),
+                uint(0x4287, 4),
+                uint(0x4285, 2),
+            ]
+            .concat(),
+        )
+    }
+
+    enum Lacing {
+        None,
+        Xiph,
+        Ebml,
+        Fixed,
+    }
+
+    /// Returns the payload of a SimpleBlock or Block.
+    fn block(track: u64, timestamp: i16, flags: u8, frames: &[&[u8]], lacing: Lacing) -> Vec<u8> {
+        let mut data = vec![0x80 | track as u8];
+        data.extend_from_slice(&timestamp.to_be_bytes());
+
+        let (lacing_flags, sizes) = match lacing {
+            Lacing::None => {
+                assert_eq!(frames.len(), 1);
+                (0, vec![])
+            }
+            Lacing::Xiph => {
+                let mut sizes = vec![];
+                for frame in &frames[..frames.len() - 1] {
+                    sizes.extend(std::iter::repeat_n(0xff, frame.len() / 255));
+                    sizes.push((frame.len() % 255) as u8);
+                }
+                (0x2, sizes)
+            }
+            Lacing::Ebml => {
+                // The first size on 8 bytes, the differences on 4 bytes.
+                let mut sizes =
+                    [&[0x01][..], &(frames[0].len() as u64).to_be_bytes()[1..]].concat();
+                for pair in frames[..frames.len() - 1].windows(2) {
+                    let diff = pair[1].len() as i64 - pair[0].len() as i64 + (1 << 27) - 1;
+                    sizes.extend_from_slice(&(0x1000_0000 | diff as u32).to_be_bytes());
+                }
+                (0x6, sizes)
+            }
+            Lacing::Fixed => (0x4, vec![]),
+        };
+
+        data.push(flags | lacing_flags);
+        if lacing_flags != 0 {
+            data.push(frames.len() as u8 - 1);
+        }
+        data.extend(sizes);
+        for frame in frames {
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    fn track_entry(number: u64, codec_id: &str, codec_private: Option<&[u8]>) -> Vec<u8> {
+        let mut data = [
+            uint(TRACK_NUMBER, number),
+            uint(0x73c5, number),
+            uint(TRACK_TYPE, 1),
+            element(CODEC_ID, codec_id.as_bytes()),
+            uint(DEFAULT_DURATION, FRAME_DURATION),
+            element(
+                VIDEO,
+                &[uint(PIXEL_WIDTH, 320), uint(PIXEL_HEIGHT, 240)].concat(),
+            ),
+        ]
+        .concat();
+        if let Some(codec_private) = codec_private {
+            data.extend(element(CODEC_PRIVATE, codec_private));
+        }
+        element(TRACK_ENTRY, &data)
+    }
+
+    fn segment(tracks: &[Vec<u8>], clusters: &[Vec<u8>]) -> Vec<u8> {
+        let info = element(INFO, &uint(TIMESTAMP_SCALE, 1_000_000));
+        let tracks = element(TRACKS, &tracks.concat());
+        unknown_size_element(SEGMENT, &[info, tracks, clusters.concat()].concat())
+    }
+
+    fn ivf_frames(stream: &[u8]) -> Vec<&[u8]> {
+        IvfIterator::new(stream).collect()
+    }
+
+    /// Writes the VP9 and VP8 frames in clusters of 10 frames each, the former
+    /// in SimpleBlocks and the latter in BlockGroups.
+    fn write_vp8_vp9(vp9: &[&[u8]], vp8: &[&[u8]]) -> Vec<u8> {
+        let mut clusters = vec![];
+
+        for (i, (vp9_frames, vp8_frames)) in vp9.chunks(10).zip(vp8.chunks(10)).enumerate() {
+            let mut data = uint(CLUSTER_TIMESTAMP, i as u64 * 400);
+            for (j, (vp9_frame, vp8_frame)) in vp9_frames.iter().zip(vp8_frames).enumerate() {
+                let is_keyframe = i == 0 && j == 0;
+                let timestamp = j as i16 * 40;
+
+                let flags = if is_keyframe { 0x80 } else { 0 };
+                data.extend(element(
+                    SIMPLE_BLOCK,
+                    &block(1, timestamp, flags, &[vp9_frame], Lacing::None),
+                ));
+
+                let mut group = element(BLOCK, &block(2, timestamp, 0, &[vp8_frame], Lacing::None));
+                group.extend(uint(BLOCK_DURATION, 20));
+                // Bit 0 of the VP8 frame tag is clear for key frames.
+                if vp8_frame[0] & 0x1 != 0 {
+                    group.extend(element(REFERENCE_BLOCK, &(-40i16).to_be_bytes()));
+                }
+                data.extend(element(BLOCK_GROUP, &group));
+            }
+
+            // Alternate between Clusters of known and unknown size.
+            clusters.push(if i % 2 == 0 {
+                element(CLUSTER, &data)
+            } else {
+                unknown_size_element(CLUSTER, &data)
+            });
+        }
+
+        let tracks = [track_entry(1, "V_VP9", None), track_entry(2, "V_VP8", None)];
+        [ebml_header("webm"), segment(&tracks, &clusters)].concat()
+    }
+
+    #[test]
+    fn demux_vp8_vp9() {
+        let vp9 = ivf_frames(STREAM_VP9);
+        let vp8 = ivf_frames(STREAM_VP8);
+        let len = std::cmp::min(vp9.len(), vp8.len());
+        let file = write_vp8_vp9(&vp9[..len], &vp8[..len]);
+
+        let demuxer = MatroskaDemuxer::new(&file).unwrap();
+        assert_eq!(demuxer.doc_type(), "webm");
+        assert_eq!(demuxer.tracks().len(), 2);
+
+        let track = demuxer.track(1).unwrap();
+        assert_eq!(track.codec, Some(TrackCodec::Vp9));
+        assert_eq!((track.width, track.height), (320, 240));
+        assert_eq!(track.num_frames(), len);
+
+        let mut parser = Vp9Parser::default();
+        for (i, frame) in demuxer.frames(1).unwrap().enumerate() {
+            assert_eq!(frame.data, vp9[i]);
+            assert_eq!(frame.timestamp, i as i64 * FRAME_DURATION as i64);
+            assert_eq!(frame.duration, Some(FRAME_DURATION));
+            assert_eq!(frame.is_keyframe, i == 0);
+            assert!(!frame.is_invisible);
+            assert!(!parser.parse_chunk(frame.data).unwrap().is_empty());
+        }
+
+        let mut parser = Vp8Parser::default();
+        for (i, frame) in demuxer.frames(2).unwrap().enumerate() {
+            assert_eq!(frame.data, vp8[i]);
+            assert_eq!(frame.timestamp, i as i64 * FRAME_DURATION as i64);
+            // The BlockDuration overrides the DefaultDuration.
+            assert_eq!(frame.duration, Some(20_000_000));
+            assert_eq!(
+                parser.parse_frame(frame.data).unwrap().header.key_frame,
+                frame.is_keyframe
+            );
+        }
+
+        assert!(matches!(
+            demuxer.frames(3),
+            Err(MatroskaError::UnknownTrack(3))
+        ));
+    }
+
+    #[test]
+    fn demux_av1_h264() {
+        let av1 = ivf_frames(STREAM_AV1);
+        let av1c = Av1CodecConfigurationRecord::default();
+        let mut av1c_data = vec![];
+        av1c.write_into(&mut av1c_data).unwrap();
+
+        // One H.264 NAL unit per frame, with the parameter sets in the record.
+        let mut cursor = Cursor::new(STREAM_H264);
+        let (mut sps, mut pps, mut h264) = (vec![], vec![], vec![]);
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            match nalu.header.type_ {
+                NaluType::Sps => sps.push(nalu.as_ref().to_vec()),
+                NaluType::Pps => pps.push(nalu.as_ref().to_vec()),
+                NaluType::Slice | NaluType::SliceIdr => {
+                    h264.push([&(nalu.size as u16).to_be_bytes()[..], nalu.as_ref()].concat())
+                }
+                _ => (),
+            }
+        }
+        sps.truncate(1);
+        pps.truncate(1);
+        let avcc = AvcDecoderConfigurationRecord::new(sps, pps, 2).unwrap();
+        let mut avcc_data = vec![];
+        avcc.write_into(&mut avcc_data).unwrap();
+
+        let mut cluster = uint(CLUSTER_TIMESTAMP, 0);
+        for (i, frame) in av1.iter().enumerate() {
+            let flags = if i == 0 { 0x80 } else { 0 };
+            let data = block(1, i as i16 * 40, flags, &[frame], Lacing::None);
+            cluster.extend(element(SIMPLE_BLOCK, &data));
+        }
+        for (i, frame) in h264.iter().enumerate() {
+            let data = block(2, i as i16 * 20, 0, &[frame], Lacing::None);
+            cluster.extend(element(SIMPLE_BLOCK, &data));
+        }
+
+        let tracks = [
+            track_entry(1, "V_AV1", Some(&av1c_data)),
+            track_entry(2, "V_MPEG4/ISO/AVC", Some(&avcc_data)),
+        ];
+        let file = [
+            ebml_header("matroska"),
+            segment(&tracks, &[element(CLUSTER, &cluster)]),
+        ]
+        .concat();
+
+        let demuxer = MatroskaDemuxer::new(&file).unwrap();
+        assert_eq!(demuxer.doc_type(), "matroska");
+        assert_eq!(demuxer.track(1).unwrap().codec, Some(TrackCodec::Av1(av1c)));
+
+        let frames: Vec<_> = demuxer.frames(1).unwrap().map(|frame| frame.data).collect();
+        assert_eq!(frames, av1);
+
+        let track = demuxer.track(2).unwrap();
+        let Some(TrackCodec::Avc(record)) = &track.codec else {
+            panic!("unexpected codec");
+        };
+        assert_eq!(*record, avcc);
+
+        let mut parser = H264Parser::default();
+        record.parse_parameter_sets(&mut parser).unwrap();
+        assert_eq!(track.num_frames(), h264.len());
+        for frame in demuxer.frames(2).unwrap() {
+            for nalu in record.nalus(frame.data) {
+                parser.parse_slice_header(nalu.unwrap()).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn demux_lacing() {
+        let frames: Vec<Vec<u8>> = [300, 10, 255, 510, 0, 7]
+            .iter()
+            .enumerate()
+            .map(|(i, &len)| vec![i as u8; len])
+            .collect();
+        let frames: Vec<&[u8]> = frames.iter().map(Vec::as_slice).collect();
+        let fixed = [&[1u8; 12][..], &[2; 12], &[3; 12]];
+
+        let cluster = [
+            uint(CLUSTER_TIMESTAMP, 1000),
+            element(SIMPLE_BLOCK, &block(1, -10, 0x80, &frames, Lacing::Xiph)),
+            element(SIMPLE_BLOCK, &block(1, 230, 0x08, &frames, Lacing::Ebml)),
+            element(SIMPLE_BLOCK, &block(1, 470, 0x80, &fixed, Lacing::Fixed)),
+        ]
+        .concat();
+        let file = [
+            ebml_header("webm"),
+            segment(
+                &[track_entry(1, "V_VP9", None)],
+                &[unknown_size_element(CLUSTER, &cluster)],
+            ),
+        ]
+        .concat();
+
+        let demuxer = MatroskaDemuxer::new(&file).unwrap();
+        let demuxed: Vec<_> = demuxer.frames(1).unwrap().collect();
+        assert_eq!(demuxed.len(), 15);
+
+        let expected = frames.iter().chain(&frames).chain(&fixed);
+        for (i, (frame, &data)) in demuxed.iter().zip(expected).enumerate() {
+            assert_eq!(frame.data, data, "frame {i}");
+
+            // The laced frames follow each other by the default duration.
+            let (block_timestamp, index) = match i {
+                0..=5 => (990, i),
+                6..=11 => (1230, i - 6),
+                _ => (1470, i - 12),
+            };
+            assert_eq!(
+                frame.timestamp,
+                block_timestamp * 1_000_000 + (index as u64 * FRAME_DURATION) as i64
+            );
+            assert_eq!(frame.is_keyframe, i == 0 || i == 12);
+            assert_eq!(frame.is_invisible, (6..12).contains(&i));
+        }
+
+        // Fixed-size lacing of frames of different sizes.
+        let data = block(1, 0, 0, &[&[0; 3], &[0; 4]], Lacing::Fixed);
+        let cluster = [uint(CLUSTER_TIMESTAMP, 0), element(SIMPLE_BLOCK, &data)].concat();
+        let file = [
+            ebml_header("webm"),
+            segment(
+                &[track_entry(1, "V_VP9", None)],
+                &[element(CLUSTER, &cluster)],
+            ),
+        ]
+        .concat();
+        assert!(matches!(
+            MatroskaDemuxer::new(&file),
+            Err(MatroskaError::InvalidElement(SIMPLE_BLOCK, _))
+        ));
+
+        // EBML lace sizes larger than the block, whose sum overflows.
+        let mut data = vec![0x81, 0, 0, 0x86, 0xff];
+        for _ in 0..255 {
+            data.extend_from_slice(&[0x01, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        }
+        let cluster = [uint(CLUSTER_TIMESTAMP, 0), element(SIMPLE_BLOCK, &data)].concat();
+        let file = [
+            ebml_header("webm"),
+            segment(
+                &[track_entry(1, "V_VP9", None)],
+                &[element(CLUSTER, &cluster)],
+            ),
+        ]
+        .concat();
+        assert!(matches!(
+            MatroskaDemuxer::new(&file),
+            Err(MatroskaError::TruncatedElement(SIMPLE_BLOCK))
+        ));
+    }
+
+    #[test]
+    fn demux_errors() {
+        let vp9 = ivf_frames(STREAM_VP9);
+        let vp8 = ivf_frames(STREAM_VP8);
+        let file = write_vp8_vp9(&vp9[..20], &vp8[..20]);
+
+        assert!(matches!(
+            MatroskaDemuxer::new(&file[4..]),
+            Err(MatroskaError::NotEbml)
+        ));
+        assert!(matches!(
+            MatroskaDemuxer::new(&ebml_header("foo")),
+            Err(MatroskaError::UnsupportedDocType(_))
+        ));
+        assert!(matches!(
+            MatroskaDemuxer::new(&ebml_header("webm")),
+            Err(MatroskaError::MissingElement(SEGMENT))
+        ));
+
+        // The last block is truncated, as the Segment has an unknown size.
+        assert!(matches!(
+            MatroskaDemuxer::new(&file[..file.len() - 10]),
+            Err(MatroskaError::TruncatedElement(_))
+        ));
+
+        // A block of an unknown track.
+        let cluster = [
+            uint(CLUSTER_TIMESTAMP, 0),
+            element(SIMPLE_BLOCK, &block(2, 0, 0x80, &[&[0; 4]], Lacing::None)),
+        ]
+        .concat();
+        let file = [
+            ebml_header("webm"),
+            segment(
+                &[track_entry(1, "V_VP9", None)],
+                &[element(CLUSTER, &cluster)],
+            ),
+        ]
+        .concat();
+        assert!(matches!(
+            MatroskaDemuxer::new(&file),
+            Err(MatroskaError::UnknownTrack(2))
+        ));
+
+        // Timestamps that do not fit in a signed integer.
+        for (cluster_timestamp, relative_timestamp, id) in [
+            (u64::MAX, 0, CLUSTER_TIMESTAMP),
+            (i64::MAX as u64, 1, SIMPLE_BLOCK),
+        ] {
+            let cluster = [
+                uint(CLUSTER_TIMESTAMP, cluster_timestamp),
+                element(
+                    SIMPLE_BLOCK,
+                    &block(1, relative_timestamp, 0x80, &[&[0; 4]], Lacing::None),
+                ),
+            ]
+            .concat();
+            let file = [
+                ebml_header("webm"),
+                segment(
+                    &[track_entry(1, "V_VP9", None)],
+                    &[element(CLUSTER, &cluster)],
+                ),
+            ]
+            .concat();
+            assert!(matches!(
+                MatroskaDemuxer::new(&file),
+                Err(MatroskaError::InvalidElement(element_id, _)) if element_id == id
+            ));
+        }
+
+        // Missing or invalid codec private data.
+        for (codec_id, codec_private) in [("V_AV1", None), ("V_MPEG4/ISO/AVC", Some(&[0u8; 3][..]))]
+        {
+            let file = [
+                ebml_header("webm"),
+                segment(&[track_entry(1, codec_id, codec_private)], &[]),
+            ]
+            .concat();
+            assert!(MatroskaDemuxer::new(&file).is_err());
+        }
+    }
+}
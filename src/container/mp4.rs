@@ -0,0 +1,1523 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! A minimal read-only demuxer of ISO base media files (ISO/IEC 14496-12), i.e.
+//! MP4 files, both progressive and fragmented.
+//!
+//! The whole file is expected in memory. Edit lists are ignored, so the
+//! timestamps are the ones of the media timeline of each track.
+
+use thiserror::Error;
+
+use crate::codec::av1::av1c::Av1CodecConfigurationRecord;
+use crate::codec::h264::avcc::AvcDecoderConfigurationRecord;
+use crate::codec::h265::hvcc::HevcDecoderConfigurationRecord;
+use crate::codec::vp9::vpcc::VpCodecConfigurationRecord;
+
+/// A box type or sample entry format.
+pub type FourCc = [u8; 4];
+
+fn fourcc_str(fourcc: &FourCc) -> String {
+    String::from_utf8_lossy(fourcc).into_owned()
+}
+
+#[derive(Error, Debug)]
+pub enum Mp4Error {
+    #[error("truncated {0} box")]
+    TruncatedBox(String),
+    #[error("invalid size {size} for {box_type} box")]
+    InvalidBoxSize { box_type: String, size: u64 },
+    #[error("missing {0} box")]
+    MissingBox(&'static str),
+    #[error("invalid {0} box: {1}")]
+    InvalidBox(&'static str, &'static str),
+    #[error("unknown track {0}")]
+    UnknownTrack(u32),
+    #[error("sample of {size} bytes at offset {offset} is out of bounds")]
+    SampleOutOfBounds { offset: u64, size: u32 },
+    #[error("invalid {0} codec configuration: {1}")]
+    InvalidConfig(String, anyhow::Error),
+}
+
+pub type Mp4Result<T> = std::result::Result<T, Mp4Error>;
+
+/// Flag of the sample flags indicating a sample that is not a sync sample.
+const SAMPLE_IS_NON_SYNC_SAMPLE: u32 = 0x1_0000;
+
+/// A box, as per section 4.2.
+struct Mp4Box<'a> {
+    type_: FourCc,
+    /// The position of the start of the box in the file.
+    offset: usize,
+    /// The position of the payload in the file.
+    payload_offset: usize,
+    /// The contents of the box, header excluded.
+    payload: &'a [u8],
+}
+
+impl<'a> Mp4Box<'a> {
+    fn reader(&self) -> BoxReader<'a> {
+        BoxReader {
+            type_: self.type_,
+            data: self.payload,
+        }
+    }
+
+    /// Returns the boxes contained in this box.
+    fn children(&self) -> BoxIterator<'a> {
+        BoxIterator::new(self.payload, self.payload_offset)
+    }
+
+    fn children_after(&self, skip: usize) -> Mp4Result<BoxIterator<'a>> {
+        let payload = self
+            .payload
+            .get(skip..)
+            .ok_or_else(|| Mp4Error::TruncatedBox(fourcc_str(&self.type_)))?;
+
+        Ok(BoxIterator::new(payload, self.payload_offset + skip))
+    }
+
+    /// Returns the first child box of type `type_`.
+    fn child(&self, type_: &FourCc) -> Mp4Result<Option<Mp4Box<'a>>> {
+        for child in self.children() {
+            let child = child?;
+            if &child.type_ == type_ {
+                return Ok(Some(child));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Returns the first child box of type `type_`, which must be present.
+    fn required_child(&self, type_: &'static str) -> Mp4Result<Mp4Box<'a>> {
+        let fourcc: FourCc = type_.as_bytes().try_into().unwrap();
+        self.child(&fourcc)?.ok_or(Mp4Error::MissingBox(type_))
+    }
+}
+
+/// Iterator over the boxes contained in a buffer. The iteration stops after
+/// the first error.
+struct BoxIterator<'a> {
+    data: &'a [u8],
+    pos: usize,
+    /// The position of `data` in the file.
+    base: usize,
+}
+
+impl<'a> BoxIterator<'a> {
+    fn new(data: &'a [u8], base: usize) -> Self {
+        Self { data, pos: 0, base }
+    }
+
+    fn read_box(&mut self) -> Mp4Result<Mp4Box<'a>> {
+        let data = &self.data[self.pos..];
+        let truncated = || Mp4Error::TruncatedBox("unknown".into());
+
+        let size = u32::from_be_bytes(data.get(0..4).ok_or_else(truncated)?.try_into().unwrap());
+        let type_: FourCc = data.get(4..8).ok_or_else(truncated)?.try_into().unwrap();
+
+        let mut header_len = 8;
+        let size = match size {
+            0 => data.len() as u64,
+            1 => {
+                header_len += 8;
+                let largesize = data
+                    .get(8..16)
+                    .ok_or_else(|| Mp4Error::TruncatedBox(fourcc_str(&type_)))?;
+                u64::from_be_bytes(largesize.try_into().unwrap())
+            }
+            size => u64::from(size),
+        };
+
+        if &type_ == b"uuid" {
+            header_len += 16;
+        }
+
+        if size < header_len as u64 {
+            return Err(Mp4Error::InvalidBoxSize {
+                box_type: fourcc_str(&type_),
+                size,
+            });
+        }
+
+        let size = usize::try_from(size)
+            .ok()
+            .filter(|&size| size <= data.len())
+            .ok_or_else(|| Mp4Error::TruncatedBox(fourcc_str(&type_)))?;
+
+        let offset = self.base + self.pos;
+        self.pos += size;
+
+        Ok(Mp4Box {
+            type_,
+            offset,
+            payload_offset: offset + header_len,
+            payload: &data[header_len..size],
+        })
+    }
+}
+
+impl<'a> Iterator for BoxIterator<'a> {
+    type Item = Mp4Result<Mp4Box<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.pos >= self.data.len() {
+            return None;
+        }
+
+        let mp4_box = self.read_box();
+        if mp4_box.is_err() {
+            self.pos = self.data.len();
+        }
+
+        Some(mp4_box)
+    }
+}
+
+/// A reader of the fields of a box, which fails on truncated data.
+struct BoxReader<'a> {
+    type_: FourCc,
+    data: &'a [u8],
+}
+
+impl<'a> BoxReader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Mp4Result<&'a [u8]> {
+        if self.data.len() < len {
+            return Err(Mp4Error::TruncatedBox(fourcc_str(&self.type_)));
+        }
+
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn skip(&mut self, len: usize) -> Mp4Result<()> {
+        self.read_bytes(len).map(|_| ())
+    }
+
+    fn read_u8(&mut self) -> Mp4Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> Mp4Result<u16> {
+        Ok(u16::from_be_bytes(self.read_bytes(2)?.try_into().unwrap()))
+    }
+
+    fn read_u32(&mut self) -> Mp4Result<u32> {
+        Ok(u32::from_be_bytes(self.read_bytes(4)?.try_into().unwrap()))
+    }
+
+    fn read_u64(&mut self) -> Mp4Result<u64> {
+        Ok(u64::from_be_bytes(self.read_bytes(8)?.try_into().unwrap()))
+    }
+
+    /// Reads a 64-bit field in version 1 boxes, or a 32-bit one otherwise.
+    fn read_versioned(&mut self, version: u8) -> Mp4Result<u64> {
+        if version == 1 {
+            self.read_u64()
+        } else {
+            self.read_u32().map(u64::from)
+        }
+    }
+
+    /// Reads the version and flags of a full box, as per section 4.2.
+    fn read_full_box_header(&mut self) -> Mp4Result<(u8, u32)> {
+        let version = self.read_u8()?;
+        let flags = u32::from(self.read_u16()?) << 8 | u32::from(self.read_u8()?);
+        Ok((version, flags))
+    }
+
+    /// Reads `count` entries with `read`, without trusting `count` for the
+    /// allocation.
+    fn read_entries<T>(
+        &mut self,
+        count: u32,
+        mut read: impl FnMut(&mut Self) -> Mp4Result<T>,
+    ) -> Mp4Result<Vec<T>> {
+        let mut entries = Vec::with_capacity(std::cmp::min(count as usize, self.data.len()));
+        for _ in 0..count {
+            entries.push(read(self)?);
+        }
+
+        Ok(entries)
+    }
+}
+
+/// The codec configuration of a sample entry, to be handed to the parser of
+/// the matching codec.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum CodecConfig {
+    /// From the `avcC` box of `avc1` and `avc3` entries. In the latter, the
+    /// parameter sets may also be found in the samples.
+    Avc(AvcDecoderConfigurationRecord),
+    /// From the `hvcC` box of `hvc1` and `hev1` entries. In the latter, the
+    /// parameter sets may also be found in the samples.
+    Hevc(HevcDecoderConfigurationRecord),
+    /// From the `av1C` box of `av01` entries.
+    Av1(Av1CodecConfigurationRecord),
+    /// From the `vpcC` box of `vp09` entries.
+    Vp9(VpCodecConfigurationRecord),
+}
+
+/// An entry of the `stsd` box, describing the format of some of the samples
+/// of a track.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SampleEntry {
+    pub format: FourCc,
+    /// The dimensions of video sample entries, or 0.
+    pub width: u16,
+    pub height: u16,
+    /// `None` for unsupported formats, e.g. audio.
+    pub config: Option<CodecConfig>,
+}
+
+/// The position and timing of a sample.
+#[derive(Clone, Debug)]
+struct SampleInfo {
+    offset: u64,
+    size: u32,
+    dts: u64,
+    pts: i64,
+    duration: u32,
+    is_sync: bool,
+    sample_entry: usize,
+}
+
+/// A track of the file.
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub track_id: u32,
+    /// The handler type of the track, e.g. `vide` for video tracks.
+    pub handler_type: FourCc,
+    /// The number of time units per second of the timestamps.
+    pub timescale: u32,
+    /// The duration of the track from its `mdhd` box, excluding the fragments.
+    pub duration: u64,
+    pub sample_entries: Vec<SampleEntry>,
+    samples: Vec<SampleInfo>,
+}
+
+impl Track {
+    /// The number of samples of the track, fragments included.
+    pub fn num_samples(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// The decoding time following the last sample, or `None` if it overflows.
+    fn end_dts(&self) -> Option<u64> {
+        self.samples
+            .last()
+            .map_or(Some(0), |s| s.dts.checked_add(u64::from(s.duration)))
+    }
+}
+
+/// A sample of a track, i.e. a frame or access unit.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sample<'a> {
+    pub data: &'a [u8],
+    /// The decoding timestamp, in units of the track's timescale.
+    pub dts: u64,
+    /// The presentation timestamp, in units of the track's timescale.
+    pub pts: i64,
+    pub duration: u32,
+    /// Whether decoding can start from this sample.
+    pub is_sync: bool,
+    /// The index of the entry describing the sample in the track's
+    /// `sample_entries`.
+    pub sample_entry: usize,
+}
+
+/// The defaults of the samples of a track in movie fragments, from its `trex`
+/// box.
+#[derive(Clone, Copy, Default)]
+struct TrackExtends {
+    track_id: u32,
+    sample_description_index: u32,
+    sample_duration: u32,
+    sample_size: u32,
+    sample_flags: u32,
+}
+
+/// A read-only demuxer of MP4 files held in memory.
+pub struct Mp4Demuxer<'a> {
+    data: &'a [u8],
+    tracks: Vec<Track>,
+}
+
+impl<'a> Mp4Demuxer<'a> {
+    /// Parses the `moov` box and the movie fragments of the file in `data`.
+    pub fn new(data: &'a [u8]) -> Mp4Result<Self> {
+        let mut tracks = None;
+        let mut trex = vec![];
+
+        for mp4_box in BoxIterator::new(data, 0) {
+            let mp4_box = mp4_box?;
+
+            match &mp4_box.type_ {
+                b"moov" => {
+                    let (moov_tracks, moov_trex) = parse_moov(&mp4_box, data.len())?;
+                    tracks = Some(moov_tracks);
+                    trex = moov_trex;
+                }
+                b"moof" => {
+                    let tracks = tracks.as_mut().ok_or(Mp4Error::MissingBox("moov"))?;
+                    parse_moof(&mp4_box, tracks, &trex, data.len())?;
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Self {
+            data,
+            tracks: tracks.ok_or(Mp4Error::MissingBox("moov"))?,
+        })
+    }
+
+    /// Returns the tracks of the file.
+    pub fn tracks(&self) -> &[Track] {
+        &self.tracks
+    }
+
+    /// Returns the track with ID `track_id`.
+    pub fn track(&self, track_id: u32) -> Option<&Track> {
+        self.tracks.iter().find(|track| track.track_id == track_id)
+    }
+
+    /// Returns an iterator over the samples of the track with ID `track_id`,
+    /// in decoding order.
+    pub fn samples(&self, track_id: u32) -> Mp4Result<Samples<'a, '_>> {
+        let track = self
+            .track(track_id)
+            .ok_or(Mp4Error::UnknownTrack(track_id))?;
+
+        Ok(Samples {
+            data: self.data,
+            samples: track.samples.iter(),
+        })
+    }
+}
+
+/// Iterator over the samples of a track.
+pub struct Samples<'a, 'd> {
+    data: &'a [u8],
+    samples: std::slice::Iter<'d, SampleInfo>,
+}
+
+impl<'a, 'd> Iterator for Samples<'a, 'd> {
+    type Item = Sample<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let info = self.samples.next()?;
+        // The bounds were checked when parsing the sample tables.
+        let offset = info.offset as usize;
+
+        Some(Sample {
+            data: &self.data[offset..offset + info.size as usize],
+            dts: info.dts,
+            pts: info.pts,
+            duration: info.duration,
+            is_sync: info.is_sync,
+            sample_entry: info.sample_entry,
+        })
+    }
+}
+
+/// Rejects sample counts that cannot fit in the file, before allocating
+/// anything for them.
+fn check_sample_count(sample_count: u32, file_len: usize, box_type: &'static str) -> Mp4Result<()> {
+    if sample_count as usize > file_len {
+        return Err(Mp4Error::InvalidBox(box_type, "too many samples"));
+    }
+
+    Ok(())
+}
+
+fn check_sample_bounds(offset: u64, size: u32, file_len: usize) -> Mp4Result<()> {
+    match offset.checked_add(u64::from(size)) {
+        Some(end) if end <= file_len as u64 => Ok(()),
+        _ => Err(Mp4Error::SampleOutOfBounds { offset, size }),
+    }
+}
+
+fn parse_moov(moov: &Mp4Box, file_len: usize) -> Mp4Result<(Vec<Track>, Vec<TrackExtends>)> {
+    let mut tracks = vec![];
+    let mut trex = vec![];
+
+    for child in moov.children() {
+        let child = child?;
+
+        match &child.type_ {
+            b"trak" => tracks.push(parse_trak(&child, file_len)?),
+            b"mvex" => {
+                for mvex_child in child.children() {
+                    let mvex_child = mvex_child?;
+                    if &mvex_child.type_ == b"trex" {
+                        trex.push(parse_trex(&mvex_child)?);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+
+    Ok((tracks, trex))
+}
+
+fn parse_trex(trex: &Mp4Box) -> Mp4Result<TrackExtends> {
+    let mut r = trex.reader();
+    r.read_full_box_header()?;
+
+    Ok(TrackExtends {
+        track_id: r.read_u32()?,
+        sample_description_index: r.read_u32()?,
+        sample_duration: r.read_u32()?,
+        sample_size: r.read_u32()?,
+        sample_flags: r.read_u32()?,
+    })
+}
+
+fn parse_trak(trak: &Mp4Box, file_len: usize) -> Mp4Result<Track> {
+    let mut r = trak.required_child("tkhd")?.reader();
+    let (version, _) = r.read_full_box_header()?;
+    // The creation and modification times.
+    r.skip(if version == 1 { 16 } else { 8 })?;
+    let track_id = r.read_u32()?;
+
+    let mdia = trak.required_child("mdia")?;
+
+    let mut r = mdia.required_child("mdhd")?.reader();
+    let (version, _) = r.read_full_box_header()?;
+    r.skip(if version == 1 { 16 } else { 8 })?;
+    let timescale = r.read_u32()?;
+    let duration = r.read_versioned(version)?;
+    if timescale == 0 {
+        return Err(Mp4Error::InvalidBox("mdhd", "zero timescale"));
+    }
+
+    let mut r = mdia.required_child("hdlr")?.reader();
+    r.read_full_box_header()?;
+    // pre_defined
+    r.skip(4)?;
+    let handler_type: FourCc = r.read_bytes(4)?.try_into().unwrap();
+
+    let stbl = mdia.required_child("minf")?.required_child("stbl")?;
+    let sample_entries = parse_stsd(&stbl.required_child("stsd")?, &handler_type)?;
+    let samples = parse_sample_table(&stbl, file_len)?;
+
+    if samples
+        .iter()
+        .any(|sample| sample.sample_entry >= sample_entries.len())
+    {
+        return Err(Mp4Error::InvalidBox(
+            "stsc",
+            "invalid sample description index",
+        ));
+    }
+
+    Ok(Track {
+        track_id,
+        handler_type,
+        timescale,
+        duration,
+        sample_entries,
+        samples,
+    })
+}
+
+fn parse_stsd(stsd: &Mp4Box, handler_type: &FourCc) -> Mp4Result<Vec<SampleEntry>> {
+    let mut r = stsd.reader();
+    r.read_full_box_header()?;
+    let entry_count = r.read_u32()?;
+
+    let entries = stsd
+        .children_after(8)?
+        .take(entry_count as usize)
+        .map(|entry| parse_sample_entry(&entry?, handler_type))
+        .collect::<Mp4Result<Vec<_>>>()?;
+
+    if entries.len() != entry_count as usize {
+        return Err(Mp4Error::TruncatedBox("stsd".into()));
+    }
+
+    Ok(entries)
+}
+
+/// The size of the fields of a VisualSampleEntry, as per section 12.1.3.
+const VISUAL_SAMPLE_ENTRY_LEN: usize = 78;
+
+fn parse_sample_entry(entry: &Mp4Box, handler_type: &FourCc) -> Mp4Result<SampleEntry> {
+    let format = entry.type_;
+    let mut sample_entry = SampleEntry {
+        format,
+        width: 0,
+        height: 0,
+        config: None,
+    };
+
+    if handler_type != b"vide" {
+        return Ok(sample_entry);
+    }
+
+    let mut r = entry.reader();
+    // The SampleEntry fields, pre_defined and reserved.
+    r.skip(24)?;
+    sample_entry.width = r.read_u16()?;
+    sample_entry.height = r.read_u16()?;
+
+    let config_box = |type_: &'static str| -> Mp4Result<Mp4Box> {
+        let fourcc: FourCc = type_.as_bytes().try_into().unwrap();
+        for child in entry.children_after(VISUAL_SAMPLE_ENTRY_LEN)? {
+            let child = child?;
+            if child.type_ == fourcc {
+                return Ok(child);
+            }
+        }
+
+        Err(Mp4Error::MissingBox(type_))
+    };
+
+    let invalid_config = |e: anyhow::Error| Mp4Error::InvalidConfig(fourcc_str(&format), e);
+
+    sample_entry.config = match &format {
+        b"avc1" | b"avc3" => Some(CodecConfig::Avc(
+            AvcDecoderConfigurationRecord::parse(config_box("avcC")?.payload)
+                .map_err(|e| invalid_config(e.into()))?,
+        )),
+        b"hvc1" | b"hev1" => Some(CodecConfig::Hevc(
+            HevcDecoderConfigurationRecord::parse(config_box("hvcC")?.payload)
+                .map_err(invalid_config)?,
+        )),
+        b"av01" => Some(CodecConfig::Av1(
+            Av1CodecConfigurationRecord::parse(config_box("av1C")?.payload)
+                .map_err(invalid_config)?,
+        )),
+        b"vp09" => {
+            let vpcc = config_box("vpcC")?;
+            let mut r = vpcc.reader();
+            r.read_full_box_header()?;
+            Some(CodecConfig::Vp9(
+                VpCodecConfigurationRecord::parse(r.data).map_err(invalid_config)?,
+            ))
+        }
+        _ => None,
+    };
+
+    Ok(sample_entry)
+}
+
+/// Builds the samples described by the sample table of a track, as per
+/// section 8.5.
+fn parse_sample_table(stbl: &Mp4Box, file_len: usize) -> Mp4Result<Vec<SampleInfo>> {
+    // Fragmented files may have no sample table at all.
+    let sizes = match stbl.child(b"stsz")? {
+        Some(stsz) => {
+            let mut r = stsz.reader();
+            r.read_full_box_header()?;
+            let sample_size = r.read_u32()?;
+            let sample_count = r.read_u32()?;
+            check_sample_count(sample_count, file_len, "stsz")?;
+
+            if sample_size == 0 {
+                r.read_entries(sample_count, BoxReader::read_u32)?
+            } else {
+                vec![sample_size; sample_count as usize]
+            }
+        }
+        None => vec![],
+    };
+
+    if sizes.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let chunk_offsets = if let Some(stco) = stbl.child(b"stco")? {
+        let mut r = stco.reader();
+        r.read_full_box_header()?;
+        let count = r.read_u32()?;
+        r.read_entries(count, |r| r.read_u32().map(u64::from))?
+    } else if let Some(co64) = stbl.child(b"co64")? {
+        let mut r = co64.reader();
+        r.read_full_box_header()?;
+        let count = r.read_u32()?;
+        r.read_entries(count, BoxReader::read_u64)?
+    } else {
+        return Err(Mp4Error::MissingBox("stco"));
+    };
+
+    // (first_chunk, samples_per_chunk, sample_description_index)
+    let mut r = stbl.required_child("stsc")?.reader();
+    r.read_full_box_header()?;
+    let count = r.read_u32()?;
+    let stsc = r.read_entries(count, |r| Ok((r.read_u32()?, r.read_u32()?, r.read_u32()?)))?;
+
+    let mut r = stbl.required_child("stts")?.reader();
+    r.read_full_box_header()?;
+    let count = r.read_u32()?;
+    let stts = r.read_entries(count, |r| Ok((r.read_u32()?, r.read_u32()?)))?;
+
+    let ctts = match stbl.child(b"ctts")? {
+        Some(ctts) => {
+            let mut r = ctts.reader();
+            r.read_full_box_header()?;
+            let count = r.read_u32()?;
+            // The offsets of version 0 boxes are unsigned, but negative ones
+            // are commonly found anyway.
+            r.read_entries(count, |r| Ok((r.read_u32()?, r.read_u32()? as i32)))?
+        }
+        None => vec![],
+    };
+
+    let sync_samples = match stbl.child(b"stss")? {
+        Some(stss) => {
+            let mut r = stss.reader();
+            r.read_full_box_header()?;
+            let count = r.read_u32()?;
+            Some(r.read_entries(count, BoxReader::read_u32)?)
+        }
+        None => None,
+    };
+
+    let mut samples = Vec::with_capacity(sizes.len());
+
+    // Sample to chunk mapping.
+    'chunks: for (i, &(first_chunk, samples_per_chunk, sample_description_index)) in
+        stsc.iter().enumerate()
+    {
+        let last_chunk = match stsc.get(i + 1) {
+            Some(&(next_first_chunk, _, _)) => next_first_chunk,
+            None => chunk_offsets.len() as u32 + 1,
+        };
+
+        if first_chunk == 0 || last_chunk < first_chunk || sample_description_index == 0 {
+            return Err(Mp4Error::InvalidBox("stsc", "invalid entry"));
+        }
+
+        for chunk in first_chunk..last_chunk {
+            let mut offset = *chunk_offsets
+                .get(chunk as usize - 1)
+                .ok_or(Mp4Error::InvalidBox("stsc", "chunk out of range"))?;
+
+            for _ in 0..samples_per_chunk {
+                let Some(&size) = sizes.get(samples.len()) else {
+                    break 'chunks;
+                };
+
+                check_sample_bounds(offset, size, file_len)?;
+                samples.push(SampleInfo {
+                    offset,
+                    size,
+                    dts: 0,
+                    pts: 0,
+                    duration: 0,
+                    is_sync: sync_samples.is_none(),
+                    sample_entry: sample_description_index as usize - 1,
+                });
+                offset += u64::from(size);
+            }
+        }
+    }
+
+    if samples.len() != sizes.len() {
+        return Err(Mp4Error::InvalidBox(
+            "stsc",
+            "not all samples are in chunks",
+        ));
+    }
+
+    // Decoding times.
+    let mut durations = stts
+        .iter()
+        .flat_map(|&(count, delta)| std::iter::repeat_n(delta, count as usize));
+    let mut dts = 0u64;
+    for sample in samples.iter_mut() {
+        sample.duration = durations.next().ok_or(Mp4Error::InvalidBox(
+            "stts",
+            "not all samples have a duration",
+        ))?;
+        sample.dts = dts;
+        dts = dts
+            .checked_add(u64::from(sample.duration))
+            .ok_or(Mp4Error::InvalidBox("stts", "invalid decoding time"))?;
+    }
+
+    // Composition times.
+    let mut offsets = ctts
+        .iter()
+        .flat_map(|&(count, offset)| std::iter::repeat_n(offset, count as usize));
+    for sample in samples.iter_mut() {
+        sample.pts = composition_time(sample.dts, i64::from(offsets.next().unwrap_or(0)))
+            .ok_or(Mp4Error::InvalidBox("ctts", "invalid composition time"))?;
+    }
+
+    for number in sync_samples.iter().flatten() {
+        if let Some(sample) = (*number as usize)
+            .checked_sub(1)
+            .and_then(|i| samples.get_mut(i))
+        {
+            sample.is_sync = true;
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Returns the composition time of a sample decoded at `dts`, or `None` if it
+/// does not fit in an `i64`.
+fn composition_time(dts: u64, composition_offset: i64) -> Option<i64> {
+    i64::try_from(dts).ok()?.checked_add(composition_offset)
+}
+
+/// Appends the samples of the movie fragment `moof` to their tracks, as per
+/// section 8.8.
+fn parse_moof(
+    moof: &Mp4Box,
+    tracks: &mut [Track],
+    trex: &[TrackExtends],
+    file_len: usize,
+) -> Mp4Result<()> {
+    let moof_offset = moof.offset as u64;
+    let mut prev_traf_end = moof_offset;
+
+    for traf in moof.children() {
+        let traf = traf?;
+        if &traf.type_ != b"traf" {
+            continue;
+        }
+
+        let mut r = traf.required_child("tfhd")?.reader();
+        let (_, flags) = r.read_full_box_header()?;
+        let track_id = r.read_u32()?;
+
+        let track = tracks
+            .iter_mut()
+            .find(|track| track.track_id == track_id)
+            .ok_or(Mp4Error::UnknownTrack(track_id))?;
+        let mut defaults = trex
+            .iter()
+            .find(|trex| trex.track_id == track_id)
+            .copied()
+            .unwrap_or(TrackExtends {
+                sample_description_index: 1,
+                ..Default::default()
+            });
+
+        // Without an explicit base data offset, the offsets are relative to
+        // the start of the movie fragment for the first track fragment, or if
+        // default-base-is-moof is set, and to the end of the data of the
+        // previous track fragment otherwise.
+        let base_data_offset = if flags & 0x1 != 0 {
+            r.read_u64()?
+        } else if flags & 0x2_0000 != 0 {
+            moof_offset
+        } else {
+            prev_traf_end
+        };
+        if flags & 0x2 != 0 {
+            defaults.sample_description_index = r.read_u32()?;
+        }
+        if flags & 0x8 != 0 {
+            defaults.sample_duration = r.read_u32()?;
+        }
+        if flags & 0x10 != 0 {
+            defaults.sample_size = r.read_u32()?;
+        }
+        if flags & 0x20 != 0 {
+            defaults.sample_flags = r.read_u32()?;
+        }
+
+        let sample_entry = (defaults.sample_description_index as usize)
+            .checked_sub(1)
+            .filter(|&index| index < track.sample_entries.len())
+            .ok_or(Mp4Error::InvalidBox(
+                "tfhd",
+                "invalid sample description index",
+            ))?;
+
+        let mut dts = match traf.child(b"tfdt")? {
+            Some(tfdt) => {
+                let mut r = tfdt.reader();
+                let (version, _) = r.read_full_box_header()?;
+                r.read_versioned(version)?
+            }
+            None => track
+                .end_dts()
+                .ok_or(Mp4Error::InvalidBox("trun", "invalid decoding time"))?,
+        };
+
+        let mut data_offset = base_data_offset;
+
+        for trun in traf.children() {
+            let trun = trun?;
+            if &trun.type_ != b"trun" {
+                continue;
+            }
+
+            let mut r = trun.reader();
+            let (_, flags) = r.read_full_box_header()?;
+            let sample_count = r.read_u32()?;
+            check_sample_count(sample_count, file_len, "trun")?;
+
+            if flags & 0x1 != 0 {
+                let offset = r.read_u32()? as i32;
+                data_offset = base_data_offset
+                    .checked_add_signed(i64::from(offset))
+                    .ok_or(Mp4Error::InvalidBox("trun", "invalid data offset"))?;
+            }
+
+            let first_sample_flags = if flags & 0x4 != 0 {
+                Some(r.read_u32()?)
+            } else {
+                None
+            };
+
+            for i in 0..sample_count {
+                let duration = if flags & 0x100 != 0 {
+                    r.read_u32()?
+                } else {
+                    defaults.sample_duration
+                };
+                let size = if flags & 0x200 != 0 {
+                    r.read_u32()?
+                } else {
+                    defaults.sample_size
+                };
+                let sample_flags = if flags & 0x400 != 0 {
+                    r.read_u32()?
+                } else {
+                    match first_sample_flags {
+                        Some(first_sample_flags) if i == 0 => first_sample_flags,
+                        _ => defaults.sample_flags,
+                    }
+                };
+                let composition_offset = if flags & 0x800 != 0 {
+                    // Signed in version 1 boxes, and treated as such in version
+                    // 0 boxes as for the ctts box.
+                    i64::from(r.read_u32()? as i32)
+                } else {
+                    0
+                };
+
+                check_sample_bounds(data_offset, size, file_len)?;
+                let pts = composition_time(dts, composition_offset)
+                    .ok_or(Mp4Error::InvalidBox("trun", "invalid composition time"))?;
+                let next_dts = dts
+                    .checked_add(u64::from(duration))
+                    .ok_or(Mp4Error::InvalidBox("trun", "invalid decoding time"))?;
+                track.samples.push(SampleInfo {
+                    offset: data_offset,
+                    size,
+                    dts,
+                    pts,
+                    duration,
+                    is_sync: sample_flags & SAMPLE_IS_NON_SYNC_SAMPLE == 0,
+                    sample_entry,
+                });
+
+                data_offset += u64::from(size);
+                dts = next_dts;
+            }
+        }
+
+        prev_traf_end = data_offset;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+    use crate::codec::h264::access_unit::AccessUnitAssembler;
+    use crate::codec::h264::parser::Nalu;
+    use crate::codec::h264::parser::NaluType;
+    use crate::codec::h264::parser::Parser;
+    use crate::utils::IvfIterator;
+
+    const STREAM_H264: &[u8] = include_bytes!("../codec/h264/test_data/test-25fps.h264");
+    const STREAM_AV1: &[u8] = include_bytes!("../codec/av1/test_data/test-25fps.ivf.av1");
+    const STREAM_VP9: &[u8] = include_bytes!("../codec/vp9/test_data/test-25fps.vp9");
+
+    const TIMESCALE: u32 = 25000;
+    const DURATION: u32 = 1000;
+
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    struct TestSample {
+        data: Vec<u8>,
+        dts: u64,
+        pts: i64,
+        is_sync: bool,
+    }
+
+    impl From<Sample<'_>> for TestSample {
+        fn from(sample: Sample) -> Self {
+            assert_eq!(sample.duration, DURATION);
+            assert_eq!(sample.sample_entry, 0);
+
+            Self {
+                data: sample.data.to_vec(),
+                dts: sample.dts,
+                pts: sample.pts,
+                is_sync: sample.is_sync,
+            }
+        }
+    }
+
+    /// Builds the test samples from the sample data, with constant durations
+    /// and varying composition offsets.
+    fn test_samples(samples: impl Iterator<Item = (Vec<u8>, bool)>) -> Vec<TestSample> {
+        samples
+            .enumerate()
+            .map(|(i, (data, is_sync))| {
+                let dts = i as u64 * u64::from(DURATION);
+                TestSample {
+                    data,
+                    dts,
+                    pts: dts as i64 + (i as i64 % 3 - 1) * i64::from(DURATION),
+                    is_sync,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the access units of the H.264 stream as length-prefixed
+    /// samples, and the record holding their parameter sets.
+    fn h264_samples() -> (AvcDecoderConfigurationRecord, Vec<TestSample>) {
+        let mut cursor = Cursor::new(STREAM_H264);
+        let mut assembler = AccessUnitAssembler::default();
+        let mut access_units = vec![];
+        while let Ok(nalu) = Nalu::next(&mut cursor) {
+            access_units.extend(assembler.push(nalu).unwrap());
+        }
+        access_units.extend(assembler.flush());
+
+        let (mut sps, mut pps) = (vec![], vec![]);
+        let samples = access_units.iter().map(|au| {
+            let mut data = vec![];
+            let slices = au.slices.iter().map(|slice| &slice.nalu);
+            for nalu in au.nalus.iter().chain(slices) {
+                match nalu.header.type_ {
+                    NaluType::Sps if sps.is_empty() => sps.push(nalu.as_ref().to_vec()),
+                    NaluType::Pps if pps.is_empty() => pps.push(nalu.as_ref().to_vec()),
+                    NaluType::Sps | NaluType::Pps => (),
+                    _ => {
+                        data.extend_from_slice(&(nalu.size as u32).to_be_bytes());
+                        data.extend_from_slice(nalu.as_ref());
+                    }
+                }
+            }
+
+            (data, au.first_slice().unwrap().nalu.header.idr_pic_flag)
+        });
+        let samples = test_samples(samples);
+
+        let record = AvcDecoderConfigurationRecord::new(sps, pps, 4).unwrap();
+        (record, samples)
+    }
+
+    fn mp4_box(type_: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = (8 + payload.len() as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(type_);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn full_box(type_: &[u8; 4], version: u8, flags: u32, payload: &[u8]) -> Vec<u8> {
+        let mut data = (u32::from(version) << 24 | flags).to_be_bytes().to_vec();
+        data.extend_from_slice(payload);
+        mp4_box(type_, &data)
+    }
+
+    fn concat<const N: usize>(parts: [&[u8]; N]) -> Vec<u8> {
+        parts.concat()
+    }
+
+    /// Returns a table of `count` entries of fields written with `write`.
+    fn table<T>(entries: &[T], write: impl Fn(&T) -> Vec<u8>) -> Vec<u8> {
+        let mut data = (entries.len() as u32).to_be_bytes().to_vec();
+        for entry in entries {
+            data.extend(write(entry));
+        }
+        data
+    }
+
+    fn visual_sample_entry(format: &[u8; 4], config: &[u8]) -> Vec<u8> {
+        let mut data = vec![0; 6];
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&320u16.to_be_bytes());
+        data.extend_from_slice(&240u16.to_be_bytes());
+        data.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        data.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+        data.extend_from_slice(&[0; 4]);
+        data.extend_from_slice(&1u16.to_be_bytes());
+        data.extend_from_slice(&[0; 32]);
+        data.extend_from_slice(&0x18u16.to_be_bytes());
+        data.extend_from_slice(&(-1i16).to_be_bytes());
+        data.extend_from_slice(config);
+        mp4_box(format, &data)
+    }
+
+    fn trak(sample_entry: &[u8], sample_table: &[u8]) -> Vec<u8> {
+        let mut tkhd = vec![0; 80];
+        tkhd[8..12].copy_from_slice(&1u32.to_be_bytes());
+
+        let mut mdhd = vec![0; 20];
+        mdhd[8..12].copy_from_slice(&TIMESCALE.to_be_bytes());
+
+        let hdlr = concat([&[0; 4], b"vide", &[0; 13]]);
+        let stsd = concat([&1u32.to_be_bytes(), sample_entry]);
+        let stbl = mp4_box(
+            b"stbl",
+            &concat([&full_box(b"stsd", 0, 0, &stsd), sample_table]),
+        );
+
+        mp4_box(
+            b"trak",
+            &concat([
+                &full_box(b"tkhd", 0, 3, &tkhd),
+                &mp4_box(
+                    b"mdia",
+                    &concat([
+                        &full_box(b"mdhd", 0, 0, &mdhd),
+                        &full_box(b"hdlr", 0, 0, &hdlr),
+                        &mp4_box(b"minf", &mp4_box(b"stbl", &stbl)[8..]),
+                    ]),
+                ),
+            ]),
+        )
+    }
+
+    fn ftyp() -> Vec<u8> {
+        mp4_box(b"ftyp", b"isom\0\0\0\x01isomavc1")
+    }
+
+    /// Writes a progressive file, with 4 chunks of 7 samples followed by
+    /// chunks of 6 samples.
+    fn write_progressive(sample_entry: &[u8], samples: &[TestSample], co64: bool) -> Vec<u8> {
+        let mut chunks = vec![];
+        let mut offset = 0u64;
+        let mut i = 0;
+        while i < samples.len() {
+            let len = if chunks.len() < 4 { 7 } else { 6 };
+            chunks.push(offset);
+            for sample in samples.iter().skip(i).take(len) {
+                offset += sample.data.len() as u64;
+            }
+            i += len;
+        }
+
+        let moov = |base: u64| {
+            let chunk_offsets = if co64 {
+                full_box(
+                    b"co64",
+                    0,
+                    0,
+                    &table(&chunks, |o| (base + o).to_be_bytes().to_vec()),
+                )
+            } else {
+                full_box(
+                    b"stco",
+                    0,
+                    0,
+                    &table(&chunks, |o| ((base + o) as u32).to_be_bytes().to_vec()),
+                )
+            };
+
+            let stsz = concat([
+                &0u32.to_be_bytes(),
+                &table(samples, |s| (s.data.len() as u32).to_be_bytes().to_vec()),
+            ]);
+            let stsc = table(&[(1u32, 7u32), (5, 6)], |(first, count)| {
+                concat([
+                    &first.to_be_bytes(),
+                    &count.to_be_bytes(),
+                    &1u32.to_be_bytes(),
+                ])
+            });
+            let stts = table(&[(samples.len() as u32, DURATION)], |(count, delta)| {
+                concat([&count.to_be_bytes(), &delta.to_be_bytes()])
+            });
+            let ctts = table(samples, |s| {
+                concat([
+                    &1u32.to_be_bytes(),
+                    &((s.pts - s.dts as i64) as i32).to_be_bytes(),
+                ])
+            });
+            let sync: Vec<u32> = (1..=samples.len() as u32)
+                .filter(|&n| samples[n as usize - 1].is_sync)
+                .collect();
+            let stss = table(&sync, |n| n.to_be_bytes().to_vec());
+
+            let sample_table = concat([
+                &full_box(b"stts", 0, 0, &stts),
+                &full_box(b"ctts", 1, 0, &ctts),
+                &full_box(b"stss", 0, 0, &stss),
+                &full_box(b"stsc", 0, 0, &stsc),
+                &full_box(b"stsz", 0, 0, &stsz),
+                &chunk_offsets,
+            ]);
+
+            mp4_box(b"moov", &trak(sample_entry, &sample_table))
+        };
+
+        let ftyp = ftyp();
+        let base = (ftyp.len() + moov(0).len() + 8) as u64;
+        let mdat: Vec<u8> = samples.iter().flat_map(|s| s.data.clone()).collect();
+
+        concat([&ftyp, &moov(base), &mp4_box(b"mdat", &mdat)])
+    }
+
+    /// Writes the ftyp and moov boxes of a fragmented file.
+    fn fragmented_header(sample_entry: &[u8]) -> Vec<u8> {
+        let empty = 0u32.to_be_bytes();
+        let sample_table = concat([
+            &full_box(b"stts", 0, 0, &empty),
+            &full_box(b"stsc", 0, 0, &empty),
+            &full_box(b"stsz", 0, 0, &[0; 8]),
+            &full_box(b"stco", 0, 0, &empty),
+        ]);
+        // Samples are not sync samples by default.
+        let trex = concat([
+            &1u32.to_be_bytes(),
+            &1u32.to_be_bytes(),
+            &0u32.to_be_bytes(),
+            &0u32.to_be_bytes(),
+            &SAMPLE_IS_NON_SYNC_SAMPLE.to_be_bytes(),
+        ]);
+        let moov = mp4_box(
+            b"moov",
+            &concat([
+                &trak(sample_entry, &sample_table),
+                &mp4_box(b"mvex", &full_box(b"trex", 0, 0, &trex)),
+            ]),
+        );
+
+        concat([&ftyp(), &moov])
+    }
+
+    /// Writes a fragmented file, with a fragment starting at each sync sample
+    /// and after `fragment_len` samples.
+    fn write_fragmented(
+        sample_entry: &[u8],
+        samples: &[TestSample],
+        fragment_len: usize,
+    ) -> Vec<u8> {
+        let mut file = fragmented_header(sample_entry);
+
+        let mut fragments: Vec<&[TestSample]> = vec![];
+        let mut start = 0;
+        for end in 1..=samples.len() {
+            if end == samples.len() || samples[end].is_sync || end - start == fragment_len {
+                fragments.push(&samples[start..end]);
+                start = end;
+            }
+        }
+
+        for (sequence_number, fragment) in fragments.into_iter().enumerate() {
+            // default-base-is-moof and default-sample-duration-present.
+            let tfhd = full_box(
+                b"tfhd",
+                0,
+                0x2_0008,
+                &concat([&1u32.to_be_bytes(), &DURATION.to_be_bytes()]),
+            );
+            let tfdt = full_box(b"tfdt", 1, 0, &fragment[0].dts.to_be_bytes());
+
+            let moof = |data_offset: u32| {
+                // The first sample flags replace the default ones for the
+                // first sample only.
+                let trun = concat([
+                    &(fragment.len() as u32).to_be_bytes(),
+                    &data_offset.to_be_bytes(),
+                    &0u32.to_be_bytes(),
+                    &fragment
+                        .iter()
+                        .flat_map(|s| {
+                            concat([
+                                &(s.data.len() as u32).to_be_bytes(),
+                                &((s.pts - s.dts as i64) as i32).to_be_bytes(),
+                            ])
+                        })
+                        .collect::<Vec<u8>>(),
+                ]);
+                let first_sample_flags = if fragment[0].is_sync { 0x4 } else { 0 };
+                let trun = if first_sample_flags == 0 {
+                    // Drop the first sample flags field.
+                    concat([&trun[..8], &trun[12..]])
+                } else {
+                    trun
+                };
+
+                mp4_box(
+                    b"moof",
+                    &concat([
+                        &full_box(b"mfhd", 0, 0, &(sequence_number as u32).to_be_bytes()),
+                        &mp4_box(
+                            b"traf",
+                            &concat([
+                                &tfhd,
+                                &tfdt,
+                                &full_box(b"trun", 1, 0xa01 | first_sample_flags, &trun),
+                            ]),
+                        ),
+                    ]),
+                )
+            };
+
+            let moof_len = moof(0).len();
+            let mdat: Vec<u8> = fragment.iter().flat_map(|s| s.data.clone()).collect();
+
+            file.extend(moof(moof_len as u32 + 8));
+            file.extend(mp4_box(b"mdat", &mdat));
+        }
+
+        file
+    }
+
+    fn demux(file: &[u8]) -> (Track, Vec<TestSample>) {
+        let demuxer = Mp4Demuxer::new(file).unwrap();
+        assert_eq!(demuxer.tracks().len(), 1);
+
+        let track = demuxer.track(1).unwrap().clone();
+        assert_eq!(track.handler_type, *b"vide");
+        assert_eq!(track.timescale, TIMESCALE);
+        assert_eq!(track.sample_entries.len(), 1);
+        assert_eq!(
+            (
+                track.sample_entries[0].width,
+                track.sample_entries[0].height
+            ),
+            (320, 240)
+        );
+
+        let samples = demuxer.samples(1).unwrap().map(TestSample::from).collect();
+        (track, samples)
+    }
+
+    #[test]
+    fn demux_h264() {
+        let (record, samples) = h264_samples();
+        let mut avcc = vec![];
+        record.write_into(&mut avcc).unwrap();
+        let sample_entry = visual_sample_entry(b"avc1", &mp4_box(b"avcC", &avcc));
+
+        for file in [
+            write_progressive(&sample_entry, &samples, false),
+            write_progressive(&sample_entry, &samples, true),
+            write_fragmented(&sample_entry, &samples, 25),
+        ] {
+            let (track, demuxed) = demux(&file);
+            assert_eq!(demuxed, samples);
+            assert_eq!(track.num_samples(), 250);
+
+            // The configuration record is handed to the parser, which can then
+            // parse the slices of the samples.
+            let Some(CodecConfig::Avc(record)) = &track.sample_entries[0].config else {
+                panic!("unexpected codec configuration");
+            };
+            let mut parser = Parser::default();
+            record.parse_parameter_sets(&mut parser).unwrap();
+
+            for sample in &demuxed {
+                let nalu = record
+                    .nalus(&sample.data)
+                    .map(Result::unwrap)
+                    .find(|nalu| matches!(nalu.header.type_, NaluType::Slice | NaluType::SliceIdr))
+                    .unwrap();
+                assert_eq!(nalu.header.idr_pic_flag, sample.is_sync);
+                parser.parse_slice_header(nalu).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn demux_av1_vp9() {
+        let packets = |stream| {
+            test_samples(
+                IvfIterator::new(stream)
+                    .enumerate()
+                    .map(|(i, packet)| (packet.to_vec(), i == 0)),
+            )
+        };
+
+        let av1c = Av1CodecConfigurationRecord::default();
+        let mut data = vec![];
+        av1c.write_into(&mut data).unwrap();
+        let av01 = visual_sample_entry(b"av01", &mp4_box(b"av1C", &data));
+
+        let vpcc = VpCodecConfigurationRecord {
+            bit_depth: 8,
+            ..Default::default()
+        };
+        let mut data = vec![];
+        vpcc.write_into(&mut data).unwrap();
+        let vp09 = visual_sample_entry(b"vp09", &full_box(b"vpcC", 1, 0, &data));
+
+        for (sample_entry, samples, config) in [
+            (av01, packets(STREAM_AV1), CodecConfig::Av1(av1c)),
+            (vp09, packets(STREAM_VP9), CodecConfig::Vp9(vpcc)),
+        ] {
+            for file in [
+                write_progressive(&sample_entry, &samples, false),
+                write_fragmented(&sample_entry, &samples, 7),
+            ] {
+                let (track, demuxed) = demux(&file);
+                assert_eq!(demuxed, samples);
+                assert_eq!(track.sample_entries[0].config.as_ref(), Some(&config));
+            }
+        }
+    }
+
+    #[test]
+    fn demux_trafs() {
+        let (record, samples) = h264_samples();
+        let mut avcc = vec![];
+        record.write_into(&mut avcc).unwrap();
+        let sample_entry = visual_sample_entry(b"avc1", &mp4_box(b"avcC", &avcc));
+        let samples = &samples[..10];
+        let (first, second) = samples.split_at(4);
+
+        // A track fragment with default-sample-duration-present, and a run
+        // with per-sample sizes, flags and composition time offsets.
+        let traf = |samples: &[TestSample], tfhd_flags: u32, data_offset: Option<u32>| {
+            let entries: Vec<u8> = samples
+                .iter()
+                .flat_map(|s| {
+                    let flags = if s.is_sync {
+                        0
+                    } else {
+                        SAMPLE_IS_NON_SYNC_SAMPLE
+                    };
+                    concat([
+                        &(s.data.len() as u32).to_be_bytes(),
+                        &flags.to_be_bytes(),
+                        &((s.pts - s.dts as i64) as i32).to_be_bytes(),
+                    ])
+                })
+                .collect();
+            let trun = match data_offset {
+                Some(data_offset) => full_box(
+                    b"trun",
+                    1,
+                    0xe01,
+                    &concat([
+                        &(samples.len() as u32).to_be_bytes(),
+                        &data_offset.to_be_bytes(),
+                        &entries,
+                    ]),
+                ),
+                None => full_box(
+                    b"trun",
+                    1,
+                    0xe00,
+                    &concat([&(samples.len() as u32).to_be_bytes(), &entries]),
+                ),
+            };
+
+            mp4_box(
+                b"traf",
+                &concat([
+                    &full_box(
+                        b"tfhd",
+                        0,
+                        0x8 | tfhd_flags,
+                        &concat([&1u32.to_be_bytes(), &DURATION.to_be_bytes()]),
+                    ),
+                    &full_box(b"tfdt", 1, 0, &samples[0].dts.to_be_bytes()),
+                    &trun,
+                ]),
+            )
+        };
+
+        let mdat: Vec<u8> = samples.iter().flat_map(|s| s.data.clone()).collect();
+        let first_len = first.iter().map(|s| s.data.len()).sum::<usize>() as u32;
+
+        // The data of the second track fragment either directly follows the
+        // data of the first one, or is addressed relative to the movie
+        // fragment with default-base-is-moof.
+        for second_traf in [
+            |_| (0, None),
+            |second_offset| (0x2_0000, Some(second_offset)),
+        ] {
+            let moof = |data_offset: u32| {
+                let (flags, second_offset) = second_traf(data_offset + first_len);
+                mp4_box(
+                    b"moof",
+                    &concat([
+                        &full_box(b"mfhd", 0, 0, &0u32.to_be_bytes()),
+                        &traf(first, 0, Some(data_offset)),
+                        &traf(second, flags, second_offset),
+                    ]),
+                )
+            };
+            let moof_len = moof(0).len();
+
+            let file = concat([
+                &fragmented_header(&sample_entry),
+                &moof(moof_len as u32 + 8),
+                &mp4_box(b"mdat", &mdat),
+            ]);
+            let (_, demuxed) = demux(&file);
+            assert_eq!(demuxed, samples);
+        }
+    }
+
+    #[test]
+    fn demux_errors() {
+        let (record, samples) = h264_samples();
+        let mut avcc = vec![];
+        record.write_into(&mut avcc).unwrap();
+        let sample_entry = visual_sample_entry(b"avc1", &mp4_box(b"avcC", &avcc));
+        let file = write_progressive(&sample_entry, &samples, false);
+        let mdat_len = 8 + samples.iter().map(|s| s.data.len()).sum::<usize>();
+
+        assert!(matches!(
+            Mp4Demuxer::new(&file).unwrap().samples(2),
+            Err(Mp4Error::UnknownTrack(2))
+        ));
+
+        // Truncated boxes.
+        assert!(matches!(
+            Mp4Demuxer::new(&file[..100]),
+            Err(Mp4Error::TruncatedBox(_))
+        ));
+
+        // Samples outside of the file.
+        assert!(matches!(
+            Mp4Demuxer::new(&file[..file.len() - 1]),
+            Err(Mp4Error::SampleOutOfBounds { .. })
+        ));
+        assert!(matches!(
+            Mp4Demuxer::new(&file[..file.len() - mdat_len]),
+            Err(Mp4Error::SampleOutOfBounds { .. })
+        ));
+
+        assert!(matches!(
+            Mp4Demuxer::new(&ftyp()),
+            Err(Mp4Error::MissingBox("moov"))
+        ));
+
+        // A fragment whose decoding times overflow.
+        let mut overflowing = samples[..10].to_vec();
+        for sample in &mut overflowing {
+            sample.dts = 0xffff_ffff_ffff_ff00;
+            sample.pts = sample.dts as i64;
+        }
+        assert!(matches!(
+            Mp4Demuxer::new(&write_fragmented(&sample_entry, &overflowing, 25)),
+            Err(Mp4Error::InvalidBox("trun", _))
+        ));
+
+        // An invalid configuration record.
+        let sample_entry = visual_sample_entry(b"avc1", &mp4_box(b"avcC", &[0; 7]));
+        let file = write_progressive(&sample_entry, &samples, false);
+        assert!(matches!(
+            Mp4Demuxer::new(&file),
+            Err(Mp4Error::InvalidConfig(..))
+        ));
+    }
+}
@@ -8,12 +8,20 @@
 //! The [codec] module contains tools to parse encoded video streams like H.264 or VP9 and extract
 //! the information useful in order to perform e.g. hardware-accelerated decoding.
 //!
+//! The [container] module contains demuxers extracting the encoded samples of container files like
+//! MP4 or Matroska, to be fed to the parsers of the [codec] module.
+//!
+//! The [probe] module detects the codec and framing of encoded video streams, e.g. to route files
+//! of unknown format to the right parser.
+//!
 //! The [utils] module contains some useful code that is shared between different parts of this
 //! crate and didn't fit any of the modules above.
 
 #![allow(clippy::collapsible_if)]
 
 pub mod codec;
+pub mod container;
+pub mod probe;
 pub mod utils;
 
 /// Rounding modes for `Resolution`
@@ -40,11 +48,11 @@ impl Resolution {
     pub fn round(mut self, rnd_mode: ResolutionRoundMode) -> Self {
         match rnd_mode {
             ResolutionRoundMode::Even => {
-                if self.width % 2 != 0 {
+                if !self.width.is_multiple_of(2) {
                     self.width += 1;
                 }
 
-                if self.height % 2 != 0 {
+                if !self.height.is_multiple_of(2) {
                     self.height += 1;
                 }
             }
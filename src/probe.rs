@@ -0,0 +1,877 @@
+// Copyright 2024 The ChromiumOS Authors
+// Use of this source code is governed by a BSD-style license that can be
+// found in the LICENSE file.
+
+//! Detection of the codec and framing of a buffer of encoded video.
+//!
+//! Each supported combination of codec and framing is scored by checking the
+//! structure of the data, and then by parsing the parameter sets or frame
+//! headers it contains. Only the first units of the buffer are looked at, so
+//! the start of a file is usually enough to probe it.
+
+use std::io::Cursor;
+use std::ops::Range;
+
+use bitreader::BitReader;
+
+use crate::codec::av1::parser::ObuHeader;
+use crate::codec::av1::parser::ObuType;
+use crate::codec::av1::parser::Parser as Av1Parser;
+use crate::codec::av1::reader::Reader as Av1Reader;
+use crate::codec::h264::parser::Nalu as H264Nalu;
+use crate::codec::h264::parser::Parser as H264Parser;
+use crate::codec::h265::parser::Nalu as H265Nalu;
+use crate::codec::h265::parser::Parser as H265Parser;
+use crate::codec::vp8::parser::Parser as Vp8Parser;
+use crate::codec::vp9::parser::Parser as Vp9Parser;
+use crate::utils::IvfFileHeader;
+use crate::utils::IvfReader;
+
+/// The maximum number of NAL units, OBUs or temporal units looked at.
+const MAX_UNITS: usize = 64;
+
+/// A video codec.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Codec {
+    H264,
+    H265,
+    Av1,
+    Vp8,
+    Vp9,
+}
+
+/// The way the units of a stream are delimited.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// NAL units preceded by start codes, or AV1 units preceded by their
+    /// length, as specified in Annex B of the respective specifications.
+    AnnexB,
+    /// NAL units preceded by their big-endian length, as stored in MP4 or
+    /// Matroska files.
+    LengthPrefixed { length_size: usize },
+    /// AV1 OBUs with a size field, as specified in section 5.2 of the AV1
+    /// specification.
+    LowOverhead,
+    /// Frames stored in an IVF file.
+    Ivf,
+    /// A single VP8 or VP9 frame (or VP9 superframe), without any container.
+    Raw,
+}
+
+/// A guess of the contents of a buffer.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProbeResult {
+    pub codec: Codec,
+    pub framing: Framing,
+    /// How likely the guess is, between 0.0 and 1.0.
+    pub confidence: f32,
+}
+
+/// Returns the most likely codec and framing of `data`, or `None` if `data`
+/// does not look like any supported format.
+pub fn probe(data: &[u8]) -> Option<ProbeResult> {
+    probe_all(data).into_iter().next()
+}
+
+/// Returns all the plausible codecs and framings of `data`, the most likely
+/// first.
+pub fn probe_all(data: &[u8]) -> Vec<ProbeResult> {
+    let mut results = vec![];
+    let mut add = |codec, framing, confidence: Option<f32>| {
+        if let Some(confidence) = confidence {
+            results.push(ProbeResult {
+                codec,
+                framing,
+                confidence: confidence.min(1.0),
+            });
+        }
+    };
+
+    if let Some((codec, confidence)) = score_ivf(data) {
+        add(codec, Framing::Ivf, Some(confidence));
+    }
+
+    add(Codec::H264, Framing::AnnexB, score_h264_annexb(data));
+    add(Codec::H265, Framing::AnnexB, score_h265_annexb(data));
+
+    for length_size in [4, 2, 1] {
+        let framing = Framing::LengthPrefixed { length_size };
+        add(
+            Codec::H264,
+            framing,
+            score_h264_length_prefixed(data, length_size),
+        );
+        add(
+            Codec::H265,
+            framing,
+            score_h265_length_prefixed(data, length_size),
+        );
+    }
+
+    add(
+        Codec::Av1,
+        Framing::LowOverhead,
+        score_av1_low_overhead(data),
+    );
+    add(Codec::Av1, Framing::AnnexB, score_av1_annexb(data));
+    add(Codec::Vp8, Framing::Raw, score_vp8(data));
+    add(Codec::Vp9, Framing::Raw, score_vp9(data));
+
+    results.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+    results
+}
+
+/// Scores an IVF file, whose codec is given by the file header, by checking
+/// that its first frame is valid for that codec.
+fn score_ivf(data: &[u8]) -> Option<(Codec, f32)> {
+    let header = IvfFileHeader::parse(data).ok()?;
+
+    let codec = match &header.codec {
+        b"VP80" => Codec::Vp8,
+        b"VP90" => Codec::Vp9,
+        b"AV01" => Codec::Av1,
+        b"H264" | b"AVC1" | b"avc1" => Codec::H264,
+        b"H265" | b"HEVC" | b"hvc1" | b"hev1" => Codec::H265,
+        _ => return None,
+    };
+
+    let frame = match IvfReader::new(data).ok()?.next() {
+        Some(Ok(frame)) => frame.data,
+        // The file header alone is still good evidence.
+        None => return Some((codec, 0.8)),
+        Some(Err(_)) => return Some((codec, 0.6)),
+    };
+
+    let valid = match codec {
+        Codec::H264 => score_h264_annexb(frame).is_some(),
+        Codec::H265 => score_h265_annexb(frame).is_some(),
+        Codec::Av1 => score_av1_low_overhead(frame).is_some() || score_av1_annexb(frame).is_some(),
+        Codec::Vp8 => Vp8Parser::default().parse_frame(frame).is_ok(),
+        Codec::Vp9 => Vp9Parser::default().parse_chunk(frame).is_ok(),
+    };
+
+    Some((codec, if valid { 1.0 } else { 0.6 }))
+}
+
+/// A NAL unit found in a buffer.
+struct NalUnit {
+    /// The position of its start code or length.
+    pos: usize,
+    /// The position of the NAL unit itself, trailing zero bytes excluded.
+    range: Range<usize>,
+}
+
+/// Returns the NAL units of an Annex B stream, and whether the stream starts
+/// with a start code.
+fn split_annexb(data: &[u8]) -> (Vec<NalUnit>, bool) {
+    let mut starts = vec![];
+    let mut pos = 0;
+
+    while starts.len() <= MAX_UNITS {
+        match data[pos..].windows(3).position(|w| w == [0x00, 0x00, 0x01]) {
+            Some(offset) => {
+                starts.push(pos + offset);
+                pos += offset + 3;
+            }
+            None => break,
+        }
+    }
+
+    let starts_with_start_code = starts
+        .first()
+        .is_some_and(|&first| data[..first].iter().all(|&b| b == 0));
+
+    let units = starts
+        .iter()
+        .enumerate()
+        .take(MAX_UNITS)
+        .map(|(i, &pos)| {
+            let mut end = starts.get(i + 1).copied().unwrap_or(data.len());
+            while end > pos + 3 && data[end - 1] == 0 {
+                end -= 1;
+            }
+
+            NalUnit {
+                pos,
+                range: pos + 3..end,
+            }
+        })
+        .collect();
+
+    (units, starts_with_start_code)
+}
+
+/// Returns the NAL units of a length-prefixed stream, and whether the lengths
+/// chain up to the end of `data`, or `None` if the data cannot be walked.
+fn split_length_prefixed(data: &[u8], length_size: usize) -> Option<(Vec<NalUnit>, bool)> {
+    let mut units = vec![];
+    let mut pos = 0;
+
+    while pos < data.len() && units.len() < MAX_UNITS {
+        let Some(length) = data.get(pos..pos + length_size) else {
+            break;
+        };
+        let length = length
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+        if length == 0 {
+            return None;
+        }
+
+        let start = pos + length_size;
+        if start + length > data.len() {
+            break;
+        }
+
+        units.push(NalUnit {
+            pos,
+            range: start..start + length,
+        });
+        pos = start + length;
+    }
+
+    let complete = pos == data.len() || units.len() == MAX_UNITS;
+
+    // A single unit that does not span the whole buffer is not enough.
+    if units.is_empty() || (units.len() == 1 && !complete) {
+        None
+    } else {
+        Some((units, complete))
+    }
+}
+
+/// Returns the type of the H.264 NAL unit starting with `nalu`, if its header
+/// is valid as per 7.4.1.
+fn h264_nalu_type(nalu: &[u8]) -> Option<u8> {
+    let &byte = nalu.first()?;
+    let ref_idc = (byte >> 5) & 0x3;
+    let type_ = byte & 0x1f;
+
+    let valid = byte & 0x80 == 0
+        && match type_ {
+            1..=4 | 14 | 19..=21 => true,
+            5 | 7 | 8 | 13 | 15 => ref_idc != 0,
+            6 | 9..=12 => ref_idc == 0,
+            _ => false,
+        };
+
+    valid.then_some(type_)
+}
+
+/// Returns the type of the H.265 NAL unit starting with `nalu`, if its header
+/// is valid as per 7.4.2.
+fn h265_nalu_type(nalu: &[u8]) -> Option<u8> {
+    let &[b0, b1, ..] = nalu else {
+        return None;
+    };
+
+    let type_ = (b0 >> 1) & 0x3f;
+    let layer_id = ((b0 & 0x1) << 5) | (b1 >> 3);
+    let temporal_id_plus1 = b1 & 0x7;
+
+    let valid = b0 & 0x80 == 0
+        && layer_id < 63
+        && temporal_id_plus1 != 0
+        && match type_ {
+            0..=9 | 34 | 35 | 38..=40 => true,
+            // IRAP pictures, VPS, SPS, EOS and EOB have a TemporalId of 0.
+            16..=21 | 32 | 33 | 36 | 37 => temporal_id_plus1 == 1,
+            _ => false,
+        };
+
+    valid.then_some(type_)
+}
+
+/// Reads the NAL unit framed at `unit.pos`.
+fn read_nalu<'a, U>(data: &'a [u8], unit: &NalUnit, framing: Framing) -> Option<U>
+where
+    U: ReadNalu<'a>,
+{
+    let mut cursor = Cursor::new(data);
+    cursor.set_position(unit.pos as u64);
+
+    match framing {
+        Framing::LengthPrefixed { length_size } => {
+            U::next_length_prefixed(&mut cursor, length_size)
+        }
+        _ => U::next(&mut cursor),
+    }
+}
+
+/// The NAL unit readers of both H.264 and H.265.
+trait ReadNalu<'a>: Sized {
+    fn next(cursor: &mut Cursor<&'a [u8]>) -> Option<Self>;
+    fn next_length_prefixed(cursor: &mut Cursor<&'a [u8]>, length_size: usize) -> Option<Self>;
+}
+
+impl<'a> ReadNalu<'a> for H264Nalu<'a> {
+    fn next(cursor: &mut Cursor<&'a [u8]>) -> Option<Self> {
+        H264Nalu::next(cursor).ok()
+    }
+
+    fn next_length_prefixed(cursor: &mut Cursor<&'a [u8]>, length_size: usize) -> Option<Self> {
+        H264Nalu::next_length_prefixed(cursor, length_size).ok()
+    }
+}
+
+impl<'a> ReadNalu<'a> for H265Nalu<'a> {
+    fn next(cursor: &mut Cursor<&'a [u8]>) -> Option<Self> {
+        H265Nalu::next(cursor).ok()
+    }
+
+    fn next_length_prefixed(cursor: &mut Cursor<&'a [u8]>, length_size: usize) -> Option<Self> {
+        H265Nalu::next_length_prefixed(cursor, length_size).ok()
+    }
+}
+
+/// Scores H.264 NAL units: 0.4 for the ratio of valid headers, 0.2 for a
+/// sound framing, 0.2 for a parsed SPS and 0.2 for a slice.
+fn score_h264(data: &[u8], units: &[NalUnit], framing: Framing, framed: bool) -> Option<f32> {
+    let mut parser = H264Parser::default();
+    let (mut valid, mut sps, mut slice) = (0, false, false);
+
+    for unit in units {
+        let Some(type_) = h264_nalu_type(&data[unit.range.clone()]) else {
+            continue;
+        };
+        valid += 1;
+
+        match type_ {
+            1..=5 => slice = true,
+            7 if !sps => {
+                sps = read_nalu::<H264Nalu>(data, unit, framing)
+                    .is_some_and(|nalu| parser.parse_sps(&nalu).is_ok());
+            }
+            _ => (),
+        }
+    }
+
+    score_nalus(units.len(), valid, framed, sps, slice)
+}
+
+/// Scores H.265 NAL units, like [`score_h264`].
+fn score_h265(data: &[u8], units: &[NalUnit], framing: Framing, framed: bool) -> Option<f32> {
+    let mut parser = H265Parser::default();
+    let (mut valid, mut sps, mut slice) = (0, false, false);
+
+    for unit in units {
+        let Some(type_) = h265_nalu_type(&data[unit.range.clone()]) else {
+            continue;
+        };
+        valid += 1;
+
+        match type_ {
+            0..=21 => slice = true,
+            32 => {
+                if let Some(nalu) = read_nalu::<H265Nalu>(data, unit, framing) {
+                    let _ = parser.parse_vps(&nalu);
+                }
+            }
+            33 if !sps => {
+                sps = read_nalu::<H265Nalu>(data, unit, framing)
+                    .is_some_and(|nalu| parser.parse_sps(&nalu).is_ok());
+            }
+            _ => (),
+        }
+    }
+
+    score_nalus(units.len(), valid, framed, sps, slice)
+}
+
+fn score_nalus(total: usize, valid: usize, framed: bool, sps: bool, slice: bool) -> Option<f32> {
+    if total == 0 || valid * 2 < total {
+        return None;
+    }
+
+    let ratio = valid as f32 / total as f32;
+    let mut confidence = 0.4 * ratio;
+    for (evidence, weight) in [(framed, 0.2), (sps, 0.2), (slice, 0.2)] {
+        if evidence {
+            confidence += weight;
+        }
+    }
+
+    Some(confidence)
+}
+
+fn score_h264_annexb(data: &[u8]) -> Option<f32> {
+    let (units, starts_with_start_code) = split_annexb(data);
+    score_h264(data, &units, Framing::AnnexB, starts_with_start_code)
+}
+
+fn score_h265_annexb(data: &[u8]) -> Option<f32> {
+    let (units, starts_with_start_code) = split_annexb(data);
+    score_h265(data, &units, Framing::AnnexB, starts_with_start_code)
+}
+
+/// Length-prefixed NAL units are walked blindly, so a single invalid header
+/// rules the framing out.
+fn score_h264_length_prefixed(data: &[u8], length_size: usize) -> Option<f32> {
+    let (units, complete) = split_length_prefixed(data, length_size)?;
+    if units
+        .iter()
+        .any(|unit| h264_nalu_type(&data[unit.range.clone()]).is_none())
+    {
+        return None;
+    }
+
+    let framing = Framing::LengthPrefixed { length_size };
+    score_h264(data, &units, framing, complete)
+}
+
+fn score_h265_length_prefixed(data: &[u8], length_size: usize) -> Option<f32> {
+    let (units, complete) = split_length_prefixed(data, length_size)?;
+    if units
+        .iter()
+        .any(|unit| h265_nalu_type(&data[unit.range.clone()]).is_none())
+    {
+        return None;
+    }
+
+    let framing = Framing::LengthPrefixed { length_size };
+    score_h265(data, &units, framing, complete)
+}
+
+/// Reads a leb128 value as per section 4.10.5 of the AV1 specification,
+/// returning it along with its length.
+fn read_leb128(data: &[u8]) -> Option<(usize, usize)> {
+    let mut r = Av1Reader::new(data);
+    let value = r.read_leb128().ok()?;
+    Some((usize::try_from(value).ok()?, r.consumed(0) as usize))
+}
+
+/// Parses the OBU header at the start of `data`, rejecting the reserved OBU
+/// types.
+fn read_obu_header(data: &[u8]) -> Option<ObuHeader> {
+    let header = Av1Parser::parse_obu_header(&mut Av1Reader::new(data)).ok()?;
+
+    matches!(
+        header.obu_type,
+        ObuType::SequenceHeader
+            | ObuType::TemporalDelimiter
+            | ObuType::FrameHeader
+            | ObuType::TileGroup
+            | ObuType::Metadata
+            | ObuType::Frame
+            | ObuType::RedundantFrameHeader
+            | ObuType::TileList
+            | ObuType::Padding
+    )
+    .then_some(header)
+}
+
+/// The evidence gathered while walking AV1 OBUs.
+#[derive(Default)]
+struct Av1Evidence {
+    sequence_header: bool,
+    frame: bool,
+}
+
+impl Av1Evidence {
+    fn add(&mut self, header: &ObuHeader) {
+        self.sequence_header |= header.obu_type == ObuType::SequenceHeader;
+        self.frame |= matches!(header.obu_type, ObuType::FrameHeader | ObuType::Frame);
+    }
+
+    fn score(&self) -> f32 {
+        0.1 * f32::from(u8::from(self.sequence_header)) + 0.1 * f32::from(u8::from(self.frame))
+    }
+}
+
+/// Scores a low-overhead AV1 stream: 0.4 for valid OBUs, 0.2 if their sizes
+/// chain up to the end of the buffer, 0.2 for a leading temporal delimiter,
+/// and 0.1 each for a sequence header and a frame.
+fn score_av1_low_overhead(data: &[u8]) -> Option<f32> {
+    let mut evidence = Av1Evidence::default();
+    let mut pos = 0;
+    let mut num_obus = 0;
+    let mut leading_temporal_delimiter = false;
+
+    while pos < data.len() && num_obus < MAX_UNITS {
+        let header = read_obu_header(&data[pos..])?;
+        if !header.has_size_field {
+            return None;
+        }
+
+        let Some((size, leb128_len)) = data.get(pos + header.len()..).and_then(read_leb128) else {
+            break;
+        };
+
+        let end = pos + header.len() + leb128_len + size;
+        if end > data.len() {
+            break;
+        }
+
+        if num_obus == 0 {
+            leading_temporal_delimiter = header.obu_type == ObuType::TemporalDelimiter;
+        }
+
+        evidence.add(&header);
+        num_obus += 1;
+        pos = end;
+    }
+
+    let complete = pos == data.len() || num_obus == MAX_UNITS;
+    if num_obus == 0 || (num_obus == 1 && !complete) {
+        return None;
+    }
+
+    let mut confidence = 0.4 + evidence.score();
+    if complete {
+        confidence += 0.2;
+    }
+    if leading_temporal_delimiter {
+        confidence += 0.2;
+    }
+
+    Some(confidence)
+}
+
+/// Walks the OBUs of an Annex B frame unit, as per section B.2.
+fn walk_av1_frame_unit(data: &[u8], first: bool, evidence: &mut Av1Evidence) -> Option<()> {
+    let mut pos = 0;
+
+    while pos < data.len() {
+        let (obu_length, leb128_len) = read_leb128(&data[pos..])?;
+        let obu = data.get(pos + leb128_len..pos + leb128_len + obu_length)?;
+
+        let header = read_obu_header(obu)?;
+        if header.has_size_field {
+            let (size, len) = read_leb128(obu.get(header.len()..)?)?;
+            if header.len() + len + size != obu_length {
+                return None;
+            }
+        } else if obu_length < header.len() {
+            return None;
+        }
+
+        // The first OBU of a temporal unit is a temporal delimiter, which
+        // appears nowhere else.
+        if (first && pos == 0) != (header.obu_type == ObuType::TemporalDelimiter) {
+            return None;
+        }
+
+        evidence.add(&header);
+        pos += leb128_len + obu_length;
+    }
+
+    Some(())
+}
+
+/// Scores an Annex B AV1 stream: 0.6 for valid temporal units, 0.2 if they
+/// chain up to the end of the buffer, and 0.1 each for a sequence header and a
+/// frame.
+fn score_av1_annexb(data: &[u8]) -> Option<f32> {
+    let mut evidence = Av1Evidence::default();
+    let mut pos = 0;
+    let mut num_temporal_units = 0;
+
+    while pos < data.len() && num_temporal_units < MAX_UNITS {
+        let (temporal_unit_size, leb128_len) = read_leb128(&data[pos..])?;
+        if temporal_unit_size == 0 {
+            return None;
+        }
+
+        let start = pos + leb128_len;
+        let Some(temporal_unit) = data.get(start..start + temporal_unit_size) else {
+            break;
+        };
+
+        let mut unit_pos = 0;
+        while unit_pos < temporal_unit.len() {
+            let (frame_unit_size, leb128_len) = read_leb128(&temporal_unit[unit_pos..])?;
+            let frame_start = unit_pos + leb128_len;
+            let frame_unit = temporal_unit.get(frame_start..frame_start + frame_unit_size)?;
+
+            walk_av1_frame_unit(frame_unit, unit_pos == 0, &mut evidence)?;
+            unit_pos = frame_start + frame_unit_size;
+        }
+
+        num_temporal_units += 1;
+        pos = start + temporal_unit_size;
+    }
+
+    if num_temporal_units == 0 {
+        return None;
+    }
+
+    let mut confidence = 0.6 + evidence.score();
+    if pos == data.len() || num_temporal_units == MAX_UNITS {
+        confidence += 0.2;
+    }
+
+    Some(confidence)
+}
+
+/// Scores a VP8 frame. Only key frames can be identified, by their start code
+/// and dimensions as per section 9.1 of RFC 6386.
+fn score_vp8(data: &[u8]) -> Option<f32> {
+    let header = data.get(..10)?;
+    let tag = u32::from(header[0]) | u32::from(header[1]) << 8 | u32::from(header[2]) << 16;
+
+    let key_frame = tag & 0x1 == 0;
+    let version = (tag >> 1) & 0x7;
+    let first_part_size = (tag >> 5) as usize;
+    let width = (u16::from(header[6]) | u16::from(header[7]) << 8) & 0x3fff;
+    let height = (u16::from(header[8]) | u16::from(header[9]) << 8) & 0x3fff;
+
+    if !key_frame
+        || version > 3
+        || header[3..6] != [0x9d, 0x01, 0x2a]
+        || width == 0
+        || height == 0
+        || first_part_size > data.len() - 10
+    {
+        return None;
+    }
+
+    if Vp8Parser::default().parse_frame(data).is_ok() {
+        Some(0.9)
+    } else {
+        Some(0.6)
+    }
+}
+
+/// Returns the frames of a VP9 superframe, if `data` ends with a valid
+/// superframe index as per Annex B of the VP9 specification.
+fn vp9_superframe_frames(data: &[u8]) -> Option<Vec<&[u8]>> {
+    let &marker = data.last()?;
+    if marker & 0xe0 != 0xc0 {
+        return None;
+    }
+
+    let bytes_per_framesize = usize::from((marker >> 3) & 0x3) + 1;
+    let frames_in_superframe = usize::from(marker & 0x7) + 1;
+    let index_size = 2 + bytes_per_framesize * frames_in_superframe;
+    let frames_size = data.len().checked_sub(index_size)?;
+    if data[frames_size] != marker {
+        return None;
+    }
+
+    let mut frames = vec![];
+    let mut offset = 0;
+    for size in data[frames_size + 1..data.len() - 1].chunks(bytes_per_framesize) {
+        let size = size
+            .iter()
+            .rev()
+            .fold(0usize, |acc, &b| (acc << 8) | usize::from(b));
+        if size == 0 {
+            return None;
+        }
+
+        frames.push(data.get(offset..offset + size)?);
+        offset += size;
+    }
+
+    (offset == frames_size).then_some(frames)
+}
+
+/// Whether `frame` starts with the sync code of a VP9 key frame or intra-only
+/// frame, as per section 6.2 of the VP9 specification.
+fn vp9_has_sync_code(frame: &[u8]) -> Result<bool, bitreader::BitReaderError> {
+    let mut r = BitReader::new(frame);
+
+    if r.read_u8(2)? != 2 {
+        return Ok(false);
+    }
+
+    let profile_low_bit = r.read_u8(1)?;
+    let profile_high_bit = r.read_u8(1)?;
+    if (profile_high_bit << 1 | profile_low_bit) == 3 && r.read_bool()? {
+        return Ok(false);
+    }
+
+    let show_existing_frame = r.read_bool()?;
+    if show_existing_frame {
+        return Ok(false);
+    }
+
+    let key_frame = !r.read_bool()?;
+    let show_frame = r.read_bool()?;
+    let error_resilient_mode = r.read_bool()?;
+
+    if !key_frame {
+        let intra_only = !show_frame && r.read_bool()?;
+        if !intra_only {
+            return Ok(false);
+        }
+
+        if !error_resilient_mode {
+            r.skip(2)?;
+        }
+    }
+
+    Ok(r.read_u32(24)? == 0x498342)
+}
+
+/// Scores a VP9 frame or superframe: 0.6 for a sync code or a superframe
+/// index, 0.1 more if both are found, and 0.3 if the frames can be parsed.
+/// Single inter frames cannot be identified.
+fn score_vp9(data: &[u8]) -> Option<f32> {
+    let superframe = vp9_superframe_frames(data);
+    let frames = superframe.clone().unwrap_or_else(|| vec![data]);
+    let sync_code = frames
+        .iter()
+        .any(|frame| vp9_has_sync_code(frame).unwrap_or(false));
+
+    let mut confidence = match (sync_code, superframe.is_some()) {
+        (false, false) => return None,
+        (true, true) => 0.7,
+        _ => 0.6,
+    };
+
+    if matches!(Vp9Parser::default().parse_chunk(data), Ok(frames) if !frames.is_empty()) {
+        confidence += 0.3;
+    }
+
+    Some(confidence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::IvfIterator;
+
+    const STREAM_H264: &[u8] = include_bytes!("codec/h264/test_data/test-25fps.h264");
+    const STREAM_H265: &[u8] = include_bytes!("codec/h265/test_data/test-25fps.h265");
+    const STREAM_H265_BEAR: &[u8] = include_bytes!("codec/h265/test_data/bear.h265");
+    const STREAM_AV1: &[u8] = include_bytes!("codec/av1/test_data/test-25fps.ivf.av1");
+    const STREAM_AV1_ANNEXB: &[u8] = include_bytes!("codec/av1/test_data/av1-annexb.ivf.av1");
+    const STREAM_VP8: &[u8] = include_bytes!("codec/vp8/test_data/test-25fps.vp8");
+    const VP8_INTRA: &[u8] = include_bytes!("codec/vp8/test_data/vp8-parser-test-0-intra.bin");
+    const STREAM_VP9: &[u8] = include_bytes!("codec/vp9/test_data/test-25fps.vp9");
+    const VP9_SUPERFRAME: &[u8] = include_bytes!("codec/vp9/test_data/vp9-superframe.bin");
+
+    fn assert_probe(data: &[u8], codec: Codec, framing: Framing) {
+        let result = probe(data).unwrap();
+        assert_eq!((result.codec, result.framing), (codec, framing));
+        assert!(result.confidence >= 0.8, "{result:?}");
+    }
+
+    fn ivf_payload(data: &[u8]) -> Vec<u8> {
+        IvfIterator::new(data).flatten().copied().collect()
+    }
+
+    /// Converts an Annex B stream into a length-prefixed one.
+    fn length_prefixed<U: std::fmt::Debug + crate::codec::h264::nalu::Header>(
+        data: &[u8],
+        length_size: usize,
+    ) -> Vec<u8> {
+        let mut cursor = Cursor::new(data);
+        let mut stream = vec![];
+        while let Ok(nalu) = crate::codec::h264::nalu::Nalu::<U>::next(&mut cursor) {
+            let nalu = nalu.as_ref();
+            stream.extend_from_slice(&nalu.len().to_be_bytes()[8 - length_size..]);
+            stream.extend_from_slice(nalu);
+        }
+
+        stream
+    }
+
+    #[test]
+    fn probe_h264_h265() {
+        use crate::codec::h264::parser::NaluHeader as H264NaluHeader;
+        use crate::codec::h265::parser::NaluHeader as H265NaluHeader;
+
+        assert_probe(STREAM_H264, Codec::H264, Framing::AnnexB);
+        assert_probe(STREAM_H265, Codec::H265, Framing::AnnexB);
+        assert_probe(STREAM_H265_BEAR, Codec::H265, Framing::AnnexB);
+
+        // A truncated stream is still recognized.
+        assert_probe(&STREAM_H264[..1000], Codec::H264, Framing::AnnexB);
+
+        for length_size in [4, 2] {
+            let framing = Framing::LengthPrefixed { length_size };
+            let stream = length_prefixed::<H264NaluHeader>(STREAM_H264, length_size);
+            assert_probe(&stream, Codec::H264, framing);
+            let stream = length_prefixed::<H265NaluHeader>(STREAM_H265, length_size);
+            assert_probe(&stream, Codec::H265, framing);
+        }
+    }
+
+    #[test]
+    fn probe_av1() {
+        assert_probe(STREAM_AV1, Codec::Av1, Framing::Ivf);
+        assert_probe(STREAM_AV1_ANNEXB, Codec::Av1, Framing::Ivf);
+
+        let stream = ivf_payload(STREAM_AV1);
+        assert_probe(&stream, Codec::Av1, Framing::LowOverhead);
+
+        // The last OBU of a truncated stream is ignored.
+        let result = probe(&stream[..500]).unwrap();
+        assert_eq!(
+            (result.codec, result.framing),
+            (Codec::Av1, Framing::LowOverhead)
+        );
+
+        let stream = ivf_payload(STREAM_AV1_ANNEXB);
+        assert_probe(&stream, Codec::Av1, Framing::AnnexB);
+    }
+
+    #[test]
+    fn probe_vp8_vp9() {
+        assert_probe(STREAM_VP8, Codec::Vp8, Framing::Ivf);
+        assert_probe(STREAM_VP9, Codec::Vp9, Framing::Ivf);
+        assert_probe(VP8_INTRA, Codec::Vp8, Framing::Raw);
+        assert_probe(VP9_SUPERFRAME, Codec::Vp9, Framing::Raw);
+
+        let frame = IvfIterator::new(STREAM_VP9).next().unwrap();
+        assert_probe(frame, Codec::Vp9, Framing::Raw);
+        let frame = IvfIterator::new(STREAM_VP8).next().unwrap();
+        assert_probe(frame, Codec::Vp8, Framing::Raw);
+    }
+
+    #[test]
+    fn probe_garbage() {
+        assert!(probe(&[]).is_none());
+        assert!(probe(b"not a video stream at all").is_none());
+
+        // An IVF file of an unknown codec.
+        let mut data = vec![];
+        IvfFileHeader::new(*b"XXXX", 320, 240, 30, 0)
+            .writo_into(&mut data)
+            .unwrap();
+        assert!(probe(&data).is_none());
+
+        // Pseudo-random data of various sizes must not panic, and is at best
+        // reported with a low confidence.
+        let mut state = 0x1234_5678u32;
+        let data: Vec<u8> = std::iter::repeat_with(|| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .take(4096)
+        .collect();
+
+        for len in [1, 2, 3, 10, 100, 4096] {
+            for result in probe_all(&data[..len]) {
+                assert!(result.confidence < 0.8, "{result:?}");
+            }
+        }
+
+        // Truncations of valid streams must not panic either.
+        for stream in [STREAM_H264, STREAM_H265, STREAM_AV1, STREAM_VP9] {
+            for len in 0..64 {
+                probe_all(&stream[..len]);
+            }
+        }
+
+        // Nor do out of range values in an SPS: sps_max_sub_layers_minus1 set
+        // to 7, and a scaling_list_pred_matrix_id_delta pointing before the
+        // first list.
+        let (units, _) = split_annexb(STREAM_H265);
+        let sps = units
+            .iter()
+            .find(|unit| h265_nalu_type(&STREAM_H265[unit.range.clone()]) == Some(33))
+            .unwrap()
+            .range
+            .start;
+        for (offset, bits) in [(2, 0x0e), (23, 0x02)] {
+            let mut data = STREAM_H265.to_vec();
+            data[sps + offset] ^= bits;
+            probe_all(&data);
+        }
+    }
+}
@@ -60,7 +60,8 @@ impl<'a> Iterator for IvfIterator<'a> {
     }
 }
 
-/// Helper struct for synthesizing IVF file header
+/// Helper struct for parsing and synthesizing IVF file header
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct IvfFileHeader {
     pub magic: [u8; 4],
     pub version: u16,
@@ -143,6 +144,206 @@ impl IvfFrameHeader {
     }
 }
 
+#[derive(Error, Debug)]
+pub enum IvfError {
+    #[error("invalid IVF signature {0:?}")]
+    InvalidSignature([u8; 4]),
+    #[error("invalid IVF header size {0}")]
+    InvalidHeaderSize(u16),
+    #[error("invalid IVF timebase {0}/{1}")]
+    InvalidTimebase(u32, u32),
+    #[error("truncated IVF file header")]
+    TruncatedFileHeader,
+    #[error("truncated IVF frame header")]
+    TruncatedFrameHeader,
+    #[error("truncated IVF frame: expected {expected} bytes, got {actual}")]
+    TruncatedFrame { expected: usize, actual: usize },
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+pub type IvfResult<T> = std::result::Result<T, IvfError>;
+
+impl IvfFileHeader {
+    /// Parses and validates the file header at the start of `data`.
+    pub fn parse(data: &[u8]) -> IvfResult<Self> {
+        let mut cursor = Cursor::new(data);
+        if cursor.remaining() < 32 {
+            return Err(IvfError::TruncatedFileHeader);
+        }
+
+        let mut magic = [0u8; 4];
+        cursor.copy_to_slice(&mut magic);
+        if magic != Self::MAGIC {
+            return Err(IvfError::InvalidSignature(magic));
+        }
+
+        let version = cursor.get_u16_le();
+        let header_size = cursor.get_u16_le();
+        if header_size < 32 {
+            return Err(IvfError::InvalidHeaderSize(header_size));
+        }
+
+        let mut codec = [0u8; 4];
+        cursor.copy_to_slice(&mut codec);
+
+        let hdr = Self {
+            magic,
+            version,
+            header_size,
+            codec,
+            width: cursor.get_u16_le(),
+            height: cursor.get_u16_le(),
+            framerate: cursor.get_u32_le(),
+            timescale: cursor.get_u32_le(),
+            frame_count: cursor.get_u32_le(),
+            unused: cursor.get_u32_le(),
+        };
+
+        if hdr.framerate == 0 || hdr.timescale == 0 {
+            return Err(IvfError::InvalidTimebase(hdr.timescale, hdr.framerate));
+        }
+
+        if data.len() < usize::from(header_size) {
+            return Err(IvfError::TruncatedFileHeader);
+        }
+
+        Ok(hdr)
+    }
+}
+
+/// A frame read from an IVF file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IvfFrame<'a> {
+    /// The presentation timestamp of the frame, in units of the timebase of
+    /// the file, i.e. `timescale / framerate` seconds.
+    pub timestamp: u64,
+    /// The frame data.
+    pub data: &'a [u8],
+}
+
+/// Reader of IVF files, which validates the file header and returns the frames
+/// along with their timestamps.
+///
+/// Unlike [`IvfIterator`], truncated data is reported as an error, after which
+/// the iteration stops.
+pub struct IvfReader<'a> {
+    header: IvfFileHeader,
+    cursor: Cursor<&'a [u8]>,
+}
+
+impl<'a> IvfReader<'a> {
+    pub fn new(data: &'a [u8]) -> IvfResult<Self> {
+        let header = IvfFileHeader::parse(data)?;
+        let mut cursor = Cursor::new(data);
+        cursor.set_position(u64::from(header.header_size));
+
+        Ok(Self { header, cursor })
+    }
+
+    /// Returns the file header.
+    pub fn header(&self) -> &IvfFileHeader {
+        &self.header
+    }
+
+    fn read_frame(&mut self) -> IvfResult<IvfFrame<'a>> {
+        if self.cursor.remaining() < 12 {
+            return Err(IvfError::TruncatedFrameHeader);
+        }
+
+        let len = self.cursor.get_u32_le() as usize;
+        let timestamp = self.cursor.get_u64_le();
+
+        if self.cursor.remaining() < len {
+            return Err(IvfError::TruncatedFrame {
+                expected: len,
+                actual: self.cursor.remaining(),
+            });
+        }
+
+        let start = self.cursor.position() as usize;
+        self.cursor.advance(len);
+
+        Ok(IvfFrame {
+            timestamp,
+            data: &self.cursor.get_ref()[start..start + len],
+        })
+    }
+}
+
+impl<'a> Iterator for IvfReader<'a> {
+    type Item = IvfResult<IvfFrame<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !self.cursor.has_remaining() {
+            return None;
+        }
+
+        let frame = self.read_frame();
+        if frame.is_err() {
+            let end = self.cursor.get_ref().len() as u64;
+            self.cursor.set_position(end);
+        }
+
+        Some(frame)
+    }
+}
+
+/// Writer of IVF files. The number of frames in the file header is updated when
+/// the writer is finished.
+pub struct IvfWriter<W: Write + Seek> {
+    writer: W,
+    header: IvfFileHeader,
+}
+
+impl<W: Write + Seek> IvfWriter<W> {
+    /// Creates a writer and writes `header` into `writer`.
+    pub fn new(mut writer: W, header: IvfFileHeader) -> IvfResult<Self> {
+        let header = IvfFileHeader {
+            header_size: 32,
+            frame_count: 0,
+            ..header
+        };
+        header.writo_into(&mut writer)?;
+
+        Ok(Self { writer, header })
+    }
+
+    /// Returns the number of frames written so far.
+    pub fn frame_count(&self) -> u32 {
+        self.header.frame_count
+    }
+
+    /// Writes a frame with the given timestamp.
+    pub fn write_frame(&mut self, data: &[u8], timestamp: u64) -> IvfResult<()> {
+        let frame_size = u32::try_from(data.len()).map_err(|_| {
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, "IVF frame too large")
+        })?;
+
+        IvfFrameHeader {
+            frame_size,
+            timestamp,
+        }
+        .writo_into(&mut self.writer)?;
+        self.writer.write_all(data)?;
+        self.header.frame_count += 1;
+
+        Ok(())
+    }
+
+    /// Rewrites the file header with the final number of frames and returns
+    /// the underlying writer, positioned at the end of the file.
+    pub fn finish(mut self) -> IvfResult<W> {
+        let end = self.writer.stream_position()?;
+        self.writer.seek(std::io::SeekFrom::Start(0))?;
+        self.header.writo_into(&mut self.writer)?;
+        self.writer.seek(std::io::SeekFrom::Start(end))?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+}
+
 /// Iterator NALUs in a bitstream.
 pub struct NalIterator<'a, Nalu>(Cursor<&'a [u8]>, PhantomData<Nalu>);
 
@@ -336,6 +537,83 @@ mod tests {
         assert_eq!(&buf, &EXPECTED2);
     }
 
+    #[test]
+    fn test_ivf_reader() {
+        const STREAM: &[u8] = include_bytes!("codec/vp9/test_data/test-25fps.vp9");
+
+        let reader = IvfReader::new(STREAM).unwrap();
+        assert_eq!(reader.header().codec, IvfFileHeader::CODEC_VP9);
+        assert_eq!((reader.header().width, reader.header().height), (320, 240));
+        assert_eq!(reader.header().frame_count, 250);
+
+        let frames = reader.collect::<IvfResult<Vec<_>>>().unwrap();
+        assert_eq!(frames.len(), 250);
+        assert_eq!(frames[1].timestamp, 40);
+        assert_eq!(
+            frames.iter().map(|f| f.data).collect::<Vec<_>>(),
+            IvfIterator::new(STREAM).collect::<Vec<_>>()
+        );
+
+        // Truncated file and frame headers, and truncated frame data.
+        assert!(matches!(
+            IvfReader::new(&STREAM[..31]),
+            Err(IvfError::TruncatedFileHeader)
+        ));
+        let mut reader = IvfReader::new(&STREAM[..40]).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(IvfError::TruncatedFrameHeader))
+        ));
+        assert!(reader.next().is_none());
+        let mut reader = IvfReader::new(&STREAM[..100]).unwrap();
+        assert!(matches!(
+            reader.next(),
+            Some(Err(IvfError::TruncatedFrame { actual: 56, .. }))
+        ));
+
+        let mut invalid = STREAM[..32].to_vec();
+        invalid[0] = b'X';
+        assert!(matches!(
+            IvfReader::new(&invalid),
+            Err(IvfError::InvalidSignature(_))
+        ));
+        invalid = STREAM[..32].to_vec();
+        invalid[16..20].copy_from_slice(&[0; 4]);
+        assert!(matches!(
+            IvfReader::new(&invalid),
+            Err(IvfError::InvalidTimebase(..))
+        ));
+    }
+
+    #[test]
+    fn test_ivf_writer() {
+        let header = IvfFileHeader::new(IvfFileHeader::CODEC_AV1, 64, 48, 30, 0);
+        let mut writer = IvfWriter::new(Cursor::new(Vec::new()), header).unwrap();
+        writer.write_frame(&[1, 2, 3], 0).unwrap();
+        writer.write_frame(&[4, 5], 7).unwrap();
+        assert_eq!(writer.frame_count(), 2);
+
+        let data = writer.finish().unwrap().into_inner();
+        assert_eq!(data.len(), 32 + 12 + 3 + 12 + 2);
+
+        let reader = IvfReader::new(&data).unwrap();
+        assert_eq!(reader.header().frame_count, 2);
+        assert_eq!(reader.header().codec, IvfFileHeader::CODEC_AV1);
+        assert_eq!(
+            reader.collect::<IvfResult<Vec<_>>>().unwrap(),
+            [
+                IvfFrame {
+                    timestamp: 0,
+                    data: &[1, 2, 3],
+                },
+                IvfFrame {
+                    timestamp: 7,
+                    data: &[4, 5],
+                },
+            ]
+        );
+    }
+
     #[test]
     fn test_bitwriter_f1() {
         let mut buf = Vec::<u8>::new();